@@ -0,0 +1,650 @@
+//! proc macros for [quickjs_runtime](https://crates.io/crates/quickjs_runtime), use these through the `macros` feature of quickjs_runtime
+//!
+//! # js_class
+//!
+//! `#[js_class]` on an impl block generates a [Proxy](https://hirofa.github.io/quickjs_es_runtime/quickjs_runtime/reflection/struct.Proxy.html)
+//! class for the type, instances are stored per thread and dropped when the JS object is finalized
+//!
+//! * a fn `new` (or a fn marked `#[js_constructor]`) which returns `Self` or `Result<Self, E>` becomes the constructor
+//! * fns with a `&self` or `&mut self` receiver become methods, fns without a receiver become static methods
+//! * fns marked `#[js_getter]` / `#[js_setter]` become accessors, a `set_` prefix is stripped from the name of a setter
+//! * `async fn`s become methods which return a Promise, they are called on a clone of the instance so the type must implement `Clone + Send + 'static`
+//! * `#[js_name = "..."]` overrides the JS name (which defaults to the camelCase name of the fn), `#[js_skip]` skips a fn
+//!
+//! arguments are converted with serde (`DeserializeOwned`), return values with `Serialize`, a `QuickJsValueAdapter`
+//! argument or return value is passed as is and a `&QuickJsRealmAdapter` argument receives the current realm,
+//! a `Result<T, E>` return value (where `JsError: From<E>`) is thrown as an exception when it is an Err
+//!
+//! besides the annotated impl the macro generates `js_proxy()` (the Proxy), `install_js_class(realm)` (install it in the global scope)
+//! and `js_instances()` (the storage of instances)
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime_macros::js_class;
+//! use quickjs_runtime::jsutils::{JsError, Script};
+//!
+//! struct Counter {
+//!     count: i32,
+//! }
+//!
+//! #[js_class(namespace = "com.example")]
+//! impl Counter {
+//!     fn new(start: Option<i32>) -> Self {
+//!         Self { count: start.unwrap_or(0) }
+//!     }
+//!     fn increment(&mut self, by: i32) -> i32 {
+//!         self.count += by;
+//!         self.count
+//!     }
+//!     #[js_getter]
+//!     fn count(&self) -> i32 {
+//!         self.count
+//!     }
+//!     fn parse(text: String) -> Result<i32, JsError> {
+//!         text.parse::<i32>().map_err(|e| JsError::new_string(format!("{e}")))
+//!     }
+//! }
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! rt.loop_realm_sync(None, |_rt, realm| {
+//!     Counter::install_js_class(realm).expect("could not install Counter");
+//! });
+//! let res = rt
+//!     .eval_sync(None, Script::new("counter.js", "let c = new com.example.Counter(40); c.increment(1); c.increment(com.example.Counter.parse('1')); c.count;"))
+//!     .expect("script failed");
+//! assert_eq!(res.get_i32(), 42);
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, FnArg, GenericArgument, ImplItem, ImplItemFn, ItemImpl, LitStr,
+    PathArguments, ReturnType, Type,
+};
+
+#[proc_macro_attribute]
+pub fn js_class(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name: Option<String> = None;
+    let mut namespace: Option<String> = None;
+    let attr_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else if meta.path.is_ident("namespace") {
+            namespace = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else {
+            Err(meta.error("unsupported js_class property, expected name or namespace"))
+        }
+    });
+    parse_macro_input!(attr with attr_parser);
+    let item_impl = parse_macro_input!(item as ItemImpl);
+
+    match expand(name, namespace, item_impl) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+enum Kind {
+    Constructor,
+    Method,
+    StaticMethod,
+    Getter,
+    Setter,
+}
+
+/// how a value is converted between JS and rust
+#[derive(PartialEq)]
+enum Conversion {
+    /// QuickJsValueAdapter, passed as is
+    Adapter,
+    /// &QuickJsRealmAdapter, only as argument
+    Realm,
+    /// ()
+    Unit,
+    /// Self, only as constructor result
+    SelfType,
+    /// serde
+    Serde,
+}
+
+struct ReturnInfo {
+    is_result: bool,
+    conversion: Conversion,
+}
+
+struct JsFn {
+    kind: Kind,
+    js_name: String,
+    ident: syn::Ident,
+    is_async: bool,
+    is_mut: bool,
+    args: Vec<(Conversion, Type)>,
+    ret: ReturnInfo,
+    span: Span,
+}
+
+fn expand(
+    name: Option<String>,
+    namespace: Option<String>,
+    mut item_impl: ItemImpl,
+) -> syn::Result<TokenStream2> {
+    if !item_impl.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item_impl.generics.span(),
+            "js_class does not support generics",
+        ));
+    }
+    if item_impl.trait_.is_some() {
+        return Err(syn::Error::new(
+            item_impl.span(),
+            "js_class should be used on an inherent impl block, not a trait impl",
+        ));
+    }
+    let self_ty = item_impl.self_ty.clone();
+    let type_ident = match &*self_ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.clone())
+            .ok_or_else(|| syn::Error::new(self_ty.span(), "js_class needs a named type"))?,
+        _ => {
+            return Err(syn::Error::new(
+                self_ty.span(),
+                "js_class needs a named type",
+            ))
+        }
+    };
+    let class_name = name.unwrap_or_else(|| type_ident.to_string());
+
+    let mut js_fns = vec![];
+    for item in item_impl.items.iter_mut() {
+        if let ImplItem::Fn(impl_fn) = item {
+            if let Some(js_fn) = parse_fn(impl_fn)? {
+                js_fns.push(js_fn);
+            }
+        }
+    }
+
+    let instances = format_ident!("__JS_CLASS_INSTANCES_{}", type_ident);
+    let krate = quote!(::quickjs_runtime);
+
+    let mut registrations = vec![];
+    let mut has_constructor = false;
+
+    // accessors are registered per name so a getter and setter end up in the same getter_setter
+    let mut accessors: Vec<(String, Option<TokenStream2>, Option<TokenStream2>)> = vec![];
+
+    for js_fn in &js_fns {
+        let js_name = js_fn.js_name.as_str();
+        match js_fn.kind {
+            Kind::Constructor => {
+                if has_constructor {
+                    return Err(syn::Error::new(
+                        js_fn.span,
+                        "js_class can only have one constructor",
+                    ));
+                }
+                has_constructor = true;
+                let (arg_decls, arg_names) = gen_args(js_fn, &krate)?;
+                let ident = &js_fn.ident;
+                let unwrap = if js_fn.ret.is_result {
+                    quote!(?)
+                } else {
+                    quote!()
+                };
+                registrations.push(quote! {
+                    proxy = proxy.constructor(|_rt, realm, id, _args| {
+                        #(#arg_decls)*
+                        let instance = <#self_ty>::#ident(#(#arg_names),*) #unwrap;
+                        #instances.with(|instances| instances.insert(realm, id, instance));
+                        Ok(())
+                    });
+                });
+            }
+            Kind::Method | Kind::StaticMethod => {
+                let (arg_decls, arg_names) = gen_args(js_fn, &krate)?;
+                let ident = &js_fn.ident;
+                let is_static = matches!(js_fn.kind, Kind::StaticMethod);
+                let body = if js_fn.is_async {
+                    let call = if is_static {
+                        quote!(<#self_ty>::#ident(#(#arg_names),*).await)
+                    } else {
+                        quote!(<#self_ty>::#ident(&instance, #(#arg_names),*).await)
+                    };
+                    let get_instance = if is_static {
+                        quote!()
+                    } else {
+                        quote! {
+                            let instance = #instances.with(|instances| {
+                                instances.with_instance(realm, *id, |instance| ::core::clone::Clone::clone(instance))
+                            })?;
+                        }
+                    };
+                    let res = if js_fn.ret.is_result {
+                        quote!(res.map_err(::core::convert::Into::<#krate::jsutils::JsError>::into))
+                    } else {
+                        quote!(Ok(res))
+                    };
+                    quote! {
+                        #(#arg_decls)*
+                        #get_instance
+                        #krate::reflection::jsclass::ret_promise(realm, async move {
+                            let res = #call;
+                            #res
+                        })
+                    }
+                } else {
+                    let call = if is_static {
+                        quote!(let res = <#self_ty>::#ident(#(#arg_names),*);)
+                    } else if js_fn.is_mut {
+                        quote! {
+                            let res = #instances.with(|instances| {
+                                instances.with_instance_mut(realm, *id, |instance| <#self_ty>::#ident(instance, #(#arg_names),*))
+                            })?;
+                        }
+                    } else {
+                        quote! {
+                            let res = #instances.with(|instances| {
+                                instances.with_instance(realm, *id, |instance| <#self_ty>::#ident(instance, #(#arg_names),*))
+                            })?;
+                        }
+                    };
+                    let ret = gen_ret(&js_fn.ret, &krate);
+                    quote! {
+                        #(#arg_decls)*
+                        #call
+                        #ret
+                    }
+                };
+                if is_static {
+                    registrations.push(quote! {
+                        proxy = proxy.static_method(#js_name, |_rt, realm, _args| {
+                            #body
+                        });
+                    });
+                } else {
+                    registrations.push(quote! {
+                        proxy = proxy.method(#js_name, |_rt, realm, id, _args| {
+                            #body
+                        });
+                    });
+                }
+            }
+            Kind::Getter => {
+                let ident = &js_fn.ident;
+                let ret = gen_ret(&js_fn.ret, &krate);
+                let getter = quote! {
+                    |_rt, realm, id| {
+                        let res = #instances.with(|instances| {
+                            instances.with_instance(realm, *id, |instance| <#self_ty>::#ident(instance))
+                        })?;
+                        #ret
+                    }
+                };
+                match accessors.iter_mut().find(|a| a.0 == js_name) {
+                    Some(accessor) => accessor.1 = Some(getter),
+                    None => accessors.push((js_name.to_string(), Some(getter), None)),
+                }
+            }
+            Kind::Setter => {
+                let ident = &js_fn.ident;
+                let value = match js_fn.args.first() {
+                    Some((Conversion::Adapter, _)) => quote!(value),
+                    Some((Conversion::Serde, ty)) => quote! {
+                        #krate::reflection::jsclass::arg::<#ty>(realm, ::core::slice::from_ref(&value), 0)?
+                    },
+                    _ => {
+                        return Err(syn::Error::new(
+                            js_fn.span,
+                            "a js_setter should have exactly one argument",
+                        ))
+                    }
+                };
+                let unwrap = if js_fn.ret.is_result {
+                    quote!(?)
+                } else {
+                    quote!()
+                };
+                let setter = quote! {
+                    |_rt, realm, id, value| {
+                        let value = #value;
+                        #instances.with(|instances| {
+                            instances.with_instance_mut(realm, *id, |instance| <#self_ty>::#ident(instance, value))
+                        })? #unwrap;
+                        Ok(())
+                    }
+                };
+                match accessors.iter_mut().find(|a| a.0 == js_name) {
+                    Some(accessor) => accessor.2 = Some(setter),
+                    None => accessors.push((js_name.to_string(), None, Some(setter))),
+                }
+            }
+        }
+    }
+
+    for (js_name, getter, setter) in accessors {
+        let getter = getter.unwrap_or_else(|| quote!(|_rt, realm, _id| realm.create_undefined()));
+        match setter {
+            Some(setter) => registrations.push(quote! {
+                proxy = proxy.getter_setter(#js_name, #getter, #setter);
+            }),
+            None => registrations.push(quote! {
+                proxy = proxy.getter(#js_name, #getter);
+            }),
+        }
+    }
+
+    let namespace = match namespace {
+        Some(ns) => {
+            let parts: Vec<&str> = ns.split('.').filter(|p| !p.is_empty()).collect();
+            quote!(proxy = proxy.namespace(&[#(#parts),*]);)
+        }
+        None => quote!(),
+    };
+
+    Ok(quote! {
+        #item_impl
+
+        thread_local! {
+            #[allow(non_upper_case_globals)]
+            static #instances: #krate::reflection::jsclass::JsClassInstances<#self_ty> = #krate::reflection::jsclass::JsClassInstances::new();
+        }
+
+        impl #self_ty {
+            /// the Proxy class for this type (generated by js_class)
+            pub fn js_proxy() -> #krate::reflection::Proxy {
+                let mut proxy = #krate::reflection::Proxy::new().name(#class_name);
+                #namespace
+                #(#registrations)*
+                proxy.finalizer(|_rt, realm, id| {
+                    #instances.with(|instances| {
+                        let _ = instances.remove(realm, id);
+                    });
+                })
+            }
+            /// install the Proxy class for this type in a realm and add it to the global scope (generated by js_class)
+            pub fn install_js_class(
+                realm: &#krate::quickjsrealmadapter::QuickJsRealmAdapter,
+            ) -> ::core::result::Result<#krate::quickjsvalueadapter::QuickJsValueAdapter, #krate::jsutils::JsError> {
+                Self::js_proxy().install(realm, true)
+            }
+            /// the instances of this type which are currently referenced from JS in this thread (generated by js_class)
+            pub fn js_instances() -> &'static ::std::thread::LocalKey<#krate::reflection::jsclass::JsClassInstances<#self_ty>> {
+                &#instances
+            }
+        }
+    })
+}
+
+/// inspect a fn and remove our helper attributes, returns None for skipped fns
+fn parse_fn(impl_fn: &mut ImplItemFn) -> syn::Result<Option<JsFn>> {
+    let mut is_constructor = false;
+    let mut is_getter = false;
+    let mut is_setter = false;
+    let mut is_skipped = false;
+    let mut js_name: Option<String> = None;
+
+    let mut retained: Vec<Attribute> = vec![];
+    for attr in impl_fn.attrs.drain(..) {
+        if attr.path().is_ident("js_constructor") {
+            is_constructor = true;
+        } else if attr.path().is_ident("js_getter") {
+            is_getter = true;
+        } else if attr.path().is_ident("js_setter") {
+            is_setter = true;
+        } else if attr.path().is_ident("js_skip") {
+            is_skipped = true;
+        } else if attr.path().is_ident("js_name") {
+            let value: LitStr = match &attr.meta {
+                syn::Meta::NameValue(syn::MetaNameValue {
+                    value:
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(s),
+                            ..
+                        }),
+                    ..
+                }) => s.clone(),
+                other => {
+                    return Err(syn::Error::new(
+                        other.span(),
+                        "expected #[js_name = \"name\"]",
+                    ))
+                }
+            };
+            js_name = Some(value.value());
+        } else {
+            retained.push(attr);
+        }
+    }
+    impl_fn.attrs = retained;
+
+    if is_skipped {
+        return Ok(None);
+    }
+
+    let sig = &impl_fn.sig;
+    let span = sig.span();
+    let ident = sig.ident.clone();
+    let receiver = sig.receiver();
+
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            sig.generics.span(),
+            "js_class fns can not be generic, use #[js_skip] to skip this fn",
+        ));
+    }
+
+    let is_mut = match receiver {
+        Some(r) if r.reference.is_none() => {
+            return Err(syn::Error::new(
+                r.span(),
+                "js_class methods should take &self or &mut self",
+            ))
+        }
+        Some(r) => r.mutability.is_some(),
+        None => false,
+    };
+
+    let kind =
+        if is_constructor || (ident == "new" && receiver.is_none() && !is_getter && !is_setter) {
+            Kind::Constructor
+        } else if is_getter {
+            Kind::Getter
+        } else if is_setter {
+            Kind::Setter
+        } else if receiver.is_some() {
+            Kind::Method
+        } else {
+            Kind::StaticMethod
+        };
+
+    let is_async = sig.asyncness.is_some();
+    match kind {
+        Kind::Constructor | Kind::Getter | Kind::Setter if is_async => {
+            return Err(syn::Error::new(
+                span,
+                "constructors, getters and setters can not be async",
+            ))
+        }
+        Kind::Method if is_async && is_mut => {
+            return Err(syn::Error::new(
+                span,
+                "async methods are called on a clone of the instance so they should take &self",
+            ))
+        }
+        Kind::Constructor if receiver.is_some() => {
+            return Err(syn::Error::new(span, "a constructor can not take self"))
+        }
+        Kind::Getter | Kind::Setter if receiver.is_none() => {
+            return Err(syn::Error::new(
+                span,
+                "getters and setters should take &self or &mut self",
+            ))
+        }
+        _ => {}
+    }
+
+    let mut args = vec![];
+    for input in sig.inputs.iter() {
+        if let FnArg::Typed(pat_type) = input {
+            let conversion = arg_conversion(&pat_type.ty);
+            if is_async && conversion == Conversion::Realm {
+                return Err(syn::Error::new(
+                    pat_type.ty.span(),
+                    "async methods can not take the realm as argument",
+                ));
+            }
+            args.push((conversion, (*pat_type.ty).clone()));
+        }
+    }
+
+    let ret = return_info(&sig.output);
+
+    let js_name = js_name.unwrap_or_else(|| {
+        let name = ident.to_string();
+        let name = match kind {
+            Kind::Setter => name.strip_prefix("set_").unwrap_or(&name).to_string(),
+            _ => name,
+        };
+        to_camel_case(name.as_str())
+    });
+
+    Ok(Some(JsFn {
+        kind,
+        js_name,
+        ident,
+        is_async,
+        is_mut,
+        args,
+        ret,
+        span,
+    }))
+}
+
+/// generate the conversion of JS arguments to rust values
+fn gen_args(
+    js_fn: &JsFn,
+    krate: &TokenStream2,
+) -> syn::Result<(Vec<TokenStream2>, Vec<TokenStream2>)> {
+    let mut decls = vec![];
+    let mut names = vec![];
+    let mut index: usize = 0;
+    for (x, (conversion, ty)) in js_fn.args.iter().enumerate() {
+        let name = format_ident!("arg{}", x);
+        match conversion {
+            Conversion::Realm => {
+                names.push(quote!(realm));
+                continue;
+            }
+            Conversion::Adapter => decls.push(quote! {
+                let #name = match _args.get(#index) {
+                    Some(value) => value.clone(),
+                    None => realm.create_undefined()?,
+                };
+            }),
+            _ => decls.push(quote! {
+                let #name = #krate::reflection::jsclass::arg::<#ty>(realm, _args, #index)?;
+            }),
+        }
+        names.push(quote!(#name));
+        index += 1;
+    }
+    Ok((decls, names))
+}
+
+/// generate the conversion of the result (named res) to a QuickJsValueAdapter
+fn gen_ret(ret: &ReturnInfo, krate: &TokenStream2) -> TokenStream2 {
+    let unwrap = if ret.is_result {
+        quote!(let res = res?;)
+    } else {
+        quote!()
+    };
+    let convert = match ret.conversion {
+        Conversion::Adapter => quote!(Ok(res)),
+        Conversion::Unit => quote! {
+            let _ = res;
+            realm.create_undefined()
+        },
+        _ => quote!(#krate::reflection::jsclass::ret(realm, &res)),
+    };
+    quote! {
+        #unwrap
+        #convert
+    }
+}
+
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+    match ty {
+        Type::Path(type_path) => type_path.path.segments.last(),
+        _ => None,
+    }
+}
+
+fn arg_conversion(ty: &Type) -> Conversion {
+    if let Type::Reference(reference) = ty {
+        if last_segment(&reference.elem).is_some_and(|s| s.ident == "QuickJsRealmAdapter") {
+            return Conversion::Realm;
+        }
+    }
+    value_conversion(ty)
+}
+
+fn value_conversion(ty: &Type) -> Conversion {
+    match ty {
+        Type::Tuple(tuple) if tuple.elems.is_empty() => Conversion::Unit,
+        _ => match last_segment(ty) {
+            Some(s) if s.ident == "QuickJsValueAdapter" => Conversion::Adapter,
+            Some(s) if s.ident == "Self" => Conversion::SelfType,
+            _ => Conversion::Serde,
+        },
+    }
+}
+
+fn return_info(output: &ReturnType) -> ReturnInfo {
+    match output {
+        ReturnType::Default => ReturnInfo {
+            is_result: false,
+            conversion: Conversion::Unit,
+        },
+        ReturnType::Type(_, ty) => {
+            if let Some(segment) = last_segment(ty) {
+                if segment.ident == "Result" {
+                    if let PathArguments::AngleBracketed(generic_args) = &segment.arguments {
+                        if let Some(GenericArgument::Type(ok_ty)) = generic_args.args.first() {
+                            return ReturnInfo {
+                                is_result: true,
+                                conversion: value_conversion(ok_ty),
+                            };
+                        }
+                    }
+                }
+            }
+            ReturnInfo {
+                is_result: false,
+                conversion: value_conversion(ty),
+            }
+        }
+    }
+}
+
+fn to_camel_case(name: &str) -> String {
+    let mut ret = String::with_capacity(name.len());
+    let mut upper_next = false;
+    for (x, c) in name.chars().enumerate() {
+        if c == '_' && x > 0 {
+            upper_next = true;
+        } else if upper_next {
+            ret.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            ret.push(c);
+        }
+    }
+    ret
+}
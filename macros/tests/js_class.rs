@@ -0,0 +1,137 @@
+use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+use quickjs_runtime::jsutils::{JsError, Script};
+use quickjs_runtime::quickjsrealmadapter::QuickJsRealmAdapter;
+use quickjs_runtime::quickjsvalueadapter::QuickJsValueAdapter;
+use quickjs_runtime::values::JsValueFacade;
+use quickjs_runtime_macros::js_class;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Clone)]
+struct Counter {
+    count: i32,
+    label: String,
+}
+
+#[js_class(namespace = "test.ns")]
+impl Counter {
+    fn new(start: i32, label: Option<String>) -> Result<Self, JsError> {
+        if start < 0 {
+            return Err(JsError::new_str("start should not be negative"));
+        }
+        Ok(Self {
+            count: start,
+            label: label.unwrap_or_else(|| "counter".to_string()),
+        })
+    }
+    fn increment(&mut self, by: Option<i32>) -> i32 {
+        self.count += by.unwrap_or(1);
+        self.count
+    }
+    fn to_point(&self) -> Point {
+        Point {
+            x: self.count,
+            y: -self.count,
+        }
+    }
+    fn add_point(&mut self, point: Point) {
+        self.count += point.x + point.y;
+    }
+    fn describe(&self, realm: &QuickJsRealmAdapter) -> Result<QuickJsValueAdapter, JsError> {
+        realm.create_string(format!("{}={}", self.label, self.count).as_str())
+    }
+    #[js_getter]
+    fn count(&self) -> i32 {
+        self.count
+    }
+    #[js_getter]
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+    #[js_setter]
+    fn set_label(&mut self, label: String) {
+        self.label = label;
+    }
+    #[js_name = "sum"]
+    fn static_sum(values: Vec<i32>) -> i32 {
+        values.iter().sum()
+    }
+    async fn delayed_count(&self) -> i32 {
+        self.count * 2
+    }
+    #[js_skip]
+    #[allow(dead_code)]
+    fn not_exported<T>(&self, _t: T) {}
+}
+
+#[test]
+fn test_js_class() {
+    let rt = QuickJsRuntimeBuilder::new().build();
+    rt.loop_realm_sync(None, |_rt, realm| {
+        Counter::install_js_class(realm).expect("could not install Counter");
+    });
+
+    let res = rt
+        .eval_sync(
+            None,
+            Script::new(
+                "js_class_test.js",
+                r#"
+                (() => {
+                let c = new test.ns.Counter(1);
+                c.increment();
+                c.increment(3);
+                c.label = "clicks";
+                c.addPoint({x: 10, y: -5});
+                let p = c.toPoint();
+                return [c.count, c.label, p.x, p.y, c.describe(), test.ns.Counter.sum([1, 2, 3]), c instanceof test.ns.Counter, typeof c.notExported].join(",");
+                })();
+                "#,
+            ),
+        )
+        .expect("script failed");
+    assert_eq!(res.get_str(), "10,clicks,10,-10,clicks=10,6,true,undefined");
+
+    let err = rt
+        .eval_sync(
+            None,
+            Script::new("js_class_test2.js", "new test.ns.Counter(-1);"),
+        )
+        .expect_err("constructor should fail");
+    assert!(err.get_message().contains("start should not be negative"));
+
+    let err = rt
+        .eval_sync(
+            None,
+            Script::new("js_class_test3.js", "new test.ns.Counter('a');"),
+        )
+        .expect_err("constructor should fail");
+    assert!(err.get_message().contains("invalid argument 0"));
+
+    let res = rt
+        .eval_sync(
+            None,
+            Script::new(
+                "js_class_test4.js",
+                "new test.ns.Counter(21).delayedCount();",
+            ),
+        )
+        .expect("script failed");
+    let JsValueFacade::JsPromise { cached_promise } = res else {
+        panic!("expected a promise");
+    };
+    let res = cached_promise
+        .get_promise_result_sync()
+        .expect("promise timed out")
+        .expect("promise rejected");
+    assert_eq!(res.get_i32(), 42);
+
+    rt.gc_sync();
+    let count = rt.exe_rt_task_in_event_loop(|_rt| Counter::js_instances().with(|i| i.len()));
+    assert_eq!(count, 0);
+}
@@ -1,9 +1,13 @@
 //! contains the QuickJsRuntimeBuilder which may be used to instantiate a new QuickjsRuntimeFacade
 
+use crate::bytecodecache::BytecodeCache;
 use crate::facades::QuickJsRuntimeFacade;
+#[cfg(feature = "console")]
+use crate::features::console::ConsoleSink;
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
 
+use crate::jsutils::importmap::ImportMap;
 use crate::jsutils::modules::{CompiledModuleLoader, NativeModuleLoader, ScriptModuleLoader};
 use crate::jsutils::{JsError, ScriptPreProcessor};
 use std::time::Duration;
@@ -32,6 +36,10 @@ pub struct QuickJsRuntimeBuilder {
     pub(crate) script_pre_processors: Vec<Box<dyn ScriptPreProcessor + Send>>,
     #[allow(clippy::type_complexity)]
     pub(crate) interrupt_handler: Option<Box<dyn Fn(&QuickJsRuntimeAdapter) -> bool + Send>>,
+    pub(crate) bytecode_cache: Option<Box<dyn BytecodeCache + Send>>,
+    pub(crate) import_map: Option<ImportMap>,
+    #[cfg(feature = "console")]
+    pub(crate) console_sink: Option<Box<dyn ConsoleSink + Send>>,
 }
 
 impl QuickJsRuntimeBuilder {
@@ -54,6 +62,10 @@ impl QuickJsRuntimeBuilder {
             runtime_init_hooks: vec![],
             script_pre_processors: vec![],
             interrupt_handler: None,
+            bytecode_cache: None,
+            import_map: None,
+            #[cfg(feature = "console")]
+            console_sink: None,
         }
     }
 
@@ -189,6 +201,42 @@ impl QuickJsRuntimeBuilder {
         self.interrupt_handler = Some(Box::new(interrupt_handler));
         self
     }
+
+    /// set a BytecodeCache, all evaluated scripts and modules loaded by a ScriptModuleLoader will be compiled once and read from the cache on subsequent loads
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+    /// use quickjs_runtime::bytecodecache::FileSystemBytecodeCache;
+    /// let rt = QuickJsRuntimeBuilder::new()
+    ///     .bytecode_cache(FileSystemBytecodeCache::new(std::env::temp_dir().join("my_app_bytecode")))
+    ///     .build();
+    /// ```
+    pub fn bytecode_cache<C: BytecodeCache + Send + 'static>(mut self, cache: C) -> Self {
+        self.bytecode_cache = Some(Box::new(cache));
+        self
+    }
+
+    /// set an import map (see <https://html.spec.whatwg.org/multipage/webappapis.html#import-maps>), it is applied to all imported specifiers before any module loader is consulted
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+    /// let rt = QuickJsRuntimeBuilder::new()
+    ///     .import_map(r#"{"imports": {"sdk": "/vendor/sdk@2/index.js"}, "scopes": {"/legacy/": {"sdk": "/vendor/sdk@1/index.js"}}}"#)
+    ///     .expect("invalid import map")
+    ///     .build();
+    /// ```
+    pub fn import_map(mut self, json: &str) -> Result<Self, JsError> {
+        self.import_map = Some(ImportMap::parse(json)?);
+        Ok(self)
+    }
+
+    /// set the ConsoleSink which receives all console output of scripts, the default sink writes to the log crate
+    /// see [ConsoleSink](../features/console/trait.ConsoleSink.html) for an example
+    #[cfg(feature = "console")]
+    pub fn console_sink<S: ConsoleSink + Send + 'static>(mut self, sink: S) -> Self {
+        self.console_sink = Some(Box::new(sink));
+        self
+    }
 }
 
 impl Default for QuickJsRuntimeBuilder {
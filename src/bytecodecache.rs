@@ -0,0 +1,458 @@
+//! a cache for compiled bytecode of scripts and modules
+//!
+//! when a BytecodeCache is set on the [QuickJsRuntimeBuilder](../builder/struct.QuickJsRuntimeBuilder.html) all scripts
+//! which are evaluated and all modules which are loaded by a ScriptModuleLoader are compiled to bytecode once, subsequent loads
+//! of the same code (also from another process) read the bytecode from the cache instead of parsing the source again
+//!
+//! the cache is keyed by the path of the script, a hash of the source, the engine flavour (bellard or quickjs-ng), a fingerprint
+//! of the bytecode format of the engine and the version of this crate
+//!
+//! the hash is computed before the ScriptPreProcessors and the TypeScript transpiler are run, these are skipped when the
+//! bytecode is read from the cache (the source map of transpiled TypeScript is stored with the bytecode), so pre-processors
+//! should produce the same code for the same source while a cache is used
+//!
+//! # Security
+//! bytecode is read with JS_ReadObject which does not validate its input, a manipulated cache entry can crash the process or
+//! execute arbitrary code, so a BytecodeCache (e.g. the directory of a FileSystemBytecodeCache) must only be writable by trusted users
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::bytecodecache::FileSystemBytecodeCache;
+//! use quickjs_runtime::jsutils::Script;
+//!
+//! let dir = std::env::temp_dir().join("quickjs_runtime_doc_bytecode_cache");
+//! let rt = QuickJsRuntimeBuilder::new()
+//!     .bytecode_cache(FileSystemBytecodeCache::new(dir))
+//!     .build();
+//! let res = rt.eval_sync(None, Script::new("cached.js", "(7 * 6);")).expect("script failed");
+//! assert_eq!(res.get_i32(), 42);
+//! ```
+
+use crate::jsutils::{JsError, Script};
+use crate::quickjs_utils::compile::{compile, from_bytecode, to_bytecode};
+use crate::quickjs_utils::modules::{compile_module, resolve_module};
+use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use libquickjs_sys as q;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+#[cfg(feature = "bellard")]
+const ENGINE_FLAVOUR: &str = "bellard";
+#[cfg(all(feature = "quickjs-ng", not(feature = "bellard")))]
+const ENGINE_FLAVOUR: &str = "quickjs-ng";
+
+/// a script which uses most of the syntax of the language, the hash of its bytecode identifies the bytecode format of the engine
+const ENGINE_PROBE: &str = r#"
+class Probe extends Array {
+    static #count = 0n;
+    #items = [];
+    constructor(...args) { super(...args); Probe.#count++; }
+    get first() { return this.#items?.[0] ?? null; }
+    *keysOf(obj) { for (const key in obj) { yield key; } }
+    async load({a, b: [c, ...d] = []}, e = `${a}-${c}`) {
+        try { await Promise.all(d); } catch { return e; } finally { delete this.x; }
+        return typeof a === 'string' ? /a+b/giu.test(a) : a ** 2 >>> 1;
+    }
+}
+label: for (let i = 0, j; i < 10; i++) { switch (i % 3) { case 0: continue label; default: j = {i, ...{j}}; } }
+new Probe(1, 2, 3).keysOf({x: 1.5, y: -0, z: void 0});
+"#;
+
+/// a fingerprint of the bytecode format of the engine this crate was built with
+///
+/// the crate version and the engine flavour do not identify the engine, a cargo update of hirofa-quickjs-sys may change
+/// the bytecode format (BC_VERSION, opcodes or atoms) and JS_ReadObject does not validate its input, so the hash of the
+/// bytecode of a probe script (compiled once per process in a separate runtime) is part of every key
+fn engine_fingerprint() -> u64 {
+    static FINGERPRINT: OnceLock<u64> = OnceLock::new();
+    *FINGERPRINT.get_or_init(|| unsafe {
+        let rt = q::JS_NewRuntime();
+        let ctx = q::JS_NewContext(rt);
+        let bytecode = match compile(ctx, Script::new("engine_probe.js", ENGINE_PROBE)) {
+            Ok(compiled) => to_bytecode(ctx, &compiled),
+            Err(e) => {
+                // should not happen, the error still identifies the engine
+                log::error!("could not compile the engine probe: {}", e);
+                e.to_string().into_bytes()
+            }
+        };
+        q::JS_FreeContext(ctx);
+        q::JS_FreeRuntime(rt);
+        fnv1a(&bytecode)
+    })
+}
+
+/// the key under which bytecode is stored in a BytecodeCache
+pub struct BytecodeCacheKey {
+    path: String,
+    content_hash: u64,
+    flavour: &'static str,
+    engine_fingerprint: u64,
+}
+
+impl BytecodeCacheKey {
+    pub fn new(path: &str, code: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            content_hash: fnv1a(code.as_bytes()),
+            flavour: ENGINE_FLAVOUR,
+            engine_fingerprint: engine_fingerprint(),
+        }
+    }
+    /// the path of the script or module
+    pub fn get_path(&self) -> &str {
+        self.path.as_str()
+    }
+    /// a hash of the source (before pre-processing) which was compiled, this is stable between processes
+    pub fn get_content_hash(&self) -> u64 {
+        self.content_hash
+    }
+    /// the engine flavour the bytecode was compiled with (bellard or quickjs-ng)
+    pub fn get_flavour(&self) -> &str {
+        self.flavour
+    }
+    /// a fingerprint of the bytecode format of the engine, this changes when an update of the engine changes its bytecode
+    pub fn get_engine_fingerprint(&self) -> u64 {
+        self.engine_fingerprint
+    }
+    /// a stable name for the cache entry of a path, this does not include the content hash so a changed script replaces its old entry
+    pub fn get_entry_name(&self) -> String {
+        let mut id = self.path.as_bytes().to_vec();
+        id.push(0);
+        id.extend_from_slice(self.flavour.as_bytes());
+        id.push(0);
+        id.extend_from_slice(&self.engine_fingerprint.to_le_bytes());
+        id.push(0);
+        id.extend_from_slice(env!("CARGO_PKG_VERSION").as_bytes());
+        format!("{:016x}.qjsc", fnv1a(&id))
+    }
+}
+
+/// stable 64 bit FNV-1a hash, std's DefaultHasher is not guaranteed to be stable between rust versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// a store for compiled bytecode
+///
+/// the stored bytes are opaque to the cache, they contain the bytecode and the source map of transpiled TypeScript
+pub trait BytecodeCache {
+    /// get the bytecode for a key, should return None if the cached entry was compiled from different code (see BytecodeCacheKey::get_content_hash)
+    fn get(&self, key: &BytecodeCacheKey) -> Option<Vec<u8>>;
+    /// store the bytecode for a key
+    fn put(&self, key: &BytecodeCacheKey, bytecode: &[u8]);
+}
+
+/// a BytecodeCache which stores bytecode as files in a directory
+///
+/// the directory must only be writable by trusted users, the bytecode is not validated when it is read
+pub struct FileSystemBytecodeCache {
+    dir: PathBuf,
+}
+
+impl FileSystemBytecodeCache {
+    /// create a new FileSystemBytecodeCache, the dir is created when the first entry is stored
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self { dir: dir.into() }
+    }
+    fn entry_path(&self, key: &BytecodeCacheKey) -> PathBuf {
+        self.dir.join(key.get_entry_name())
+    }
+}
+
+impl BytecodeCache for FileSystemBytecodeCache {
+    fn get(&self, key: &BytecodeCacheKey) -> Option<Vec<u8>> {
+        let mut contents = std::fs::read(self.entry_path(key)).ok()?;
+        // first 8 bytes are the content hash
+        if contents.len() <= 8 {
+            return None;
+        }
+        let mut hash_bytes = [0_u8; 8];
+        hash_bytes.copy_from_slice(&contents[..8]);
+        if u64::from_le_bytes(hash_bytes) != key.get_content_hash() {
+            return None;
+        }
+        contents.drain(..8);
+        Some(contents)
+    }
+
+    fn put(&self, key: &BytecodeCacheKey, bytecode: &[u8]) {
+        let path = self.entry_path(key);
+        let tmp_path = path.with_extension(format!("tmp{}", thread_id::get()));
+        let mut contents = Vec::with_capacity(bytecode.len() + 8);
+        contents.extend_from_slice(&key.get_content_hash().to_le_bytes());
+        contents.extend_from_slice(bytecode);
+        // write to a tmp file first so concurrent readers never see a partial entry
+        let res = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| std::fs::write(&tmp_path, &contents))
+            .and_then(|_| std::fs::rename(&tmp_path, &path));
+        if let Err(e) = res {
+            log::error!(
+                "could not store bytecode for {} in {}: {}",
+                key.get_path(),
+                path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// write a cache entry, the length of the source map (0 if there is none) followed by the source map and the bytecode
+fn write_entry(bytecode: &[u8], map: Option<&str>) -> Vec<u8> {
+    let map = map.unwrap_or_default().as_bytes();
+    let mut entry = Vec::with_capacity(4 + map.len() + bytecode.len());
+    entry.extend_from_slice(&(map.len() as u32).to_le_bytes());
+    entry.extend_from_slice(map);
+    entry.extend_from_slice(bytecode);
+    entry
+}
+
+/// read a cache entry as written by write_entry, returns the bytecode and the source map
+fn read_entry(entry: &[u8]) -> Option<(&[u8], Option<&str>)> {
+    let mut len_bytes = [0_u8; 4];
+    len_bytes.copy_from_slice(entry.get(..4)?);
+    let map_end = 4 + u32::from_le_bytes(len_bytes) as usize;
+    let map = std::str::from_utf8(entry.get(4..map_end)?).ok()?;
+    let bytecode = entry.get(map_end..)?;
+    if bytecode.is_empty() {
+        return None;
+    }
+    Some((bytecode, if map.is_empty() { None } else { Some(map) }))
+}
+
+/// pre-process and compile a script or module, if the runtime has a BytecodeCache the bytecode is read from or stored in that
+/// cache, when it is read from the cache the ScriptPreProcessors and the TypeScript transpiler are not run
+///
+/// a module read from the cache is resolved (its imports are loaded) before it is returned
+/// # Safety
+/// please ensure the corresponding QuickJSContext is still valid
+pub(crate) unsafe fn compile_cached(
+    context: *mut q::JSContext,
+    script: Script,
+    module: bool,
+) -> Result<QuickJsValueAdapter, JsError> {
+    QuickJsRuntimeAdapter::do_with(|q_js_rt| {
+        let do_compile = |script: Script| {
+            let script = QuickJsRuntimeAdapter::pre_process(script)?;
+            let map = script.get_map().map(|map| map.to_string());
+            let compiled = if module {
+                compile_module(context, script)?
+            } else {
+                compile(context, script)?
+            };
+            Ok::<_, JsError>((compiled, map))
+        };
+        match q_js_rt.bytecode_cache.as_ref() {
+            None => Ok(do_compile(script)?.0),
+            Some(cache) => {
+                // keyed on the source before pre-processing so a hit does not need to run the pre-processors
+                let key = BytecodeCacheKey::new(script.get_path(), script.get_code());
+                if let Some(entry) = cache.get(&key) {
+                    match read_entry(&entry) {
+                        Some((bytecode, map)) => match from_bytecode(context, bytecode) {
+                            Ok(compiled) => {
+                                log::trace!("read bytecode for {} from cache", key.get_path());
+                                if module {
+                                    resolve_module(context, &compiled)?;
+                                }
+                                #[cfg(feature = "typescript")]
+                                if let Some(map) = map {
+                                    crate::typescript::register_source_map(key.get_path(), map);
+                                }
+                                #[cfg(not(feature = "typescript"))]
+                                let _ = map;
+                                return Ok(compiled);
+                            }
+                            Err(e) => {
+                                log::error!(
+                                    "could not read cached bytecode for {}, recompiling: {}",
+                                    key.get_path(),
+                                    e
+                                );
+                            }
+                        },
+                        None => {
+                            log::error!("invalid cache entry for {}, recompiling", key.get_path());
+                        }
+                    }
+                }
+                let (compiled, map) = do_compile(script)?;
+                cache.put(
+                    &key,
+                    &write_entry(&to_bytecode(context, &compiled), map.as_deref()),
+                );
+                Ok(compiled)
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
+    use crate::bytecodecache::{
+        fnv1a, BytecodeCache, BytecodeCacheKey, FileSystemBytecodeCache, ENGINE_PROBE,
+    };
+    use crate::jsutils::modules::ScriptModuleLoader;
+    use crate::jsutils::{JsError, Script, ScriptPreProcessor};
+    use crate::quickjs_utils::compile::{compile, to_bytecode};
+    use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct MemCache {
+        entries: Arc<Mutex<HashMap<String, (u64, Vec<u8>)>>>,
+        hits: Arc<Mutex<usize>>,
+    }
+
+    impl BytecodeCache for MemCache {
+        fn get(&self, key: &BytecodeCacheKey) -> Option<Vec<u8>> {
+            let entries = &*self.entries.lock().unwrap();
+            match entries.get(&key.get_entry_name()) {
+                Some((hash, bytecode)) if *hash == key.get_content_hash() => {
+                    *self.hits.lock().unwrap() += 1;
+                    Some(bytecode.clone())
+                }
+                _ => None,
+            }
+        }
+
+        fn put(&self, key: &BytecodeCacheKey, bytecode: &[u8]) {
+            self.entries.lock().unwrap().insert(
+                key.get_entry_name(),
+                (key.get_content_hash(), bytecode.to_vec()),
+            );
+        }
+    }
+
+    struct TestModuleLoader {}
+    impl ScriptModuleLoader for TestModuleLoader {
+        fn normalize_path(
+            &self,
+            _realm: &QuickJsRealmAdapter,
+            _ref_path: &str,
+            path: &str,
+        ) -> Option<String> {
+            Some(path.to_string())
+        }
+
+        fn load_module(&self, _realm: &QuickJsRealmAdapter, _absolute_path: &str) -> String {
+            "export const foo = 12;".to_string()
+        }
+    }
+
+    /// counts the scripts it processes, these should only be the scripts which are not cached yet
+    #[derive(Clone, Default)]
+    struct CountingPreProcessor {
+        count: Arc<Mutex<usize>>,
+    }
+
+    impl ScriptPreProcessor for CountingPreProcessor {
+        fn process(&self, _script: &mut Script) -> Result<(), JsError> {
+            *self.count.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cache_hits() {
+        let cache = MemCache::default();
+        let pre_processor = CountingPreProcessor::default();
+
+        for run in 0..2 {
+            let rt = QuickJsRuntimeBuilder::new()
+                .bytecode_cache(cache.clone())
+                .script_pre_processor(pre_processor.clone())
+                .script_module_loader(TestModuleLoader {})
+                .build();
+            let res = rt
+                .eval_sync(None, Script::new("cache_test.js", "(5 * 5);"))
+                .expect("script failed");
+            assert_eq!(res.get_i32(), 25);
+            rt.eval_module_sync(
+                None,
+                Script::new(
+                    "cache_test_mod.mjs",
+                    "import {foo} from 'cache_dep.mjs';\nglobalThis.fooRes = foo;",
+                ),
+            )
+            .expect("module failed");
+            let res = rt
+                .eval_sync(None, Script::new("cache_test_res.js", "(fooRes);"))
+                .expect("script failed");
+            assert_eq!(res.get_i32(), 12);
+            if run == 0 {
+                assert_eq!(*cache.hits.lock().unwrap(), 0);
+            }
+        }
+        // second run should have read all four scripts from the cache without pre-processing them
+        assert_eq!(*cache.hits.lock().unwrap(), 4);
+        assert_eq!(*pre_processor.count.lock().unwrap(), 4);
+        assert_eq!(cache.entries.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_engine_fingerprint() {
+        // the fingerprint is the hash of the bytecode of the probe as compiled by the engine of a regular runtime
+        let rt = QuickJsRuntimeBuilder::new().build();
+        let bytecode = rt.exe_rt_task_in_event_loop(|q_js_rt| unsafe {
+            let realm = q_js_rt.get_main_realm();
+            let compiled = compile(realm.context, Script::new("engine_probe.js", ENGINE_PROBE))
+                .expect("probe failed to compile");
+            to_bytecode(realm.context, &compiled)
+        });
+        let key = BytecodeCacheKey::new("test.js", "1 + 1;");
+        assert_eq!(key.get_engine_fingerprint(), fnv1a(&bytecode));
+        assert_eq!(
+            key.get_engine_fingerprint(),
+            BytecodeCacheKey::new("other.js", "1 + 2;").get_engine_fingerprint()
+        );
+    }
+
+    #[test]
+    fn test_fs_cache() {
+        let dir = std::env::temp_dir().join(format!(
+            "quickjs_runtime_test_bytecode_cache_{}",
+            std::process::id()
+        ));
+        let cache = FileSystemBytecodeCache::new(dir.clone());
+        let key = BytecodeCacheKey::new("test.js", "1 + 1;");
+        assert!(cache.get(&key).is_none());
+        cache.put(&key, &[1, 2, 3]);
+        assert_eq!(cache.get(&key), Some(vec![1, 2, 3]));
+        // changed code should not read the old entry
+        let changed_key = BytecodeCacheKey::new("test.js", "1 + 2;");
+        assert_eq!(key.get_entry_name(), changed_key.get_entry_name());
+        assert!(cache.get(&changed_key).is_none());
+
+        let rt = QuickJsRuntimeBuilder::new()
+            .bytecode_cache(FileSystemBytecodeCache::new(dir.clone()))
+            .build();
+        let res = rt
+            .eval_sync(None, Script::new("fs_cache_test.js", "(6 * 7);"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 42);
+        let entry =
+            dir.join(BytecodeCacheKey::new("fs_cache_test.js", "(6 * 7);").get_entry_name());
+        assert!(entry.exists());
+        drop(rt);
+
+        let rt = QuickJsRuntimeBuilder::new()
+            .bytecode_cache(FileSystemBytecodeCache::new(dir.clone()))
+            .build();
+        let res = rt
+            .eval_sync(None, Script::new("fs_cache_test.js", "(6 * 7);"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 42);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,185 @@
+//! cancellation of jobs which are queued on the EventLoop
+//!
+//! a CancellationToken can be passed to e.g. [eval_with_token](../facades/struct.QuickJsRuntimeFacade.html#method.eval_with_token),
+//! when the token is cancelled before the job started the job is skipped, when the job is already running the script is interrupted,
+//! in both cases the result will be a JsError with name "CancellationError"
+//!
+//! the futures returned by eval, eval_module, invoke_function and loop_realm cancel their job when they are dropped before
+//! they completed
+//!
+//! the EventLoop queue does not support removing a job, a job which is cancelled before it started stays queued until the
+//! EventLoop reaches it and is skipped then, so the job and what it captured (e.g. the Script or the arguments) are only
+//! freed when the jobs queued before it have run
+//!
+//! # Example
+//! ```rust
+//! use futures::executor::block_on;
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::cancellation::CancellationToken;
+//! use quickjs_runtime::jsutils::Script;
+//! use std::time::Duration;
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! let token = CancellationToken::new();
+//! let fut = rt.eval_with_token(None, Script::new("endless.js", "while(true){}"), token.clone());
+//! std::thread::sleep(Duration::from_millis(10));
+//! token.cancel();
+//! let res = block_on(fut);
+//! assert_eq!(res.expect_err("script should have been cancelled").get_name(), "CancellationError");
+//! ```
+
+use crate::jsutils::JsError;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// a token which can be used to cancel a job, clones of a token share their state
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// cancel the job(s) this token was passed to
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+pub(crate) fn new_cancellation_error() -> JsError {
+    JsError::new(
+        "CancellationError".to_string(),
+        "the job was cancelled".to_string(),
+        "".to_string(),
+    )
+}
+
+/// replace the error of a job which was interrupted because its token was cancelled with a CancellationError
+pub(crate) fn map_cancelled<R>(
+    token: &CancellationToken,
+    res: Result<R, JsError>,
+) -> Result<R, JsError> {
+    res.map_err(|err| {
+        if token.is_cancelled() {
+            log::debug!("job interrupted because it was cancelled: {}", err);
+            new_cancellation_error()
+        } else {
+            err
+        }
+    })
+}
+
+/// a Future which cancels its token when it is dropped before it completed
+pub(crate) struct CancelOnDrop<F: Future + Unpin> {
+    future: F,
+    token: CancellationToken,
+    done: bool,
+}
+
+impl<F: Future + Unpin> CancelOnDrop<F> {
+    pub(crate) fn new(future: F, token: CancellationToken) -> Self {
+        Self {
+            future,
+            token,
+            done: false,
+        }
+    }
+}
+
+impl<F: Future + Unpin> Future for CancelOnDrop<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let res = Pin::new(&mut self.future).poll(cx);
+        if res.is_ready() {
+            self.done = true;
+        }
+        res
+    }
+}
+
+impl<F: Future + Unpin> Drop for CancelOnDrop<F> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.token.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::cancellation::CancellationToken;
+    use crate::facades::tests::init_test_rt;
+    use crate::jsutils::Script;
+    use futures::executor::block_on;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cancellation() {
+        let rt = init_test_rt();
+
+        // cancelled before the job started
+        let token = CancellationToken::new();
+        token.cancel();
+        let res = block_on(rt.eval_with_token(
+            None,
+            Script::new("test_cancel1.js", "globalThis.cancelled_ran = true;"),
+            token,
+        ));
+        assert_eq!(
+            res.expect_err("job should have been cancelled").get_name(),
+            "CancellationError"
+        );
+
+        // dropping the future removes the job
+        let blocker = rt.eval(
+            None,
+            Script::new(
+                "test_cancel2.js",
+                "let start = Date.now(); while (Date.now() - start < 100) {}",
+            ),
+        );
+        let dropped = rt.eval(
+            None,
+            Script::new("test_cancel3.js", "globalThis.cancelled_ran = true;"),
+        );
+        drop(dropped);
+        block_on(blocker).expect("script failed");
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new("test_cancel4.js", "typeof globalThis.cancelled_ran;"),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_str(), "undefined");
+
+        // a running job is interrupted
+        let token = CancellationToken::new();
+        let fut = rt.eval_with_token(
+            None,
+            Script::new("test_cancel5.js", "while(true) {}"),
+            token.clone(),
+        );
+        std::thread::sleep(Duration::from_millis(50));
+        token.cancel();
+        let res = block_on(fut);
+        assert_eq!(
+            res.expect_err("job should have been cancelled").get_name(),
+            "CancellationError"
+        );
+
+        // the runtime remains usable
+        let res = rt
+            .eval_sync(None, Script::new("test_cancel6.js", "1 + 1;"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 2);
+    }
+}
@@ -1,7 +1,9 @@
 //! contains the QuickJsRuntimeFacade
 
 use crate::builder::QuickJsRuntimeBuilder;
+use crate::cancellation::{map_cancelled, new_cancellation_error, CancelOnDrop, CancellationToken};
 use crate::jsutils::{JsError, Script};
+use crate::profiler::{Profile, DEFAULT_SAMPLE_INTERVAL};
 use crate::quickjs_utils::{functions, objects};
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsruntimeadapter::{
@@ -9,6 +11,8 @@ use crate::quickjsruntimeadapter::{
     ScriptModuleLoaderAdapter, QJS_RT,
 };
 use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::realmquota::{RealmQuota, RealmUsage};
+use crate::realmtemplate::{RealmTemplate, RealmTemplateBuilder};
 use crate::reflection;
 use crate::values::JsValueFacade;
 use either::{Either, Left, Right};
@@ -22,6 +26,7 @@ use std::num::NonZeroUsize;
 use std::pin::Pin;
 use std::rc::Rc;
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 use tokio::task::JoinError;
 
 lazy_static! {
@@ -202,7 +207,10 @@ impl QuickJsRuntimeFacade {
             feature = "settimeout",
             feature = "setinterval",
             feature = "console",
-            feature = "setimmediate"
+            feature = "setimmediate",
+            feature = "eventtarget",
+            feature = "webglobals",
+            feature = "web-encoding"
         ))]
         {
             let res = crate::features::init(&ret);
@@ -251,6 +259,12 @@ impl QuickJsRuntimeFacade {
                     ));
                 }
                 q_js_rt.script_pre_processors = builder.script_pre_processors;
+                q_js_rt.bytecode_cache = builder.bytecode_cache;
+                q_js_rt.import_map = builder.import_map;
+                #[cfg(feature = "console")]
+                if let Some(console_sink) = builder.console_sink {
+                    q_js_rt.console_sink = console_sink;
+                }
 
                 if let Some(limit) = builder.opt_memory_limit_bytes {
                     unsafe {
@@ -290,6 +304,21 @@ impl QuickJsRuntimeFacade {
         self.loop_async(|rt| rt.memory_usage()).await
     }
 
+    /// start the sampling CPU profiler, see the [profiler](../profiler/index.html) mod for an example
+    pub fn start_profiling(&self) {
+        self.start_profiling_with_interval(DEFAULT_SAMPLE_INTERVAL)
+    }
+
+    /// start the sampling CPU profiler with a custom interval between samples
+    pub fn start_profiling_with_interval(&self, interval: Duration) {
+        self.exe_rt_task_in_event_loop(move |rt| rt.start_profiling(interval))
+    }
+
+    /// stop the sampling CPU profiler and get the Profile, which can be written as a .cpuprofile or as folded stacks
+    pub fn stop_profiling(&self) -> Profile {
+        self.exe_rt_task_in_event_loop(|rt| rt.stop_profiling())
+    }
+
     pub(crate) fn clear_contexts(&self) {
         log::trace!("EsRuntime::clear_contexts");
         self.exe_task_in_event_loop(|| {
@@ -548,12 +577,13 @@ fn loop_realm_func<
     let res: Either<R, C> = QuickJsRuntimeAdapter::do_with(|q_js_rt| {
         if let Some(realm_str) = realm_name.as_ref() {
             if let Some(realm) = q_js_rt.get_realm(realm_str) {
-                Left(consumer(q_js_rt, realm))
+                Left(realm.run_job(q_js_rt, || consumer(q_js_rt, realm)))
             } else {
                 Right(consumer)
             }
         } else {
-            Left(consumer(q_js_rt, q_js_rt.get_main_realm()))
+            let realm = q_js_rt.get_main_realm();
+            Left(realm.run_job(q_js_rt, || consumer(q_js_rt, realm)))
         }
     });
 
@@ -597,7 +627,7 @@ fn loop_realm_func<
                     }
                 }
 
-                consumer(q_js_rt, realm)
+                realm.run_job(q_js_rt, || consumer(q_js_rt, realm))
             })
         }
     }
@@ -616,6 +646,67 @@ impl QuickJsRuntimeFacade {
         self.exe_task_in_event_loop(move || QuickJsRuntimeAdapter::remove_context(name.as_str()))
     }
 
+    /// build a [RealmTemplate](../realmtemplate/struct.RealmTemplate.html), see the [realmtemplate](../realmtemplate/index.html) module for an example
+    pub fn create_realm_template(
+        &self,
+        builder: RealmTemplateBuilder,
+    ) -> Result<RealmTemplate, JsError> {
+        self.inner.event_loop.exe(move || builder.build_template())
+    }
+
+    /// create a new realm and initialise it from a [RealmTemplate](../realmtemplate/struct.RealmTemplate.html)
+    pub fn create_realm_from_template(
+        &self,
+        name: &str,
+        template: &RealmTemplate,
+    ) -> Result<(), JsError> {
+        let name = name.to_string();
+        let template = template.clone();
+        self.inner.event_loop.exe(move || {
+            QuickJsRuntimeAdapter::create_context(name.as_str())?;
+            let res = QuickJsRuntimeAdapter::do_with(|q_js_rt| {
+                template.apply(q_js_rt.get_context(name.as_str()))
+            });
+            if res.is_err() {
+                // don't leave a half initialised realm behind
+                let _ = QuickJsRuntimeAdapter::remove_context(name.as_str());
+            }
+            res
+        })
+    }
+
+    /// set or clear the quota of a realm, see the [realmquota](../realmquota/index.html) module for an example
+    pub fn set_realm_quota(&self, name: &str, quota: Option<RealmQuota>) -> Result<(), JsError> {
+        let name = name.to_string();
+        self.exe_rt_task_in_event_loop(move |rt| match rt.get_realm(name.as_str()) {
+            Some(realm) => {
+                realm.set_quota(quota);
+                Ok(())
+            }
+            None => Err(JsError::new_string(format!("no such realm: {name}"))),
+        })
+    }
+
+    /// get the resources used by a realm, returns None if the realm does not exist
+    pub fn get_realm_usage(&self, name: &str) -> Option<RealmUsage> {
+        let name = name.to_string();
+        self.exe_rt_task_in_event_loop(move |rt| {
+            rt.get_realm(name.as_str()).map(|realm| realm.get_usage())
+        })
+    }
+
+    /// reset the accounted heap bytes and cpu time of a realm, this also accepts jobs again after the quota was exceeded
+    pub fn reset_realm_usage(&self, name: &str) -> Result<(), JsError> {
+        let name = name.to_string();
+        self.exe_rt_task_in_event_loop(move |rt| match rt.get_realm(name.as_str()) {
+            Some(realm) => {
+                realm.reset_usage();
+                Ok(())
+            }
+            None => Err(JsError::new_string(format!("no such realm: {name}"))),
+        })
+    }
+
     pub fn has_realm(&self, name: &str) -> Result<bool, JsError> {
         let name = name.to_string();
         self.exe_rt_task_in_event_loop(move |rt| Ok(rt.get_realm(name.as_str()).is_some()))
@@ -671,6 +762,7 @@ impl QuickJsRuntimeFacade {
 
     /// add a job to the eventloop which will be executed async (placed at end of eventloop)
     /// returns a Future which can be waited ob with .await
+    /// when the Future is dropped before it completed the job is cancelled
     pub fn loop_realm<
         R: Send + 'static,
         C: FnOnce(&QuickJsRuntimeAdapter, &QuickJsRealmAdapter) -> R + Send + 'static,
@@ -679,8 +771,39 @@ impl QuickJsRuntimeFacade {
         realm_name: Option<&str>,
         consumer: C,
     ) -> Pin<Box<dyn Future<Output = R> + Send>> {
+        let token = CancellationToken::new();
+        let fut = CancelOnDrop::new(
+            self.loop_realm_with_token(realm_name, token.clone(), consumer),
+            token,
+        );
+        // the token is only cancelled when the future is dropped so nobody can observe the Err
+        Box::pin(async move { fut.await.expect("job was cancelled") })
+    }
+
+    /// add a job to the eventloop which will be executed async (placed at end of eventloop)
+    /// when the token is cancelled before the job started the job is skipped and the result will be a CancellationError,
+    /// when the job is already running the script is interrupted
+    ///
+    /// a skipped job stays queued (and keeps what it captured) until the jobs before it have run
+    pub fn loop_realm_with_token<
+        R: Send + 'static,
+        C: FnOnce(&QuickJsRuntimeAdapter, &QuickJsRealmAdapter) -> R + Send + 'static,
+    >(
+        &self,
+        realm_name: Option<&str>,
+        token: CancellationToken,
+        consumer: C,
+    ) -> Pin<Box<dyn Future<Output = Result<R, JsError>> + Send>> {
         let realm_name = realm_name.map(|s| s.to_string());
-        Box::pin(self.add_task_to_event_loop(|| loop_realm_func(realm_name, consumer)))
+        Box::pin(self.add_task_to_event_loop(move || {
+            if token.is_cancelled() {
+                log::debug!("job was cancelled before it started");
+                return Err(new_cancellation_error());
+            }
+            Ok(loop_realm_func(realm_name, move |rt, realm| {
+                rt.with_cancellation(&token, || consumer(rt, realm))
+            }))
+        }))
     }
 
     /// add a job for a specific realm without expecting a result.
@@ -714,13 +837,31 @@ impl QuickJsRuntimeFacade {
         realm_name: Option<&str>,
         script: Script,
     ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
-        self.loop_realm(realm_name, |_rt, realm| {
-            let res = realm.eval(script);
-            match res {
+        let token = CancellationToken::new();
+        Box::pin(CancelOnDrop::new(
+            self.eval_with_token(realm_name, script, token.clone()),
+            token,
+        ))
+    }
+
+    /// Evaluate a script asynchronously, the job can be cancelled with a CancellationToken
+    /// see the [cancellation](../cancellation/index.html) module for an example
+    #[allow(clippy::type_complexity)]
+    pub fn eval_with_token(
+        &self,
+        realm_name: Option<&str>,
+        script: Script,
+        token: CancellationToken,
+    ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
+        let job_token = token.clone();
+        let fut = self.loop_realm_with_token(realm_name, token, move |_rt, realm| {
+            let res = realm.with_quota(|| realm.eval(script));
+            match map_cancelled(&job_token, res) {
                 Ok(jsvr) => realm.to_js_value_facade(&jsvr),
                 Err(e) => Err(e),
             }
-        })
+        });
+        Box::pin(async move { fut.await? })
     }
 
     /// Evaluate a script and return the result synchronously
@@ -740,7 +881,7 @@ impl QuickJsRuntimeFacade {
         script: Script,
     ) -> Result<JsValueFacade, JsError> {
         self.loop_realm_sync(realm_name, |_rt, realm| {
-            let res = realm.eval(script);
+            let res = realm.with_quota(|| realm.eval(script));
             match res {
                 Ok(jsvr) => realm.to_js_value_facade(&jsvr),
                 Err(e) => Err(e),
@@ -788,7 +929,7 @@ impl QuickJsRuntimeFacade {
         script: Script,
     ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
         self.loop_realm(realm_name, |_rt, realm| {
-            let res = realm.eval_module(script)?;
+            let res = realm.with_quota(|| realm.eval_module(script))?;
             realm.to_js_value_facade(&res)
         })
     }
@@ -831,7 +972,7 @@ impl QuickJsRuntimeFacade {
         script: Script,
     ) -> Result<JsValueFacade, JsError> {
         self.loop_realm_sync(realm_name, |_rt, realm| {
-            let res = realm.eval_module(script)?;
+            let res = realm.with_quota(|| realm.eval_module(script))?;
             realm.to_js_value_facade(&res)
         })
     }
@@ -870,11 +1011,13 @@ impl QuickJsRuntimeFacade {
                 .map(|s| s.as_str())
                 .collect::<Vec<&str>>();
 
-            let res = realm.invoke_function_by_name(
-                namespace.as_slice(),
-                movable_method_name.as_str(),
-                args_adapters.as_slice(),
-            );
+            let res = realm.with_quota(|| {
+                realm.invoke_function_by_name(
+                    namespace.as_slice(),
+                    movable_method_name.as_str(),
+                    args_adapters.as_slice(),
+                )
+            });
 
             match res {
                 Ok(jsvr) => realm.to_js_value_facade(&jsvr),
@@ -887,13 +1030,16 @@ impl QuickJsRuntimeFacade {
     /// N.B. func_name is not a &str because of <https://github.com/rust-lang/rust/issues/56238> (i think)
     /// # example
     /// ```rust
+    /// use futures::executor::block_on;
     /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
     /// use quickjs_runtime::jsutils::Script;
     /// use quickjs_runtime::values::JsValueConvertable;
     /// let rt = QuickJsRuntimeBuilder::new().build();
     /// let script = Script::new("my_file.es", "this.com = {my: {methodA: function(a, b){return a*b;}}};");
     /// rt.eval_sync(None, script).ok().expect("script failed");
-    /// rt.invoke_function(None, &["com", "my"], "methodA", vec![7.to_js_value_facade(), 5.to_js_value_facade()]);
+    /// // in real life you would .await this, dropping the future cancels the job
+    /// let res = block_on(rt.invoke_function(None, &["com", "my"], "methodA", vec![7.to_js_value_facade(), 5.to_js_value_facade()]));
+    /// assert_eq!(res.expect("func failed").get_i32(), 35);
     /// ```
     #[allow(clippy::type_complexity)]
     pub fn invoke_function(
@@ -902,11 +1048,35 @@ impl QuickJsRuntimeFacade {
         namespace: &[&str],
         method_name: &str,
         args: Vec<JsValueFacade>,
+    ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
+        let token = CancellationToken::new();
+        Box::pin(CancelOnDrop::new(
+            self.invoke_function_with_token(
+                realm_name,
+                namespace,
+                method_name,
+                args,
+                token.clone(),
+            ),
+            token,
+        ))
+    }
+
+    /// invoke a function in the engine asynchronously, the job can be cancelled with a CancellationToken
+    #[allow(clippy::type_complexity)]
+    pub fn invoke_function_with_token(
+        &self,
+        realm_name: Option<&str>,
+        namespace: &[&str],
+        method_name: &str,
+        args: Vec<JsValueFacade>,
+        token: CancellationToken,
     ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
         let movable_namespace: Vec<String> = namespace.iter().map(|s| s.to_string()).collect();
         let movable_method_name = method_name.to_string();
+        let job_token = token.clone();
 
-        self.loop_realm(realm_name, move |_rt, realm| {
+        let fut = self.loop_realm_with_token(realm_name, token, move |_rt, realm| {
             let args_adapters: Vec<QuickJsValueAdapter> = args
                 .into_iter()
                 .map(|jsvf| realm.from_js_value_facade(jsvf).expect("conversion failed"))
@@ -917,17 +1087,20 @@ impl QuickJsRuntimeFacade {
                 .map(|s| s.as_str())
                 .collect::<Vec<&str>>();
 
-            let res = realm.invoke_function_by_name(
-                namespace.as_slice(),
-                movable_method_name.as_str(),
-                args_adapters.as_slice(),
-            );
+            let res = realm.with_quota(|| {
+                realm.invoke_function_by_name(
+                    namespace.as_slice(),
+                    movable_method_name.as_str(),
+                    args_adapters.as_slice(),
+                )
+            });
 
-            match res {
+            match map_cancelled(&job_token, res) {
                 Ok(jsvr) => realm.to_js_value_facade(&jsvr),
                 Err(e) => Err(e),
             }
-        })
+        });
+        Box::pin(async move { fut.await? })
     }
 
     pub fn invoke_function_void(
@@ -952,11 +1125,13 @@ impl QuickJsRuntimeFacade {
                 .collect::<Vec<&str>>();
 
             let res = realm
-                .invoke_function_by_name(
-                    namespace.as_slice(),
-                    movable_method_name.as_str(),
-                    args_adapters.as_slice(),
-                )
+                .with_quota(|| {
+                    realm.invoke_function_by_name(
+                        namespace.as_slice(),
+                        movable_method_name.as_str(),
+                        args_adapters.as_slice(),
+                    )
+                })
                 .map(|jsvr| realm.to_js_value_facade(&jsvr));
 
             match res {
@@ -976,6 +1151,80 @@ impl QuickJsRuntimeFacade {
             }
         })
     }
+
+    /// Evaluate a script asynchronously with a deadline
+    /// if the script is still running when the deadline passes it is interrupted and the result will be
+    /// a JsError with name "TimeoutError", the runtime remains usable afterwards
+    /// # Example
+    /// ```rust
+    /// use futures::executor::block_on;
+    /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+    /// use quickjs_runtime::jsutils::Script;
+    /// use std::time::{Duration, Instant};
+    /// let rt = QuickJsRuntimeBuilder::new().build();
+    /// let deadline = Instant::now() + Duration::from_millis(100);
+    /// let res = block_on(rt.eval_with_deadline(None, Script::new("endless.js", "while(true){}"), deadline));
+    /// assert_eq!(res.expect_err("script should have timed out").get_name(), "TimeoutError");
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn eval_with_deadline(
+        &self,
+        realm_name: Option<&str>,
+        script: Script,
+        deadline: Instant,
+    ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
+        self.loop_realm(realm_name, move |_rt, realm| {
+            let res = realm.with_quota(|| realm.eval_with_deadline(script, deadline));
+            match res {
+                Ok(jsvr) => realm.to_js_value_facade(&jsvr),
+                Err(e) => Err(e),
+            }
+        })
+    }
+
+    /// invoke a function in the engine asynchronously with a timeout
+    /// the timeout starts when the function is actually invoked in the EventLoop
+    /// if the function is still running when the timeout expires it is interrupted and the result will be
+    /// a JsError with name "TimeoutError", the runtime remains usable afterwards
+    #[allow(clippy::type_complexity)]
+    pub fn invoke_function_with_timeout(
+        &self,
+        realm_name: Option<&str>,
+        namespace: &[&str],
+        method_name: &str,
+        args: Vec<JsValueFacade>,
+        timeout: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
+        let movable_namespace: Vec<String> = namespace.iter().map(|s| s.to_string()).collect();
+        let movable_method_name = method_name.to_string();
+
+        self.loop_realm(realm_name, move |rt, realm| {
+            let args_adapters: Vec<QuickJsValueAdapter> = args
+                .into_iter()
+                .map(|jsvf| realm.from_js_value_facade(jsvf).expect("conversion failed"))
+                .collect();
+
+            let namespace = movable_namespace
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<&str>>();
+
+            let res = realm.with_quota(|| {
+                rt.with_deadline(Instant::now() + timeout, || {
+                    realm.invoke_function_by_name(
+                        namespace.as_slice(),
+                        movable_method_name.as_str(),
+                        args_adapters.as_slice(),
+                    )
+                })
+            });
+
+            match res {
+                Ok(jsvr) => realm.to_js_value_facade(&jsvr),
+                Err(e) => Err(e),
+            }
+        })
+    }
 }
 
 #[cfg(test)]
@@ -997,7 +1246,7 @@ pub mod tests {
     use futures::executor::block_on;
     use log::debug;
     use std::panic;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     struct TestNativeModuleLoader {}
     struct TestScriptModuleLoader {}
@@ -1186,6 +1435,55 @@ pub mod tests {
         assert_eq!(res.get_i32(), 14);
     }
 
+    #[test]
+    fn test_eval_with_deadline() {
+        let rt = init_test_rt();
+
+        let deadline = Instant::now() + Duration::from_millis(200);
+        let err = block_on(rt.eval_with_deadline(
+            None,
+            Script::new("test_deadline.es", "while(true){}"),
+            deadline,
+        ))
+        .expect_err("script should have timed out");
+        assert_eq!(err.get_name(), "TimeoutError");
+
+        // runtime should still be usable
+        let res = rt
+            .eval_sync(None, Script::new("test_deadline2.es", "(3 * 7);"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 21);
+
+        rt.eval_sync(
+            None,
+            Script::new(
+                "test_deadline3.es",
+                "this.com = {my: {endless: function(){while(true){}}, quick: function(a){return a * 2;}}};",
+            ),
+        )
+        .expect("script failed");
+
+        let err = block_on(rt.invoke_function_with_timeout(
+            None,
+            &["com", "my"],
+            "endless",
+            vec![],
+            Duration::from_millis(100),
+        ))
+        .expect_err("function should have timed out");
+        assert_eq!(err.get_name(), "TimeoutError");
+
+        let res = block_on(rt.invoke_function_with_timeout(
+            None,
+            &["com", "my"],
+            "quick",
+            vec![4.to_js_value_facade()],
+            Duration::from_secs(5),
+        ))
+        .expect("function failed");
+        assert_eq!(res.get_i32(), 8);
+    }
+
     #[test]
     fn t1234() {
         // test stack overflow
@@ -6,11 +6,22 @@
 //! * console.error()
 //! * console.warning()
 //! * console.trace()
+//! * console.debug()
+//! * console.assert()
+//! * console.count() / console.countReset()
+//! * console.time() / console.timeLog() / console.timeEnd()
+//! * console.group() / console.groupCollapsed() / console.groupEnd(), subsequent messages are indented
+//! * console.table(), renders an array or object as an ascii table
+//! * console.dir(), accepts an options object with a depth (defaults to 2, null for no limit)
 //!
-//! The methods use rust's log crate to output messages. e.g. console.info() uses the log::info!() macro
-//! so the console messages should appear in the log you initialized from rust
+//! counters, timers and the group indentation are tracked per realm
 //!
-//! All methods accept a single message string and optional substitution values
+//! By default the methods use rust's log crate to output messages. e.g. console.info() uses the log::info!() macro
+//! so the console messages should appear in the log you initialized from rust, a different [ConsoleSink] can be
+//! set with [QuickJsRuntimeBuilder::console_sink](../../builder/struct.QuickJsRuntimeBuilder.html#method.console_sink)
+//!
+//! All methods accept a single message string and optional substitution values, arguments which are not strings
+//! are formatted like node's util.inspect, see [inspect](../../quickjs_utils/inspect/index.html)
 //!
 //! e.g.
 //! ```javascript
@@ -19,7 +30,7 @@
 //! will output 'Oh dear some guy totaly failed 12 times because of a 2.4600 variance in the space time continuum'
 //!
 //! The string substitution you can use are
-//! * %o or %O Outputs a JavaScript object (serialized)
+//! * %o or %O Outputs a JavaScript object formatted by [inspect](../../quickjs_utils/inspect/index.html) (%o with a depth of 4)
 //! * %d or %i Outputs an integer. Number formatting is supported, for example  console.log("Foo %.2d", 1.1) will output the number as two significant figures with a leading 0: Foo 01
 //! * %s Outputs a string (will attempt to call .toString() on objects, use %o to output a serialized JSON string)
 //! * %f Outputs a floating-point value. Formatting is supported, for example  console.log("Foo %.2f", 1.1) will output the number to 2 decimal places: Foo 1.10
@@ -44,15 +55,18 @@
 use crate::jsutils::{JsError, JsValueType};
 use crate::quickjs_utils;
 use crate::quickjs_utils::functions::call_to_string;
-use crate::quickjs_utils::json::stringify;
-use crate::quickjs_utils::{functions, json, parse_args, primitives};
+use crate::quickjs_utils::inspect::InspectOptions;
+use crate::quickjs_utils::{arrays, errors, functions, objects, parse_args, primitives};
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
 use crate::quickjsvalueadapter::QuickJsValueAdapter;
 use crate::reflection::Proxy;
+use crate::values::JsValueFacade;
 use libquickjs_sys as q;
-use log::LevelFilter;
+use log::Level;
+use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Instant;
 
 pub fn init(q_js_rt: &QuickJsRuntimeAdapter) -> Result<(), JsError> {
     q_js_rt.add_context_init_hook(|_q_js_rt, q_ctx| init_ctx(q_ctx))
@@ -66,12 +80,30 @@ pub(crate) fn init_ctx(q_ctx: &QuickJsRealmAdapter) -> Result<(), JsError> {
         .static_native_method("info", Some(console_info))
         .static_native_method("warn", Some(console_warn))
         .static_native_method("error", Some(console_error))
-        //.static_native_method("assert", Some(console_assert)) // todo
         .static_native_method("debug", Some(console_debug))
+        .static_method("assert", console_assert)
+        .static_method("count", console_count)
+        .static_method("countReset", console_count_reset)
+        .static_method("time", console_time)
+        .static_method("timeLog", console_time_log)
+        .static_method("timeEnd", console_time_end)
+        .static_method("group", console_group)
+        .static_method("groupCollapsed", console_group)
+        .static_method("groupEnd", console_group_end)
+        .static_method("table", console_table)
+        .static_method("dir", console_dir)
         .install(q_ctx, true)
         .map(|_| {})
 }
 
+/// per realm state of the console: counters, timers and the indentation of console.group
+#[derive(Default)]
+pub(crate) struct ConsoleState {
+    counters: HashMap<String, u64>,
+    timers: HashMap<String, Instant>,
+    group_depth: usize,
+}
+
 #[allow(clippy::or_fun_call)]
 unsafe fn parse_field_value(
     ctx: *mut q::JSContext,
@@ -149,58 +181,36 @@ unsafe fn parse_field_value(
             }
             return f_val;
         }
-    } else if field.ends_with('o') || field.ends_with('O') {
-        let json_str_res = json::stringify(ctx, value, None);
-        let json = match json_str_res {
-            Ok(json_str) => {
-                if json_str.is_undefined() {
-                    // if undefined is passed tp json.stringify it returns undefined, else always a string
-                    "undefined".to_string()
-                } else {
-                    primitives::to_string(ctx, &json_str).unwrap_or_default()
-                }
-            }
-            Err(_e) => "".to_string(),
-        };
-        return json;
+    } else if field.ends_with('o') {
+        return inspect_log_obj(ctx, value, InspectOptions::default().depth(Some(4)));
+    } else if field.ends_with('O') {
+        return inspect_log_obj(ctx, value, InspectOptions::default());
     }
     call_to_string(ctx, value).unwrap_or_default()
 }
 
-unsafe fn stringify_log_obj(ctx: *mut q::JSContext, arg: &QuickJsValueAdapter) -> String {
-    match stringify(ctx, arg, None) {
-        Ok(r) => match primitives::to_string(ctx, &r) {
-            Ok(s) => s,
-            Err(e) => format!("Error: {e}"),
-        },
+unsafe fn inspect_log_obj(
+    ctx: *mut q::JSContext,
+    arg: &QuickJsValueAdapter,
+    options: InspectOptions,
+) -> String {
+    QuickJsRealmAdapter::with_context(ctx, |realm| match realm.inspect(arg, options) {
+        Ok(s) => s,
         Err(e) => format!("Error: {e}"),
-    }
+    })
 }
 
 #[allow(clippy::or_fun_call)]
-unsafe fn parse_line(ctx: *mut q::JSContext, args: Vec<QuickJsValueAdapter>) -> String {
+unsafe fn format_message(ctx: *mut q::JSContext, args: &[QuickJsValueAdapter]) -> String {
     let mut output = String::new();
 
-    output.push_str("JS_REALM:");
-    QuickJsRealmAdapter::with_context(ctx, |realm| {
-        output.push('[');
-        output.push_str(realm.id.as_str());
-        output.push_str("][");
-        if let Ok(script_or_module_name) = quickjs_utils::get_script_or_module_name_q(realm) {
-            output.push_str(script_or_module_name.as_str());
-        }
-        output.push_str("]: ");
-    });
-
     if args.is_empty() {
         return output;
     }
 
     let message = match &args[0].get_js_type() {
-        JsValueType::Object => stringify_log_obj(ctx, &args[0]),
-        JsValueType::Function => stringify_log_obj(ctx, &args[0]),
-        JsValueType::Array => stringify_log_obj(ctx, &args[0]),
-        _ => functions::call_to_string(ctx, &args[0]).unwrap_or_default(),
+        JsValueType::String => functions::call_to_string(ctx, &args[0]).unwrap_or_default(),
+        _ => inspect_log_obj(ctx, &args[0], InspectOptions::default()),
     };
 
     let mut field_code = String::new();
@@ -214,7 +224,13 @@ unsafe fn parse_line(ctx: *mut q::JSContext, args: Vec<QuickJsValueAdapter>) ->
         for chr in message.chars() {
             if in_field {
                 field_code.push(chr);
-                if chr.eq(&'s') || chr.eq(&'d') || chr.eq(&'f') || chr.eq(&'o') || chr.eq(&'i') {
+                if chr.eq(&'s')
+                    || chr.eq(&'d')
+                    || chr.eq(&'f')
+                    || chr.eq(&'o')
+                    || chr.eq(&'O')
+                    || chr.eq(&'i')
+                {
                     // end field
 
                     if x < args.len() {
@@ -242,10 +258,8 @@ unsafe fn parse_line(ctx: *mut q::JSContext, args: Vec<QuickJsValueAdapter>) ->
         // add args which we're not filled in str
         output.push(' ');
         let tail_arg = match arg.get_js_type() {
-            JsValueType::Object => stringify_log_obj(ctx, arg),
-            JsValueType::Function => stringify_log_obj(ctx, arg),
-            JsValueType::Array => stringify_log_obj(ctx, arg),
-            _ => call_to_string(ctx, arg).unwrap_or_default(),
+            JsValueType::String => call_to_string(ctx, arg).unwrap_or_default(),
+            _ => inspect_log_obj(ctx, arg, InspectOptions::default()),
         };
         output.push_str(tail_arg.as_str());
     }
@@ -253,30 +267,185 @@ unsafe fn parse_line(ctx: *mut q::JSContext, args: Vec<QuickJsValueAdapter>) ->
     output
 }
 
-unsafe extern "C" fn console_log(
+/// a single console call as passed to a [ConsoleSink]
+pub struct ConsoleRecord {
+    pub level: Level,
+    pub realm_id: String,
+    /// name of the script or module which called the console, may be empty
+    pub script_name: String,
+    /// the formatted message (without indentation for console.group)
+    pub message: String,
+    /// the raw arguments of the call, empty if the sink does not [include_args](ConsoleSink::include_args)
+    pub args: Vec<JsValueFacade>,
+    /// the stack for console.trace() or of an Error passed as an argument
+    pub stack: Option<String>,
+    /// the number of open console.group() calls
+    pub group_depth: usize,
+}
+
+/// a ConsoleSink receives all output of the console feature, it can be set with
+/// [QuickJsRuntimeBuilder::console_sink](../../builder/struct.QuickJsRuntimeBuilder.html#method.console_sink), the
+/// default is [LogConsoleSink]
+/// # Example
+/// ```rust
+/// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+/// use quickjs_runtime::features::console::{ConsoleRecord, ConsoleSink};
+/// use quickjs_runtime::jsutils::Script;
+/// use std::sync::{Arc, Mutex};
+///
+/// struct CapturingSink {
+///     messages: Arc<Mutex<Vec<String>>>,
+/// }
+///
+/// impl ConsoleSink for CapturingSink {
+///     fn write(&self, record: ConsoleRecord) {
+///         self.messages.lock().unwrap().push(record.message);
+///     }
+/// }
+///
+/// let messages = Arc::new(Mutex::new(vec![]));
+/// let rt = QuickJsRuntimeBuilder::new()
+///     .console_sink(CapturingSink { messages: messages.clone() })
+///     .build();
+/// rt.eval_sync(None, Script::new("sink.js", "console.info('hello %s', 'world');")).expect("script failed");
+/// assert_eq!(messages.lock().unwrap().as_slice(), &["hello world".to_string()]);
+/// ```
+pub trait ConsoleSink {
+    /// when this returns false for a level the message is not formatted and write is not called
+    fn enabled(&self, _level: Level) -> bool {
+        true
+    }
+    /// when this returns false the args of a ConsoleRecord are not converted to JsValueFacades
+    fn include_args(&self) -> bool {
+        true
+    }
+    fn write(&self, record: ConsoleRecord);
+}
+
+/// the default ConsoleSink, writes records to the log crate
+/// as `JS_REALM:[realm_id][script_name]: message`
+pub struct LogConsoleSink;
+
+impl ConsoleSink for LogConsoleSink {
+    fn enabled(&self, level: Level) -> bool {
+        log::max_level() >= level
+    }
+
+    fn include_args(&self) -> bool {
+        false
+    }
+
+    fn write(&self, record: ConsoleRecord) {
+        let mut output = format!("JS_REALM:[{}][{}]: ", record.realm_id, record.script_name);
+
+        if record.group_depth > 0 {
+            let indent = "  ".repeat(record.group_depth);
+            let indented = record
+                .message
+                .lines()
+                .map(|line| format!("{indent}{line}"))
+                .collect::<Vec<String>>()
+                .join("\n");
+            output.push_str(indented.as_str());
+        } else {
+            output.push_str(record.message.as_str());
+        }
+
+        log::log!(record.level, "{}", output);
+    }
+}
+
+fn log_enabled(level: Level) -> bool {
+    QuickJsRuntimeAdapter::do_with(|q_js_rt| q_js_rt.console_sink.enabled(level))
+}
+
+/// get the stack of the first Error in args
+fn get_error_stack(realm: &QuickJsRealmAdapter, args: &[QuickJsValueAdapter]) -> Option<String> {
+    let error = args.iter().find(|arg| arg.is_error())?;
+    let stack = objects::get_property_q(realm, error, "stack").ok()?;
+    if stack.is_string() {
+        primitives::to_string_q(realm, &stack).ok()
+    } else {
+        None
+    }
+}
+
+/// pass a message to the ConsoleSink of the runtime
+fn write_record(
+    realm: &QuickJsRealmAdapter,
+    level: Level,
+    message: String,
+    args: &[QuickJsValueAdapter],
+    stack: Option<String>,
+) {
+    let stack = stack.or_else(|| get_error_stack(realm, args));
+    let group_depth = realm.console_state.borrow().group_depth;
+    QuickJsRuntimeAdapter::do_with(|q_js_rt| {
+        let sink = &q_js_rt.console_sink;
+        let args = if sink.include_args() {
+            args.iter()
+                .map(|arg| {
+                    realm.to_js_value_facade(arg).unwrap_or_else(|err| {
+                        log::error!("could not convert console arg: {}", err);
+                        JsValueFacade::Undefined
+                    })
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        let script_name = quickjs_utils::get_script_or_module_name_q(realm).unwrap_or_default();
+        sink.write(ConsoleRecord {
+            level,
+            realm_id: realm.id.clone(),
+            script_name,
+            message,
+            args,
+            stack,
+            group_depth,
+        });
+    })
+}
+
+unsafe fn console_native(
     ctx: *mut q::JSContext,
-    _this_val: q::JSValue,
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
+    level: Level,
 ) -> q::JSValue {
-    if log::max_level() >= LevelFilter::Info {
+    if log_enabled(level) {
         let args = parse_args(ctx, argc, argv);
-        log::info!("{}", parse_line(ctx, args));
+        QuickJsRealmAdapter::with_context(ctx, |realm| {
+            let message = format_message(ctx, &args);
+            let stack = if level == Level::Trace {
+                errors::get_stack(realm)
+                    .and_then(|stack| primitives::to_string_q(realm, &stack))
+                    .ok()
+            } else {
+                None
+            };
+            write_record(realm, level, message, &args, stack);
+        });
     }
     quickjs_utils::new_null()
 }
 
+unsafe extern "C" fn console_log(
+    ctx: *mut q::JSContext,
+    _this_val: q::JSValue,
+    argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    console_native(ctx, argc, argv, Level::Info)
+}
+
 unsafe extern "C" fn console_trace(
     ctx: *mut q::JSContext,
     _this_val: q::JSValue,
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    if log::max_level() >= LevelFilter::Trace {
-        let args = parse_args(ctx, argc, argv);
-        log::trace!("{}", parse_line(ctx, args));
-    }
-    quickjs_utils::new_null()
+    console_native(ctx, argc, argv, Level::Trace)
 }
 
 unsafe extern "C" fn console_debug(
@@ -285,11 +454,7 @@ unsafe extern "C" fn console_debug(
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    if log::max_level() >= LevelFilter::Debug {
-        let args = parse_args(ctx, argc, argv);
-        log::debug!("{}", parse_line(ctx, args));
-    }
-    quickjs_utils::new_null()
+    console_native(ctx, argc, argv, Level::Debug)
 }
 
 unsafe extern "C" fn console_info(
@@ -298,11 +463,7 @@ unsafe extern "C" fn console_info(
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    if log::max_level() >= LevelFilter::Info {
-        let args = parse_args(ctx, argc, argv);
-        log::info!("{}", parse_line(ctx, args));
-    }
-    quickjs_utils::new_null()
+    console_native(ctx, argc, argv, Level::Info)
 }
 
 unsafe extern "C" fn console_warn(
@@ -311,11 +472,7 @@ unsafe extern "C" fn console_warn(
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    if log::max_level() >= LevelFilter::Warn {
-        let args = parse_args(ctx, argc, argv);
-        log::warn!("{}", parse_line(ctx, args));
-    }
-    quickjs_utils::new_null()
+    console_native(ctx, argc, argv, Level::Warn)
 }
 
 unsafe extern "C" fn console_error(
@@ -324,17 +481,347 @@ unsafe extern "C" fn console_error(
     argc: ::std::os::raw::c_int,
     argv: *mut q::JSValue,
 ) -> q::JSValue {
-    if log::max_level() >= LevelFilter::Error {
-        let args = parse_args(ctx, argc, argv);
-        log::error!("{}", parse_line(ctx, args));
+    console_native(ctx, argc, argv, Level::Error)
+}
+
+fn write_realm_line(
+    realm: &QuickJsRealmAdapter,
+    level: Level,
+    message: String,
+    args: &[QuickJsValueAdapter],
+) {
+    if log_enabled(level) {
+        write_record(realm, level, message, args, None);
     }
-    quickjs_utils::new_null()
+}
+
+/// the label argument of count, time etc, defaults to "default"
+fn get_label(realm: &QuickJsRealmAdapter, args: &[QuickJsValueAdapter]) -> Result<String, JsError> {
+    match args.first() {
+        Some(label) if !label.is_undefined() => functions::call_to_string_q(realm, label),
+        _ => Ok("default".to_string()),
+    }
+}
+
+fn console_assert(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let asserted = match args.first() {
+        Some(condition) => unsafe { q::JS_ToBool(realm.context, *condition.borrow_value()) > 0 },
+        None => false,
+    };
+    if !asserted && log_enabled(Level::Error) {
+        let message = if args.len() > 1 {
+            format!("Assertion failed: {}", unsafe {
+                format_message(realm.context, &args[1..])
+            })
+        } else {
+            "Assertion failed".to_string()
+        };
+        write_realm_line(
+            realm,
+            Level::Error,
+            message,
+            args.get(1..).unwrap_or_default(),
+        );
+    }
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+fn console_count(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let label = get_label(realm, args)?;
+    let count = {
+        let state = &mut *realm.console_state.borrow_mut();
+        let count = state.counters.entry(label.clone()).or_insert(0);
+        *count += 1;
+        *count
+    };
+    write_realm_line(realm, Level::Info, format!("{label}: {count}"), args);
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+fn console_count_reset(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let label = get_label(realm, args)?;
+    let existed = match realm.console_state.borrow_mut().counters.get_mut(&label) {
+        Some(count) => {
+            *count = 0;
+            true
+        }
+        None => false,
+    };
+    if !existed {
+        write_realm_line(
+            realm,
+            Level::Warn,
+            format!("Count for '{label}' does not exist"),
+            args,
+        );
+    }
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+fn console_time(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let label = get_label(realm, args)?;
+    let exists = {
+        let timers = &mut realm.console_state.borrow_mut().timers;
+        if timers.contains_key(&label) {
+            true
+        } else {
+            timers.insert(label.clone(), Instant::now());
+            false
+        }
+    };
+    if exists {
+        write_realm_line(
+            realm,
+            Level::Warn,
+            format!("Timer '{label}' already exists"),
+            args,
+        );
+    }
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+fn log_timer(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+    end: bool,
+) -> Result<QuickJsValueAdapter, JsError> {
+    let label = get_label(realm, args)?;
+    let started = {
+        let timers = &mut realm.console_state.borrow_mut().timers;
+        if end {
+            timers.remove(&label)
+        } else {
+            timers.get(&label).cloned()
+        }
+    };
+    match started {
+        Some(started) => {
+            let mut message = format!("{label}: {:.3}ms", started.elapsed().as_secs_f64() * 1000.0);
+            if !end && args.len() > 1 {
+                message.push(' ');
+                message.push_str(unsafe { format_message(realm.context, &args[1..]) }.as_str());
+            }
+            write_realm_line(realm, Level::Info, message, args);
+        }
+        None => {
+            write_realm_line(
+                realm,
+                Level::Warn,
+                format!("Timer '{label}' does not exist"),
+                args,
+            );
+        }
+    }
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+fn console_time_log(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    log_timer(realm, args, false)
+}
+
+fn console_time_end(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    log_timer(realm, args, true)
+}
+
+fn console_group(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    if !args.is_empty() && log_enabled(Level::Info) {
+        let message = unsafe { format_message(realm.context, args) };
+        write_realm_line(realm, Level::Info, message, args);
+    }
+    realm.console_state.borrow_mut().group_depth += 1;
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+fn console_group_end(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    _args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let state = &mut *realm.console_state.borrow_mut();
+    state.group_depth = state.group_depth.saturating_sub(1);
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+fn console_dir(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    if !log_enabled(Level::Info) {
+        return Ok(quickjs_utils::new_undefined_ref());
+    }
+    // depth defaults to 2, null means no limit
+    let mut options = InspectOptions::default();
+    if let Some(dir_options) = args.get(1) {
+        if dir_options.is_object() {
+            let depth = objects::get_property_q(realm, dir_options, "depth")?;
+            if depth.is_null() {
+                options.depth = None;
+            } else if depth.is_i32() {
+                options.depth = Some(depth.to_i32().max(0) as usize);
+            } else if depth.is_f64() {
+                options.depth = Some(depth.to_f64().max(0.0) as usize);
+            }
+        }
+    }
+    let message = match args.first() {
+        Some(value) => realm.inspect(value, options)?,
+        None => "undefined".to_string(),
+    };
+    write_realm_line(realm, Level::Info, message, args);
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+/// render rows of cells as an ascii table
+fn render_table(header: &[String], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (index, cell) in row.iter().enumerate() {
+            widths[index] = widths[index].max(cell.chars().count());
+        }
+    }
+    let separator = format!(
+        "+{}+",
+        widths
+            .iter()
+            .map(|w| "-".repeat(w + 2))
+            .collect::<Vec<String>>()
+            .join("+")
+    );
+    let render_row = |row: &[String]| {
+        let cells = row
+            .iter()
+            .enumerate()
+            .map(|(index, cell)| {
+                let pad = widths[index] - cell.chars().count();
+                format!(" {cell}{} ", " ".repeat(pad))
+            })
+            .collect::<Vec<String>>();
+        format!("|{}|", cells.join("|"))
+    };
+    let mut lines = vec![separator.clone(), render_row(header), separator.clone()];
+    for row in rows {
+        lines.push(render_row(row));
+    }
+    lines.push(separator);
+    lines.join("\n")
+}
+
+fn console_table(
+    _q_js_rt: &QuickJsRuntimeAdapter,
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    if !log_enabled(Level::Info) {
+        return Ok(quickjs_utils::new_undefined_ref());
+    }
+    let data = match args.first() {
+        Some(data) if data.is_object() && !data.is_function() => data,
+        _ => {
+            // not tabular, log like console.log
+            let message = unsafe { format_message(realm.context, args) };
+            write_realm_line(realm, Level::Info, message, args);
+            return Ok(quickjs_utils::new_undefined_ref());
+        }
+    };
+
+    // the optional second arg restricts the columns
+    let mut columns: Vec<String> = vec![];
+    let restricted = match args.get(1) {
+        Some(properties) if properties.is_array() => {
+            for index in 0..arrays::get_length_q(realm, properties)? {
+                let column = arrays::get_element_q(realm, properties, index)?;
+                columns.push(functions::call_to_string_q(realm, &column)?);
+            }
+            true
+        }
+        _ => false,
+    };
+
+    // nested objects in cells are abbreviated
+    let cell_options = InspectOptions::default()
+        .depth(Some(0))
+        .break_length(usize::MAX);
+    let mut has_values = false;
+    let mut rows: Vec<(String, HashMap<String, String>, Option<String>)> = vec![];
+    for row_name in objects::get_property_names_q(realm, data)? {
+        let row_value = objects::get_property_q(realm, data, row_name.as_str())?;
+        let mut cells = HashMap::new();
+        let mut value_cell = None;
+        if row_value.is_object() && !row_value.is_function() {
+            for column in objects::get_property_names_q(realm, &row_value)? {
+                if !restricted && !columns.contains(&column) {
+                    columns.push(column.clone());
+                }
+                let cell = objects::get_property_q(realm, &row_value, column.as_str())?;
+                cells.insert(column, realm.inspect(&cell, cell_options)?);
+            }
+        } else {
+            has_values = true;
+            value_cell = Some(realm.inspect(&row_value, cell_options)?);
+        }
+        rows.push((row_name, cells, value_cell));
+    }
+
+    let mut header = vec!["(index)".to_string()];
+    header.extend(columns.iter().cloned());
+    if has_values {
+        header.push("Values".to_string());
+    }
+    let table_rows = rows
+        .into_iter()
+        .map(|(row_name, mut cells, value_cell)| {
+            let mut row = vec![row_name];
+            for column in &columns {
+                row.push(cells.remove(column).unwrap_or_default());
+            }
+            if has_values {
+                row.push(value_cell.unwrap_or_default());
+            }
+            row
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    write_realm_line(realm, Level::Info, render_table(&header, &table_rows), args);
+    Ok(quickjs_utils::new_undefined_ref())
 }
 
 #[cfg(test)]
 pub mod tests {
     use crate::builder::QuickJsRuntimeBuilder;
+    use crate::facades::tests::init_test_rt;
+    use crate::features::console::{render_table, ConsoleRecord, ConsoleSink};
     use crate::jsutils::Script;
+    use log::Level;
+    use std::sync::{Arc, Mutex};
     use std::thread;
     use std::time::Duration;
 
@@ -395,4 +882,148 @@ pub mod tests {
 
         thread::sleep(Duration::from_secs(1));
     }
+
+    #[test]
+    fn test_console_state() {
+        let rt = init_test_rt();
+        rt.create_context("console_a")
+            .expect("could not create realm");
+        rt.create_context("console_b")
+            .expect("could not create realm");
+
+        let script = "console.count('c'); console.count('c'); console.count();\
+            console.time('t'); console.timeLog('t', 'busy'); console.group('g1'); console.groupCollapsed();\
+            console.table([{a: 1, b: 'x'}, {a: 2}, 3], ['a']); console.dir({a: {b: {c: {d: 1}}}}, {depth: null});\
+            console.assert(1 === 2, 'math %s', 'broke'); console.assert(true);";
+        rt.eval_sync(
+            Some("console_a"),
+            Script::new("test_console_state.js", script),
+        )
+        .expect("script failed");
+
+        let (c, default, timer, depth) = rt.loop_realm_sync(Some("console_a"), |_rt, realm| {
+            let state = realm.console_state.borrow();
+            (
+                state.counters.get("c").cloned(),
+                state.counters.get("default").cloned(),
+                state.timers.contains_key("t"),
+                state.group_depth,
+            )
+        });
+        assert_eq!(c, Some(2));
+        assert_eq!(default, Some(1));
+        assert!(timer);
+        assert_eq!(depth, 2);
+
+        // state is not shared between realms
+        let (c, depth) = rt.loop_realm_sync(Some("console_b"), |_rt, realm| {
+            let state = realm.console_state.borrow();
+            (state.counters.get("c").cloned(), state.group_depth)
+        });
+        assert_eq!(c, None);
+        assert_eq!(depth, 0);
+
+        rt.eval_sync(
+            Some("console_a"),
+            Script::new(
+                "test_console_state2.js",
+                "console.countReset('c'); console.timeEnd('t'); console.groupEnd(); console.groupEnd(); console.groupEnd();",
+            ),
+        )
+        .expect("script failed");
+        let (c, timer, depth) = rt.loop_realm_sync(Some("console_a"), |_rt, realm| {
+            let state = realm.console_state.borrow();
+            (
+                state.counters.get("c").cloned(),
+                state.timers.contains_key("t"),
+                state.group_depth,
+            )
+        });
+        assert_eq!(c, Some(0));
+        assert!(!timer);
+        assert_eq!(depth, 0);
+    }
+
+    struct CapturingSink {
+        records: Arc<Mutex<Vec<(Level, String, String, String, usize, bool, usize)>>>,
+    }
+
+    impl ConsoleSink for CapturingSink {
+        fn write(&self, record: ConsoleRecord) {
+            self.records.lock().unwrap().push((
+                record.level,
+                record.realm_id,
+                record.script_name,
+                record.message,
+                record.args.len(),
+                record.stack.is_some(),
+                record.group_depth,
+            ));
+        }
+    }
+
+    #[test]
+    fn test_console_sink() {
+        let records = Arc::new(Mutex::new(vec![]));
+        let rt = QuickJsRuntimeBuilder::new()
+            .console_sink(CapturingSink {
+                records: records.clone(),
+            })
+            .build();
+        rt.create_context("sink_realm")
+            .expect("could not create realm");
+        rt.eval_sync(
+            Some("sink_realm"),
+            Script::new(
+                "test_console_sink.js",
+                "console.warn('a %s', 'b', 1); console.group(); console.trace('t'); console.groupEnd(); console.error(new Error('e'));\
+                 let o = {m: new Map([[1, 'x']])}; o.o = o; console.log('%o', o, [1]);",
+            ),
+        )
+        .expect("script failed");
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(
+            records[0],
+            (
+                Level::Warn,
+                "sink_realm".to_string(),
+                "test_console_sink.js".to_string(),
+                "a b 1".to_string(),
+                3,
+                false,
+                0
+            )
+        );
+        assert_eq!(records[1].0, Level::Trace);
+        assert!(records[1].5);
+        assert_eq!(records[1].6, 1);
+        assert_eq!(records[2].0, Level::Error);
+        assert!(records[2].5);
+        assert_eq!(
+            records[3].3,
+            "{ m: Map(1) { 1 => 'x' }, o: [Circular] } [ 1 ]"
+        );
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = render_table(
+            &["(index)".to_string(), "a".to_string()],
+            &[
+                vec!["0".to_string(), "1".to_string()],
+                vec!["1".to_string(), "'long'".to_string()],
+            ],
+        );
+        assert_eq!(
+            table,
+            "+---------+--------+\n\
+             | (index) | a      |\n\
+             +---------+--------+\n\
+             | 0       | 1      |\n\
+             | 1       | 'long' |\n\
+             +---------+--------+"
+        );
+    }
 }
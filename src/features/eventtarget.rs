@@ -0,0 +1,52 @@
+use crate::jsutils::JsError;
+use crate::quickjs_utils::{get_global_q, objects};
+use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
+use crate::reflection::eventtarget;
+use libquickjs_sys as q;
+
+/// provides the Event, CustomEvent, EventTarget, AbortController and AbortSignal classes for the runtime
+/// these share their impl with the Proxy classes which are an EventTarget (see [eventtarget](crate::reflection::eventtarget))
+/// # Example
+/// ```rust
+/// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+/// use quickjs_runtime::jsutils::Script;
+/// let rt = QuickJsRuntimeBuilder::new().build();
+/// let res = rt.eval_sync(None, Script::new("test_eventtarget.es", r#"
+///     let target = new EventTarget();
+///     let controller = new AbortController();
+///     let calls = [];
+///     target.addEventListener('ping', (evt) => calls.push('once:' + evt.detail), {once: true});
+///     target.addEventListener('ping', (evt) => calls.push('aborted'), {signal: controller.signal});
+///     controller.abort();
+///     let res = target.dispatchEvent(new CustomEvent('ping', {detail: 1, cancelable: true}));
+///     target.dispatchEvent(new CustomEvent('ping', {detail: 2}));
+///     calls.join(',') + ':' + res;
+/// "#)).expect("script failed");
+/// assert_eq!(res.get_str(), "once:1:true");
+/// ```
+pub fn init(q_js_rt: &QuickJsRuntimeAdapter) -> Result<(), JsError> {
+    log::trace!("eventtarget::init");
+
+    q_js_rt.add_context_init_hook(|_q_js_rt, q_ctx| {
+        let intrinsics = eventtarget::get_intrinsics(q_ctx)?;
+        let global = get_global_q(q_ctx);
+        for class_name in [
+            "Event",
+            "CustomEvent",
+            "EventTarget",
+            "AbortController",
+            "AbortSignal",
+        ] {
+            let class_ref = objects::get_property_q(q_ctx, &intrinsics, class_name)?;
+            objects::set_property2_q(
+                q_ctx,
+                &global,
+                class_name,
+                &class_ref,
+                (q::JS_PROP_CONFIGURABLE | q::JS_PROP_WRITABLE) as i32,
+            )?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
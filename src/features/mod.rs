@@ -1,19 +1,28 @@
-//! contains engine features like console, setTimeout, setInterval and setImmediate
+//! contains engine features like console, setTimeout, setInterval, setImmediate, EventTarget, queueMicrotask/structuredClone/reportError and TextEncoder/TextDecoder/atob/btoa/URL/URLSearchParams
 
 use crate::facades::QuickJsRuntimeFacade;
 use crate::jsutils::JsError;
 #[cfg(feature = "console")]
 pub mod console;
+#[cfg(feature = "eventtarget")]
+pub mod eventtarget;
 #[cfg(any(feature = "settimeout", feature = "setinterval"))]
 pub mod set_timeout;
 #[cfg(feature = "setimmediate")]
 pub mod setimmediate;
+#[cfg(feature = "web-encoding")]
+pub mod webencoding;
+#[cfg(feature = "webglobals")]
+pub mod webglobals;
 
 #[cfg(any(
     feature = "settimeout",
     feature = "setinterval",
     feature = "console",
-    feature = "setimmediate"
+    feature = "setimmediate",
+    feature = "eventtarget",
+    feature = "webglobals",
+    feature = "web-encoding"
 ))]
 pub fn init(es_rt: &QuickJsRuntimeFacade) -> Result<(), JsError> {
     log::trace!("features::init");
@@ -23,6 +32,12 @@ pub fn init(es_rt: &QuickJsRuntimeFacade) -> Result<(), JsError> {
         console::init(q_js_rt)?;
         #[cfg(feature = "setimmediate")]
         setimmediate::init(q_js_rt)?;
+        #[cfg(feature = "eventtarget")]
+        eventtarget::init(q_js_rt)?;
+        #[cfg(feature = "webglobals")]
+        webglobals::init(q_js_rt)?;
+        #[cfg(feature = "web-encoding")]
+        webencoding::init(q_js_rt)?;
 
         #[cfg(any(feature = "settimeout", feature = "setinterval"))]
         set_timeout::init(q_js_rt)?;
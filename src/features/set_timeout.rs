@@ -1,9 +1,11 @@
 use crate::jsutils::JsError;
 use crate::quickjs_utils;
-use crate::quickjs_utils::{functions, get_global, objects, parse_args, primitives};
+use crate::quickjs_utils::{errors, functions, get_global, objects, parse_args, primitives};
 use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
 use hirofa_utils::eventloop::EventLoop;
 use libquickjs_sys as q;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::time::Duration;
 
 /// provides the setImmediate methods for the runtime
@@ -95,19 +97,32 @@ unsafe extern "C" fn set_timeout(
             0
         };
 
+        if let Err(err) = q_ctx.accounting.add_timer(q_ctx) {
+            return throw_js_error(context, err);
+        }
+
         let q_ctx_id = q_ctx.id.clone();
+        let timer_id = Rc::new(Cell::new(0));
+        let timer_id2 = timer_id.clone();
 
         let id = EventLoop::add_timeout(
             move || {
                 QuickJsRuntimeAdapter::do_with(|q_js_rt| {
                     let func = &args[0];
                     if let Some(q_ctx) = q_js_rt.opt_context(q_ctx_id.as_str()) {
-                        match functions::call_function_q(q_ctx, func, &args[2..], None) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                log::error!("setTimeout func failed: {}", e);
-                            }
-                        };
+                        q_ctx.accounting.timer_removed(timer_id2.get());
+                        if let Err(e) = q_ctx.accounting.check_quota() {
+                            log::error!("setTimeout func not run: {}", e);
+                        } else {
+                            match q_ctx.run_job(q_js_rt, || {
+                                functions::call_function_q(q_ctx, func, &args[2..], None)
+                            }) {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    log::error!("setTimeout func failed: {}", e);
+                                }
+                            };
+                        }
                     } else {
                         log::error!("setTimeout func failed: no such context: {}", q_ctx_id);
                     }
@@ -116,6 +131,8 @@ unsafe extern "C" fn set_timeout(
             },
             Duration::from_millis(delay_ms),
         );
+        timer_id.set(id);
+        q_ctx.accounting.timer_added(id);
         log::trace!("set_timeout: {}", id);
         primitives::from_i32(id).clone_value_incr_rc()
     })
@@ -156,6 +173,10 @@ unsafe extern "C" fn set_interval(
             0
         };
 
+        if let Err(err) = q_ctx.accounting.add_timer(q_ctx) {
+            return throw_js_error(context, err);
+        }
+
         let q_ctx_id = q_ctx.id.clone();
 
         let id = EventLoop::add_interval(
@@ -164,12 +185,18 @@ unsafe extern "C" fn set_interval(
                     if let Some(q_ctx) = q_js_rt.opt_context(q_ctx_id.as_str()) {
                         let func = &args[0];
 
-                        match functions::call_function_q(q_ctx, func, &args[2..], None) {
-                            Ok(_) => {}
-                            Err(e) => {
-                                log::error!("setInterval func failed: {}", e);
-                            }
-                        };
+                        if let Err(e) = q_ctx.accounting.check_quota() {
+                            log::error!("setInterval func not run: {}", e);
+                        } else {
+                            match q_ctx.run_job(q_js_rt, || {
+                                functions::call_function_q(q_ctx, func, &args[2..], None)
+                            }) {
+                                Ok(_) => {}
+                                Err(e) => {
+                                    log::error!("setInterval func failed: {}", e);
+                                }
+                            };
+                        }
                     } else {
                         log::error!("setInterval func failed: no such context: {}", q_ctx_id);
                     }
@@ -179,6 +206,7 @@ unsafe extern "C" fn set_interval(
             Duration::from_millis(delay_ms),
             Duration::from_millis(delay_ms),
         );
+        q_ctx.accounting.timer_added(id);
         log::trace!("set_interval: {}", id);
         primitives::from_i32(id).clone_value_incr_rc()
     })
@@ -205,6 +233,7 @@ unsafe extern "C" fn clear_interval(
         let id = primitives::to_i32(&args[0]).ok().unwrap();
         log::trace!("clear_interval: {}", id);
         EventLoop::clear_interval(id);
+        q_ctx.accounting.timer_removed(id);
         quickjs_utils::new_null()
     })
 }
@@ -232,11 +261,20 @@ unsafe extern "C" fn clear_timeout(
         log::trace!("clear_timeout: {}", id);
 
         EventLoop::clear_timeout(id);
+        q_ctx.accounting.timer_removed(id);
 
         quickjs_utils::new_null()
     })
 }
 
+/// throw a JsError (e.g. a QuotaExceededError) from a native function
+#[cfg(any(feature = "settimeout", feature = "setinterval"))]
+unsafe fn throw_js_error(context: *mut q::JSContext, err: JsError) -> q::JSValue {
+    let err_ref = errors::new_error(context, err.get_name(), err.get_message(), err.get_stack())
+        .expect("could not create err");
+    errors::throw(context, err_ref)
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::facades::tests::init_test_rt;
@@ -0,0 +1,185 @@
+//! atob and btoa
+
+use crate::jsutils::JsError;
+use crate::quickjs_utils::{functions, get_global_q, objects};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+/// decode with the forgiving-base64 algorithm (see https://infra.spec.whatwg.org/#forgiving-base64-decode),
+/// ASCII whitespace is ignored and padding is optional, None is returned for invalid input
+pub(crate) fn decode(input: &str) -> Option<Vec<u8>> {
+    let mut data: Vec<u8> = input
+        .bytes()
+        .filter(|b| !matches!(b, b'\t' | b'\n' | b'\x0C' | b'\r' | b' '))
+        .collect();
+    if data.len() % 4 == 0 && data.ends_with(b"=") {
+        data.pop();
+        if data.ends_with(b"=") {
+            data.pop();
+        }
+    }
+    if data.len() % 4 == 1 {
+        return None;
+    }
+    let mut res = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for b in data {
+        let value = ALPHABET.iter().position(|a| *a == b)? as u32;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(res)
+}
+
+fn invalid_character_error(message: &str) -> JsError {
+    JsError::new(
+        "InvalidCharacterError".to_string(),
+        message.to_string(),
+        "".to_string(),
+    )
+}
+
+fn get_data_arg(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+    func_name: &str,
+) -> Result<String, JsError> {
+    match args.first() {
+        Some(data) => functions::call_to_string_q(realm, data),
+        None => Err(JsError::new(
+            "TypeError".to_string(),
+            format!("{func_name}: 1 argument required, but only 0 present"),
+            "".to_string(),
+        )),
+    }
+}
+
+pub(crate) fn init_ctx(q_ctx: &QuickJsRealmAdapter) -> Result<(), JsError> {
+    let global = get_global_q(q_ctx);
+
+    let btoa_func = functions::new_function_q(
+        q_ctx,
+        "btoa",
+        |realm, _this, args| {
+            let data = get_data_arg(realm, args, "btoa")?;
+            let bytes = data
+                .chars()
+                .map(u8::try_from)
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| {
+                    invalid_character_error(
+                        "btoa: the string contains characters outside of the Latin1 range",
+                    )
+                })?;
+            realm.create_string(encode(&bytes).as_str())
+        },
+        1,
+    )?;
+    objects::set_property2_q(q_ctx, &global, "btoa", &btoa_func, 0)?;
+
+    let atob_func = functions::new_function_q(
+        q_ctx,
+        "atob",
+        |realm, _this, args| {
+            let data = get_data_arg(realm, args, "atob")?;
+            let bytes = decode(data.as_str()).ok_or_else(|| {
+                invalid_character_error("atob: the string to be decoded is not correctly encoded")
+            })?;
+            let res: String = bytes.into_iter().map(char::from).collect();
+            realm.create_string(res.as_str())
+        },
+        1,
+    )?;
+    objects::set_property2_q(q_ctx, &global, "atob", &atob_func, 0)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::features::webencoding::base64::{decode, encode};
+    use crate::jsutils::Script;
+
+    #[test]
+    fn test_base64() {
+        // RFC 4648 test vectors
+        for (plain, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+            assert_eq!(decode(encoded).expect("invalid base64"), plain.as_bytes());
+        }
+
+        // forgiving-base64 (html/webappapis/atob/base64.any.js)
+        assert_eq!(decode(" Zm9v\nYg ").unwrap(), b"foob");
+        assert_eq!(decode("Zm9vYg").unwrap(), b"foob");
+        assert_eq!(decode("ab").unwrap(), vec![0x69]);
+        assert_eq!(decode("abc").unwrap(), vec![0x69, 0xB7]);
+        assert!(decode("ab=").is_none());
+        assert!(decode("a").is_none());
+        assert!(decode("abcde").is_none());
+        assert!(decode("ab==c").is_none());
+        assert!(decode("ab=c").is_none());
+        assert!(decode("a-b_").is_none());
+        assert!(decode("abc===").is_none());
+    }
+
+    #[test]
+    fn test_atob_btoa() {
+        let rt = init_test_rt();
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_atob_btoa.js",
+                    r#"
+                    let errors = [];
+                    try { btoa('€'); } catch (e) { errors.push(e.name); }
+                    try { atob('a'); } catch (e) { errors.push(e.name); }
+                    try { atob(); } catch (e) { errors.push(e.name); }
+                    [btoa('\xff\xfe'), atob('//4=') === '\xff\xfe', atob(btoa('hello')), btoa(null), errors.join(',')].join('|');
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "//4=|true|hello|bnVsbA==|InvalidCharacterError,InvalidCharacterError,TypeError"
+        );
+    }
+}
@@ -0,0 +1,481 @@
+//! TextEncoder and TextDecoder
+
+use crate::features::webencoding::type_error;
+use crate::jsutils::JsError;
+use crate::quickjs_utils::typedarrays::{
+    copy_view_bytes_q, get_typed_array_type_q, new_uint8_array_q,
+};
+use crate::quickjs_utils::{arrays, functions, objects, primitives};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::reflection::jsclass::JsClassInstances;
+use crate::reflection::Proxy;
+use crate::values::TypedArrayType;
+
+thread_local! {
+    static DECODERS: JsClassInstances<Decoder> = JsClassInstances::new();
+}
+
+/// the encodings supported by TextDecoder
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Encoding {
+    Utf8,
+    Utf16Le,
+    Windows1252,
+}
+
+impl Encoding {
+    /// get an encoding by one of its labels (see https://encoding.spec.whatwg.org/#names-and-labels)
+    pub(crate) fn from_label(label: &str) -> Option<Self> {
+        let label = label
+            .trim_matches(|c: char| c.is_ascii_whitespace())
+            .to_ascii_lowercase();
+        match label.as_str() {
+            "unicode-1-1-utf-8" | "unicode11utf8" | "unicode20utf8" | "utf-8" | "utf8"
+            | "x-unicode20utf8" => Some(Self::Utf8),
+            "csunicode" | "iso-10646-ucs-2" | "ucs-2" | "unicode" | "unicodefeff" | "utf-16"
+            | "utf-16le" => Some(Self::Utf16Le),
+            "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819"
+            | "iso-8859-1" | "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1"
+            | "iso_8859-1:1987" | "l1" | "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" => {
+                Some(Self::Windows1252)
+            }
+            _ => None,
+        }
+    }
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Windows1252 => "windows-1252",
+        }
+    }
+}
+
+/// the code points of windows-1252 bytes 0x80 to 0x9F, the other bytes map to the code point with the same value
+const WINDOWS_1252_HIGH: [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+/// the state of a TextDecoder, when streaming the bytes of an incomplete sequence at the end of a chunk are kept
+/// until the next call to decode
+pub(crate) struct Decoder {
+    encoding: Encoding,
+    fatal: bool,
+    ignore_bom: bool,
+    pending: Vec<u8>,
+    bom_checked: bool,
+}
+
+impl Decoder {
+    pub(crate) fn new(encoding: Encoding, fatal: bool, ignore_bom: bool) -> Self {
+        Self {
+            encoding,
+            fatal,
+            ignore_bom,
+            pending: vec![],
+            bom_checked: false,
+        }
+    }
+    /// decode a chunk, when stream is false the decoder is reset afterwards and an incomplete sequence at the end is
+    /// an error
+    pub(crate) fn decode(&mut self, input: &[u8], stream: bool) -> Result<String, JsError> {
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(input);
+        let res = match self.encoding {
+            Encoding::Utf8 => self.decode_utf8(&bytes, stream),
+            Encoding::Utf16Le => self.decode_utf16le(&bytes, stream),
+            Encoding::Windows1252 => Ok(decode_windows_1252(&bytes)),
+        };
+        let mut res = match res {
+            Ok(res) => res,
+            Err(err) => {
+                self.reset();
+                return Err(err);
+            }
+        };
+        if !self.bom_checked && !res.is_empty() {
+            self.bom_checked = true;
+            if !self.ignore_bom && res.starts_with('\u{FEFF}') {
+                res.remove(0);
+            }
+        }
+        if !stream {
+            self.reset();
+        }
+        Ok(res)
+    }
+    fn reset(&mut self) {
+        self.pending.clear();
+        self.bom_checked = false;
+    }
+    fn invalid(&self, out: &mut String) -> Result<(), JsError> {
+        if self.fatal {
+            Err(type_error(
+                format!(
+                    "The encoded data was not valid for encoding {}",
+                    self.encoding.name()
+                )
+                .as_str(),
+            ))
+        } else {
+            out.push(char::REPLACEMENT_CHARACTER);
+            Ok(())
+        }
+    }
+    fn decode_utf8(&mut self, bytes: &[u8], stream: bool) -> Result<String, JsError> {
+        let mut out = String::with_capacity(bytes.len());
+        let mut rest = bytes;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    return Ok(out);
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    out.push_str(std::str::from_utf8(valid).expect("valid utf-8"));
+                    match err.error_len() {
+                        Some(len) => {
+                            self.invalid(&mut out)?;
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            // the input ends with an incomplete sequence
+                            if stream {
+                                self.pending = invalid.to_vec();
+                            } else {
+                                self.invalid(&mut out)?;
+                            }
+                            return Ok(out);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    fn decode_utf16le(&mut self, bytes: &[u8], stream: bool) -> Result<String, JsError> {
+        let mut units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        // a lead surrogate at the end may be completed by the next chunk, at the end of the stream a lead surrogate
+        // followed by an odd byte is a single error
+        if (stream || bytes.len() % 2 == 1)
+            && units
+                .last()
+                .is_some_and(|unit| (0xD800..0xDC00).contains(unit))
+        {
+            units.pop();
+        }
+        let mut out = String::with_capacity(units.len());
+        for res in char::decode_utf16(units.iter().copied()) {
+            match res {
+                Ok(c) => out.push(c),
+                Err(_) => self.invalid(&mut out)?,
+            }
+        }
+        let consumed = units.len() * 2;
+        if consumed < bytes.len() {
+            if stream {
+                self.pending = bytes[consumed..].to_vec();
+            } else {
+                self.invalid(&mut out)?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| match b {
+            0x80..=0x9F => char::from_u32(WINDOWS_1252_HIGH[(b - 0x80) as usize] as u32)
+                .expect("valid code point"),
+            _ => *b as char,
+        })
+        .collect()
+}
+
+/// encode as much of source as fits in a buffer of capacity bytes, returns the bytes and the number of UTF-16 code
+/// units which were read
+fn encode_into(source: &str, capacity: usize) -> (Vec<u8>, usize) {
+    let mut end = 0;
+    let mut read = 0;
+    for c in source.chars() {
+        if end + c.len_utf8() > capacity {
+            break;
+        }
+        end += c.len_utf8();
+        read += c.len_utf16();
+    }
+    (source.as_bytes()[..end].to_vec(), read)
+}
+
+fn get_bool_option(
+    realm: &QuickJsRealmAdapter,
+    options: Option<&QuickJsValueAdapter>,
+    name: &str,
+) -> Result<bool, JsError> {
+    match options {
+        Some(options) if options.is_object() => {
+            let value = objects::get_property_q(realm, options, name)?;
+            Ok(value.is_bool() && primitives::to_bool(&value)?)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn get_string_arg(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+    index: usize,
+) -> Result<String, JsError> {
+    match args.get(index) {
+        Some(arg) if !arg.is_undefined() => functions::call_to_string_q(realm, arg),
+        _ => Ok("".to_string()),
+    }
+}
+
+pub(crate) fn init_ctx(q_ctx: &QuickJsRealmAdapter) -> Result<(), JsError> {
+    Proxy::new()
+        .name("TextEncoder")
+        .constructor(|_rt, _realm, _id, _args| Ok(()))
+        .getter("encoding", |_rt, realm, _id| realm.create_string("utf-8"))
+        .method("encode", |_rt, realm, _id, args| {
+            let input = get_string_arg(realm, args, 0)?;
+            new_uint8_array_q(realm, input.into_bytes())
+        })
+        .method("encodeInto", |_rt, realm, _id, args| {
+            let source = get_string_arg(realm, args, 0)?;
+            let destination = match args.get(1) {
+                Some(destination)
+                    if get_typed_array_type_q(destination) == Some(TypedArrayType::Uint8) =>
+                {
+                    destination
+                }
+                _ => {
+                    return Err(type_error(
+                        "TextEncoder.encodeInto: destination is not a Uint8Array",
+                    ))
+                }
+            };
+            let capacity = arrays::get_length_q(realm, destination)? as usize;
+            let (bytes, read) = encode_into(source.as_str(), capacity);
+            let written = bytes.len();
+            let src = new_uint8_array_q(realm, bytes)?;
+            functions::invoke_member_function_q(realm, destination, "set", &[src])?;
+
+            let res = realm.create_object()?;
+            realm.set_object_property(&res, "read", &realm.create_i32(read as i32)?)?;
+            realm.set_object_property(&res, "written", &realm.create_i32(written as i32)?)?;
+            Ok(res)
+        })
+        .install(q_ctx, true)?;
+
+    Proxy::new()
+        .name("TextDecoder")
+        .constructor(|_rt, realm, id, args| {
+            let label = match args.first() {
+                Some(label) if !label.is_undefined() => functions::call_to_string_q(realm, label)?,
+                _ => "utf-8".to_string(),
+            };
+            let encoding = Encoding::from_label(label.as_str()).ok_or_else(|| {
+                JsError::new(
+                    "RangeError".to_string(),
+                    format!("The encoding label provided ('{label}') is invalid."),
+                    "".to_string(),
+                )
+            })?;
+            let fatal = get_bool_option(realm, args.get(1), "fatal")?;
+            let ignore_bom = get_bool_option(realm, args.get(1), "ignoreBOM")?;
+            DECODERS.with(|d| d.insert(realm, id, Decoder::new(encoding, fatal, ignore_bom)));
+            Ok(())
+        })
+        .getter("encoding", |_rt, realm, id| {
+            let name = DECODERS.with(|d| d.with_instance(realm, *id, |d| d.encoding.name()))?;
+            realm.create_string(name)
+        })
+        .getter("fatal", |_rt, realm, id| {
+            let fatal = DECODERS.with(|d| d.with_instance(realm, *id, |d| d.fatal))?;
+            realm.create_boolean(fatal)
+        })
+        .getter("ignoreBOM", |_rt, realm, id| {
+            let ignore_bom = DECODERS.with(|d| d.with_instance(realm, *id, |d| d.ignore_bom))?;
+            realm.create_boolean(ignore_bom)
+        })
+        .method("decode", |_rt, realm, id, args| {
+            let bytes = match args.first() {
+                Some(input) if !input.is_undefined() => {
+                    copy_view_bytes_q(realm, input).map_err(|_| {
+                        type_error(
+                            "TextDecoder.decode: input is not an ArrayBuffer or ArrayBufferView",
+                        )
+                    })?
+                }
+                _ => vec![],
+            };
+            let stream = get_bool_option(realm, args.get(1), "stream")?;
+            let res = DECODERS.with(|d| {
+                d.with_instance_mut(realm, *id, |decoder| decoder.decode(&bytes, stream))
+            })??;
+            realm.create_string(res.as_str())
+        })
+        .finalizer(|_rt, realm, id| {
+            DECODERS.with(|d| d.remove(realm, id));
+        })
+        .install(q_ctx, true)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::features::webencoding::encoding::{Decoder, Encoding};
+    use crate::jsutils::Script;
+
+    fn decode_all(decoder: &mut Decoder, chunks: &[&[u8]]) -> String {
+        let mut res = String::new();
+        for chunk in chunks {
+            res.push_str(decoder.decode(chunk, true).expect("decode failed").as_str());
+        }
+        res.push_str(decoder.decode(&[], false).expect("decode failed").as_str());
+        res
+    }
+
+    #[test]
+    fn test_decoder() {
+        // labels (encoding/textdecoder-labels.any.js)
+        assert_eq!(Encoding::from_label(" UTF8\n"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_label("latin1"), Some(Encoding::Windows1252));
+        assert_eq!(Encoding::from_label("utf-16"), Some(Encoding::Utf16Le));
+        assert_eq!(Encoding::from_label("utf-7"), None);
+
+        // streaming splits multi byte sequences (encoding/textdecoder-streaming.any.js)
+        let bytes = "z\u{a2}\u{6c34}\u{1d11e}\u{10fffd}".as_bytes().to_vec();
+        for size in 1..bytes.len() {
+            let chunks: Vec<&[u8]> = bytes.chunks(size).collect();
+            let mut decoder = Decoder::new(Encoding::Utf8, false, false);
+            assert_eq!(
+                decode_all(&mut decoder, &chunks),
+                "z\u{a2}\u{6c34}\u{1d11e}\u{10fffd}"
+            );
+        }
+        let utf16: Vec<u8> = "z\u{1d11e}"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        for size in 1..utf16.len() {
+            let chunks: Vec<&[u8]> = utf16.chunks(size).collect();
+            let mut decoder = Decoder::new(Encoding::Utf16Le, true, false);
+            assert_eq!(decode_all(&mut decoder, &chunks), "z\u{1d11e}");
+        }
+
+        // invalid sequences (encoding/textdecoder-fatal.any.js)
+        let mut decoder = Decoder::new(Encoding::Utf8, false, false);
+        assert_eq!(
+            decoder
+                .decode(&[0x61, 0xC0, 0x62, 0xF0, 0x9F], false)
+                .unwrap(),
+            "a\u{FFFD}b\u{FFFD}"
+        );
+        let mut decoder = Decoder::new(Encoding::Utf8, true, false);
+        assert!(decoder.decode(&[0xFF], false).is_err());
+        assert!(decoder.decode(&[0xED, 0xA0, 0x80], false).is_err());
+        assert!(decoder.decode(&[0xE2, 0x82], true).is_ok());
+        assert!(decoder.decode(&[], false).is_err());
+        let mut decoder = Decoder::new(Encoding::Utf16Le, false, false);
+        assert_eq!(
+            decoder.decode(&[0x00, 0xD8, 0x61], false).unwrap(),
+            "\u{FFFD}"
+        );
+        assert_eq!(
+            decoder.decode(&[0x00, 0xD8, 0x61, 0x00], false).unwrap(),
+            "\u{FFFD}a"
+        );
+
+        // BOM handling (encoding/textdecoder-byte-order-marks.any.js)
+        let mut decoder = Decoder::new(Encoding::Utf8, false, false);
+        assert_eq!(
+            decoder.decode(&[0xEF, 0xBB, 0xBF, 0x61], false).unwrap(),
+            "a"
+        );
+        assert_eq!(decoder.decode(&[0xEF, 0xBB], true).unwrap(), "");
+        assert_eq!(
+            decoder.decode(&[0xBF, 0xEF, 0xBB, 0xBF], false).unwrap(),
+            "\u{FEFF}"
+        );
+        let mut decoder = Decoder::new(Encoding::Utf8, false, true);
+        assert_eq!(
+            decoder.decode(&[0xEF, 0xBB, 0xBF, 0x61], false).unwrap(),
+            "\u{FEFF}a"
+        );
+        let mut decoder = Decoder::new(Encoding::Utf16Le, false, false);
+        assert_eq!(
+            decoder.decode(&[0xFF, 0xFE, 0x61, 0x00], false).unwrap(),
+            "a"
+        );
+
+        // single byte (encoding/legacy-mb-*, windows-1252 index)
+        let mut decoder = Decoder::new(Encoding::Windows1252, true, false);
+        assert_eq!(
+            decoder
+                .decode(&[0x41, 0x80, 0x81, 0x9F, 0xE9], false)
+                .unwrap(),
+            "A\u{20AC}\u{81}\u{178}\u{E9}"
+        );
+    }
+
+    #[test]
+    fn test_text_encoder_decoder() {
+        let rt = init_test_rt();
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_text_encoder_decoder.js",
+                    r#"
+                    let encoder = new TextEncoder();
+                    let bytes = encoder.encode('aé€𝄞');
+                    let target = new Uint8Array(5);
+                    let into = encoder.encodeInto('aé€', target);
+                    let decoder = new TextDecoder();
+                    let streamed = decoder.decode(bytes.subarray(0, 4), {stream: true}) + decoder.decode(bytes.subarray(4));
+                    let fatalError;
+                    try {
+                        new TextDecoder('utf-8', {fatal: true}).decode(new Uint8Array([0xff]));
+                    } catch (e) {
+                        fatalError = e.name;
+                    }
+                    let labelError;
+                    try {
+                        new TextDecoder('nope');
+                    } catch (e) {
+                        labelError = e.name;
+                    }
+                    [
+                        encoder.encoding,
+                        bytes.join(' '),
+                        into.read + '/' + into.written + ':' + target.join(' '),
+                        streamed === 'aé€𝄞',
+                        new TextDecoder('latin1').encoding,
+                        new TextDecoder('utf-16le').decode(new Uint16Array([0x68, 0x69]).buffer),
+                        new TextDecoder('utf-8', {fatal: true, ignoreBOM: true}).fatal,
+                        fatalError,
+                        labelError,
+                        new TextDecoder().decode(),
+                    ].join('|');
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "utf-8|97 195 169 226 130 172 240 157 132 158|2/3:97 195 169 0 0|true|windows-1252|hi|true|TypeError|RangeError|"
+        );
+    }
+}
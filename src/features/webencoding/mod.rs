@@ -0,0 +1,50 @@
+//! the web-encoding feature installs the TextEncoder, TextDecoder, URL and URLSearchParams classes and the atob and btoa
+//! functions in every realm, these are implemented natively
+//!
+//! * TextEncoder encodes strings as UTF-8 (encode and encodeInto)
+//! * TextDecoder decodes utf-8, utf-16le and windows-1252 (latin1), it supports the fatal and ignoreBOM options and streaming
+//!   with decode(chunk, {stream: true})
+//! * atob and btoa convert between binary strings and base64
+//! * URL and URLSearchParams follow the WHATWG URL standard (using the url crate), url.searchParams and its URL are kept
+//!   in sync
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! let res = rt.eval_sync(None, Script::new("webencoding.js", r#"
+//!     let bytes = new TextEncoder().encode('€uro');
+//!     let url = new URL('../b?x=1#top', 'https://example.com/a/c');
+//!     url.searchParams.append('q', new TextDecoder().decode(bytes));
+//!     url.href + ' ' + btoa('hi');
+//! "#)).expect("script failed");
+//! assert_eq!(res.get_str(), "https://example.com/b?x=1&q=%E2%82%ACuro#top aGk=");
+//! ```
+
+use crate::jsutils::JsError;
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
+
+mod base64;
+mod encoding;
+mod url;
+mod urlsearchparams;
+
+pub fn init(q_js_rt: &QuickJsRuntimeAdapter) -> Result<(), JsError> {
+    log::trace!("webencoding::init");
+
+    q_js_rt.add_context_init_hook(|_q_js_rt, q_ctx| init_ctx(q_ctx))
+}
+
+fn init_ctx(q_ctx: &QuickJsRealmAdapter) -> Result<(), JsError> {
+    encoding::init_ctx(q_ctx)?;
+    base64::init_ctx(q_ctx)?;
+    url::init_ctx(q_ctx)?;
+    urlsearchparams::init_ctx(q_ctx)
+}
+
+/// a TypeError which is thrown in script
+pub(crate) fn type_error(message: &str) -> JsError {
+    JsError::new("TypeError".to_string(), message.to_string(), "".to_string())
+}
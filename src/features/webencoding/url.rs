@@ -0,0 +1,308 @@
+//! the URL class, parsing and the setters follow the WHATWG URL standard and are implemented by the url crate
+//! (including the UTS #46 processing of domains)
+
+use crate::features::webencoding::type_error;
+use crate::features::webencoding::urlsearchparams::{self, SEARCH_PARAMS};
+use crate::jsutils::JsError;
+use crate::quickjs_utils::functions;
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::reflection::jsclass::JsClassInstances;
+use crate::reflection::{JsProxyInstanceId, Proxy};
+use ::url::{quirks, Url};
+
+thread_local! {
+    pub(crate) static URLS: JsClassInstances<UrlInstance> = JsClassInstances::new();
+}
+
+/// the rust part of a URL instance, the URLSearchParams object is created when searchParams is first used
+pub(crate) struct UrlInstance {
+    pub(crate) url: Url,
+    search_params: Option<(JsProxyInstanceId, QuickJsValueAdapter)>,
+}
+
+/// parse a URL, a relative URL is parsed against base (which should be a valid URL itself)
+pub(crate) fn parse(input: &str, base: Option<&str>) -> Option<Url> {
+    match base {
+        Some(base) => Url::parse(base).ok()?.join(input).ok(),
+        None => Url::parse(input).ok(),
+    }
+}
+
+fn get_string_arg(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+    index: usize,
+) -> Result<Option<String>, JsError> {
+    match args.get(index) {
+        Some(arg) if !arg.is_undefined() => Ok(Some(functions::call_to_string_q(realm, arg)?)),
+        _ => Ok(None),
+    }
+}
+
+/// parse the url and base arguments of the URL constructor, URL.parse and URL.canParse
+fn parse_args(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<Option<Url>, JsError> {
+    let Some(url) = get_string_arg(realm, args, 0)? else {
+        return Err(type_error("URL: 1 argument required, but only 0 present"));
+    };
+    let base = get_string_arg(realm, args, 1)?;
+    Ok(parse(url.as_str(), base.as_deref()))
+}
+
+fn with_url<R, C>(
+    realm: &QuickJsRealmAdapter,
+    id: &JsProxyInstanceId,
+    consumer: C,
+) -> Result<R, JsError>
+where
+    C: FnOnce(&Url) -> R,
+{
+    URLS.with(|urls| urls.with_instance(realm, *id, |url| consumer(&url.url)))
+}
+
+/// update a URL with a setter, when the query changed the URLSearchParams of the URL are updated
+fn update_url<C>(
+    realm: &QuickJsRealmAdapter,
+    id: &JsProxyInstanceId,
+    updater: C,
+) -> Result<(), JsError>
+where
+    C: FnOnce(&mut Url),
+{
+    let query_update = URLS.with(|urls| {
+        urls.with_instance_mut(realm, *id, |url| {
+            let old_query = url.url.query().map(|query| query.to_string());
+            updater(&mut url.url);
+            match &url.search_params {
+                Some((params_id, _)) if url.url.query() != old_query.as_deref() => {
+                    Some((*params_id, url.url.query().unwrap_or_default().to_string()))
+                }
+                _ => None,
+            }
+        })
+    })?;
+    if let Some((params_id, query)) = query_update {
+        urlsearchparams::set_list_from_query(realm, params_id, query.as_str())?;
+    }
+    Ok(())
+}
+
+fn getter<G>(proxy: Proxy, name: &str, getter: G) -> Proxy
+where
+    G: Fn(&Url) -> String + 'static,
+{
+    proxy.getter(name, move |_rt, realm, id| {
+        let value = with_url(realm, id, &getter)?;
+        realm.create_string(value.as_str())
+    })
+}
+
+/// a getter and setter from url::quirks, the setters ignore invalid values like the URL standard does
+fn getter_setter<G, S>(proxy: Proxy, name: &str, getter: G, setter: S) -> Proxy
+where
+    G: Fn(&Url) -> &str + 'static,
+    S: Fn(&mut Url, &str) + 'static,
+{
+    proxy.getter_setter(
+        name,
+        move |_rt, realm, id| {
+            let value = with_url(realm, id, |url| getter(url).to_string())?;
+            realm.create_string(value.as_str())
+        },
+        move |_rt, realm, id, value| {
+            let value = functions::call_to_string_q(realm, &value)?;
+            update_url(realm, id, |url| setter(url, value.as_str()))
+        },
+    )
+}
+
+pub(crate) fn init_ctx(q_ctx: &QuickJsRealmAdapter) -> Result<(), JsError> {
+    let mut proxy = Proxy::new()
+        .name("URL")
+        .constructor(|_rt, realm, id, args| {
+            let url = parse_args(realm, args)?.ok_or_else(|| type_error("Invalid URL"))?;
+            URLS.with(|urls| {
+                urls.insert(
+                    realm,
+                    id,
+                    UrlInstance {
+                        url,
+                        search_params: None,
+                    },
+                )
+            });
+            Ok(())
+        })
+        .static_method("canParse", |_rt, realm, args| {
+            realm.create_boolean(parse_args(realm, args)?.is_some())
+        })
+        .static_method("parse", |_rt, realm, args| {
+            if parse_args(realm, args)?.is_some() {
+                Ok(realm.instantiate_proxy(&[], "URL", args)?.1)
+            } else {
+                realm.create_null()
+            }
+        })
+        .getter("searchParams", |_rt, realm, id| {
+            let (existing, query) = URLS.with(|urls| {
+                urls.with_instance(realm, *id, |url| {
+                    (
+                        url.search_params.as_ref().map(|(_, params)| params.clone()),
+                        url.url.query().unwrap_or_default().to_string(),
+                    )
+                })
+            })?;
+            if let Some(params) = existing {
+                return Ok(params);
+            }
+            let (params_id, params) =
+                realm.instantiate_proxy(&[], "URLSearchParams", &[realm.create_string(&query)?])?;
+            SEARCH_PARAMS.with(|p| p.with_instance_mut(realm, params_id, |p| p.url = Some(*id)))?;
+            URLS.with(|urls| {
+                urls.with_instance_mut(realm, *id, |url| {
+                    url.search_params = Some((params_id, params.clone()))
+                })
+            })?;
+            Ok(params)
+        })
+        .method("toString", |_rt, realm, id, _args| {
+            let href = with_url(realm, id, |url| quirks::href(url).to_string())?;
+            realm.create_string(href.as_str())
+        })
+        .method("toJSON", |_rt, realm, id, _args| {
+            let href = with_url(realm, id, |url| quirks::href(url).to_string())?;
+            realm.create_string(href.as_str())
+        })
+        .finalizer(|_rt, realm, id| {
+            // the removed instance is dropped after the map was borrowed, this may finalize the URLSearchParams object
+            URLS.with(|urls| urls.remove(realm, id));
+        });
+
+    // unlike the other setters, setting an invalid href throws a TypeError instead of being ignored
+    proxy = proxy.getter_setter(
+        "href",
+        |_rt, realm, id| {
+            let href = with_url(realm, id, |url| quirks::href(url).to_string())?;
+            realm.create_string(href.as_str())
+        },
+        |_rt, realm, id, value| {
+            let value = functions::call_to_string_q(realm, &value)?;
+            let new_url = parse(value.as_str(), None).ok_or_else(|| type_error("Invalid URL"))?;
+            update_url(realm, id, |url| *url = new_url)
+        },
+    );
+    proxy = getter(proxy, "origin", quirks::origin);
+    proxy = getter_setter(proxy, "protocol", quirks::protocol, |url, value| {
+        let _ = quirks::set_protocol(url, value);
+    });
+    proxy = getter_setter(proxy, "username", quirks::username, |url, value| {
+        let _ = quirks::set_username(url, value);
+    });
+    proxy = getter_setter(proxy, "password", quirks::password, |url, value| {
+        let _ = quirks::set_password(url, value);
+    });
+    proxy = getter_setter(proxy, "host", quirks::host, |url, value| {
+        let _ = quirks::set_host(url, value);
+    });
+    proxy = getter_setter(proxy, "hostname", quirks::hostname, |url, value| {
+        let _ = quirks::set_hostname(url, value);
+    });
+    proxy = getter_setter(proxy, "port", quirks::port, |url, value| {
+        let _ = quirks::set_port(url, value);
+    });
+    proxy = getter_setter(proxy, "pathname", quirks::pathname, quirks::set_pathname);
+    proxy = getter_setter(proxy, "search", quirks::search, quirks::set_search);
+    proxy = getter_setter(proxy, "hash", quirks::hash, quirks::set_hash);
+    proxy.install(q_ctx, true)?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::features::webencoding::url::parse;
+    use crate::jsutils::Script;
+
+    #[test]
+    fn test_parse() {
+        for (input, base, expected) in [
+            (
+                "/foo/../bar",
+                Some("http://example.org/a/b"),
+                Some("http://example.org/bar"),
+            ),
+            (
+                "//other/x",
+                Some("https://example.org/a"),
+                Some("https://other/x"),
+            ),
+            ("http://ñ.test/", None, Some("http://xn--ida.test/")),
+            (
+                "http://Bücher.example/",
+                None,
+                Some("http://xn--bcher-kva.example/"),
+            ),
+            ("test", None, None),
+            ("test", Some("nope"), None),
+            ("?query", Some("about:blank"), None),
+        ] {
+            assert_eq!(
+                parse(input, base).map(|url| url.to_string()).as_deref(),
+                expected,
+                "parsing {input:?} with base {base:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_url() {
+        let rt = init_test_rt();
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_url.js",
+                    r#"
+                    let url = new URL('https://user:pw@example.com:8080/a/b?x=1#frag');
+                    let parts = [url.protocol, url.username, url.password, url.host, url.hostname, url.port, url.pathname, url.search, url.hash, url.origin];
+                    url.protocol = 'http';
+                    url.hostname = 'EXAMPLE.org';
+                    url.port = '80';
+                    url.pathname = '/c d';
+                    url.hash = '';
+                    url.search = '?q=a b';
+                    let changed = url.href;
+                    let params = url.searchParams;
+                    params.append('n', '1');
+                    let synced = url.search;
+                    url.search = '?z=2';
+                    let errors = [];
+                    try { new URL('nope'); } catch (e) { errors.push(e.name); }
+                    try { url.href = 'nope'; } catch (e) { errors.push(e.name); }
+                    [
+                        parts.join(','),
+                        changed,
+                        synced,
+                        params.get('z'),
+                        url.searchParams === params,
+                        new URL('../x', 'http://h/a/b/c').href,
+                        URL.canParse('x', 'http://h/'),
+                        URL.canParse('x'),
+                        URL.parse('nope'),
+                        JSON.stringify({u: new URL('http://h')}),
+                        errors.join(','),
+                    ].join('|');
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "https:,user,pw,example.com:8080,example.com,8080,/a/b,?x=1,#frag,https://example.com:8080|\
+             http://user:pw@example.org/c%20d?q=a%20b|?q=a+b&n=1|2|true|http://h/a/x|true|false||{\"u\":\"http://h/\"}|TypeError,TypeError"
+        );
+    }
+}
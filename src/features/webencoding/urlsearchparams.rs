@@ -0,0 +1,434 @@
+//! the URLSearchParams class, the application/x-www-form-urlencoded parser and serializer of the url crate are used
+
+use crate::features::webencoding::type_error;
+use crate::features::webencoding::url::URLS;
+use crate::jsutils::JsError;
+use crate::quickjs_utils::{arrays, functions, get_global_q, objects, primitives};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::reflection::jsclass::JsClassInstances;
+use crate::reflection::{iterators, JsProxyInstanceId, Proxy};
+use crate::values::JsValueFacade;
+use ::url::form_urlencoded;
+
+thread_local! {
+    pub(crate) static SEARCH_PARAMS: JsClassInstances<SearchParams> = JsClassInstances::new();
+}
+
+/// the rust part of a URLSearchParams instance
+pub(crate) struct SearchParams {
+    list: Vec<(String, String)>,
+    /// the URL whose query is updated when the list changes (for url.searchParams)
+    pub(crate) url: Option<JsProxyInstanceId>,
+}
+
+impl SearchParams {
+    /// write the list to the query of the URL
+    fn update(&self, realm: &QuickJsRealmAdapter) {
+        if let Some(url_id) = self.url {
+            let query = serialize(&self.list);
+            // the URL may already have been finalized
+            let _ = URLS.with(|urls| {
+                urls.with_instance_mut(realm, url_id, |url| {
+                    if query.is_empty() {
+                        url.url.set_query(None);
+                    } else {
+                        url.url.set_query(Some(query.as_str()));
+                    }
+                })
+            });
+        }
+    }
+}
+
+/// parse application/x-www-form-urlencoded name/value pairs
+pub(crate) fn parse(input: &str) -> Vec<(String, String)> {
+    form_urlencoded::parse(input.as_bytes())
+        .into_owned()
+        .collect()
+}
+
+/// serialize name/value pairs as application/x-www-form-urlencoded
+pub(crate) fn serialize(list: &[(String, String)]) -> String {
+    form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(list)
+        .finish()
+}
+
+/// replace the list of a URLSearchParams, this is called when the query of its URL is set
+pub(crate) fn set_list_from_query(
+    realm: &QuickJsRealmAdapter,
+    id: JsProxyInstanceId,
+    query: &str,
+) -> Result<(), JsError> {
+    SEARCH_PARAMS.with(|p| p.with_instance_mut(realm, id, |params| params.list = parse(query)))
+}
+
+fn is_iterable(realm: &QuickJsRealmAdapter, value: &QuickJsValueAdapter) -> Result<bool, JsError> {
+    let global = get_global_q(realm);
+    let symbol = objects::get_property_q(realm, &global, "Symbol")?;
+    let iterator_symbol = objects::get_property_q(realm, &symbol, "iterator")?;
+    let reflect = objects::get_property_q(realm, &global, "Reflect")?;
+    let res = functions::invoke_member_function_q(
+        realm,
+        &reflect,
+        "has",
+        &[value.clone(), iterator_symbol],
+    )?;
+    primitives::to_bool(&res)
+}
+
+fn array_from(
+    realm: &QuickJsRealmAdapter,
+    value: &QuickJsValueAdapter,
+) -> Result<QuickJsValueAdapter, JsError> {
+    let array = objects::get_property_q(realm, &get_global_q(realm), "Array")?;
+    functions::invoke_member_function_q(realm, &array, "from", &[value.clone()])
+}
+
+/// convert the init argument of the constructor, this is a sequence of pairs, a record or a query string
+fn init_list(
+    realm: &QuickJsRealmAdapter,
+    init: &QuickJsValueAdapter,
+) -> Result<Vec<(String, String)>, JsError> {
+    if init.is_proxy_instance() {
+        if let Ok((class_name, other_id)) = realm.get_proxy_instance_info(init) {
+            if class_name == "URLSearchParams" {
+                return SEARCH_PARAMS
+                    .with(|p| p.with_instance(realm, other_id, |other| other.list.clone()));
+            }
+        }
+    }
+    if !init.is_object() {
+        let query = functions::call_to_string_q(realm, init)?;
+        return Ok(parse(query.strip_prefix('?').unwrap_or(query.as_str())));
+    }
+    let mut list = vec![];
+    if is_iterable(realm, init)? {
+        let pairs = array_from(realm, init)?;
+        for index in 0..arrays::get_length_q(realm, &pairs)? {
+            let pair = arrays::get_element_q(realm, &pairs, index)?;
+            if !pair.is_object() || !is_iterable(realm, &pair)? {
+                return Err(type_error("URLSearchParams: a pair is not iterable"));
+            }
+            let pair = array_from(realm, &pair)?;
+            if arrays::get_length_q(realm, &pair)? != 2 {
+                return Err(type_error(
+                    "URLSearchParams: a pair does not consist of exactly two items",
+                ));
+            }
+            let name = arrays::get_element_q(realm, &pair, 0)?;
+            let value = arrays::get_element_q(realm, &pair, 1)?;
+            list.push((
+                functions::call_to_string_q(realm, &name)?,
+                functions::call_to_string_q(realm, &value)?,
+            ));
+        }
+    } else {
+        for name in objects::get_property_names_q(realm, init)? {
+            let value = objects::get_property_q(realm, init, name.as_str())?;
+            list.push((name, functions::call_to_string_q(realm, &value)?));
+        }
+    }
+    Ok(list)
+}
+
+fn string_arg(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+    index: usize,
+    method: &str,
+) -> Result<String, JsError> {
+    match args.get(index) {
+        Some(arg) => functions::call_to_string_q(realm, arg),
+        None => Err(type_error(
+            format!(
+                "URLSearchParams.{method}: {} arguments required, but only {} present",
+                index + 1,
+                args.len()
+            )
+            .as_str(),
+        )),
+    }
+}
+
+/// the optional value argument of delete and has
+fn optional_value_arg(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<Option<String>, JsError> {
+    match args.get(1) {
+        Some(value) if !value.is_undefined() => {
+            Ok(Some(functions::call_to_string_q(realm, value)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn with_params<R, C>(
+    realm: &QuickJsRealmAdapter,
+    id: &JsProxyInstanceId,
+    consumer: C,
+) -> Result<R, JsError>
+where
+    C: FnOnce(&SearchParams) -> R,
+{
+    SEARCH_PARAMS.with(|p| p.with_instance(realm, *id, consumer))
+}
+
+/// change the list and update the URL
+fn update_params<C>(
+    realm: &QuickJsRealmAdapter,
+    id: &JsProxyInstanceId,
+    updater: C,
+) -> Result<QuickJsValueAdapter, JsError>
+where
+    C: FnOnce(&mut Vec<(String, String)>),
+{
+    SEARCH_PARAMS.with(|p| {
+        p.with_instance_mut(realm, *id, |params| {
+            updater(&mut params.list);
+            params.update(realm);
+        })
+    })?;
+    realm.create_undefined()
+}
+
+fn new_pairs_iterator<F>(
+    realm: &QuickJsRealmAdapter,
+    id: &JsProxyInstanceId,
+    mapper: F,
+) -> Result<QuickJsValueAdapter, JsError>
+where
+    F: Fn((String, String)) -> JsValueFacade + 'static,
+{
+    let list = with_params(realm, id, |params| params.list.clone())?;
+    iterators::new_iterator(
+        realm,
+        Box::new(list.into_iter().map(move |pair| Ok(mapper(pair)))),
+    )
+}
+
+fn entry(pair: (String, String)) -> JsValueFacade {
+    JsValueFacade::Array {
+        val: vec![
+            JsValueFacade::new_string(pair.0),
+            JsValueFacade::new_string(pair.1),
+        ],
+    }
+}
+
+pub(crate) fn init_ctx(q_ctx: &QuickJsRealmAdapter) -> Result<(), JsError> {
+    Proxy::new()
+        .name("URLSearchParams")
+        .constructor(|_rt, realm, id, args| {
+            let list = match args.first() {
+                Some(init) if !init.is_undefined() => init_list(realm, init)?,
+                _ => vec![],
+            };
+            SEARCH_PARAMS.with(|p| p.insert(realm, id, SearchParams { list, url: None }));
+            Ok(())
+        })
+        .getter("size", |_rt, realm, id| {
+            let size = with_params(realm, id, |params| params.list.len())?;
+            realm.create_i32(size as i32)
+        })
+        .method("append", |_rt, realm, id, args| {
+            let name = string_arg(realm, args, 0, "append")?;
+            let value = string_arg(realm, args, 1, "append")?;
+            update_params(realm, id, |list| list.push((name, value)))
+        })
+        .method("delete", |_rt, realm, id, args| {
+            let name = string_arg(realm, args, 0, "delete")?;
+            let value = optional_value_arg(realm, args)?;
+            update_params(realm, id, |list| {
+                list.retain(|(n, v)| *n != name || (value.is_some() && value.as_ref() != Some(v)))
+            })
+        })
+        .method("get", |_rt, realm, id, args| {
+            let name = string_arg(realm, args, 0, "get")?;
+            let value = with_params(realm, id, |params| {
+                params
+                    .list
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, v)| v.clone())
+            })?;
+            match value {
+                Some(value) => realm.create_string(value.as_str()),
+                None => realm.create_null(),
+            }
+        })
+        .method("getAll", |_rt, realm, id, args| {
+            let name = string_arg(realm, args, 0, "getAll")?;
+            let values = with_params(realm, id, |params| {
+                params
+                    .list
+                    .iter()
+                    .filter(|(n, _)| *n == name)
+                    .map(|(_, v)| v.clone())
+                    .collect::<Vec<String>>()
+            })?;
+            let res = realm.create_array()?;
+            for (index, value) in values.iter().enumerate() {
+                arrays::set_element_q(realm, &res, index as u32, &realm.create_string(value)?)?;
+            }
+            Ok(res)
+        })
+        .method("has", |_rt, realm, id, args| {
+            let name = string_arg(realm, args, 0, "has")?;
+            let value = optional_value_arg(realm, args)?;
+            let has = with_params(realm, id, |params| {
+                params
+                    .list
+                    .iter()
+                    .any(|(n, v)| *n == name && (value.is_none() || value.as_ref() == Some(v)))
+            })?;
+            realm.create_boolean(has)
+        })
+        .method("set", |_rt, realm, id, args| {
+            let name = string_arg(realm, args, 0, "set")?;
+            let value = string_arg(realm, args, 1, "set")?;
+            update_params(realm, id, |list| {
+                // the first pair with the name gets the value, the others are removed
+                match list.iter().position(|(n, _)| *n == name) {
+                    Some(first) => {
+                        list[first].1 = value;
+                        let mut index = 0;
+                        list.retain(|(n, _)| {
+                            index += 1;
+                            index - 1 == first || *n != name
+                        });
+                    }
+                    None => list.push((name, value)),
+                }
+            })
+        })
+        .method("sort", |_rt, realm, id, _args| {
+            // a stable sort by the UTF-16 code units of the names
+            update_params(realm, id, |list| {
+                list.sort_by(|a, b| a.0.encode_utf16().cmp(b.0.encode_utf16()))
+            })
+        })
+        .method("toString", |_rt, realm, id, _args| {
+            let res = with_params(realm, id, |params| serialize(&params.list))?;
+            realm.create_string(res.as_str())
+        })
+        .method("forEach", |_rt, realm, id, args| {
+            let callback = match args.first() {
+                Some(callback) if callback.is_function() => callback.clone(),
+                _ => {
+                    return Err(type_error(
+                        "URLSearchParams.forEach: the callback is not a function",
+                    ))
+                }
+            };
+            let list = with_params(realm, id, |params| params.list.clone())?;
+            for (name, value) in list {
+                functions::call_function_q(
+                    realm,
+                    &callback,
+                    &[realm.create_string(&value)?, realm.create_string(&name)?],
+                    args.get(1),
+                )?;
+            }
+            realm.create_undefined()
+        })
+        .method("entries", |_rt, realm, id, _args| {
+            new_pairs_iterator(realm, id, entry)
+        })
+        .method("keys", |_rt, realm, id, _args| {
+            new_pairs_iterator(realm, id, |(name, _)| JsValueFacade::new_string(name))
+        })
+        .method("values", |_rt, realm, id, _args| {
+            new_pairs_iterator(realm, id, |(_, value)| JsValueFacade::new_string(value))
+        })
+        .iterator(|_rt, realm, id| {
+            let list = with_params(realm, id, |params| params.list.clone())?;
+            Ok(Box::new(list.into_iter().map(|pair| Ok(entry(pair)))))
+        })
+        .finalizer(|_rt, realm, id| {
+            SEARCH_PARAMS.with(|p| p.remove(realm, id));
+        })
+        .install(q_ctx, true)?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::features::webencoding::urlsearchparams::{parse, serialize};
+    use crate::jsutils::Script;
+
+    fn pairs(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_form_urlencoded() {
+        // url/urlencoded-parser.any.js
+        assert_eq!(parse("test"), pairs(&[("test", "")]));
+        assert_eq!(
+            parse("\u{FEFF}test=\u{FEFF}"),
+            pairs(&[("\u{FEFF}test", "\u{FEFF}")])
+        );
+        assert_eq!(
+            parse("%EF%BB%BFtest=%EF%BB%BF"),
+            pairs(&[("\u{FEFF}test", "\u{FEFF}")])
+        );
+        assert_eq!(parse("&&&a=b&&&&c=d&"), pairs(&[("a", "b"), ("c", "d")]));
+        assert_eq!(parse("a=b=c&d"), pairs(&[("a", "b=c"), ("d", "")]));
+        assert_eq!(parse("a+b=c+d%2B"), pairs(&[("a b", "c d+")]));
+        assert_eq!(parse("%FE%FF"), pairs(&[("\u{FFFD}\u{FFFD}", "")]));
+        assert_eq!(parse("%zz=%4"), pairs(&[("%zz", "%4")]));
+
+        // url/urlsearchparams-stringifier.any.js
+        assert_eq!(
+            serialize(&pairs(&[("a b", "c+d"), ("e", "*-._~!'()"), ("€", "\n")])),
+            "a+b=c%2Bd&e=*-._%7E%21%27%28%29&%E2%82%AC=%0A"
+        );
+    }
+
+    #[test]
+    fn test_url_search_params() {
+        let rt = init_test_rt();
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_url_search_params.js",
+                    r#"
+                    let params = new URLSearchParams('?a=1&b=2&a=3');
+                    let res = [params.get('a'), params.getAll('a').join(','), params.get('c'), params.has('b'), params.has('b', '3'), params.size];
+                    params.set('a', '4');
+                    params.delete('b');
+                    params.append('c', 'x y');
+                    res.push(params.toString());
+                    let sorted = new URLSearchParams([['z', '1'], ['\uFFFD', '2'], ['\u{1F308}', '3'], ['a', '4'], ['z', '0']]);
+                    sorted.sort();
+                    res.push([...sorted.keys()].join(','));
+                    res.push(new URLSearchParams({k: 'v', n: 1}).toString());
+                    res.push(new URLSearchParams(new Map([['m', 'p']])).toString());
+                    res.push(new URLSearchParams(params).toString());
+                    let seen = [];
+                    params.forEach((value, name) => seen.push(name + ':' + value));
+                    res.push(seen.join(','));
+                    res.push(JSON.stringify([...params]));
+                    res.push(JSON.stringify([...params.entries()]) === JSON.stringify([...params]));
+                    let url = new URL('http://h/p?x=1');
+                    url.searchParams.delete('x');
+                    res.push(url.href);
+                    try { new URLSearchParams([['a']]); } catch (e) { res.push(e.name); }
+                    res.join('|');
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "1|1,3||true|false|3|a=4&c=x+y|a,z,z,\u{1F308},\u{FFFD}|k=v&n=1|m=p|a=4&c=x+y|a:4,c:x y|[[\"a\",\"4\"],[\"c\",\"x y\"]]|true|http://h/p|TypeError"
+        );
+    }
+}
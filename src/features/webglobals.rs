@@ -0,0 +1,445 @@
+//! the webglobals feature installs the queueMicrotask, structuredClone and reportError functions in every realm
+//!
+//! * queueMicrotask(callback) runs the callback as a microtask, errors thrown by the callback are reported like unhandled errors
+//! * structuredClone(value, {transfer}) deep clones a value, supporting Date, Map, Set, Error, ArrayBuffer, TypedArrays,
+//!   DataView and cyclic references, ArrayBuffers in the transfer list are detached after being cloned, values which can
+//!   not be cloned (like functions and symbols) result in a DataCloneError
+//! * reportError(error) reports an error like an unhandled error (it is logged with its stack) without throwing it
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! let res = rt.eval_sync(None, Script::new("webglobals.js", r#"
+//!     let original = {date: new Date(0), map: new Map([['a', 1]])};
+//!     original.self = original;
+//!     let clone = structuredClone(original);
+//!     clone !== original && clone.self === clone && clone.map.get('a') === 1 && clone.date.getTime() === 0;
+//! "#)).expect("script failed");
+//! assert!(res.get_bool());
+//! ```
+
+use crate::jsutils::{JsError, JsValueType};
+use crate::quickjs_utils;
+use crate::quickjs_utils::errors::report_unhandled_error;
+use crate::quickjs_utils::objects::construct_object;
+use crate::quickjs_utils::typedarrays::{
+    detach_array_buffer_buffer_q, get_array_buffer_buffer_copy_q, get_typed_array_type_q,
+    new_array_buffer_q,
+};
+use crate::quickjs_utils::{
+    arrays, dates, functions, get_constructor, get_global_q, maps, objects, primitives, sets,
+};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::values::TypedArrayType;
+use libquickjs_sys as q;
+use std::collections::HashMap;
+
+pub fn init(q_js_rt: &QuickJsRuntimeAdapter) -> Result<(), JsError> {
+    log::trace!("webglobals::init");
+
+    q_js_rt.add_context_init_hook(|_q_js_rt, q_ctx| init_ctx(q_ctx))
+}
+
+fn init_ctx(q_ctx: &QuickJsRealmAdapter) -> Result<(), JsError> {
+    let global = get_global_q(q_ctx);
+
+    let queue_microtask_func = functions::new_function_q(
+        q_ctx,
+        "queueMicrotask",
+        |realm, _this, args| queue_microtask(realm, args),
+        1,
+    )?;
+    objects::set_property2_q(q_ctx, &global, "queueMicrotask", &queue_microtask_func, 0)?;
+
+    let structured_clone_func = functions::new_function_q(
+        q_ctx,
+        "structuredClone",
+        |realm, _this, args| structured_clone(realm, args),
+        1,
+    )?;
+    objects::set_property2_q(q_ctx, &global, "structuredClone", &structured_clone_func, 0)?;
+
+    let report_error_func = functions::new_function_q(
+        q_ctx,
+        "reportError",
+        |realm, _this, args| {
+            let error = args
+                .first()
+                .cloned()
+                .unwrap_or_else(quickjs_utils::new_undefined_ref);
+            report_unhandled_error(realm, "reportError", &error);
+            Ok(quickjs_utils::new_undefined_ref())
+        },
+        1,
+    )?;
+    objects::set_property2_q(q_ctx, &global, "reportError", &report_error_func, 0)?;
+
+    Ok(())
+}
+
+fn queue_microtask(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let callback = match args.first() {
+        Some(callback) if callback.is_function() => callback,
+        _ => {
+            return Err(JsError::new_str(
+                "queueMicrotask requires a function as first argument",
+            ))
+        }
+    };
+    let mut job_args = [*callback.borrow_value()];
+    let res =
+        unsafe { q::JS_EnqueueJob(realm.context, Some(run_microtask), 1, job_args.as_mut_ptr()) };
+    if res != 0 {
+        return Err(JsError::new_str("could not queue microtask"));
+    }
+    Ok(quickjs_utils::new_undefined_ref())
+}
+
+unsafe extern "C" fn run_microtask(
+    ctx: *mut q::JSContext,
+    _argc: ::std::os::raw::c_int,
+    argv: *mut q::JSValue,
+) -> q::JSValue {
+    let res = q::JS_Call(
+        ctx,
+        *argv,
+        quickjs_utils::new_undefined(),
+        0,
+        std::ptr::null_mut(),
+    );
+    let res_ref = QuickJsValueAdapter::new(ctx, res, false, true, "webglobals::run_microtask");
+    if res_ref.is_exception() {
+        let exception = QuickJsValueAdapter::new(
+            ctx,
+            q::JS_GetException(ctx),
+            false,
+            true,
+            "webglobals::run_microtask exception",
+        );
+        QuickJsRealmAdapter::with_context(ctx, |realm| {
+            report_unhandled_error(realm, "uncaught error in microtask", &exception);
+        });
+    }
+    // errors are reported above so the job always succeeds
+    quickjs_utils::new_undefined()
+}
+
+fn new_data_clone_error(message: &str) -> JsError {
+    JsError::new(
+        "DataCloneError".to_string(),
+        message.to_string(),
+        "".to_string(),
+    )
+}
+
+fn structured_clone(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let value = args
+        .first()
+        .cloned()
+        .unwrap_or_else(quickjs_utils::new_undefined_ref);
+
+    let mut cloner = Cloner {
+        realm,
+        memo: HashMap::new(),
+    };
+
+    // the buffers in the transfer list are cloned up front so views on them end up on the clone
+    let mut transferred = vec![];
+    if let Some(options) = args.get(1) {
+        if options.is_object() {
+            let transfer = objects::get_property_q(realm, options, "transfer")?;
+            if !transfer.is_null_or_undefined() {
+                if !transfer.is_array() {
+                    return Err(JsError::new_str("transfer should be an Array"));
+                }
+                for index in 0..arrays::get_length_q(realm, &transfer)? {
+                    let buffer = arrays::get_element_q(realm, &transfer, index)?;
+                    if get_typed_array_type_q(&buffer) != Some(TypedArrayType::ArrayBuffer) {
+                        return Err(new_data_clone_error("only ArrayBuffers can be transferred"));
+                    }
+                    if cloner.memo.contains_key(&buffer) {
+                        return Err(new_data_clone_error(
+                            "an ArrayBuffer was transferred more than once",
+                        ));
+                    }
+                    let clone =
+                        new_array_buffer_q(realm, get_array_buffer_buffer_copy_q(realm, &buffer)?)?;
+                    cloner.memo.insert(buffer.clone(), clone);
+                    transferred.push(buffer);
+                }
+            }
+        }
+    }
+
+    let clone = cloner.clone_value(&value)?;
+
+    for buffer in &transferred {
+        detach_array_buffer_buffer_q(realm, buffer)?;
+    }
+
+    Ok(clone)
+}
+
+struct Cloner<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    // the clones of the original objects (keyed by the object pointer), used to keep cycles and shared references intact
+    memo: HashMap<QuickJsValueAdapter, QuickJsValueAdapter>,
+}
+
+impl Cloner<'_> {
+    fn clone_value(&mut self, value: &QuickJsValueAdapter) -> Result<QuickJsValueAdapter, JsError> {
+        if !value.is_object() {
+            return match value.get_js_type() {
+                JsValueType::Symbol => Err(new_data_clone_error("a Symbol could not be cloned")),
+                // primitives are immutable
+                _ => Ok(value.clone()),
+            };
+        }
+
+        if let Some(clone) = self.memo.get(value) {
+            return Ok(clone.clone());
+        }
+
+        let realm = self.realm;
+        if value.is_proxy_instance() {
+            return Err(new_data_clone_error(
+                "an instance of a Proxy class could not be cloned",
+            ));
+        }
+        if let Some(array_type) = get_typed_array_type_q(value) {
+            return self.clone_typed_array(value, array_type);
+        }
+
+        match value.get_js_type() {
+            JsValueType::Function => Err(new_data_clone_error("a function could not be cloned")),
+            JsValueType::Promise => Err(new_data_clone_error("a Promise could not be cloned")),
+            JsValueType::Date => {
+                let clone = dates::new_date_q(realm)?;
+                dates::set_time_q(realm, &clone, dates::get_time_q(realm, value)?)?;
+                self.memo.insert(value.clone(), clone.clone());
+                Ok(clone)
+            }
+            JsValueType::Error => {
+                let name = objects::get_property_q(realm, value, "name")?;
+                let name = if name.is_string() {
+                    primitives::to_string_q(realm, &name)?
+                } else {
+                    "Error".to_string()
+                };
+                // only the native error types are preserved
+                let constructor_name = match name.as_str() {
+                    "EvalError" | "RangeError" | "ReferenceError" | "SyntaxError" | "TypeError"
+                    | "URIError" => name.as_str(),
+                    _ => "Error",
+                };
+                let constructor = unsafe { get_constructor(realm.context, constructor_name)? };
+                let message = objects::get_property_q(realm, value, "message")?;
+                let clone = unsafe { construct_object(realm.context, &constructor, &[&message])? };
+                let stack = objects::get_property_q(realm, value, "stack")?;
+                if stack.is_string() {
+                    objects::set_property_q(realm, &clone, "stack", &stack)?;
+                }
+                self.memo.insert(value.clone(), clone.clone());
+                Ok(clone)
+            }
+            JsValueType::Map => {
+                let clone = maps::new_map_q(realm)?;
+                self.memo.insert(value.clone(), clone.clone());
+                for (key, val) in maps::entries_q(realm, value, |k, v| Ok((k, v)))? {
+                    let key = self.clone_value(&key)?;
+                    let val = self.clone_value(&val)?;
+                    maps::set_q(realm, &clone, key, val)?;
+                }
+                Ok(clone)
+            }
+            JsValueType::Set => {
+                let clone = sets::new_set_q(realm)?;
+                self.memo.insert(value.clone(), clone.clone());
+                for val in sets::values_q(realm, value, Ok)? {
+                    let val = self.clone_value(&val)?;
+                    sets::add_q(realm, &clone, val)?;
+                }
+                Ok(clone)
+            }
+            JsValueType::Array => {
+                let clone = arrays::create_array_q(realm)?;
+                self.memo.insert(value.clone(), clone.clone());
+                for index in 0..arrays::get_length_q(realm, value)? {
+                    let element = arrays::get_element_q(realm, value, index)?;
+                    arrays::set_element_q(realm, &clone, index, &self.clone_value(&element)?)?;
+                }
+                Ok(clone)
+            }
+            _ => {
+                // the prototype is not preserved, class instances are cloned as plain objects
+                let clone = objects::create_object_q(realm)?;
+                self.memo.insert(value.clone(), clone.clone());
+                for prop_name in objects::get_property_names_q(realm, value)? {
+                    let prop = objects::get_property_q(realm, value, prop_name.as_str())?;
+                    objects::set_property_q(
+                        realm,
+                        &clone,
+                        prop_name.as_str(),
+                        &self.clone_value(&prop)?,
+                    )?;
+                }
+                Ok(clone)
+            }
+        }
+    }
+
+    /// clone an ArrayBuffer, or a view and the buffer it views so views sharing a buffer still share the cloned buffer
+    fn clone_typed_array(
+        &mut self,
+        value: &QuickJsValueAdapter,
+        array_type: TypedArrayType,
+    ) -> Result<QuickJsValueAdapter, JsError> {
+        let realm = self.realm;
+        if array_type == TypedArrayType::ArrayBuffer {
+            let clone = new_array_buffer_q(realm, get_array_buffer_buffer_copy_q(realm, value)?)?;
+            self.memo.insert(value.clone(), clone.clone());
+            return Ok(clone);
+        }
+
+        let buffer = objects::get_property_q(realm, value, "buffer")?;
+        let buffer_clone = self.clone_value(&buffer)?;
+        let byte_offset = objects::get_property_q(realm, value, "byteOffset")?;
+        let length = if array_type == TypedArrayType::DataView {
+            objects::get_property_q(realm, value, "byteLength")?
+        } else {
+            objects::get_property_q(realm, value, "length")?
+        };
+        let constructor =
+            unsafe { get_constructor(realm.context, array_type.get_constructor_name())? };
+        let clone = unsafe {
+            construct_object(
+                realm.context,
+                &constructor,
+                &[&buffer_clone, &byte_offset, &length],
+            )?
+        };
+        self.memo.insert(value.clone(), clone.clone());
+        Ok(clone)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::jsutils::Script;
+
+    #[test]
+    fn test_structured_clone() {
+        let rt = init_test_rt();
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_structured_clone.js",
+                    r#"
+                    let buf = new ArrayBuffer(8);
+                    let original = {
+                        date: new Date(1234),
+                        map: new Map([[{k: 1}, new Set([1, 2])]]),
+                        bytes: new Uint8Array(buf, 2, 4),
+                        view: new DataView(buf),
+                        err: new TypeError('oops'),
+                        nested: [1, 'two', null, undefined, 3n]
+                    };
+                    original.self = original;
+                    original.bytes[0] = 7;
+                    let clone = structuredClone(original);
+                    let checks = [
+                        clone !== original,
+                        clone.self === clone,
+                        clone.date.getTime() === 1234,
+                        [...clone.map.values()][0].has(2),
+                        clone.bytes.byteOffset === 2 && clone.bytes.length === 4 && clone.bytes[0] === 7,
+                        clone.bytes.buffer === clone.view.buffer && clone.bytes.buffer !== buf,
+                        clone.err instanceof TypeError && clone.err.message === 'oops',
+                        clone.nested.length === 5 && clone.nested[4] === 3n
+                    ];
+                    checks.every(c => c) || JSON.stringify(checks);
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert!(res.is_bool(), "checks failed: {}", res.stringify());
+        assert!(res.get_bool());
+
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_structured_clone_transfer.js",
+                    r#"
+                    let tbuf = new Uint8Array([1, 2, 3]).buffer;
+                    let moved = structuredClone({b: tbuf}, {transfer: [tbuf]});
+                    tbuf.byteLength === 0 && moved.b.byteLength === 3;
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert!(res.get_bool());
+
+        let err = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_structured_clone_fail.js",
+                    "structuredClone({f: () => 1});",
+                ),
+            )
+            .expect_err("cloning a function should fail");
+        assert_eq!(err.get_name(), "DataCloneError");
+    }
+
+    #[test]
+    fn test_queue_microtask() {
+        let rt = init_test_rt();
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_queue_microtask.js",
+                    r#"
+                    globalThis.order = [];
+                    setTimeout(() => order.push('timeout'), 0);
+                    Promise.resolve().then(() => order.push('promise'));
+                    queueMicrotask(() => { throw new Error('reported, not fatal'); });
+                    queueMicrotask(() => order.push('microtask'));
+                    reportError(new Error('reported'));
+                    order.push('sync');
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 1);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new("test_queue_microtask2.js", "order.join(',');"),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_str(), "sync,promise,microtask,timeout");
+
+        let err = rt
+            .eval_sync(
+                None,
+                Script::new("test_queue_microtask3.js", "queueMicrotask(1);"),
+            )
+            .expect_err("queueMicrotask without a function should fail");
+        assert!(err.get_message().contains("function"));
+    }
+}
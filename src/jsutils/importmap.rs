@@ -0,0 +1,312 @@
+//! import maps (<https://html.spec.whatwg.org/multipage/webappapis.html#import-maps>)
+//!
+//! an import map is applied to a specifier before any module loader is consulted, it maps bare specifiers (and
+//! prefixes ending with a `/`) to other specifiers, optionally scoped to the path of the importing module
+//!
+//! since module paths are not URLs, keys and addresses which start with `./` or `../` are resolved against `/`, specifiers
+//! which start with `/`, `./` or `../` are resolved against the path of the importing module before they are matched
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::jsutils::importmap::ImportMap;
+//! let map = ImportMap::parse(r#"{
+//!     "imports": {"sdk": "/vendor/sdk@2/index.js", "sdk/": "/vendor/sdk@2/"},
+//!     "scopes": {"/legacy/": {"sdk": "/vendor/sdk@1/index.js"}}
+//! }"#).expect("invalid import map");
+//! assert_eq!(map.resolve("/app/main.js", "sdk").unwrap(), Some("/vendor/sdk@2/index.js".to_string()));
+//! assert_eq!(map.resolve("/app/main.js", "sdk/util.js").unwrap(), Some("/vendor/sdk@2/util.js".to_string()));
+//! assert_eq!(map.resolve("/legacy/main.js", "sdk").unwrap(), Some("/vendor/sdk@1/index.js".to_string()));
+//! assert_eq!(map.resolve("/app/main.js", "other").unwrap(), None);
+//! ```
+
+use crate::jsutils::JsError;
+use serde_json::Value;
+
+/// a specifier map, entries are sorted by key length (longest first) so the most specific prefix matches first
+/// an address of None means the key is blocked (its address was invalid)
+type SpecifierMap = Vec<(String, Option<String>)>;
+
+#[derive(Default)]
+pub struct ImportMap {
+    imports: SpecifierMap,
+    scopes: Vec<(String, SpecifierMap)>,
+}
+
+impl ImportMap {
+    /// parse an import map from json
+    pub fn parse(json: &str) -> Result<Self, JsError> {
+        let value: Value = serde_json::from_str(json)
+            .map_err(|e| JsError::new_string(format!("could not parse import map: {e}")))?;
+        let root = value
+            .as_object()
+            .ok_or_else(|| JsError::new_str("import map must be a JSON object"))?;
+
+        let imports = match root.get("imports") {
+            None => vec![],
+            Some(imports) => parse_specifier_map(imports, "imports")?,
+        };
+
+        let mut scopes = vec![];
+        match root.get("scopes") {
+            None => {}
+            Some(Value::Object(scope_map)) => {
+                for (scope_prefix, map) in scope_map {
+                    scopes.push((
+                        normalize_address(scope_prefix),
+                        parse_specifier_map(map, scope_prefix.as_str())?,
+                    ));
+                }
+            }
+            Some(_) => return Err(JsError::new_str("import map scopes must be an object")),
+        }
+        scopes.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        Ok(Self { imports, scopes })
+    }
+
+    /// resolve a specifier imported by the module with path ref_path
+    /// returns Ok(None) if the import map does not contain a mapping for the specifier and an Err if the mapping was blocked
+    pub fn resolve(&self, ref_path: &str, specifier: &str) -> Result<Option<String>, JsError> {
+        let specifier = resolve_path_like(ref_path, specifier);
+        for (scope_prefix, map) in &self.scopes {
+            let scope_matches = ref_path == scope_prefix
+                || (scope_prefix.ends_with('/') && ref_path.starts_with(scope_prefix.as_str()));
+            if scope_matches {
+                if let Some(res) = resolve_in_map(map, specifier.as_str())? {
+                    return Ok(Some(res));
+                }
+            }
+        }
+        resolve_in_map(&self.imports, specifier.as_str())
+    }
+}
+
+fn parse_specifier_map(value: &Value, name: &str) -> Result<SpecifierMap, JsError> {
+    let map = value
+        .as_object()
+        .ok_or_else(|| JsError::new_string(format!("import map {name} must be an object")))?;
+    let mut ret: SpecifierMap = vec![];
+    for (key, address) in map {
+        if key.is_empty() {
+            log::warn!("ignoring empty specifier key in import map");
+            continue;
+        }
+        let address = match address.as_str() {
+            Some(address) if key.ends_with('/') && !address.ends_with('/') => {
+                log::warn!("import map address for {key} should end with a / because its key does, blocking {key}");
+                None
+            }
+            Some(address) => Some(normalize_address(address)),
+            None => {
+                log::warn!("import map address for {key} is not a string, blocking {key}");
+                None
+            }
+        };
+        ret.push((normalize_address(key), address));
+    }
+    ret.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+    Ok(ret)
+}
+
+fn resolve_in_map(map: &SpecifierMap, specifier: &str) -> Result<Option<String>, JsError> {
+    for (key, address) in map {
+        let matched = if key == specifier {
+            Some("")
+        } else if key.ends_with('/') {
+            specifier.strip_prefix(key.as_str())
+        } else {
+            None
+        };
+        if let Some(after_prefix) = matched {
+            return match address {
+                None => Err(JsError::new_string(format!(
+                    "import of {specifier} was blocked by the import map"
+                ))),
+                Some(address) => Ok(Some(format!("{address}{after_prefix}"))),
+            };
+        }
+    }
+    Ok(None)
+}
+
+fn is_relative(path: &str) -> bool {
+    path.starts_with("./") || path.starts_with("../")
+}
+
+/// resolve ./ and ../ against / since we don't have a base URL
+fn normalize_address(address: &str) -> String {
+    if is_relative(address) {
+        join_path("/", address)
+    } else {
+        address.to_string()
+    }
+}
+
+/// resolve a specifier which starts with /, ./ or ../ against the path of the importing module (like the URL-like
+/// specifiers of the WHATWG algorithm), bare specifiers are returned as is
+fn resolve_path_like(ref_path: &str, specifier: &str) -> String {
+    if is_relative(specifier) {
+        let ref_dir = match ref_path.rfind('/') {
+            Some(idx) => &ref_path[..=idx],
+            None => "/",
+        };
+        join_path(ref_dir, specifier)
+    } else if specifier.starts_with('/') {
+        join_path("/", specifier)
+    } else {
+        specifier.to_string()
+    }
+}
+
+/// join a path to a dir and remove the . and .. segments, the result always starts with a /
+fn join_path(dir: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = vec![];
+    for part in dir.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    let mut res = format!("/{}", parts.join("/"));
+    if path.ends_with('/') && !res.ends_with('/') {
+        res.push('/');
+    }
+    res
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
+    use crate::jsutils::importmap::ImportMap;
+    use crate::jsutils::modules::ScriptModuleLoader;
+    use crate::jsutils::Script;
+    use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+
+    #[test]
+    fn test_resolve() {
+        let map = ImportMap::parse(
+            r#"{
+                "imports": {
+                    "sdk": "./vendor/sdk@2/index.js",
+                    "sdk/": "./vendor/sdk@2/",
+                    "sdk/special/": "/special/",
+                    "blocked": 12,
+                    "bad/": "/no/trailing/slash"
+                },
+                "scopes": {
+                    "/plugins/": {"sdk": "/vendor/sdk@1/index.js"},
+                    "/plugins/new/": {"sdk": "/vendor/sdk@3/index.js"}
+                }
+            }"#,
+        )
+        .expect("could not parse import map");
+
+        assert_eq!(
+            map.resolve("/main.js", "sdk").unwrap(),
+            Some("/vendor/sdk@2/index.js".to_string())
+        );
+        assert_eq!(
+            map.resolve("/main.js", "sdk/a/b.js").unwrap(),
+            Some("/vendor/sdk@2/a/b.js".to_string())
+        );
+        // longest prefix wins
+        assert_eq!(
+            map.resolve("/main.js", "sdk/special/c.js").unwrap(),
+            Some("/special/c.js".to_string())
+        );
+        // most specific scope wins, falls back to less specific scopes and imports
+        assert_eq!(
+            map.resolve("/plugins/old.js", "sdk").unwrap(),
+            Some("/vendor/sdk@1/index.js".to_string())
+        );
+        assert_eq!(
+            map.resolve("/plugins/new/p.js", "sdk").unwrap(),
+            Some("/vendor/sdk@3/index.js".to_string())
+        );
+        assert_eq!(
+            map.resolve("/plugins/new/p.js", "sdk/x.js").unwrap(),
+            Some("/vendor/sdk@2/x.js".to_string())
+        );
+        assert_eq!(map.resolve("/main.js", "other").unwrap(), None);
+        assert!(map.resolve("/main.js", "blocked").is_err());
+        assert!(map.resolve("/main.js", "bad/x.js").is_err());
+
+        // relative specifiers are resolved against the importing module before they are matched
+        let map = ImportMap::parse(
+            r#"{
+                "imports": {
+                    "/lib/foo": "/lib/foo.v2.js",
+                    "./shared/": "/vendor/shared/"
+                }
+            }"#,
+        )
+        .expect("could not parse import map");
+        assert_eq!(
+            map.resolve("/lib/x.js", "./foo").unwrap(),
+            Some("/lib/foo.v2.js".to_string())
+        );
+        assert_eq!(
+            map.resolve("/lib/sub/y.js", "../foo").unwrap(),
+            Some("/lib/foo.v2.js".to_string())
+        );
+        assert_eq!(
+            map.resolve("/lib/x.js", "/lib/./foo").unwrap(),
+            Some("/lib/foo.v2.js".to_string())
+        );
+        assert_eq!(
+            map.resolve("/app/main.js", "../shared/a.js").unwrap(),
+            Some("/vendor/shared/a.js".to_string())
+        );
+        assert_eq!(map.resolve("/app/main.js", "./foo").unwrap(), None);
+        assert_eq!(map.resolve("/app/main.js", "lib/foo").unwrap(), None);
+
+        assert!(ImportMap::parse("[]").is_err());
+        assert!(ImportMap::parse(r#"{"imports": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_import_map() {
+        struct VendorLoader {}
+        impl ScriptModuleLoader for VendorLoader {
+            fn normalize_path(
+                &self,
+                _realm: &QuickJsRealmAdapter,
+                _ref_path: &str,
+                path: &str,
+            ) -> Option<String> {
+                if path.starts_with("/vendor/") {
+                    Some(path.to_string())
+                } else {
+                    None
+                }
+            }
+
+            fn load_module(&self, _realm: &QuickJsRealmAdapter, absolute_path: &str) -> String {
+                let version = if absolute_path.contains("@2") { 2 } else { 1 };
+                format!("export const version = {version};")
+            }
+        }
+
+        let rt = QuickJsRuntimeBuilder::new()
+            .script_module_loader(VendorLoader {})
+            .import_map(r#"{"imports": {"sdk": "/vendor/sdk@2.js"}}"#)
+            .expect("invalid import map")
+            .build();
+
+        rt.eval_module_sync(
+            None,
+            Script::new(
+                "import_map_test.mjs",
+                "import {version} from 'sdk';\nglobalThis.sdkVersion = version;",
+            ),
+        )
+        .expect("module failed");
+        let res = rt
+            .eval_sync(None, Script::new("import_map_res.js", "sdkVersion;"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 2);
+    }
+}
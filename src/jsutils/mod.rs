@@ -3,14 +3,19 @@
 //! The facade classes are for use outside the worker thread, they are Send
 //!
 
+use crate::jsutils::stacktrace::JsStackFrame;
 use crate::values::JsValueFacade;
 use backtrace::Backtrace;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 use std::fmt::{Debug, Display, Error, Formatter};
 
 pub mod helper_tasks;
+pub mod importmap;
 pub mod jsproxies;
 pub mod modules;
 pub mod promises;
+pub mod stacktrace;
 
 pub trait ScriptPreProcessor {
     fn process(&self, script: &mut Script) -> Result<(), JsError>;
@@ -32,6 +37,9 @@ pub enum JsValueType {
     Undefined,
     Array,
     Error,
+    Map,
+    Set,
+    Symbol,
 }
 
 impl Display for JsValueType {
@@ -50,6 +58,9 @@ impl Display for JsValueType {
             JsValueType::Undefined => f.write_str("Undefined"),
             JsValueType::Array => f.write_str("Array"),
             JsValueType::Error => f.write_str("Error"),
+            JsValueType::Map => f.write_str("Map"),
+            JsValueType::Set => f.write_str("Set"),
+            JsValueType::Symbol => f.write_str("Symbol"),
         }
     }
 }
@@ -103,6 +114,37 @@ impl JsError {
     pub fn get_cause(&self) -> &Option<Box<JsValueFacade>> {
         &self.cause
     }
+    /// the frames of the stack, when the error was thrown from TypeScript these are already mapped to the TypeScript source
+    /// lines of the stack which are not a JS stack frame (e.g. for errors created in rust) are skipped
+    pub fn frames(&self) -> Vec<JsStackFrame> {
+        self.stack
+            .lines()
+            .filter_map(|line| line.parse::<JsStackFrame>().ok())
+            .collect()
+    }
+}
+
+impl Serialize for JsError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("JsError", 5)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("message", &self.message)?;
+        state.serialize_field("stack", &self.stack)?;
+        state.serialize_field("frames", &self.frames())?;
+        match self.cause.as_deref() {
+            None => state.serialize_field("cause", &Option::<()>::None)?,
+            Some(JsValueFacade::JsError { val }) => state.serialize_field("cause", val)?,
+            Some(JsValueFacade::I32 { val }) => state.serialize_field("cause", val)?,
+            Some(JsValueFacade::F64 { val }) => state.serialize_field("cause", val)?,
+            Some(JsValueFacade::Boolean { val }) => state.serialize_field("cause", val)?,
+            Some(JsValueFacade::String { val }) => state.serialize_field("cause", &**val)?,
+            Some(other) => state.serialize_field("cause", &other.stringify())?,
+        }
+        state.end()
+    }
 }
 
 impl std::error::Error for JsError {
@@ -1,5 +1,6 @@
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 pub trait ScriptModuleLoader {
@@ -10,6 +11,11 @@ pub trait ScriptModuleLoader {
         path: &str,
     ) -> Option<String>;
     fn load_module(&self, realm: &QuickJsRealmAdapter, absolute_path: &str) -> String;
+    /// load the raw source of a module, this is used for imports with a type attribute (json, text or bytes)
+    /// the default impl returns the bytes of load_module, loaders which can load binary files should override this
+    fn load_module_bytes(&self, realm: &QuickJsRealmAdapter, absolute_path: &str) -> Vec<u8> {
+        self.load_module(realm, absolute_path).into_bytes()
+    }
 }
 
 pub trait CompiledModuleLoader {
@@ -30,4 +36,394 @@ pub trait NativeModuleLoader {
         realm: &QuickJsRealmAdapter,
         module_name: &str,
     ) -> Vec<(&str, QuickJsValueAdapter)>;
+    /// the names of the modules this loader provides, these are used to generate TypeScript declarations (see [generate_typings](crate::reflection::typings::generate_typings))
+    /// the default impl returns no names so the modules are not declared
+    fn get_module_names(&self, _realm: &QuickJsRealmAdapter) -> Vec<&str> {
+        vec![]
+    }
+    /// the TypeScript type of an export of a module (e.g. "(name: string) => string"), None means the export is declared as `any`
+    fn get_module_export_typing(
+        &self,
+        _realm: &QuickJsRealmAdapter,
+        _module_name: &str,
+        _export_name: &str,
+    ) -> Option<String> {
+        None
+    }
+}
+
+/// a ScriptModuleLoader which loads modules from a directory on the filesystem
+///
+/// specifiers are resolved Node-style:
+/// * relative specifiers (`./a`, `../b`) are resolved against the dir of the importing module
+/// * absolute specifiers (`/lib/c`) are resolved against the root dir
+/// * bare specifiers (`some_pkg`, `@scope/pkg/sub`) are looked up in `node_modules` dirs, honouring the `exports`, `module` and `main` fields of `package.json`
+///
+/// if a path does not exist the extensions `.js`, `.mjs` and `.ts` are tried, if it is a dir `index.js` (and `index.mjs`, `index.ts`) is tried
+///
+/// modules outside of the root dir are never loaded, also not via `..` or symlinks
+///
+/// # Example
+/// ```rust
+/// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+/// use quickjs_runtime::jsutils::modules::FileSystemModuleLoader;
+/// let rt = QuickJsRuntimeBuilder::new()
+///     .script_module_loader(FileSystemModuleLoader::new("./scripts"))
+///     .build();
+/// ```
+pub struct FileSystemModuleLoader {
+    root: PathBuf,
+    extensions: Vec<&'static str>,
+}
+
+impl FileSystemModuleLoader {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        let root = root.into();
+        let root = root.canonicalize().unwrap_or(root);
+        Self {
+            root,
+            extensions: vec!["js", "mjs", "ts"],
+        }
+    }
+
+    /// get the root dir of this loader
+    pub fn get_root(&self) -> &Path {
+        self.root.as_path()
+    }
+
+    /// resolve a specifier to an absolute path inside the root dir
+    pub fn resolve(&self, ref_path: &str, path: &str) -> Option<PathBuf> {
+        let base_dir = self.base_dir(ref_path);
+        let resolved = if path.starts_with("./") || path.starts_with("../") {
+            self.resolve_file(&base_dir.join(path))
+        } else if path.starts_with('/') {
+            // an already resolved path (e.g. from has_module) or a path relative to the root
+            let as_is = Path::new(path);
+            if as_is.starts_with(&self.root) {
+                self.resolve_file(as_is)
+            } else {
+                self.resolve_file(&self.root.join(path.trim_start_matches('/')))
+            }
+        } else {
+            self.resolve_bare(&base_dir, path)
+        }?;
+        let canonical = resolved.canonicalize().ok()?;
+        if canonical.starts_with(&self.root) {
+            Some(canonical)
+        } else {
+            log::error!(
+                "refusing to load module {} from outside of root {}",
+                canonical.display(),
+                self.root.display()
+            );
+            None
+        }
+    }
+
+    fn base_dir(&self, ref_path: &str) -> PathBuf {
+        let ref_path = Path::new(ref_path);
+        if ref_path.is_absolute() && ref_path.starts_with(&self.root) {
+            if let Some(parent) = ref_path.parent() {
+                return parent.to_path_buf();
+            }
+        }
+        self.root.clone()
+    }
+
+    fn resolve_file(&self, path: &Path) -> Option<PathBuf> {
+        if path.is_file() {
+            return Some(path.to_path_buf());
+        }
+        for ext in &self.extensions {
+            let mut with_ext = path.as_os_str().to_os_string();
+            with_ext.push(".");
+            with_ext.push(ext);
+            let with_ext = PathBuf::from(with_ext);
+            if with_ext.is_file() {
+                return Some(with_ext);
+            }
+        }
+        if path.is_dir() {
+            for ext in &self.extensions {
+                let index = path.join(format!("index.{ext}"));
+                if index.is_file() {
+                    return Some(index);
+                }
+            }
+        }
+        None
+    }
+
+    fn resolve_bare(&self, base_dir: &Path, specifier: &str) -> Option<PathBuf> {
+        // @scope/name/sub/path or name/sub/path
+        let mut parts = specifier.splitn(if specifier.starts_with('@') { 3 } else { 2 }, '/');
+        let package_name = if specifier.starts_with('@') {
+            format!("{}/{}", parts.next()?, parts.next()?)
+        } else {
+            parts.next()?.to_string()
+        };
+        let sub_path = parts.next().unwrap_or("");
+
+        let mut dir = Some(base_dir);
+        while let Some(current) = dir {
+            if !current.starts_with(&self.root) {
+                break;
+            }
+            let package_dir = current.join("node_modules").join(&package_name);
+            if package_dir.is_dir() {
+                return self.resolve_package(&package_dir, sub_path);
+            }
+            dir = current.parent();
+        }
+        None
+    }
+
+    fn resolve_package(&self, package_dir: &Path, sub_path: &str) -> Option<PathBuf> {
+        let package_json = std::fs::read_to_string(package_dir.join("package.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(s.as_str()).ok());
+
+        if let Some(package_json) = package_json {
+            let export_key = if sub_path.is_empty() {
+                ".".to_string()
+            } else {
+                format!("./{sub_path}")
+            };
+            if let Some(exports) = package_json.get("exports") {
+                // when exports is defined it is the only way into the package
+                let target = resolve_package_exports(exports, export_key.as_str())?;
+                return self.resolve_file(&package_dir.join(target));
+            }
+            if sub_path.is_empty() {
+                for field in ["module", "main"] {
+                    if let Some(entry) = package_json.get(field).and_then(|v| v.as_str()) {
+                        if let Some(resolved) = self.resolve_file(&package_dir.join(entry)) {
+                            return Some(resolved);
+                        }
+                    }
+                }
+            }
+        }
+        self.resolve_file(&package_dir.join(sub_path))
+    }
+}
+
+/// resolve a subpath (".", "./sub") against the exports field of a package.json
+fn resolve_package_exports(exports: &serde_json::Value, export_key: &str) -> Option<String> {
+    match exports {
+        serde_json::Value::Object(map) if map.keys().any(|k| k.starts_with('.')) => {
+            if let Some(target) = map.get(export_key) {
+                return resolve_export_conditions(target);
+            }
+            // patterns like "./features/*": "./src/features/*.js"
+            for (key, target) in map {
+                if let Some((prefix, suffix)) = key.split_once('*') {
+                    if export_key.starts_with(prefix)
+                        && export_key.ends_with(suffix)
+                        && export_key.len() >= prefix.len() + suffix.len()
+                    {
+                        let matched = &export_key[prefix.len()..export_key.len() - suffix.len()];
+                        return resolve_export_conditions(target).map(|t| t.replace('*', matched));
+                    }
+                }
+            }
+            None
+        }
+        // exports is a string or a conditions object, both only export "."
+        _ if export_key == "." => resolve_export_conditions(exports),
+        _ => None,
+    }
+}
+
+fn resolve_export_conditions(target: &serde_json::Value) -> Option<String> {
+    match target {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Object(conditions) => ["import", "module", "default"]
+            .iter()
+            .filter_map(|c| conditions.get(*c))
+            .find_map(resolve_export_conditions),
+        serde_json::Value::Array(alternatives) => {
+            alternatives.iter().find_map(resolve_export_conditions)
+        }
+        _ => None,
+    }
+}
+
+impl ScriptModuleLoader for FileSystemModuleLoader {
+    fn normalize_path(
+        &self,
+        _realm: &QuickJsRealmAdapter,
+        ref_path: &str,
+        path: &str,
+    ) -> Option<String> {
+        self.resolve(ref_path, path)
+            .map(|p| p.to_string_lossy().to_string())
+    }
+
+    fn load_module(&self, _realm: &QuickJsRealmAdapter, absolute_path: &str) -> String {
+        match std::fs::read_to_string(absolute_path) {
+            Ok(code) => code,
+            Err(e) => {
+                log::error!("could not read module {}: {}", absolute_path, e);
+                let msg = serde_json::Value::String(format!(
+                    "could not read module {absolute_path}: {e}"
+                ));
+                format!("throw new Error({msg});")
+            }
+        }
+    }
+
+    fn load_module_bytes(&self, _realm: &QuickJsRealmAdapter, absolute_path: &str) -> Vec<u8> {
+        match std::fs::read(absolute_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::error!("could not read module {}: {}", absolute_path, e);
+                vec![]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
+    use crate::jsutils::modules::FileSystemModuleLoader;
+    use crate::jsutils::Script;
+    use std::path::{Path, PathBuf};
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let file = root.join(path);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, contents).unwrap();
+    }
+
+    fn init_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "quickjs_runtime_test_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let root = dir.join("root");
+        write(&root, "main.js", "export const main = 1;");
+        write(&root, "lib/util.mjs", "export const util = 2;");
+        write(&root, "lib/sub/index.js", "export const sub = 3;");
+        write(&root, "lib/typed.ts", "export const typed: number = 4;");
+        write(
+            &root,
+            "node_modules/plain/package.json",
+            r#"{"name": "plain", "main": "dist/plain.js"}"#,
+        );
+        write(
+            &root,
+            "node_modules/plain/dist/plain.js",
+            "export default 5;",
+        );
+        write(
+            &root,
+            "node_modules/@scope/exp/package.json",
+            r#"{"name": "@scope/exp", "main": "wrong.js", "exports": {".": {"require": "./cjs.js", "import": "./esm.js"}, "./features/*": "./src/features/*.js"}}"#,
+        );
+        write(&root, "node_modules/@scope/exp/esm.js", "export default 6;");
+        write(
+            &root,
+            "node_modules/@scope/exp/src/features/a.js",
+            "export default 7;",
+        );
+        write(
+            &root,
+            "node_modules/@scope/exp/hidden.js",
+            "export default 8;",
+        );
+        write(&dir, "outside.js", "export const secret = 9;");
+        dir
+    }
+
+    #[test]
+    fn test_resolve() {
+        let dir = init_test_dir("fs_loader_resolve");
+        let loader = FileSystemModuleLoader::new(dir.join("root"));
+        let root = loader.get_root().to_path_buf();
+        let main = root.join("main.js");
+        let main_str = main.to_str().unwrap();
+
+        assert_eq!(loader.resolve("test.mjs", "./main"), Some(main.clone()));
+        assert_eq!(
+            loader.resolve(main_str, "./lib/util"),
+            Some(root.join("lib/util.mjs"))
+        );
+        assert_eq!(
+            loader.resolve(root.join("lib/util.mjs").to_str().unwrap(), "./sub"),
+            Some(root.join("lib/sub/index.js"))
+        );
+        assert_eq!(
+            loader.resolve(
+                root.join("lib/sub/index.js").to_str().unwrap(),
+                "/lib/typed"
+            ),
+            Some(root.join("lib/typed.ts"))
+        );
+        // already normalized paths resolve to themselves
+        assert_eq!(loader.resolve(main_str, main_str), Some(main.clone()));
+
+        assert_eq!(
+            loader.resolve(main_str, "plain"),
+            Some(root.join("node_modules/plain/dist/plain.js"))
+        );
+        assert_eq!(
+            loader.resolve(
+                root.join("lib/sub/index.js").to_str().unwrap(),
+                "@scope/exp"
+            ),
+            Some(root.join("node_modules/@scope/exp/esm.js"))
+        );
+        assert_eq!(
+            loader.resolve(main_str, "@scope/exp/features/a"),
+            Some(root.join("node_modules/@scope/exp/src/features/a.js"))
+        );
+        // not exported
+        assert_eq!(loader.resolve(main_str, "@scope/exp/hidden.js"), None);
+        // escaping the root
+        assert_eq!(loader.resolve(main_str, "../outside.js"), None);
+        assert_eq!(loader.resolve(main_str, "./lib/../../outside"), None);
+        assert_eq!(loader.resolve(main_str, "./missing"), None);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = init_test_dir("fs_loader_import");
+        let rt = QuickJsRuntimeBuilder::new()
+            .script_module_loader(FileSystemModuleLoader::new(dir.join("root")))
+            .build();
+
+        rt.eval_module_sync(
+            None,
+            Script::new(
+                "fs_loader_test.mjs",
+                "import {main} from './main.js';\n\
+                 import {util} from './lib/util';\n\
+                 import plain from 'plain';\n\
+                 import exp from '@scope/exp';\n\
+                 globalThis.fsLoaderRes = main + util + plain + exp;",
+            ),
+        )
+        .expect("module failed");
+        let res = rt
+            .eval_sync(None, Script::new("fs_loader_res.js", "fsLoaderRes;"))
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 1 + 2 + 5 + 6);
+
+        let res = rt.eval_module_sync(
+            None,
+            Script::new(
+                "fs_loader_test2.mjs",
+                "import {secret} from '../outside.js';",
+            ),
+        );
+        assert!(res.is_err());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
 }
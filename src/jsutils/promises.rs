@@ -20,6 +20,8 @@ where
     P: FnOnce() -> Result<R, JsError> + Send + 'static,
     M: FnOnce(&QuickJsRealmAdapter, R) -> Result<QuickJsValueAdapter, JsError> + Send + 'static,
 {
+    realm.accounting.add_promise(realm)?;
+
     // create promise
     let promise_ref = realm.create_promise()?;
     let return_ref = promise_ref.js_promise_get_value(realm);
@@ -131,6 +133,8 @@ where
     P: Future<Output = Result<R, JsError>> + Send + 'static,
     M: FnOnce(&QuickJsRealmAdapter, R) -> Result<QuickJsValueAdapter, JsError> + Send + 'static,
 {
+    realm.accounting.add_promise(realm)?;
+
     // create promise
     let promise_ref = realm.create_promise()?;
     let return_ref = promise_ref.js_promise_get_value(realm);
@@ -0,0 +1,186 @@
+//! parsing of quickjs stack traces into structured frames
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::jsutils::stacktrace::JsStackFrame;
+//! let frame: JsStackFrame = "    at func (file.js:12:4)".parse().expect("not a frame");
+//! assert_eq!(frame.get_function_name(), "func");
+//! assert_eq!(frame.get_file_name(), "file.js");
+//! assert_eq!(frame.get_line_number(), Some(12));
+//! assert_eq!(frame.get_column_number(), Some(4));
+//! ```
+
+use serde::Serialize;
+use std::str::FromStr;
+
+/// a single frame of a stack trace
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+pub struct JsStackFrame {
+    pub(crate) function_name: String,
+    pub(crate) file_name: String,
+    pub(crate) line_number: Option<u32>,
+    pub(crate) column_number: Option<u32>,
+}
+
+impl JsStackFrame {
+    pub fn new(
+        function_name: &str,
+        file_name: &str,
+        line_number: Option<u32>,
+        column_number: Option<u32>,
+    ) -> Self {
+        Self {
+            function_name: function_name.to_string(),
+            file_name: file_name.to_string(),
+            line_number,
+            column_number,
+        }
+    }
+    /// the name of the function, `(anonymous)` if the frame has no function name
+    pub fn get_function_name(&self) -> &str {
+        self.function_name.as_str()
+    }
+    /// the file name (or path) of the script, `native` for functions implemented in rust or C
+    pub fn get_file_name(&self) -> &str {
+        self.file_name.as_str()
+    }
+    /// the line number (1-based)
+    pub fn get_line_number(&self) -> Option<u32> {
+        self.line_number
+    }
+    /// the column number (1-based), only available with quickjs-ng or when source-mapped
+    pub fn get_column_number(&self) -> Option<u32> {
+        self.column_number
+    }
+}
+
+impl FromStr for JsStackFrame {
+    type Err = String;
+
+    /// parse a line of a quickjs stack trace e.g. `    at func (file.js:12:4)`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let line = s
+            .trim()
+            .strip_prefix("at ")
+            .ok_or_else(|| format!("not a stack frame: {s}"))?;
+        let (function_name, location) = match line.strip_suffix(')') {
+            Some(l) => match l.split_once(" (") {
+                Some((function_name, location)) => (function_name, location),
+                None => ("(anonymous)", line),
+            },
+            None => ("(anonymous)", line),
+        };
+        let mut file_name = location;
+        let mut numbers = vec![];
+        // take at most two trailing numeric parts (line and column), file names may contain a ':' (e.g. file:///a.js)
+        while numbers.len() < 2 {
+            match file_name.rsplit_once(':') {
+                Some((rest, num)) if !num.is_empty() && num.chars().all(|c| c.is_ascii_digit()) => {
+                    numbers.insert(
+                        0,
+                        num.parse::<u32>()
+                            .map_err(|e| format!("invalid number in {s}: {e}"))?,
+                    );
+                    file_name = rest;
+                }
+                _ => break,
+            }
+        }
+        Ok(Self {
+            function_name: function_name.to_string(),
+            file_name: file_name.to_string(),
+            line_number: numbers.first().cloned(),
+            column_number: numbers.get(1).cloned(),
+        })
+    }
+}
+
+/// parse a stack trace, fails if a (non empty) line is not a stack frame
+pub fn parse_stack_trace(stack_trace: &str) -> Result<Vec<JsStackFrame>, String> {
+    stack_trace
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse::<JsStackFrame>())
+        .collect()
+}
+
+/// serialize frames in the same format quickjs uses
+pub fn serialize_stack(frames: &[JsStackFrame]) -> String {
+    let mut result = String::new();
+
+    for frame in frames {
+        let fname_lnum = if let Some(line_number) = frame.line_number {
+            if let Some(column_number) = frame.column_number {
+                format!("{}:{line_number}:{column_number}", frame.file_name)
+            } else {
+                format!("{}:{line_number}", frame.file_name)
+            }
+        } else {
+            frame.file_name.clone()
+        };
+
+        result.push_str(&format!("    at {} ({fname_lnum})", frame.function_name));
+
+        result.push('\n');
+    }
+
+    result
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
+    use crate::jsutils::stacktrace::JsStackFrame;
+    use crate::jsutils::Script;
+
+    #[test]
+    fn test_parse_frame() {
+        let frame: JsStackFrame = "    at burn (file:///a/b.js:12:4)"
+            .parse()
+            .expect("no frame");
+        assert_eq!(frame.get_function_name(), "burn");
+        assert_eq!(frame.get_file_name(), "file:///a/b.js");
+        assert_eq!(frame.get_line_number(), Some(12));
+        assert_eq!(frame.get_column_number(), Some(4));
+
+        let frame: JsStackFrame = "at <eval> (test.js:3)".parse().expect("no frame");
+        assert_eq!(frame.get_function_name(), "<eval>");
+        assert_eq!(frame.get_file_name(), "test.js");
+        assert_eq!(frame.get_line_number(), Some(3));
+        assert_eq!(frame.get_column_number(), None);
+
+        let frame: JsStackFrame = "at test.js:3:1".parse().expect("no frame");
+        assert_eq!(frame.get_function_name(), "(anonymous)");
+        assert_eq!(frame.get_file_name(), "test.js");
+
+        assert!("Error: hi".parse::<JsStackFrame>().is_err());
+    }
+
+    #[test]
+    fn test_error_frames() {
+        let rt = QuickJsRuntimeBuilder::new().build();
+        let err = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "frames_test.js",
+                    "function a() {\n  throw Error('oops');\n}\nfunction b() {\n  a();\n}\nb();",
+                ),
+            )
+            .expect_err("script should fail");
+        let frames = err.frames();
+        assert!(frames.len() >= 2);
+        assert_eq!(frames[0].get_function_name(), "a");
+        assert_eq!(frames[0].get_file_name(), "frames_test.js");
+        assert_eq!(frames[0].get_line_number(), Some(2));
+        assert_eq!(frames[1].get_function_name(), "b");
+        assert_eq!(frames[1].get_line_number(), Some(5));
+
+        let json = serde_json::to_value(&err).expect("could not serialize");
+        assert_eq!(json["name"], "Error");
+        assert_eq!(json["message"], "oops");
+        assert_eq!(json["frames"][0]["function_name"], "a");
+        assert_eq!(json["frames"][0]["line_number"], 2);
+    }
+}
@@ -76,25 +76,36 @@ extern crate lazy_static;
 extern crate core;
 
 pub mod builder;
+pub mod bytecodecache;
+pub mod cancellation;
 pub mod facades;
 #[cfg(any(
     feature = "settimeout",
     feature = "setinterval",
     feature = "console",
-    feature = "setimmediate"
+    feature = "setimmediate",
+    feature = "eventtarget",
+    feature = "webglobals",
+    feature = "web-encoding"
 ))]
 pub mod features;
 pub mod jsutils;
+pub mod profiler;
 pub mod quickjs_utils;
 pub mod quickjsrealmadapter;
 pub mod quickjsruntimeadapter;
 pub mod quickjsvalueadapter;
+pub mod realmquota;
+pub mod realmtemplate;
 pub mod reflection;
+pub mod runtimepool;
 #[cfg(feature = "typescript")]
 pub mod typescript;
 pub mod values;
 
 pub use libquickjs_sys;
+#[cfg(feature = "macros")]
+pub use quickjs_runtime_macros::js_class;
 
 #[cfg(test)]
 pub mod tests {
@@ -0,0 +1,334 @@
+//! a sampling CPU profiler
+//!
+//! while profiling is active the JS call stack is sampled from the interrupt handler (which quickjs calls periodically while
+//! running script), the result is a [Profile] which can be written as a Chrome `.cpuprofile` (loadable in the Performance
+//! tab of Chrome DevTools) or as folded stacks (for flamegraph.pl / inferno)
+//!
+//! when the `typescript` feature is used locations are mapped back to the TypeScript source
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! rt.start_profiling();
+//! rt.eval_sync(None, Script::new("profile_me.js", "function burn(){let end = Date.now() + 50; while (Date.now() < end) {}}; burn();")).expect("script failed");
+//! let profile = rt.stop_profiling();
+//! let _cpuprofile_json = profile.to_cpuprofile();
+//! let _folded = profile.to_folded_stacks();
+//! ```
+
+use crate::jsutils::stacktrace::JsStackFrame;
+use crate::jsutils::JsError;
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use libquickjs_sys as q;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::CString;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// the default interval between two samples
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// a sampled stack, frames are ordered from the innermost to the outermost function (like a stack trace)
+#[derive(Clone, Debug)]
+pub struct ProfileSample {
+    /// time since the start of profiling
+    pub timestamp: Duration,
+    pub frames: Vec<JsStackFrame>,
+}
+
+/// the result of a profiling session
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub interval: Duration,
+    pub duration: Duration,
+    pub samples: Vec<ProfileSample>,
+}
+
+impl Profile {
+    /// serialize to the Chrome DevTools `.cpuprofile` format
+    pub fn to_cpuprofile(&self) -> String {
+        struct Node {
+            frame: Option<JsStackFrame>,
+            hit_count: u64,
+            children: Vec<usize>,
+        }
+        let mut nodes = vec![Node {
+            frame: None,
+            hit_count: 0,
+            children: vec![],
+        }];
+        let mut node_lookup: HashMap<(usize, JsStackFrame), usize> = HashMap::new();
+        let mut samples = vec![];
+        let mut time_deltas = vec![];
+        let mut last_timestamp = Duration::ZERO;
+
+        for sample in &self.samples {
+            let mut node_idx = 0;
+            for frame in sample.frames.iter().rev() {
+                let key = (node_idx, frame.clone());
+                node_idx = match node_lookup.get(&key) {
+                    Some(idx) => *idx,
+                    None => {
+                        nodes.push(Node {
+                            frame: Some(frame.clone()),
+                            hit_count: 0,
+                            children: vec![],
+                        });
+                        let idx = nodes.len() - 1;
+                        nodes[node_idx].children.push(idx);
+                        node_lookup.insert(key, idx);
+                        idx
+                    }
+                };
+            }
+            nodes[node_idx].hit_count += 1;
+            samples.push(node_idx + 1);
+            time_deltas.push((sample.timestamp - last_timestamp).as_micros() as u64);
+            last_timestamp = sample.timestamp;
+        }
+
+        let mut script_ids: HashMap<&str, usize> = HashMap::new();
+        let json_nodes: Vec<serde_json::Value> = nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, node)| {
+                let call_frame = match &node.frame {
+                    None => json!({
+                        "functionName": "(root)",
+                        "scriptId": "0",
+                        "url": "",
+                        "lineNumber": -1,
+                        "columnNumber": -1,
+                    }),
+                    Some(frame) => {
+                        let next_id = script_ids.len() + 1;
+                        let script_id = *script_ids
+                            .entry(frame.file_name.as_str())
+                            .or_insert(next_id);
+                        json!({
+                            "functionName": frame.function_name,
+                            "scriptId": script_id.to_string(),
+                            "url": frame.file_name,
+                            "lineNumber": frame.line_number.map(|l| l as i64 - 1).unwrap_or(-1),
+                            "columnNumber": frame.column_number.map(|c| c as i64 - 1).unwrap_or(-1),
+                        })
+                    }
+                };
+                json!({
+                    "id": idx + 1,
+                    "callFrame": call_frame,
+                    "hitCount": node.hit_count,
+                    "children": node.children.iter().map(|c| c + 1).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+
+        json!({
+            "nodes": json_nodes,
+            "startTime": 0,
+            "endTime": self.duration.as_micros() as u64,
+            "samples": samples,
+            "timeDeltas": time_deltas,
+        })
+        .to_string()
+    }
+
+    /// serialize to folded stacks (one line per unique stack, outermost frame first, followed by the number of samples)
+    pub fn to_folded_stacks(&self) -> String {
+        let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+        for sample in &self.samples {
+            let stack = sample
+                .frames
+                .iter()
+                .rev()
+                .map(|f| frame_label(f).replace(';', ":"))
+                .collect::<Vec<_>>()
+                .join(";");
+            *counts.entry(stack).or_insert(0) += 1;
+        }
+        let mut ret = String::new();
+        for (stack, count) in counts {
+            ret.push_str(format!("{stack} {count}\n").as_str());
+        }
+        ret
+    }
+}
+
+/// the state of a running profiling session, this lives in the QuickJsRuntimeAdapter
+pub(crate) struct Profiler {
+    interval: Duration,
+    started: Instant,
+    last_sample: Option<Instant>,
+    // raw stack traces are interned, source mapping and parsing is done once per unique stack when profiling stops
+    raw_samples: Vec<(Duration, Rc<str>)>,
+    raw_stacks: HashSet<Rc<str>>,
+}
+
+impl Profiler {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            started: Instant::now(),
+            last_sample: None,
+            raw_samples: vec![],
+            raw_stacks: HashSet::new(),
+        }
+    }
+
+    pub(crate) fn is_due(&self) -> bool {
+        match self.last_sample {
+            None => true,
+            Some(last) => last.elapsed() >= self.interval,
+        }
+    }
+
+    pub(crate) fn add_sample(&mut self, stack: String) {
+        let now = Instant::now();
+        self.last_sample = Some(now);
+        let stack: Rc<str> = match self.raw_stacks.get(stack.as_str()) {
+            Some(interned) => interned.clone(),
+            None => {
+                let interned: Rc<str> = Rc::from(stack);
+                self.raw_stacks.insert(interned.clone());
+                interned
+            }
+        };
+        self.raw_samples.push((now - self.started, stack));
+    }
+
+    pub(crate) fn finish(self) -> Profile {
+        let mut parsed: HashMap<Rc<str>, Vec<JsStackFrame>> = HashMap::new();
+        let samples = self
+            .raw_samples
+            .into_iter()
+            .map(|(timestamp, raw)| {
+                let frames = parsed
+                    .entry(raw.clone())
+                    .or_insert_with(|| parse_stack(raw.as_ref()))
+                    .clone();
+                ProfileSample { timestamp, frames }
+            })
+            .collect();
+        Profile {
+            interval: self.interval,
+            duration: self.started.elapsed(),
+            samples,
+        }
+    }
+}
+
+/// the name of a frame in a profile
+fn frame_label(frame: &JsStackFrame) -> String {
+    let function_name = if frame.function_name.is_empty() {
+        "(anonymous)"
+    } else {
+        frame.function_name.as_str()
+    };
+    match frame.line_number {
+        Some(line_number) => format!("{function_name} ({}:{line_number})", frame.file_name),
+        None => format!("{function_name} ({})", frame.file_name),
+    }
+}
+
+fn parse_stack(stack: &str) -> Vec<JsStackFrame> {
+    #[cfg(feature = "typescript")]
+    let stack = crate::typescript::unmap_stack_trace(stack);
+    stack
+        .lines()
+        .filter_map(|line| line.parse::<JsStackFrame>().ok())
+        .collect()
+}
+
+/// capture the current JS call stack, an error is thrown and caught again through the C API so the engine builds the
+/// backtrace (of the whole runtime) natively, this does not call the global Error constructor which script may have replaced
+pub(crate) fn capture_stack(realm: &QuickJsRealmAdapter) -> Result<String, JsError> {
+    let msg = CString::new("profiler sample").expect("invalid message");
+    let error = unsafe {
+        q::JS_ThrowInternalError(realm.context, msg.as_ptr());
+        QuickJsValueAdapter::new(
+            realm.context,
+            q::JS_GetException(realm.context),
+            false,
+            true,
+            "profiler::capture_stack error",
+        )
+    };
+    let stack = realm.get_object_property(&error, "stack")?;
+    stack.to_string()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
+    use crate::jsutils::Script;
+
+    #[test]
+    fn test_profile() {
+        let rt = QuickJsRuntimeBuilder::new().build();
+        rt.start_profiling();
+        rt.eval_sync(
+            None,
+            Script::new(
+                "profile_test.js",
+                "function burn() {\n    let end = Date.now() + 100;\n    let a = 0;\n    while (Date.now() < end) {a++;}\n    return a;\n}\nfunction outer() {\n    return burn();\n}\nouter();",
+            ),
+        )
+        .expect("script failed");
+        let profile = rt.stop_profiling();
+
+        assert!(!profile.samples.is_empty());
+        assert!(profile.samples.iter().any(|s| {
+            s.frames.len() >= 2
+                && s.frames[0].function_name == "burn"
+                && s.frames[0].file_name == "profile_test.js"
+                && s.frames[1].function_name == "outer"
+        }));
+
+        let cpuprofile: serde_json::Value =
+            serde_json::from_str(profile.to_cpuprofile().as_str()).expect("invalid json");
+        let nodes = cpuprofile["nodes"].as_array().expect("no nodes");
+        assert_eq!(nodes[0]["callFrame"]["functionName"], "(root)");
+        assert!(nodes
+            .iter()
+            .any(|n| n["callFrame"]["functionName"] == "burn"));
+        assert_eq!(
+            cpuprofile["samples"].as_array().unwrap().len(),
+            profile.samples.len()
+        );
+
+        let folded = profile.to_folded_stacks();
+        assert!(folded.contains("outer (profile_test.js:"));
+        assert!(folded.contains(";burn (profile_test.js:"));
+
+        // not profiling anymore
+        let profile = rt.stop_profiling();
+        assert!(profile.samples.is_empty());
+    }
+
+    #[test]
+    fn test_profile_replaced_error() {
+        // sampling should not call the global Error constructor
+        let rt = QuickJsRuntimeBuilder::new().build();
+        rt.start_profiling();
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "profile_error_test.js",
+                    "let errorCalls = 0;\nglobalThis.Error = function() { errorCalls++; throw 'no'; };\nfunction burn() {\n    let end = Date.now() + 50;\n    while (Date.now() < end) {}\n}\nburn();\nerrorCalls;",
+                ),
+            )
+            .expect("script failed");
+        let profile = rt.stop_profiling();
+
+        assert_eq!(res.get_i32(), 0);
+        assert!(profile
+            .samples
+            .iter()
+            .any(|s| s.frames.first().is_some_and(|f| f.function_name == "burn")));
+    }
+}
@@ -75,13 +75,15 @@ pub unsafe fn compile(
 }
 
 /// run a compiled function, see compile for an example
+///
+/// a compiled module (see [compile_module](../modules/fn.compile_module.html)) can also be run, this evaluates the module
 /// # Safety
 /// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
 pub unsafe fn run_compiled_function(
     context: *mut q::JSContext,
     compiled_func: &QuickJsValueAdapter,
 ) -> Result<QuickJsValueAdapter, JsError> {
-    assert!(compiled_func.is_compiled_function());
+    assert!(compiled_func.is_compiled_function() || compiled_func.is_module());
     let val = q::JS_EvalFunction(context, compiled_func.clone_value_incr_rc());
     let val_ref =
         QuickJsValueAdapter::new(context, val, false, true, "run_compiled_function result");
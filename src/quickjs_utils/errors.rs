@@ -1,7 +1,7 @@
 //! utils for getting and reporting exceptions
 
 use crate::jsutils::JsError;
-use crate::quickjs_utils::{objects, primitives};
+use crate::quickjs_utils::{functions, objects, primitives};
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsvalueadapter::{QuickJsValueAdapter, TAG_EXCEPTION};
 use libquickjs_sys as q;
@@ -164,6 +164,48 @@ pub fn get_stack(realm: &QuickJsRealmAdapter) -> Result<QuickJsValueAdapter, JsE
     realm.get_object_property(&e, "stack")
 }
 
+/// log an error which was not handled by script, this is used for unhandled promise rejections and reportError()
+/// the stack of the reason is logged if it is an Error, else the current stack
+pub(crate) fn report_unhandled_error(
+    realm: &QuickJsRealmAdapter,
+    description: &str,
+    reason: &QuickJsValueAdapter,
+) {
+    let reason_str_res = functions::call_to_string_q(realm, reason);
+    let stack_res = if reason.is_error() {
+        realm.get_object_property(reason, "stack")
+    } else {
+        get_stack(realm)
+    };
+    let stack = match stack_res {
+        Ok(s) if s.is_string() => s.to_string().unwrap_or_default(),
+        _ => "".to_string(),
+    };
+    #[cfg(feature = "typescript")]
+    let stack = crate::typescript::unmap_stack_trace(stack.as_str());
+
+    match reason_str_res {
+        Ok(reason_str) => {
+            log::error!(
+                "[{}] {}, reason: {}\nstack:\n{}",
+                realm.get_realm_id(),
+                description,
+                reason_str,
+                stack
+            );
+        }
+        Err(e) => {
+            log::error!(
+                "[{}] {}, could not get reason: {}\nstack:\n{}",
+                realm.get_realm_id(),
+                description,
+                e,
+                stack
+            );
+        }
+    }
+}
+
 /// Throw an error and get an Exception JSValue to return from native methods
 /// # Safety
 /// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
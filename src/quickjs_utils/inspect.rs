@@ -0,0 +1,500 @@
+//! a formatter for values which works like node's [util.inspect](https://nodejs.org/api/util.html#utilinspectobject-options)
+//!
+//! it is used by the console feature to format objects and is available as [QuickJsRealmAdapter::inspect]
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! use quickjs_runtime::quickjs_utils::inspect::InspectOptions;
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! let formatted = rt.exe_rt_task_in_event_loop(|q_js_rt| {
+//!     let realm = q_js_rt.get_main_realm();
+//!     let value = realm
+//!         .eval(Script::new("inspect.js", "let o = {a: [1, 'two'], m: new Map([[1, {b: 2}]])}; o.self = o; o;"))
+//!         .expect("script failed");
+//!     realm.inspect(&value, InspectOptions::default()).expect("inspect failed")
+//! });
+//! assert_eq!(formatted, "{ a: [ 1, 'two' ], m: Map(1) { 1 => { b: 2 } }, self: [Circular] }");
+//! ```
+
+use crate::jsutils::{JsError, JsValueType};
+use crate::quickjs_utils::typedarrays::get_typed_array_type_q;
+use crate::quickjs_utils::{arrays, bigints, functions, maps, objects, primitives, sets};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::values::TypedArrayType;
+
+/// options for [inspect_q]
+#[derive(Clone, Copy, Debug)]
+pub struct InspectOptions {
+    /// the number of nested levels of objects which are formatted, deeper objects are shown as e.g. `[Object]`, None means no limit, defaults to 2
+    pub depth: Option<usize>,
+    /// the max number of elements of Arrays, TypedArrays, Maps and Sets which are formatted, defaults to 100
+    pub max_array_length: usize,
+    /// objects which would be longer than this are split over multiple lines, defaults to 80
+    pub break_length: usize,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        Self {
+            depth: Some(2),
+            max_array_length: 100,
+            break_length: 80,
+        }
+    }
+}
+
+impl InspectOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn depth(mut self, depth: Option<usize>) -> Self {
+        self.depth = depth;
+        self
+    }
+    pub fn max_array_length(mut self, max_array_length: usize) -> Self {
+        self.max_array_length = max_array_length;
+        self
+    }
+    pub fn break_length(mut self, break_length: usize) -> Self {
+        self.break_length = break_length;
+        self
+    }
+}
+
+/// format a value like node's util.inspect
+pub fn inspect_q(
+    realm: &QuickJsRealmAdapter,
+    value: &QuickJsValueAdapter,
+    options: &InspectOptions,
+) -> Result<String, JsError> {
+    let mut inspector = Inspector {
+        realm,
+        options,
+        seen: vec![],
+    };
+    inspector.format_value(value, 0, 0)
+}
+
+struct Inspector<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    options: &'a InspectOptions,
+    // the objects which are currently being formatted, used to detect cycles
+    seen: Vec<QuickJsValueAdapter>,
+}
+
+impl Inspector<'_> {
+    fn format_value(
+        &mut self,
+        value: &QuickJsValueAdapter,
+        depth: usize,
+        indentation: usize,
+    ) -> Result<String, JsError> {
+        let realm = self.realm;
+        match value.get_js_type() {
+            JsValueType::Undefined => Ok("undefined".to_string()),
+            JsValueType::Null => Ok("null".to_string()),
+            JsValueType::Boolean => Ok(value.to_bool().to_string()),
+            JsValueType::I32 => Ok(value.to_i32().to_string()),
+            JsValueType::F64 => {
+                let val = value.to_f64();
+                if val == 0.0 && val.is_sign_negative() {
+                    Ok("-0".to_string())
+                } else {
+                    functions::call_to_string_q(realm, value)
+                }
+            }
+            JsValueType::String => Ok(quote(primitives::to_string_q(realm, value)?.as_str())),
+            JsValueType::BigInt => Ok(format!("{}n", bigints::to_string_q(realm, value)?)),
+            JsValueType::Symbol => self.format_symbol(value),
+            JsValueType::Function => self.format_function(value),
+            JsValueType::Error => self.format_error(value, indentation),
+            JsValueType::Date => {
+                match functions::invoke_member_function_q(realm, value, "toISOString", &[]) {
+                    Ok(iso) => primitives::to_string_q(realm, &iso),
+                    Err(_) => Ok("Invalid Date".to_string()),
+                }
+            }
+            JsValueType::Promise => Ok("Promise {}".to_string()),
+            JsValueType::Array | JsValueType::Map | JsValueType::Set | JsValueType::Object => {
+                if self.seen.contains(value) {
+                    return Ok("[Circular]".to_string());
+                }
+                self.seen.push(value.clone());
+                let res = self.format_object(value, depth, indentation);
+                self.seen.pop();
+                res
+            }
+        }
+    }
+
+    fn format_symbol(&self, value: &QuickJsValueAdapter) -> Result<String, JsError> {
+        let description = objects::get_property_q(self.realm, value, "description")?;
+        if description.is_string() {
+            Ok(format!(
+                "Symbol({})",
+                primitives::to_string_q(self.realm, &description)?
+            ))
+        } else {
+            Ok("Symbol()".to_string())
+        }
+    }
+
+    fn format_function(&self, value: &QuickJsValueAdapter) -> Result<String, JsError> {
+        let name = objects::get_property_q(self.realm, value, "name")?;
+        let name = if name.is_string() {
+            primitives::to_string_q(self.realm, &name)?
+        } else {
+            "".to_string()
+        };
+        let source = functions::call_to_string_q(self.realm, value).unwrap_or_default();
+        let kind = if source.starts_with("class") {
+            "class"
+        } else {
+            "Function"
+        };
+        if name.is_empty() {
+            Ok(format!("[{kind} (anonymous)]"))
+        } else if kind == "class" {
+            Ok(format!("[class {name}]"))
+        } else {
+            Ok(format!("[Function: {name}]"))
+        }
+    }
+
+    fn format_error(
+        &self,
+        value: &QuickJsValueAdapter,
+        indentation: usize,
+    ) -> Result<String, JsError> {
+        let message = functions::call_to_string_q(self.realm, value)?;
+        let stack = objects::get_property_q(self.realm, value, "stack")?;
+        let stack = if stack.is_string() {
+            primitives::to_string_q(self.realm, &stack)?
+        } else {
+            "".to_string()
+        };
+        #[cfg(feature = "typescript")]
+        let stack = crate::typescript::unmap_stack_trace(stack.as_str());
+
+        let stack = stack.trim_end();
+        if stack.is_empty() {
+            Ok(format!("[{message}]"))
+        } else {
+            let indent = " ".repeat(indentation);
+            let stack = stack
+                .lines()
+                .map(|line| format!("{indent}{line}"))
+                .collect::<Vec<String>>()
+                .join("\n");
+            Ok(format!("{message}\n{stack}"))
+        }
+    }
+
+    fn format_object(
+        &mut self,
+        value: &QuickJsValueAdapter,
+        depth: usize,
+        indentation: usize,
+    ) -> Result<String, JsError> {
+        let realm = self.realm;
+        let js_type = value.get_js_type();
+        let typed_array_type = get_typed_array_type_q(value);
+
+        let name = match js_type {
+            JsValueType::Array => "Array".to_string(),
+            JsValueType::Map => "Map".to_string(),
+            JsValueType::Set => "Set".to_string(),
+            _ => {
+                if value.is_proxy_instance() {
+                    realm.get_proxy_instance_info(value)?.0
+                } else {
+                    self.get_constructor_name(value)?
+                        .unwrap_or_else(|| "Object".to_string())
+                }
+            }
+        };
+
+        if self
+            .options
+            .depth
+            .is_some_and(|max_depth| depth > max_depth)
+        {
+            return Ok(format!("[{name}]"));
+        }
+
+        let mut entries = vec![];
+        let (prefix, open, close) = match js_type {
+            JsValueType::Array => {
+                let length = arrays::get_length_q(realm, value)?;
+                self.push_elements(value, length, depth, indentation, &mut entries)?;
+                ("".to_string(), "[", "]")
+            }
+            JsValueType::Map => {
+                let map_entries = maps::entries_q(realm, value, |k, v| Ok((k, v)))?;
+                let size = map_entries.len();
+                for (key, val) in map_entries.iter().take(self.options.max_array_length) {
+                    let key = self.format_value(key, depth + 1, indentation + 2)?;
+                    let val = self.format_value(val, depth + 1, indentation + 2)?;
+                    entries.push(format!("{key} => {val}"));
+                }
+                push_more_items(size, self.options.max_array_length, &mut entries);
+                (format!("Map({size}) "), "{", "}")
+            }
+            JsValueType::Set => {
+                let values = sets::values_q(realm, value, Ok)?;
+                let size = values.len();
+                for val in values.iter().take(self.options.max_array_length) {
+                    entries.push(self.format_value(val, depth + 1, indentation + 2)?);
+                }
+                push_more_items(size, self.options.max_array_length, &mut entries);
+                (format!("Set({size}) "), "{", "}")
+            }
+            _ => match typed_array_type {
+                Some(TypedArrayType::ArrayBuffer) | Some(TypedArrayType::DataView) => {
+                    for prop_name in ["byteLength", "byteOffset"] {
+                        let prop = objects::get_property_q(realm, value, prop_name)?;
+                        if !prop.is_undefined() {
+                            entries.push(format!(
+                                "{prop_name}: {}",
+                                self.format_value(&prop, depth + 1, indentation + 2)?
+                            ));
+                        }
+                    }
+                    (format!("{name} "), "{", "}")
+                }
+                Some(_) => {
+                    let length = objects::get_property_q(realm, value, "length")?;
+                    let length = if length.is_i32() {
+                        length.to_i32().max(0) as u32
+                    } else {
+                        0
+                    };
+                    self.push_elements(value, length, depth, indentation, &mut entries)?;
+                    (format!("{name}({length}) "), "[", "]")
+                }
+                None => {
+                    let prefix = if name == "Object" {
+                        if objects::get_prototype_of_q(realm, value)?.is_null() {
+                            "[Object: null prototype] ".to_string()
+                        } else {
+                            "".to_string()
+                        }
+                    } else {
+                        format!("{name} ")
+                    };
+                    for prop_name in objects::get_property_names_q(realm, value)? {
+                        let prop = objects::get_property_q(realm, value, prop_name.as_str())?;
+                        entries.push(format!(
+                            "{}: {}",
+                            format_key(prop_name.as_str()),
+                            self.format_value(&prop, depth + 1, indentation + 2)?
+                        ));
+                    }
+                    (prefix, "{", "}")
+                }
+            },
+        };
+
+        Ok(self.reduce_to_single_string(prefix, open, close, entries, indentation))
+    }
+
+    fn push_elements(
+        &mut self,
+        value: &QuickJsValueAdapter,
+        length: u32,
+        depth: usize,
+        indentation: usize,
+        entries: &mut Vec<String>,
+    ) -> Result<(), JsError> {
+        let shown = (length as usize).min(self.options.max_array_length);
+        for index in 0..shown {
+            let element = arrays::get_element_q(self.realm, value, index as u32)?;
+            entries.push(self.format_value(&element, depth + 1, indentation + 2)?);
+        }
+        push_more_items(length as usize, self.options.max_array_length, entries);
+        Ok(())
+    }
+
+    /// get the name of the constructor of an object, None for plain objects
+    fn get_constructor_name(&self, value: &QuickJsValueAdapter) -> Result<Option<String>, JsError> {
+        let constructor = objects::get_property_q(self.realm, value, "constructor")?;
+        if !constructor.is_function() {
+            return Ok(None);
+        }
+        let name = objects::get_property_q(self.realm, &constructor, "name")?;
+        if !name.is_string() {
+            return Ok(None);
+        }
+        let name = primitives::to_string_q(self.realm, &name)?;
+        if name.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(name))
+        }
+    }
+
+    /// put the entries on a single line if they fit within break_length, else put every entry on its own line
+    fn reduce_to_single_string(
+        &self,
+        prefix: String,
+        open: &str,
+        close: &str,
+        entries: Vec<String>,
+        indentation: usize,
+    ) -> String {
+        if entries.is_empty() {
+            return format!("{prefix}{open}{close}");
+        }
+        let total_length = indentation
+            + prefix.len()
+            + open.len()
+            + close.len()
+            + entries.iter().map(|entry| entry.len() + 2).sum::<usize>();
+        if total_length <= self.options.break_length
+            && !entries.iter().any(|entry| entry.contains('\n'))
+        {
+            format!("{prefix}{open} {} {close}", entries.join(", "))
+        } else {
+            let indent = " ".repeat(indentation);
+            let entries = entries
+                .iter()
+                .map(|entry| format!("{indent}  {entry}"))
+                .collect::<Vec<String>>()
+                .join(",\n");
+            format!("{prefix}{open}\n{entries}\n{indent}{close}")
+        }
+    }
+}
+
+fn push_more_items(length: usize, max_array_length: usize, entries: &mut Vec<String>) {
+    if length > max_array_length {
+        let more = length - max_array_length;
+        entries.push(format!(
+            "... {more} more item{}",
+            if more > 1 { "s" } else { "" }
+        ));
+    }
+}
+
+/// quote a string with single quotes, or double quotes if it contains single quotes
+fn quote(val: &str) -> String {
+    let quote = if val.contains('\'') && !val.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut res = String::with_capacity(val.len() + 2);
+    res.push(quote);
+    for chr in val.chars() {
+        match chr {
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            '\\' => res.push_str("\\\\"),
+            c if c == quote => {
+                res.push('\\');
+                res.push(c);
+            }
+            c => res.push(c),
+        }
+    }
+    res.push(quote);
+    res
+}
+
+/// property names which are not valid identifiers are quoted
+fn format_key(key: &str) -> String {
+    let mut chars = key.chars();
+    let is_identifier = match chars.next() {
+        Some(first) => {
+            (first.is_alphabetic() || first == '_' || first == '$')
+                && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        }
+        None => false,
+    };
+    if is_identifier {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::jsutils::Script;
+    use crate::quickjs_utils::inspect::InspectOptions;
+
+    fn inspect(code: &str, options: InspectOptions) -> String {
+        let rt = init_test_rt();
+        let code = code.to_string();
+        rt.exe_rt_task_in_event_loop(move |q_js_rt| {
+            let realm = q_js_rt.get_main_realm();
+            let value = realm
+                .eval(Script::new("test_inspect.js", code.as_str()))
+                .expect("script failed");
+            realm.inspect(&value, options).expect("inspect failed")
+        })
+    }
+
+    #[test]
+    fn test_inspect() {
+        let options = InspectOptions::default();
+        assert_eq!(inspect("'it\\'s'", options), "\"it's\"");
+        assert_eq!(inspect("-0", options), "-0");
+        assert_eq!(
+            inspect("12345678901234567890n", options),
+            "12345678901234567890n"
+        );
+        assert_eq!(inspect("Symbol('s')", options), "Symbol(s)");
+        assert_eq!(
+            inspect(
+                "({f: function named() {}, g: () => 1, c: class Foo {}})",
+                options
+            ),
+            "{ f: [Function: named], g: [Function: g], c: [class Foo] }"
+        );
+        assert_eq!(
+            inspect("({'a-b': 1, a: {b: {c: {d: 1}}}})", options),
+            "{ 'a-b': 1, a: { b: { c: [Object] } } }"
+        );
+        assert_eq!(
+            inspect("({a: {b: {c: {d: 1}}}})", options.depth(None)),
+            "{ a: { b: { c: { d: 1 } } } }"
+        );
+        assert_eq!(
+            inspect("let a = [1]; a.push(a); a;", options),
+            "[ 1, [Circular] ]"
+        );
+        assert_eq!(
+            inspect("new Set(['a', new Map([['k', null]])])", options),
+            "Set(2) { 'a', Map(1) { 'k' => null } }"
+        );
+        assert_eq!(
+            inspect("new Uint8Array([1, 2, 3])", options.max_array_length(2)),
+            "Uint8Array(3) [ 1, 2, ... 1 more item ]"
+        );
+        assert_eq!(
+            inspect(
+                "class Point {constructor() {this.x = 1;}}; new Point();",
+                options
+            ),
+            "Point { x: 1 }"
+        );
+        assert_eq!(
+            inspect("Object.create(null)", options),
+            "[Object: null prototype] {}"
+        );
+        assert_eq!(
+            inspect("[1, 2, 3]", options.break_length(5)),
+            "[\n  1,\n  2,\n  3\n]"
+        );
+        let err = inspect("new TypeError('oops')", options);
+        assert!(err.starts_with("TypeError: oops\n"));
+        assert!(err.contains("test_inspect.js"));
+    }
+}
@@ -18,8 +18,15 @@ pub(crate) fn init(q_js_rt: &QuickJsRuntimeAdapter) {
 
 unsafe extern "C" fn interrupt_handler(_rt: *mut q::JSRuntime, _opaque: *mut c_void) -> c_int {
     QuickJsRuntimeAdapter::do_with(|q_js_rt| {
-        let handler = q_js_rt.interrupt_handler.as_ref().unwrap();
-        i32::from(handler(q_js_rt))
+        if q_js_rt.is_deadline_exceeded() || q_js_rt.is_quota_exceeded() || q_js_rt.is_cancelled() {
+            return 1;
+        }
+        q_js_rt.sample_profiler();
+        if let Some(handler) = q_js_rt.interrupt_handler.as_ref() {
+            i32::from(handler(q_js_rt))
+        } else {
+            0
+        }
     })
 }
 
@@ -137,6 +137,7 @@ pub mod compile;
 pub mod dates;
 pub mod errors;
 pub mod functions;
+pub mod inspect;
 pub mod interrupthandler;
 pub mod iterators;
 pub mod json;
@@ -147,6 +148,7 @@ pub mod primitives;
 pub mod promises;
 pub mod properties;
 pub mod runtime;
+pub mod serialization;
 pub mod sets;
 pub mod typedarrays;
 
@@ -3,6 +3,7 @@
 use crate::jsutils::{JsError, Script};
 use crate::quickjs_utils::atoms;
 use crate::quickjs_utils::atoms::JSAtomRef;
+use crate::quickjs_utils::{new_undefined_ref, objects, primitives};
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
 use crate::quickjsvalueadapter::QuickJsValueAdapter;
@@ -10,6 +11,7 @@ use core::ptr;
 
 use libquickjs_sys as q;
 use std::ffi::{CStr, CString};
+use std::os::raw::c_int;
 
 /// compile a module, used for module loading
 /// # Safety
@@ -56,6 +58,29 @@ pub unsafe fn compile_module(
     }
 }
 
+/// resolve the imports of a module which was read from bytecode, compile_module resolves the imports itself but a module read
+/// by [from_bytecode](../compile/fn.from_bytecode.html) needs to be resolved before it can be run
+/// # Safety
+/// please ensure the corresponding QuickJSContext is still valid
+pub unsafe fn resolve_module(
+    context: *mut q::JSContext,
+    module: &QuickJsValueAdapter,
+) -> Result<(), JsError> {
+    assert!(module.is_module());
+    if q::JS_ResolveModule(context, *module.borrow_value()) < 0 {
+        let ex_opt = QuickJsRealmAdapter::get_exception(context);
+        if let Some(ex) = ex_opt {
+            Err(ex)
+        } else {
+            Err(JsError::new_str(
+                "resolve_module failed and could not get exception",
+            ))
+        }
+    } else {
+        Ok(())
+    }
+}
+
 // get the ModuleDef obj from a JSValue, this is used for module loading
 pub fn get_module_def(value: &QuickJsValueAdapter) -> *mut q::JSModuleDef {
     log::trace!("get_module_def");
@@ -69,11 +94,141 @@ pub fn set_module_loader(q_js_rt: &QuickJsRuntimeAdapter) {
     log::trace!("setting up module loader");
 
     let module_normalize: q::JSModuleNormalizeFunc = Some(js_module_normalize);
-    let module_loader: q::JSModuleLoaderFunc = Some(js_module_loader);
+    let module_loader: q::JSModuleLoaderFunc2 = Some(js_module_loader);
+    let module_check_attributes: q::JSModuleCheckSupportedImportAttributes =
+        Some(js_module_check_attributes);
 
     let opaque = std::ptr::null_mut();
 
-    unsafe { q::JS_SetModuleLoaderFunc(q_js_rt.runtime, module_normalize, module_loader, opaque) }
+    unsafe {
+        q::JS_SetModuleLoaderFunc2(
+            q_js_rt.runtime,
+            module_normalize,
+            module_loader,
+            module_check_attributes,
+            opaque,
+        )
+    }
+}
+
+/// the type of an import as specified by the type import attribute (`import cfg from "./cfg.json" with { type: "json" };`)
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ImportType {
+    JavaScript,
+    Json,
+    Text,
+    Bytes,
+}
+
+impl ImportType {
+    /// get the ImportType from the attributes object of an import
+    /// # Safety
+    /// Please ensure the context passed is still valid
+    pub unsafe fn from_attributes(
+        ctx: *mut q::JSContext,
+        attributes: &QuickJsValueAdapter,
+    ) -> Result<Self, JsError> {
+        if !attributes.is_object() {
+            return Ok(ImportType::JavaScript);
+        }
+        let type_ref = objects::get_property(ctx, attributes, "type")?;
+        if type_ref.is_null_or_undefined() {
+            return Ok(ImportType::JavaScript);
+        }
+        let type_str = primitives::to_string(ctx, &type_ref)?;
+        match type_str.as_str() {
+            "javascript" => Ok(ImportType::JavaScript),
+            "json" => Ok(ImportType::Json),
+            "text" => Ok(ImportType::Text),
+            "bytes" => Ok(ImportType::Bytes),
+            _ => Err(JsError::new_string(format!(
+                "unsupported import type: {type_str}"
+            ))),
+        }
+    }
+}
+
+/// create a module with the parsed source as default export, used for json, text and bytes imports
+/// # Safety
+/// Please ensure the realm passed is still valid
+pub unsafe fn new_synthetic_module(
+    realm: &QuickJsRealmAdapter,
+    name: &str,
+    import_type: ImportType,
+    source: Vec<u8>,
+) -> Result<*mut q::JSModuleDef, JsError> {
+    let default_export = match import_type {
+        ImportType::Json => {
+            let json = String::from_utf8(source).map_err(|e| {
+                JsError::new_string(format!("json module {name} is not valid utf-8: {e}"))
+            })?;
+            realm.json_parse(json.as_str())?
+        }
+        ImportType::Text => {
+            let text = String::from_utf8(source).map_err(|e| {
+                JsError::new_string(format!("text module {name} is not valid utf-8: {e}"))
+            })?;
+            realm.create_string(text.as_str())?
+        }
+        ImportType::Bytes => realm.create_typed_array_uint8(source)?,
+        ImportType::JavaScript => {
+            return Err(JsError::new_str(
+                "can not create a synthetic module for javascript",
+            ))
+        }
+    };
+    let module = new_module(realm.context, name, Some(synthetic_module_init))?;
+    add_module_export(realm.context, module, "default")?;
+    // the default export is kept by the module itself until it is initialized, it is freed with the module
+    set_module_private_value(realm.context, module, default_export)?;
+    Ok(module)
+}
+
+unsafe extern "C" fn synthetic_module_init(
+    ctx: *mut q::JSContext,
+    module: *mut q::JSModuleDef,
+) -> c_int {
+    let module_name = get_module_name(ctx, module).expect("could not get name");
+    log::trace!("synthetic_module_init: {}", module_name);
+
+    let default_export = get_module_private_value(ctx, module);
+    if default_export.is_undefined() {
+        QuickJsRealmAdapter::report_ex_ctx(
+            ctx,
+            format!("Failed to init module: {module_name}, no default export found").as_str(),
+        );
+        return -1;
+    }
+    let res = set_module_export(ctx, module, "default", default_export).and_then(|_| {
+        // the export now holds the value
+        set_module_private_value(ctx, module, new_undefined_ref())
+    });
+    match res {
+        Ok(_) => 0,
+        Err(e) => {
+            QuickJsRealmAdapter::report_ex_ctx(
+                ctx,
+                format!("Failed to init module: {module_name} caused by {e}").as_str(),
+            );
+            -1
+        }
+    }
+}
+
+unsafe extern "C" fn js_module_check_attributes(
+    ctx: *mut q::JSContext,
+    _opaque: *mut ::std::os::raw::c_void,
+    attributes: q::JSValue,
+) -> c_int {
+    let attributes_ref =
+        QuickJsValueAdapter::new(ctx, attributes, true, true, "js_module_check_attributes");
+    match ImportType::from_attributes(ctx, &attributes_ref) {
+        Ok(_) => 0,
+        Err(e) => {
+            QuickJsRealmAdapter::report_ex_ctx(ctx, e.get_message());
+            -1
+        }
+    }
 }
 
 /// detect if a script is module (contains import or export statements)
@@ -160,6 +315,33 @@ pub unsafe fn add_module_export(
     }
 }
 
+/// set the private value of a JSModuleDef, the module holds a reference to the value until it is freed
+/// # Safety
+/// Please ensure the context passed is still valid
+pub unsafe fn set_module_private_value(
+    ctx: *mut q::JSContext,
+    module: *mut q::JSModuleDef,
+    js_val: QuickJsValueAdapter,
+) -> Result<(), JsError> {
+    let res = q::JS_SetModulePrivateValue(ctx, module, js_val.clone_value_incr_rc());
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(JsError::new_str("JS_SetModulePrivateValue failed"))
+    }
+}
+
+/// get the private value of a JSModuleDef, this is undefined if it was never set
+/// # Safety
+/// Please ensure the context passed is still valid
+pub unsafe fn get_module_private_value(
+    ctx: *mut q::JSContext,
+    module: *mut q::JSModuleDef,
+) -> QuickJsValueAdapter {
+    let val = q::JS_GetModulePrivateValue(ctx, module);
+    QuickJsValueAdapter::new(ctx, val, false, true, "get_module_private_value result")
+}
+
 /// get the name of an JSModuleDef struct
 /// # Safety
 /// Please ensure the context passed is still valid
@@ -198,6 +380,15 @@ unsafe extern "C" fn js_module_normalize(
     QuickJsRuntimeAdapter::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(ctx);
 
+        let name_str = match q_js_rt.apply_import_map(base_str, name_str) {
+            Ok(mapped) => mapped,
+            Err(e) => {
+                q_ctx.report_ex(e.get_message());
+                return ptr::null_mut();
+            }
+        };
+        let name_str = name_str.as_ref();
+
         if let Some(res) = q_js_rt.with_all_module_loaders(|loader| {
             if let Some(normalized_path) = loader.normalize_path(q_ctx, base_str, name_str) {
                 let c_absolute_path = CString::new(normalized_path.as_str()).expect("fail");
@@ -218,6 +409,7 @@ unsafe extern "C" fn js_module_loader(
     ctx: *mut q::JSContext,
     module_name_raw: *const ::std::os::raw::c_char,
     _opaque: *mut ::std::os::raw::c_void,
+    attributes: q::JSValue,
 ) -> *mut q::JSModuleDef {
     log::trace!("js_module_loader called.");
 
@@ -226,8 +418,41 @@ unsafe extern "C" fn js_module_loader(
 
     log::trace!("js_module_loader called: {}", module_name);
 
+    let attributes_ref = QuickJsValueAdapter::new(ctx, attributes, true, true, "js_module_loader");
+    let import_type = match ImportType::from_attributes(ctx, &attributes_ref) {
+        Ok(import_type) => import_type,
+        Err(e) => {
+            QuickJsRealmAdapter::report_ex_ctx(ctx, e.get_message());
+            return std::ptr::null_mut();
+        }
+    };
+
     QuickJsRuntimeAdapter::do_with(|q_js_rt| {
         QuickJsRealmAdapter::with_context(ctx, |q_ctx| {
+            if import_type != ImportType::JavaScript {
+                // json, text and bytes modules are loaded as raw source by the script module loaders
+                return match q_js_rt.load_module_source(q_ctx, module_name) {
+                    Some(source) => {
+                        match new_synthetic_module(q_ctx, module_name, import_type, source) {
+                            Ok(module) => module,
+                            Err(e) => {
+                                let err =
+                                    format!("Module load failed for {module_name} because of: {e}");
+                                log::error!("{}", err);
+                                q_ctx.report_ex(err.as_str());
+                                std::ptr::null_mut()
+                            }
+                        }
+                    }
+                    None => {
+                        q_ctx.report_ex(
+                            format!("Module {module_name} can not be imported with type {import_type:?}")
+                                .as_str(),
+                        );
+                        std::ptr::null_mut()
+                    }
+                };
+            }
             if let Some(res) = q_js_rt.with_all_module_loaders(|module_loader| {
                 if module_loader.has_module(q_ctx, module_name) {
                     let mod_val_res = module_loader.load_module(q_ctx, module_name);
@@ -254,9 +479,12 @@ unsafe extern "C" fn js_module_loader(
 
 #[cfg(test)]
 pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
     use crate::facades::tests::init_test_rt;
+    use crate::jsutils::modules::ScriptModuleLoader;
     use crate::jsutils::Script;
     use crate::quickjs_utils::modules::detect_module;
+    use crate::quickjsrealmadapter::QuickJsRealmAdapter;
     use crate::values::JsValueFacade;
     use std::time::Duration;
 
@@ -386,4 +614,108 @@ pub mod tests {
 
         log::info!("< test_module_sandbox");
     }
+
+    #[test]
+    fn test_import_attributes() {
+        struct DataModuleLoader {}
+        impl ScriptModuleLoader for DataModuleLoader {
+            fn normalize_path(
+                &self,
+                _realm: &QuickJsRealmAdapter,
+                _ref_path: &str,
+                path: &str,
+            ) -> Option<String> {
+                if path.starts_with("data/") {
+                    Some(path.to_string())
+                } else {
+                    None
+                }
+            }
+
+            fn load_module(&self, _realm: &QuickJsRealmAdapter, absolute_path: &str) -> String {
+                match absolute_path {
+                    "data/cfg.json" => r#"{"a": 1, "b": [1, 2]}"#.to_string(),
+                    "data/readme.txt" => "hello".to_string(),
+                    _ => "export default 1;".to_string(),
+                }
+            }
+
+            fn load_module_bytes(
+                &self,
+                realm: &QuickJsRealmAdapter,
+                absolute_path: &str,
+            ) -> Vec<u8> {
+                if absolute_path == "data/blob.bin" {
+                    vec![0xff, 0x00, 0x61]
+                } else {
+                    self.load_module(realm, absolute_path).into_bytes()
+                }
+            }
+        }
+
+        let rt = QuickJsRuntimeBuilder::new()
+            .script_module_loader(DataModuleLoader {})
+            .build();
+
+        rt.eval_module_sync(
+            None,
+            Script::new(
+                "import_attributes_test.mjs",
+                "import cfg from 'data/cfg.json' with { type: 'json' };\n\
+                 import txt from 'data/readme.txt' with { type: 'text' };\n\
+                 import blob from 'data/blob.bin' with { type: 'bytes' };\n\
+                 globalThis.importAttrRes = [cfg.b[1], txt, blob instanceof Uint8Array, blob[0], blob.length];",
+            ),
+        )
+        .expect("module failed");
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new("import_attributes_res.js", "JSON.stringify(importAttrRes);"),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_str(), r#"[2,"hello",true,255,3]"#);
+
+        let res = rt.eval_module_sync(
+            None,
+            Script::new(
+                "import_attributes_test2.mjs",
+                "import css from 'data/style.css' with { type: 'css' };",
+            ),
+        );
+        assert!(res.is_err());
+
+        // a synthetic module which is never initialized (a sibling import fails) keeps its value in its own realm
+        rt.create_context("synthetic_a")
+            .expect("could not create realm");
+        let res = rt.eval_module_sync(
+            Some("synthetic_a"),
+            Script::new(
+                "import_attributes_test3.mjs",
+                "import cfg from 'data/cfg.json' with { type: 'json' };
+                 import missing from 'missing/module.mjs';",
+            ),
+        );
+        assert!(res.is_err());
+        rt.create_context("synthetic_b")
+            .expect("could not create realm");
+        rt.drop_context("synthetic_a")
+            .expect("could not drop realm");
+        rt.eval_module_sync(
+            Some("synthetic_b"),
+            Script::new(
+                "import_attributes_test4.mjs",
+                "import cfg from 'data/cfg.json' with { type: 'json' };
+                 globalThis.importAttrRes = cfg.a;",
+            ),
+        )
+        .expect("module failed");
+        let res = rt
+            .eval_sync(
+                Some("synthetic_b"),
+                Script::new("import_attributes_res2.js", "importAttrRes;"),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 1);
+    }
 }
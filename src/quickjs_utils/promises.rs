@@ -2,7 +2,7 @@ use crate::jsutils::JsError;
 use crate::quickjs_utils;
 #[cfg(feature = "bellard")]
 use crate::quickjs_utils::class_ids::JS_CLASS_PROMISE;
-use crate::quickjs_utils::errors::get_stack;
+use crate::quickjs_utils::errors::report_unhandled_error;
 use crate::quickjs_utils::functions;
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
@@ -180,6 +180,40 @@ pub unsafe fn new_promise(context: *mut q::JSContext) -> Result<QuickJsPromiseAd
     })
 }
 
+/// the state of a Promise, a settled Promise holds its value or the reason it was rejected
+pub enum PromiseState {
+    Pending,
+    Fulfilled(QuickJsValueAdapter),
+    Rejected(QuickJsValueAdapter),
+}
+
+/// get the state of a Promise without waiting for it
+/// # Safety
+/// When passing a context pointer please make sure the corresponding QuickJsContext is still valid
+pub unsafe fn get_promise_state(
+    context: *mut q::JSContext,
+    promise: &QuickJsValueAdapter,
+) -> PromiseState {
+    debug_assert!(is_promise(context, promise));
+    let state = q::JS_PromiseState(context, *promise.borrow_value());
+    let result = || {
+        QuickJsValueAdapter::new(
+            context,
+            q::JS_PromiseResult(context, *promise.borrow_value()),
+            false,
+            true,
+            "promises::get_promise_state result",
+        )
+    };
+    if state == q::JSPromiseStateEnum_JS_PROMISE_FULFILLED {
+        PromiseState::Fulfilled(result())
+    } else if state == q::JSPromiseStateEnum_JS_PROMISE_REJECTED {
+        PromiseState::Rejected(result())
+    } else {
+        PromiseState::Pending
+    }
+}
+
 pub(crate) fn init_promise_rejection_tracker(q_js_rt: &QuickJsRuntimeAdapter) {
     let tracker: q::JSHostPromiseRejectionTracker = Some(promise_rejection_tracker);
 
@@ -263,38 +297,9 @@ unsafe extern "C" fn promise_rejection_tracker(
             false,
             "promises::promise_rejection_tracker reason",
         );
-        let reason_str_res = functions::call_to_string(ctx, &reason_ref);
         QuickJsRuntimeAdapter::do_with(|rt| {
             let realm = rt.get_quickjs_context(ctx);
-            let realm_id = realm.get_realm_id();
-            let stack = match get_stack(realm) {
-                Ok(s) => match s.to_string() {
-                    Ok(s) => s,
-                    Err(_) => "".to_string(),
-                },
-                Err(_) => "".to_string(),
-            };
-            #[cfg(feature = "typescript")]
-            let stack = crate::typescript::unmap_stack_trace(stack.as_str());
-
-            match reason_str_res {
-                Ok(reason_str) => {
-                    log::error!(
-                        "[{}] unhandled promise rejection, reason: {}\nRejection stack:\n{}",
-                        realm_id,
-                        reason_str,
-                        stack
-                    );
-                }
-                Err(e) => {
-                    log::error!(
-                        "[{}] unhandled promise rejection, could not get reason: {}\nRejection stack:\n{}",
-                        realm_id,
-                        e,
-                        stack
-                    );
-                }
-            }
+            report_unhandled_error(realm, "unhandled promise rejection", &reason_ref);
         });
     }
 }
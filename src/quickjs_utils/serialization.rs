@@ -0,0 +1,1210 @@
+//! serde Serializer and Deserializer implementations which convert directly between rust types and QuickJsValueAdapter
+//!
+//! This avoids a round trip through a JSON string, numbers beyond the safe integer range (2^53) are converted to BigInt and
+//! byte buffers (e.g. serde_bytes) are converted to a Uint8Array
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct Item {
+//!     name: String,
+//!     price: f64,
+//! }
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! rt.loop_realm_sync(None, |_rt, realm| {
+//!     let item = Item { name: "apple".to_string(), price: 1.5 };
+//!     let adapter = realm.to_value_adapter(&item).expect("could not serialize");
+//!     let name = realm.get_object_property(&adapter, "name").expect("no name");
+//!     assert_eq!(name.to_string().expect("not a string"), "apple");
+//!
+//!     let back: Item = realm.from_value_adapter(&adapter).expect("could not deserialize");
+//!     assert_eq!(back, item);
+//! });
+//! ```
+
+use crate::jsutils::{JsError, JsValueType};
+use crate::quickjs_utils::typedarrays::{copy_view_bytes_q, get_typed_array_type_q};
+use crate::quickjs_utils::{bigints, functions};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::values::TypedArrayType;
+use serde::de::value::{SeqDeserializer, StringDeserializer};
+use serde::de::{
+    DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use serde::ser::{
+    SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+    SerializeTupleStruct, SerializeTupleVariant,
+};
+use serde::{Deserializer, Serialize, Serializer};
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+
+/// the largest integer which can be represented in a JavaScript Number without loss of precision
+const MAX_SAFE_INTEGER: i64 = (1_i64 << 53) - 1;
+
+/// Error produced by the serde Serializer and Deserializer, it keeps track of the path in the value where the error occurred
+#[derive(Debug)]
+pub struct SerializationError {
+    message: String,
+    path: VecDeque<String>,
+}
+
+impl SerializationError {
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            path: VecDeque::new(),
+        }
+    }
+    /// get the error message without the path
+    pub fn get_message(&self) -> &str {
+        self.message.as_str()
+    }
+    /// get the path where the error occurred, e.g. `$.items[3].price`
+    pub fn get_path(&self) -> String {
+        let mut ret = "$".to_string();
+        for segment in &self.path {
+            ret.push_str(segment.as_str());
+        }
+        ret
+    }
+    fn at_index(mut self, index: u32) -> Self {
+        self.path.push_front(format!("[{index}]"));
+        self
+    }
+    fn at_key(mut self, key: &str) -> Self {
+        self.path.push_front(format!(".{key}"));
+        self
+    }
+}
+
+impl Display for SerializationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.get_path())
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl serde::ser::Error for SerializationError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg.to_string())
+    }
+}
+
+impl serde::de::Error for SerializationError {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg.to_string())
+    }
+}
+
+impl From<JsError> for SerializationError {
+    fn from(err: JsError) -> Self {
+        Self::new(err.get_message().to_string())
+    }
+}
+
+impl From<SerializationError> for JsError {
+    fn from(err: SerializationError) -> Self {
+        JsError::new("TypeError".to_string(), err.to_string(), "".to_string())
+    }
+}
+
+/// serialize a rust value to a QuickJsValueAdapter
+pub fn to_value_adapter_q<T>(
+    realm: &QuickJsRealmAdapter,
+    value: &T,
+) -> Result<QuickJsValueAdapter, JsError>
+where
+    T: Serialize + ?Sized,
+{
+    value
+        .serialize(ValueAdapterSerializer::new(realm))
+        .map_err(JsError::from)
+}
+
+/// deserialize a QuickJsValueAdapter to a rust value
+pub fn from_value_adapter_q<T>(
+    realm: &QuickJsRealmAdapter,
+    value: &QuickJsValueAdapter,
+) -> Result<T, JsError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    T::deserialize(ValueAdapterDeserializer::new(realm, value.clone())).map_err(JsError::from)
+}
+
+/// serde Serializer which produces a QuickJsValueAdapter
+pub struct ValueAdapterSerializer<'a> {
+    realm: &'a QuickJsRealmAdapter,
+}
+
+impl<'a> ValueAdapterSerializer<'a> {
+    pub fn new(realm: &'a QuickJsRealmAdapter) -> Self {
+        Self { realm }
+    }
+    /// wrap a value in an object with a single property (the variant name), this is how enum variants with data are represented
+    fn wrap_variant(
+        realm: &QuickJsRealmAdapter,
+        variant: Option<&'static str>,
+        value: QuickJsValueAdapter,
+    ) -> Result<QuickJsValueAdapter, SerializationError> {
+        match variant {
+            None => Ok(value),
+            Some(variant) => {
+                let wrapper = realm.create_object()?;
+                realm.set_object_property(&wrapper, variant, &value)?;
+                Ok(wrapper)
+            }
+        }
+    }
+}
+
+impl<'a> Serializer for ValueAdapterSerializer<'a> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+    type SerializeSeq = ArraySerializer<'a>;
+    type SerializeTuple = ArraySerializer<'a>;
+    type SerializeTupleStruct = ArraySerializer<'a>;
+    type SerializeTupleVariant = ArraySerializer<'a>;
+    type SerializeMap = ObjectSerializer<'a>;
+    type SerializeStruct = ObjectSerializer<'a>;
+    type SerializeStructVariant = ObjectSerializer<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(self.realm.create_boolean(v)?)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(self.realm.create_i32(v)?)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        if let Ok(i) = i32::try_from(v) {
+            self.serialize_i32(i)
+        } else if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v) {
+            self.serialize_f64(v as f64)
+        } else {
+            Ok(bigints::new_bigint_i64_q(self.realm, v)?)
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        if v <= MAX_SAFE_INTEGER as u64 {
+            self.serialize_i64(v as i64)
+        } else {
+            Ok(bigints::new_bigint_u64_q(self.realm, v)?)
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(self.realm.create_f64(v)?)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(v.to_string().as_str())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(self.realm.create_string(v)?)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(self.realm.create_typed_array_uint8_copy(v)?)
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.realm.create_null()?)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(self.realm.create_null()?)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let realm = self.realm;
+        let inner = value.serialize(self).map_err(|e| e.at_key(variant))?;
+        Self::wrap_variant(realm, Some(variant), inner)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        ArraySerializer::new(self.realm, None)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        ArraySerializer::new(self.realm, None)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        ArraySerializer::new(self.realm, None)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        ArraySerializer::new(self.realm, Some(variant))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        ObjectSerializer::new(self.realm, None)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        ObjectSerializer::new(self.realm, None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        ObjectSerializer::new(self.realm, Some(variant))
+    }
+}
+
+/// serializes sequences and tuples to an Array
+pub struct ArraySerializer<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    array: QuickJsValueAdapter,
+    index: u32,
+    variant: Option<&'static str>,
+}
+
+impl<'a> ArraySerializer<'a> {
+    fn new(
+        realm: &'a QuickJsRealmAdapter,
+        variant: Option<&'static str>,
+    ) -> Result<Self, SerializationError> {
+        Ok(Self {
+            realm,
+            array: realm.create_array()?,
+            index: 0,
+            variant,
+        })
+    }
+    fn push<T>(&mut self, value: &T) -> Result<(), SerializationError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let index = self.index;
+        let variant = self.variant;
+        let element = value
+            .serialize(ValueAdapterSerializer::new(self.realm))
+            .map_err(|e| {
+                let e = e.at_index(index);
+                match variant {
+                    None => e,
+                    Some(variant) => e.at_key(variant),
+                }
+            })?;
+        self.realm.set_array_element(&self.array, index, &element)?;
+        self.index += 1;
+        Ok(())
+    }
+    fn finish(self) -> Result<QuickJsValueAdapter, SerializationError> {
+        ValueAdapterSerializer::wrap_variant(self.realm, self.variant, self.array)
+    }
+}
+
+impl SerializeSeq for ArraySerializer<'_> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTuple for ArraySerializer<'_> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleStruct for ArraySerializer<'_> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeTupleVariant for ArraySerializer<'_> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+/// serializes maps and structs to an Object
+pub struct ObjectSerializer<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    object: QuickJsValueAdapter,
+    next_key: Option<String>,
+    variant: Option<&'static str>,
+}
+
+impl<'a> ObjectSerializer<'a> {
+    fn new(
+        realm: &'a QuickJsRealmAdapter,
+        variant: Option<&'static str>,
+    ) -> Result<Self, SerializationError> {
+        Ok(Self {
+            realm,
+            object: realm.create_object()?,
+            next_key: None,
+            variant,
+        })
+    }
+    fn set<T>(&mut self, key: &str, value: &T) -> Result<(), SerializationError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let variant = self.variant;
+        let property = value
+            .serialize(ValueAdapterSerializer::new(self.realm))
+            .map_err(|e| {
+                let e = e.at_key(key);
+                match variant {
+                    None => e,
+                    Some(variant) => e.at_key(variant),
+                }
+            })?;
+        self.realm
+            .set_object_property(&self.object, key, &property)?;
+        Ok(())
+    }
+    fn finish(self) -> Result<QuickJsValueAdapter, SerializationError> {
+        ValueAdapterSerializer::wrap_variant(self.realm, self.variant, self.object)
+    }
+}
+
+impl SerializeMap for ObjectSerializer<'_> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key_adapter = key.serialize(ValueAdapterSerializer::new(self.realm))?;
+        match key_adapter.get_js_type() {
+            JsValueType::String | JsValueType::I32 | JsValueType::F64 | JsValueType::BigInt => {
+                self.next_key = Some(key_adapter.to_string()?);
+                Ok(())
+            }
+            t => Err(SerializationError::new(format!(
+                "map key must be a string or a number, got {t}"
+            ))),
+        }
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self.next_key.take().ok_or_else(|| {
+            SerializationError::new("serialize_value called before serialize_key".to_string())
+        })?;
+        self.set(key.as_str(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeStruct for ObjectSerializer<'_> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.set(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl SerializeStructVariant for ObjectSerializer<'_> {
+    type Ok = QuickJsValueAdapter;
+    type Error = SerializationError;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.set(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+/// serde Deserializer which reads from a QuickJsValueAdapter
+pub struct ValueAdapterDeserializer<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    value: QuickJsValueAdapter,
+}
+
+impl<'a> ValueAdapterDeserializer<'a> {
+    pub fn new(realm: &'a QuickJsRealmAdapter, value: QuickJsValueAdapter) -> Self {
+        Self { realm, value }
+    }
+
+    fn deserialize_integer<'de, V>(self, visitor: V) -> Result<V::Value, SerializationError>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.get_js_type() {
+            JsValueType::I32 => visitor.visit_i32(self.value.to_i32()),
+            JsValueType::F64 => {
+                let f = self.value.to_f64();
+                if f.fract() == 0.0 && (i64::MIN as f64..0.0).contains(&f) {
+                    visitor.visit_i64(f as i64)
+                } else if f.fract() == 0.0 && (0.0..18446744073709551616.0).contains(&f) {
+                    // the upper bound is 2^64, u64::MAX as f64 rounds up to 2^64 which does not fit
+                    visitor.visit_u64(f as u64)
+                } else {
+                    visitor.visit_f64(f)
+                }
+            }
+            JsValueType::BigInt => {
+                let s = bigints::to_string_q(self.realm, &self.value)?;
+                if let Ok(i) = s.parse::<i64>() {
+                    visitor.visit_i64(i)
+                } else if let Ok(u) = s.parse::<u64>() {
+                    visitor.visit_u64(u)
+                } else {
+                    Err(SerializationError::new(format!(
+                        "BigInt {s} does not fit in a 64 bit integer"
+                    )))
+                }
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn variant_of(&self) -> Result<(String, Option<QuickJsValueAdapter>), SerializationError> {
+        match self.value.get_js_type() {
+            JsValueType::String => Ok((self.value.to_string()?, None)),
+            JsValueType::Object => {
+                let props = self.realm.get_object_properties(&self.value)?;
+                if props.len() != 1 {
+                    return Err(SerializationError::new(format!(
+                        "expected an object with exactly one property for an enum variant, got {} properties",
+                        props.len()
+                    )));
+                }
+                let variant = props.into_iter().next().unwrap();
+                let value = self
+                    .realm
+                    .get_object_property(&self.value, variant.as_str())?;
+                Ok((variant, Some(value)))
+            }
+            t => Err(SerializationError::new(format!(
+                "expected a string or an object for an enum, got {t}"
+            ))),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for ValueAdapterDeserializer<'_> {
+    type Error = SerializationError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value.get_js_type() {
+            JsValueType::I32 => visitor.visit_i32(self.value.to_i32()),
+            JsValueType::F64 => visitor.visit_f64(self.value.to_f64()),
+            JsValueType::String => visitor.visit_string(self.value.to_string()?),
+            JsValueType::Boolean => visitor.visit_bool(self.value.to_bool()),
+            JsValueType::Null | JsValueType::Undefined => visitor.visit_unit(),
+            JsValueType::BigInt => {
+                let s = bigints::to_string_q(self.realm, &self.value)?;
+                if let Ok(i) = s.parse::<i64>() {
+                    visitor.visit_i64(i)
+                } else if let Ok(u) = s.parse::<u64>() {
+                    visitor.visit_u64(u)
+                } else {
+                    visitor.visit_string(s)
+                }
+            }
+            JsValueType::Array => {
+                let len = self.realm.get_array_length(&self.value)?;
+                visitor.visit_seq(ArrayAccess {
+                    realm: self.realm,
+                    array: self.value,
+                    index: 0,
+                    len,
+                })
+            }
+            JsValueType::Object => match get_typed_array_type_q(&self.value) {
+                // byte arrays and buffers are visited as a sequence of the viewed bytes (a DataView has no elements)
+                Some(
+                    TypedArrayType::Uint8
+                    | TypedArrayType::Uint8Clamped
+                    | TypedArrayType::ArrayBuffer
+                    | TypedArrayType::DataView,
+                ) => {
+                    let bytes = copy_view_bytes_q(self.realm, &self.value)?;
+                    visitor.visit_seq(SeqDeserializer::new(bytes.into_iter()))
+                }
+                // other TypedArrays are visited as a sequence of their element values
+                Some(_) => {
+                    let len = self.realm.get_array_length(&self.value)?;
+                    visitor.visit_seq(ArrayAccess {
+                        realm: self.realm,
+                        array: self.value,
+                        index: 0,
+                        len,
+                    })
+                }
+                None => {
+                    let keys = self.realm.get_object_properties(&self.value)?;
+                    visitor.visit_map(ObjectAccess {
+                        realm: self.realm,
+                        object: self.value,
+                        keys: keys.into_iter(),
+                        current_key: None,
+                    })
+                }
+            },
+            JsValueType::Date => {
+                // like JSON.stringify
+                let iso = functions::invoke_member_function_q(
+                    self.realm,
+                    &self.value,
+                    "toISOString",
+                    &[],
+                )?;
+                visitor.visit_string(iso.to_string()?)
+            }
+            t @ (JsValueType::Function
+            | JsValueType::Promise
+            | JsValueType::Error
+            | JsValueType::Map
+            | JsValueType::Set
+            | JsValueType::Symbol) => Err(SerializationError::new(format!(
+                "can not deserialize a value of type {t}"
+            ))),
+        }
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_integer(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if get_typed_array_type_q(&self.value).is_some() {
+            let bytes = copy_view_bytes_q(self.realm, &self.value)?;
+            visitor.visit_byte_buf(bytes)
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.value.is_null_or_undefined() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let (variant, value) = self.variant_of()?;
+        let err_variant = variant.clone();
+        visitor
+            .visit_enum(EnumDeserializer {
+                realm: self.realm,
+                variant,
+                value,
+            })
+            .map_err(|e| e.at_key(err_variant.as_str()))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char str string unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct ArrayAccess<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    array: QuickJsValueAdapter,
+    index: u32,
+    len: u32,
+}
+
+impl<'de> SeqAccess<'de> for ArrayAccess<'_> {
+    type Error = SerializationError;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.index >= self.len {
+            return Ok(None);
+        }
+        let index = self.index;
+        self.index += 1;
+        let element = self
+            .realm
+            .get_array_element(&self.array, index)
+            .map_err(|e| SerializationError::from(e).at_index(index))?;
+        seed.deserialize(ValueAdapterDeserializer::new(self.realm, element))
+            .map(Some)
+            .map_err(|e| e.at_index(index))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some((self.len - self.index) as usize)
+    }
+}
+
+struct ObjectAccess<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    object: QuickJsValueAdapter,
+    keys: std::vec::IntoIter<String>,
+    current_key: Option<String>,
+}
+
+impl<'de> MapAccess<'de> for ObjectAccess<'_> {
+    type Error = SerializationError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.keys.next() {
+            None => Ok(None),
+            Some(key) => {
+                self.current_key = Some(key.clone());
+                let deserializer: StringDeserializer<SerializationError> = key.into_deserializer();
+                seed.deserialize(deserializer).map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let key = self.current_key.take().ok_or_else(|| {
+            SerializationError::new("next_value_seed called before next_key_seed".to_string())
+        })?;
+        let value = self
+            .realm
+            .get_object_property(&self.object, key.as_str())
+            .map_err(|e| SerializationError::from(e).at_key(key.as_str()))?;
+        seed.deserialize(ValueAdapterDeserializer::new(self.realm, value))
+            .map_err(|e| e.at_key(key.as_str()))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.keys.len())
+    }
+}
+
+struct EnumDeserializer<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    variant: String,
+    value: Option<QuickJsValueAdapter>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for EnumDeserializer<'a> {
+    type Error = SerializationError;
+    type Variant = VariantDeserializer<'a>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let deserializer: StringDeserializer<SerializationError> = self.variant.into_deserializer();
+        let variant = seed.deserialize(deserializer)?;
+        Ok((
+            variant,
+            VariantDeserializer {
+                realm: self.realm,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+struct VariantDeserializer<'a> {
+    realm: &'a QuickJsRealmAdapter,
+    value: Option<QuickJsValueAdapter>,
+}
+
+impl VariantDeserializer<'_> {
+    fn take_value(self) -> Result<QuickJsValueAdapter, SerializationError> {
+        self.value.ok_or_else(|| {
+            SerializationError::new("expected an enum variant with data".to_string())
+        })
+    }
+}
+
+impl<'de> VariantAccess<'de> for VariantDeserializer<'_> {
+    type Error = SerializationError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.value {
+            None => Ok(()),
+            Some(v) if v.is_null_or_undefined() => Ok(()),
+            Some(_) => Err(SerializationError::new(
+                "expected a unit enum variant".to_string(),
+            )),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let realm = self.realm;
+        let value = self.take_value()?;
+        seed.deserialize(ValueAdapterDeserializer::new(realm, value))
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let realm = self.realm;
+        let value = self.take_value()?;
+        ValueAdapterDeserializer::new(realm, value).deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let realm = self.realm;
+        let value = self.take_value()?;
+        ValueAdapterDeserializer::new(realm, value).deserialize_map(visitor)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::jsutils::{JsValueType, Script};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { w: i32, h: i32 },
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Item {
+        name: String,
+        price: f64,
+        count: u32,
+        big: u64,
+        tags: Vec<String>,
+        shape: Shape,
+        #[serde(with = "bytes")]
+        data: Vec<u8>,
+        attrs: HashMap<String, i64>,
+        note: Option<String>,
+    }
+
+    // minimal stand in for serde_bytes
+    mod bytes {
+        use serde::de::{SeqAccess, Visitor};
+        use serde::{Deserializer, Serializer};
+        use std::fmt::Formatter;
+
+        pub fn serialize<S: Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+            s.serialize_bytes(v)
+        }
+
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Vec<u8>;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(v)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut ret = vec![];
+                while let Some(b) = seq.next_element()? {
+                    ret.push(b);
+                }
+                Ok(ret)
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+            d.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rt = init_test_rt();
+        rt.loop_realm_sync(None, |_rt, realm| {
+            let mut attrs = HashMap::new();
+            attrs.insert("a".to_string(), -12_i64);
+            let item = Item {
+                name: "apple".to_string(),
+                price: 1.25,
+                count: 3,
+                big: u64::MAX,
+                tags: vec!["fruit".to_string(), "green".to_string()],
+                shape: Shape::Rect { w: 2, h: 4 },
+                data: vec![1, 2, 3],
+                attrs,
+                note: None,
+            };
+
+            let adapter = realm.to_value_adapter(&item).expect("could not serialize");
+
+            let big = realm
+                .get_object_property(&adapter, "big")
+                .expect("no big prop");
+            assert_eq!(big.get_js_type(), JsValueType::BigInt);
+            let data = realm
+                .get_object_property(&adapter, "data")
+                .expect("no data prop");
+            assert!(data.is_typed_array());
+
+            let back: Item = realm
+                .from_value_adapter(&adapter)
+                .expect("could not deserialize");
+            assert_eq!(back, item);
+
+            let shapes: Vec<Shape> = realm
+                .from_value_adapter(
+                    &realm
+                        .eval(Script::new(
+                            "shapes.js",
+                            "(['Point', {Circle: 2.5}, {Rect: {w: 1, h: 2}}])",
+                        ))
+                        .expect("script failed"),
+                )
+                .expect("could not deserialize shapes");
+            assert_eq!(
+                shapes,
+                vec![Shape::Point, Shape::Circle(2.5), Shape::Rect { w: 1, h: 2 }]
+            );
+        });
+    }
+
+    #[test]
+    fn test_typed_array_views() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Data {
+            #[serde(with = "bytes")]
+            data: Vec<u8>,
+        }
+
+        let rt = init_test_rt();
+        rt.loop_realm_sync(None, |_rt, realm| {
+            let eval = |code: &str| {
+                realm
+                    .eval(Script::new("typed_arrays.js", code))
+                    .expect("script failed")
+            };
+
+            // only the viewed part of the buffer is deserialized
+            let sub = eval("new Uint8Array([0, 1, 2, 3, 4, 5]).subarray(2, 4)");
+            let bytes: Vec<u8> = realm
+                .from_value_adapter(&sub)
+                .expect("could not deserialize");
+            assert_eq!(bytes, vec![2, 3]);
+            let data: Data = realm
+                .from_value_adapter(&eval(
+                    "({data: new Uint8Array(new Uint8Array([0, 1, 2, 3, 4, 5]).buffer, 1, 4)})",
+                ))
+                .expect("could not deserialize");
+            assert_eq!(data.data, vec![1, 2, 3, 4]);
+            let buffer: Vec<u8> = realm
+                .from_value_adapter(&eval("new Uint8Array([7, 8]).buffer"))
+                .expect("could not deserialize");
+            assert_eq!(buffer, vec![7, 8]);
+
+            // other TypedArrays are deserialized by element
+            let floats: Vec<f32> = realm
+                .from_value_adapter(&eval("new Float32Array([0.5, -1.5, 2])"))
+                .expect("could not deserialize");
+            assert_eq!(floats, vec![0.5, -1.5, 2.0]);
+            let ints: Vec<i16> = realm
+                .from_value_adapter(&eval("new Int16Array([-1, 2, 3, 4]).subarray(1, 3)"))
+                .expect("could not deserialize");
+            assert_eq!(ints, vec![2, 3]);
+        });
+    }
+
+    #[test]
+    fn test_error_path() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Priced {
+            price: f64,
+        }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Order {
+            items: Vec<Priced>,
+        }
+
+        let rt = init_test_rt();
+        rt.loop_realm_sync(None, |_rt, realm| {
+            let value = realm
+                .eval(Script::new(
+                    "order.js",
+                    "({items: [{price: 1}, {price: 2}, {price: 3}, {price: 'free'}]})",
+                ))
+                .expect("script failed");
+            let err = realm
+                .from_value_adapter::<Order>(&value)
+                .expect_err("should not deserialize");
+            assert!(
+                err.get_message().ends_with("at $.items[3].price"),
+                "unexpected message: {}",
+                err.get_message()
+            );
+
+            // 2^64 is not a u64, it should not saturate to u64::MAX
+            let too_big = realm
+                .eval(Script::new("too_big.js", "2 ** 64"))
+                .expect("script failed");
+            assert!(realm.from_value_adapter::<u64>(&too_big).is_err());
+            let max = realm
+                .eval(Script::new("max.js", "2 ** 63"))
+                .expect("script failed");
+            assert_eq!(
+                realm
+                    .from_value_adapter::<u64>(&max)
+                    .expect("could not deserialize"),
+                1_u64 << 63
+            );
+        });
+    }
+}
@@ -1,15 +1,22 @@
-//! this module is a work in progress and is currently used by me to pass Vec<u8>'s from rust to js and back again
-//!
+//! utils for passing TypedArrays, DataViews and ArrayBuffers between rust and js
 //!
+//! buffers which are created from rust (new_array_buffer, new_uint8_array, new_typed_array) are not copied, the Vec is owned by
+//! rust and dropped when js no longer uses it, such a Vec can also be moved back to rust without copying by detaching the ArrayBuffer
 //!
 use crate::jsutils::JsError;
-use crate::quickjs_utils::class_ids::JS_CLASS_ARRAY_BUFFER;
-use crate::quickjs_utils::get_constructor;
+use crate::quickjs_utils::class_ids::{
+    JS_CLASS_ARRAY_BUFFER, JS_CLASS_BIG_INT64_ARRAY, JS_CLASS_BIG_UINT64_ARRAY, JS_CLASS_DATAVIEW,
+    JS_CLASS_FLOAT16_ARRAY, JS_CLASS_FLOAT32_ARRAY, JS_CLASS_FLOAT64_ARRAY, JS_CLASS_INT16_ARRAY,
+    JS_CLASS_INT32_ARRAY, JS_CLASS_INT8_ARRAY, JS_CLASS_UINT16_ARRAY, JS_CLASS_UINT32_ARRAY,
+    JS_CLASS_UINT8C_ARRAY, JS_CLASS_UINT8_ARRAY,
+};
 use crate::quickjs_utils::objects::{
     construct_object, get_property, get_prototype_of, is_instance_of, set_property2,
 };
+use crate::quickjs_utils::{errors, get_constructor};
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::values::TypedArrayType;
 use hirofa_utils::auto_id_map::AutoIdMap;
 use libquickjs_sys as q;
 use libquickjs_sys::JS_GetClassID;
@@ -197,6 +204,13 @@ pub unsafe fn detach_array_buffer_buffer(
 
     // check if vec is one we buffered, if not we create a new one from the slice we got from quickjs
     // abuf->opaque seems impossible to get at, so we store the id ourselves as well
+    let mut len: usize = 0;
+    let ptr = q::JS_GetArrayBuffer(ctx, &mut len, *array_buffer.borrow_value());
+    if ptr.is_null() {
+        return Err(errors::get_exception(ctx)
+            .unwrap_or_else(|| JsError::new_str("could not get ArrayBuffer data")));
+    }
+
     let id_prop = get_property(ctx, array_buffer, "__buffer_id")?;
     let id_opt = if id_prop.is_i32() {
         Some(id_prop.to_i32() as usize)
@@ -204,17 +218,25 @@ pub unsafe fn detach_array_buffer_buffer(
         None
     };
 
-    let v = if let Some(id) = id_opt {
+    // only reclaim our Vec if the buffer really is the one we stored
+    let owned = id_opt.and_then(|id| {
         BUFFERS.with(|rc| {
             let buffers = &mut *rc.borrow_mut();
-            buffers.remove(&id)
+            if buffers
+                .get(&id)
+                .is_some_and(|b| std::ptr::eq(b.as_ptr(), ptr))
+            {
+                Some(buffers.remove(&id))
+            } else {
+                None
+            }
         })
-    } else {
-        let mut len: usize = 0;
-
-        let ptr = q::JS_GetArrayBuffer(ctx, &mut len, *array_buffer.borrow_value());
+    });
 
-        Vec::from_raw_parts(ptr, len as _, len as _)
+    // memory which was allocated by quickjs can not be owned by a Vec, so that is copied
+    let v = match owned {
+        Some(v) => v,
+        None => std::slice::from_raw_parts(ptr, len).to_vec(),
     };
 
     q::JS_DetachArrayBuffer(ctx, *array_buffer.borrow_value());
@@ -334,6 +356,165 @@ pub unsafe fn new_uint8_array_copy(
     construct_object(ctx, &constructor, &[&array_buffer])
 }
 
+/// get the type of a TypedArray, DataView or ArrayBuffer, returns None for all other values
+pub fn get_typed_array_type_q(obj: &QuickJsValueAdapter) -> Option<TypedArrayType> {
+    unsafe { get_typed_array_type(obj) }
+}
+
+/// get the type of a TypedArray, DataView or ArrayBuffer, returns None for all other values
+/// # Safety
+/// please ensure that the relevant QuickjsRealmAdapter is not dropped while using this function or a result of this function
+pub unsafe fn get_typed_array_type(obj: &QuickJsValueAdapter) -> Option<TypedArrayType> {
+    if !obj.is_object() {
+        return None;
+    }
+    match JS_GetClassID(*obj.borrow_value()) {
+        JS_CLASS_UINT8_ARRAY => Some(TypedArrayType::Uint8),
+        JS_CLASS_UINT8C_ARRAY => Some(TypedArrayType::Uint8Clamped),
+        JS_CLASS_INT8_ARRAY => Some(TypedArrayType::Int8),
+        JS_CLASS_UINT16_ARRAY => Some(TypedArrayType::Uint16),
+        JS_CLASS_INT16_ARRAY => Some(TypedArrayType::Int16),
+        JS_CLASS_UINT32_ARRAY => Some(TypedArrayType::Uint32),
+        JS_CLASS_INT32_ARRAY => Some(TypedArrayType::Int32),
+        JS_CLASS_FLOAT16_ARRAY => Some(TypedArrayType::Float16),
+        JS_CLASS_FLOAT32_ARRAY => Some(TypedArrayType::Float32),
+        JS_CLASS_FLOAT64_ARRAY => Some(TypedArrayType::Float64),
+        JS_CLASS_BIG_INT64_ARRAY => Some(TypedArrayType::BigInt64),
+        JS_CLASS_BIG_UINT64_ARRAY => Some(TypedArrayType::BigUint64),
+        JS_CLASS_DATAVIEW => Some(TypedArrayType::DataView),
+        JS_CLASS_ARRAY_BUFFER => Some(TypedArrayType::ArrayBuffer),
+        _ => None,
+    }
+}
+
+/// get the ArrayBuffer of a TypedArray, DataView or ArrayBuffer and the range (offset and length in bytes) which is viewed
+unsafe fn get_viewed_range(
+    ctx: *mut q::JSContext,
+    obj: &QuickJsValueAdapter,
+) -> Result<(QuickJsValueAdapter, usize, usize), JsError> {
+    let prop_to_usize = |name: &str| -> Result<usize, JsError> {
+        let prop = get_property(ctx, obj, name)?;
+        if prop.is_i32() {
+            Ok(prop.to_i32() as usize)
+        } else if prop.is_f64() {
+            Ok(prop.to_f64() as usize)
+        } else {
+            Err(JsError::new_string(format!("{name} is not a number")))
+        }
+    };
+    match get_typed_array_type(obj) {
+        None => Err(JsError::new_str(
+            "value is not a TypedArray, DataView or ArrayBuffer",
+        )),
+        Some(TypedArrayType::ArrayBuffer) => Ok((obj.clone(), 0, prop_to_usize("byteLength")?)),
+        Some(_) => Ok((
+            get_property(ctx, obj, "buffer")?,
+            prop_to_usize("byteOffset")?,
+            prop_to_usize("byteLength")?,
+        )),
+    }
+}
+
+/// copy the bytes of a TypedArray, DataView or ArrayBuffer, for a view only the viewed part of its buffer is copied
+pub fn copy_view_bytes_q(
+    q_ctx: &QuickJsRealmAdapter,
+    obj: &QuickJsValueAdapter,
+) -> Result<Vec<u8>, JsError> {
+    unsafe { copy_view_bytes(q_ctx.context, obj) }
+}
+
+/// copy the bytes of a TypedArray, DataView or ArrayBuffer, for a view only the viewed part of its buffer is copied
+/// # Safety
+/// please ensure that the relevant QuickjsRealmAdapter is not dropped while using this function or a result of this function
+pub unsafe fn copy_view_bytes(
+    ctx: *mut q::JSContext,
+    obj: &QuickJsValueAdapter,
+) -> Result<Vec<u8>, JsError> {
+    let (array_buffer, offset, length) = get_viewed_range(ctx, obj)?;
+    let mut len: usize = 0;
+    let ptr = q::JS_GetArrayBuffer(ctx, &mut len, *array_buffer.borrow_value());
+    if ptr.is_null() {
+        return Err(errors::get_exception(ctx)
+            .unwrap_or_else(|| JsError::new_str("could not get ArrayBuffer data")));
+    }
+    if offset + length > len {
+        return Err(JsError::new_str("view is out of bounds of its ArrayBuffer"));
+    }
+    Ok(std::slice::from_raw_parts(ptr.add(offset), length).to_vec())
+}
+
+/// move the bytes of a TypedArray, DataView or ArrayBuffer to rust, the ArrayBuffer is detached so it (and all views on it) can no longer be used in js
+///
+/// if the buffer was created from rust and the view covers the whole buffer the original Vec is returned without copying,
+/// else the viewed bytes are copied once before the ArrayBuffer is detached
+pub fn transfer_view_bytes_q(
+    q_ctx: &QuickJsRealmAdapter,
+    obj: &QuickJsValueAdapter,
+) -> Result<Vec<u8>, JsError> {
+    unsafe { transfer_view_bytes(q_ctx.context, obj) }
+}
+
+/// move the bytes of a TypedArray, DataView or ArrayBuffer to rust, the ArrayBuffer is detached so it (and all views on it) can no longer be used in js
+///
+/// if the buffer was created from rust and the view covers the whole buffer the original Vec is returned without copying,
+/// else the viewed bytes are copied once before the ArrayBuffer is detached
+/// # Safety
+/// please ensure that the relevant QuickjsRealmAdapter is not dropped while using this function or a result of this function
+pub unsafe fn transfer_view_bytes(
+    ctx: *mut q::JSContext,
+    obj: &QuickJsValueAdapter,
+) -> Result<Vec<u8>, JsError> {
+    let (array_buffer, offset, length) = get_viewed_range(ctx, obj)?;
+    let mut len: usize = 0;
+    let ptr = q::JS_GetArrayBuffer(ctx, &mut len, *array_buffer.borrow_value());
+    if ptr.is_null() {
+        return Err(errors::get_exception(ctx)
+            .unwrap_or_else(|| JsError::new_str("could not get ArrayBuffer data")));
+    }
+    if offset == 0 && length == len {
+        detach_array_buffer_buffer(ctx, &array_buffer)
+    } else {
+        let bytes = copy_view_bytes(ctx, obj)?;
+        q::JS_DetachArrayBuffer(ctx, *array_buffer.borrow_value());
+        Ok(bytes)
+    }
+}
+
+/// create a new TypedArray, DataView or ArrayBuffer, the buffer is consumed (not copied) and dropped when it is no longer used in js
+/// it can be reclaimed without copying by calling transfer_view_bytes_q
+pub fn new_typed_array_q(
+    q_ctx: &QuickJsRealmAdapter,
+    array_type: TypedArrayType,
+    buf: Vec<u8>,
+) -> Result<QuickJsValueAdapter, JsError> {
+    unsafe { new_typed_array(q_ctx.context, array_type, buf) }
+}
+
+/// create a new TypedArray, DataView or ArrayBuffer, the buffer is consumed (not copied) and dropped when it is no longer used in js
+/// it can be reclaimed without copying by calling transfer_view_bytes_q
+/// # Safety
+/// please ensure that the relevant QuickjsRealmAdapter is not dropped while using this function or a result of this function
+pub unsafe fn new_typed_array(
+    ctx: *mut q::JSContext,
+    array_type: TypedArrayType,
+    buf: Vec<u8>,
+) -> Result<QuickJsValueAdapter, JsError> {
+    let bytes_per_element = array_type.get_bytes_per_element();
+    if buf.len() % bytes_per_element != 0 {
+        return Err(JsError::new_string(format!(
+            "the length of the buffer for a {} should be a multiple of {bytes_per_element}, it was {}",
+            array_type.get_constructor_name(),
+            buf.len()
+        )));
+    }
+    let array_buffer = new_array_buffer(ctx, buf)?;
+    if array_type == TypedArrayType::ArrayBuffer {
+        return Ok(array_buffer);
+    }
+    let constructor = get_constructor(ctx, array_type.get_constructor_name())?;
+    construct_object(ctx, &constructor, &[&array_buffer])
+}
+
 unsafe extern "C" fn free_func(
     _rt: *mut q::JSRuntime,
     opaque: *mut ::std::os::raw::c_void,
@@ -540,4 +721,117 @@ pub mod tests {
 
         thread::sleep(Duration::from_secs(1));
     }
+
+    #[test]
+    fn test_typed_array_types() {
+        let rt = init_test_rt();
+
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new("tat.js", "(new Float32Array([1.5, -2]));"),
+            )
+            .expect("script failed");
+        match res {
+            JsValueFacade::TypedArray { buffer, array_type } => {
+                assert_eq!(array_type, TypedArrayType::Float32);
+                let mut expected = 1.5f32.to_le_bytes().to_vec();
+                expected.extend_from_slice(&(-2f32).to_le_bytes());
+                assert_eq!(buffer, expected);
+            }
+            _ => panic!("not a TypedArray"),
+        }
+
+        // only the viewed part of the buffer
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new("tat.js", "(new Uint8Array([1, 2, 3, 4]).subarray(1, 3));"),
+            )
+            .expect("script failed");
+        match res {
+            JsValueFacade::TypedArray { buffer, array_type } => {
+                assert_eq!(array_type, TypedArrayType::Uint8);
+                assert_eq!(buffer, vec![2, 3]);
+            }
+            _ => panic!("not a TypedArray"),
+        }
+
+        for (code, expected_type) in [
+            (
+                "(new DataView(new ArrayBuffer(4), 2));",
+                TypedArrayType::DataView,
+            ),
+            ("(new ArrayBuffer(4));", TypedArrayType::ArrayBuffer),
+            ("(new BigInt64Array(1));", TypedArrayType::BigInt64),
+            ("(new Uint8ClampedArray(1));", TypedArrayType::Uint8Clamped),
+        ] {
+            match rt
+                .eval_sync(None, Script::new("tat.js", code))
+                .expect("script failed")
+            {
+                JsValueFacade::TypedArray { array_type, .. } => {
+                    assert_eq!(array_type, expected_type)
+                }
+                _ => panic!("not a TypedArray"),
+            }
+        }
+
+        rt.loop_realm_sync(None, |_rt, realm| {
+            // rust to js
+            let mut bytes = 0.25f64.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&8f64.to_le_bytes());
+            let arr = realm
+                .from_js_value_facade(JsValueFacade::TypedArray {
+                    buffer: bytes,
+                    array_type: TypedArrayType::Float64,
+                })
+                .expect("conversion failed");
+            let global = get_global_q(realm);
+            set_property_q(realm, &global, "f64Arr", &arr).expect("could not set prop");
+            let ok = realm
+                .eval(Script::new(
+                    "tat2.js",
+                    "(f64Arr instanceof Float64Array && f64Arr.length === 2 && f64Arr[0] === 0.25 && f64Arr[1] === 8);",
+                ))
+                .expect("script failed");
+            assert!(ok.to_bool());
+            assert!(realm
+                .create_typed_array(TypedArrayType::Int32, vec![1, 2, 3])
+                .is_err());
+
+            // zero-copy transfer of a rust owned buffer
+            let buffer: Vec<u8> = vec![7; 1024];
+            let ptr = buffer.as_ptr();
+            let arr = realm
+                .create_typed_array(TypedArrayType::Int16, buffer)
+                .expect("could not create array");
+            match realm.transfer_typed_array(&arr).expect("transfer failed") {
+                JsValueFacade::TypedArray { buffer, array_type } => {
+                    assert_eq!(array_type, TypedArrayType::Int16);
+                    assert_eq!(buffer.len(), 1024);
+                    assert!(std::ptr::eq(buffer.as_ptr(), ptr));
+                }
+                _ => panic!("not a TypedArray"),
+            }
+            set_property_q(realm, &global, "transferred", &arr).expect("could not set prop");
+            let len = realm
+                .eval(Script::new("tat3.js", "(transferred.length);"))
+                .expect("script failed");
+            assert_eq!(len.to_i32(), 0);
+
+            // a js owned buffer is copied and detached
+            let arr = realm
+                .eval(Script::new("tat4.js", "(globalThis.jsArr = new Uint8Array([5, 6]));"))
+                .expect("script failed");
+            match realm.transfer_typed_array(&arr).expect("transfer failed") {
+                JsValueFacade::TypedArray { buffer, .. } => assert_eq!(buffer, vec![5, 6]),
+                _ => panic!("not a TypedArray"),
+            }
+            let len = realm
+                .eval(Script::new("tat5.js", "(jsArr.length);"))
+                .expect("script failed");
+            assert_eq!(len.to_i32(), 0);
+        });
+    }
 }
@@ -1,16 +1,24 @@
+use crate::bytecodecache::compile_cached;
 use crate::facades::QuickjsRuntimeFacadeInner;
+use crate::quickjs_utils::compile::run_compiled_function;
+use crate::quickjs_utils::inspect::{inspect_q, InspectOptions};
 use crate::quickjs_utils::objects::construct_object;
 use crate::quickjs_utils::primitives::{from_bool, from_f64, from_i32, from_string_q};
 use crate::quickjs_utils::typedarrays::{
-    detach_array_buffer_buffer_q, get_array_buffer_buffer_copy_q, get_array_buffer_q,
-    new_uint8_array_copy_q, new_uint8_array_q,
+    copy_view_bytes_q, detach_array_buffer_buffer_q, get_array_buffer_buffer_copy_q,
+    get_array_buffer_q, get_typed_array_type_q, new_typed_array_q, new_uint8_array_copy_q,
+    new_uint8_array_q, transfer_view_bytes_q,
+};
+use crate::quickjs_utils::{
+    arrays, bigints, dates, errors, functions, get_global_q, json, maps, new_null_ref, objects,
+    serialization, sets,
 };
-use crate::quickjs_utils::{arrays, errors, functions, get_global_q, json, new_null_ref, objects};
 use crate::quickjsruntimeadapter::{make_cstring, QuickJsRuntimeAdapter};
 use crate::quickjsvalueadapter::{QuickJsValueAdapter, TAG_EXCEPTION};
+use crate::realmquota::{new_quota_error, RealmAccounting, RealmQuota, RealmUsage};
 use crate::reflection::eventtarget::dispatch_event;
 use crate::reflection::eventtarget::dispatch_static_event;
-use crate::reflection::{new_instance, new_instance3, Proxy};
+use crate::reflection::{call_constructors, new_instance3, next_id, Proxy};
 use hirofa_utils::auto_id_map::AutoIdMap;
 
 use crate::jsutils::jsproxies::{JsProxy, JsProxyInstanceId};
@@ -29,6 +37,7 @@ use std::future::Future;
 use std::os::raw::c_void;
 use std::rc::Rc;
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
 use crate::jsutils::promises::new_resolving_promise;
 use crate::jsutils::promises::new_resolving_promise_async;
@@ -40,10 +49,10 @@ type ProxyEventListenerMaps = HashMap<
         usize, /*proxy_instance_id*/
         HashMap<
             String, /*event_id*/
-            HashMap<
+            Vec<(
                 QuickJsValueAdapter, /*listener_func*/
-                QuickJsValueAdapter, /*options_obj*/
-            >,
+                QuickJsValueAdapter, /*listener_record*/
+            )>,
         >,
     >,
 >;
@@ -52,10 +61,10 @@ type ProxyStaticEventListenerMaps = HashMap<
     String, /*proxy_class_name*/
     HashMap<
         String, /*event_id*/
-        HashMap<
+        Vec<(
             QuickJsValueAdapter, /*listener_func*/
-            QuickJsValueAdapter, /*options_obj*/
-        >,
+            QuickJsValueAdapter, /*listener_record*/
+        )>,
     >,
 >;
 
@@ -66,6 +75,19 @@ pub struct QuickJsRealmAdapter {
     pub(crate) proxy_constructor_refs: RefCell<HashMap<String, QuickJsValueAdapter>>,
     pub(crate) proxy_event_listeners: RefCell<ProxyEventListenerMaps>,
     pub(crate) proxy_static_event_listeners: RefCell<ProxyStaticEventListenerMaps>,
+    pub(crate) proxy_event_bubble_targets: RefCell<
+        HashMap<
+            (
+                String, /*proxy_class_name*/
+                usize,  /*proxy_instance_id*/
+            ),
+            QuickJsValueAdapter,
+        >,
+    >,
+    pub(crate) event_intrinsics: RefCell<Option<QuickJsValueAdapter>>,
+    pub(crate) accounting: RealmAccounting,
+    #[cfg(feature = "console")]
+    pub(crate) console_state: RefCell<crate::features::console::ConsoleState>,
     pub id: String,
     pub context: *mut q::JSContext,
 }
@@ -155,6 +177,19 @@ impl QuickJsRealmAdapter {
         // drop outside of borrowmut so finalizers don;t get error when trying to get mut borrow on map
         all_listeners.clear();
 
+        let mut all_static_listeners = {
+            let proxy_static_event_listeners: &mut ProxyStaticEventListenerMaps =
+                &mut self.proxy_static_event_listeners.borrow_mut();
+            std::mem::take(proxy_static_event_listeners)
+        };
+        all_static_listeners.clear();
+
+        let mut all_bubble_targets =
+            std::mem::take(&mut *self.proxy_event_bubble_targets.borrow_mut());
+        all_bubble_targets.clear();
+
+        drop(self.event_intrinsics.take());
+
         // hmm these should still exist minus the constrcutor ref on free, so we need to remove the constructor refs, then call free, then call gc and then clear proxies
         // so here we should just clear the refs..
         let mut all_constructor_refs = {
@@ -186,7 +221,9 @@ impl QuickJsRealmAdapter {
             panic!("ContextCreationFailed");
         }
 
-        Self {
+        let quota = q_js_rt.destroyed_realm_quotas.borrow_mut().remove(&id);
+
+        let realm = Self {
             id,
             context,
             object_cache: RefCell::new(AutoIdMap::new_with_max_size(i32::MAX as usize)),
@@ -195,7 +232,17 @@ impl QuickJsRealmAdapter {
             proxy_constructor_refs: RefCell::new(Default::default()),
             proxy_event_listeners: RefCell::new(Default::default()),
             proxy_static_event_listeners: RefCell::new(Default::default()),
+            proxy_event_bubble_targets: RefCell::new(Default::default()),
+            event_intrinsics: RefCell::new(None),
+            accounting: RealmAccounting::default(),
+            #[cfg(feature = "console")]
+            console_state: RefCell::new(Default::default()),
+        };
+        if quota.is_some() {
+            // the realm was destroyed because it exceeded its quota, a new realm with the same id gets the same quota
+            realm.accounting.set_quota(quota);
         }
+        realm
     }
     /// get the id of a QuickJsContext from a JSContext
     /// # Safety
@@ -229,6 +276,16 @@ impl QuickJsRealmAdapter {
         unsafe { Self::eval_ctx(self.context, script, Some(this)) }
     }
 
+    /// evaluate a script with a deadline
+    /// if the script is still running when the deadline passes it is interrupted and a JsError with name "TimeoutError" is returned
+    pub fn eval_with_deadline(
+        &self,
+        script: Script,
+        deadline: Instant,
+    ) -> Result<QuickJsValueAdapter, JsError> {
+        QuickJsRuntimeAdapter::do_with(|rt| rt.with_deadline(deadline, || self.eval(script)))
+    }
+
     /// # Safety
     /// when passing a context ptr please be sure that the corresponding QuickJsContext is still active
     pub unsafe fn eval_ctx(
@@ -238,6 +295,12 @@ impl QuickJsRealmAdapter {
     ) -> Result<QuickJsValueAdapter, JsError> {
         log::debug!("q_js_rt.eval file {}", script.get_path());
 
+        if this_opt.is_none() && QuickJsRuntimeAdapter::do_with(|rt| rt.has_bytecode_cache()) {
+            // compile_cached pre-processes the script when it is not cached yet
+            let compiled = compile_cached(context, script, false)?;
+            return run_compiled_function(context, &compiled);
+        }
+
         script = QuickJsRuntimeAdapter::pre_process(script)?;
 
         let code_str = script.get_runnable_code();
@@ -299,27 +362,34 @@ impl QuickJsRealmAdapter {
     ) -> Result<QuickJsValueAdapter, JsError> {
         log::debug!("q_js_rt.eval_module file {}", script.get_path());
 
-        script = QuickJsRuntimeAdapter::pre_process(script)?;
+        let path = script.get_path().to_string();
 
-        let code_str = script.get_runnable_code();
+        let value_raw = if QuickJsRuntimeAdapter::do_with(|rt| rt.has_bytecode_cache()) {
+            // compile_cached pre-processes the module when it is not cached yet
+            let compiled = compile_cached(context, script, true)?;
+            q::JS_EvalFunction(context, compiled.clone_value_incr_rc())
+        } else {
+            script = QuickJsRuntimeAdapter::pre_process(script)?;
+            let code_str = script.get_runnable_code();
 
-        let filename_c = make_cstring(script.get_path())?;
-        let code_c = make_cstring(code_str)?;
+            let filename_c = make_cstring(path.as_str())?;
+            let code_c = make_cstring(code_str)?;
 
-        let value_raw = q::JS_Eval(
-            context,
-            code_c.as_ptr(),
-            code_str.len() as _,
-            filename_c.as_ptr(),
-            q::JS_EVAL_TYPE_MODULE as i32,
-        );
+            q::JS_Eval(
+                context,
+                code_c.as_ptr(),
+                code_str.len() as _,
+                filename_c.as_ptr(),
+                q::JS_EVAL_TYPE_MODULE as i32,
+            )
+        };
 
         let ret = QuickJsValueAdapter::new(
             context,
             value_raw,
             false,
             true,
-            format!("eval_module result of {}", script.get_path()).as_str(),
+            format!("eval_module result of {path}").as_str(),
         );
 
         log::trace!("evalled module yielded a {}", ret.borrow_value().tag);
@@ -467,6 +537,11 @@ impl QuickJsRealmAdapter {
         proxy.install(self, add_global_var)
     }
 
+    /// generate TypeScript declarations (.d.ts) for the Proxy classes installed in this realm and the modules of the native module loaders, see [generate_typings](crate::reflection::typings::generate_typings)
+    pub fn generate_typings(&self) -> Result<String, JsError> {
+        crate::reflection::typings::generate_typings(self)
+    }
+
     pub fn instantiate_proxy_with_id(
         &self,
         namespace: &[&str],
@@ -483,7 +558,7 @@ impl QuickJsRealmAdapter {
         let proxy_map = self.proxy_registry.borrow();
         let proxy = proxy_map.get(cn.as_str()).expect("class not found");
 
-        new_instance3(proxy, instance_id, self)
+        new_instance3(proxy, instance_id, self, None)
     }
 
     pub fn instantiate_proxy(
@@ -502,14 +577,15 @@ impl QuickJsRealmAdapter {
         let proxy_map = self.proxy_registry.borrow();
         let proxy = proxy_map.get(cn.as_str()).expect("class not found");
 
-        let instance_info = new_instance(cn.as_str(), self)?;
+        // call the constructors (of the class and its parent classes) myself
+        let instance_id = next_id(proxy);
+        QuickJsRuntimeAdapter::do_with(|rt| {
+            call_constructors(rt, self, &proxy_map, cn.as_str(), instance_id, arguments)
+        })?;
 
-        if let Some(constructor) = &proxy.constructor {
-            // call constructor myself
-            QuickJsRuntimeAdapter::do_with(|rt| constructor(rt, self, instance_info.0, arguments))?
-        }
+        let instance = new_instance3(proxy, instance_id, self, None)?;
 
-        Ok(instance_info)
+        Ok((instance_id, instance))
     }
 
     pub fn dispatch_proxy_event(
@@ -641,6 +717,23 @@ impl QuickJsRealmAdapter {
         functions::call_function_q_ref_args(self, function_obj, args, this_obj)
     }
 
+    /// invoke a function with a timeout
+    /// if the function is still running when the timeout expires it is interrupted and a JsError with name "TimeoutError" is returned
+    pub fn invoke_function_with_timeout(
+        &self,
+        this_obj: Option<&QuickJsValueAdapter>,
+        function_obj: &QuickJsValueAdapter,
+        args: &[&QuickJsValueAdapter],
+        timeout: Duration,
+    ) -> Result<QuickJsValueAdapter, JsError> {
+        let deadline = Instant::now() + timeout;
+        QuickJsRuntimeAdapter::do_with(|rt| {
+            rt.with_deadline(deadline, || {
+                self.invoke_function(this_obj, function_obj, args)
+            })
+        })
+    }
+
     pub fn create_function<
         F: Fn(
                 &Self,
@@ -887,6 +980,59 @@ impl QuickJsRealmAdapter {
         map.remove_opt(&id)
     }
 
+    pub(crate) fn pending_promise_count(&self) -> usize {
+        self.promise_cache.borrow().len()
+    }
+
+    /// set or clear the quota for this realm, see the [realmquota](../realmquota/index.html) module
+    pub fn set_quota(&self, quota: Option<RealmQuota>) {
+        self.accounting.set_quota(quota);
+    }
+
+    /// get the resources used by this realm
+    pub fn get_usage(&self) -> RealmUsage {
+        self.accounting.get_usage(self)
+    }
+
+    /// reset the accounted heap bytes and cpu time, this also accepts jobs again after the quota was exceeded
+    pub fn reset_usage(&self) {
+        self.accounting.reset_usage();
+    }
+
+    /// run a job which targets this realm, the job is accounted and the quota of the realm is enforced
+    pub(crate) fn run_job<R, J: FnOnce() -> R>(
+        &self,
+        q_js_rt: &QuickJsRuntimeAdapter,
+        job: J,
+    ) -> R {
+        self.accounting.run_job(q_js_rt, self, job)
+    }
+
+    /// run a job which produces a Result, fails if jobs for this realm are rejected and replaces the error of a
+    /// job which was interrupted because the quota was exceeded with a QuotaExceededError
+    pub(crate) fn with_quota<R, J: FnOnce() -> Result<R, JsError>>(
+        &self,
+        job: J,
+    ) -> Result<R, JsError> {
+        self.accounting.check_quota()?;
+        job().map_err(|err| {
+            let exceeded = QuickJsRuntimeAdapter::do_with(|q_js_rt| {
+                q_js_rt
+                    .quota_job
+                    .borrow()
+                    .as_ref()
+                    .and_then(|job| job.get_exceeded())
+            });
+            match exceeded {
+                Some(reason) => {
+                    log::debug!("job interrupted because quota was exceeded: {}", err);
+                    new_quota_error(reason.as_str())
+                }
+                None => err,
+            }
+        })
+    }
+
     pub fn dispose_cached_object(&self, id: i32) {
         let _ = self.consume_cached_obj(id);
     }
@@ -957,6 +1103,40 @@ impl QuickJsRealmAdapter {
         get_array_buffer_buffer_copy_q(self, &abuf)
     }
 
+    /// create a TypedArray, DataView or ArrayBuffer, the buffer is not copied
+    pub fn create_typed_array(
+        &self,
+        array_type: TypedArrayType,
+        buffer: Vec<u8>,
+    ) -> Result<QuickJsValueAdapter, JsError> {
+        new_typed_array_q(self, array_type, buffer)
+    }
+
+    /// convert a TypedArray, DataView or ArrayBuffer to a JsValueFacade by detaching its ArrayBuffer instead of copying it
+    /// (to_js_value_facade copies the buffer), after this the buffer can no longer be used in js
+    /// buffers which were created from rust are moved without copying, see [typedarrays::transfer_view_bytes_q](crate::quickjs_utils::typedarrays::transfer_view_bytes_q)
+    pub fn transfer_typed_array(
+        &self,
+        array: &QuickJsValueAdapter,
+    ) -> Result<JsValueFacade, JsError> {
+        let array_type = get_typed_array_type_q(array).ok_or_else(|| {
+            JsError::new_str("value is not a TypedArray, DataView or ArrayBuffer")
+        })?;
+        Ok(JsValueFacade::TypedArray {
+            buffer: transfer_view_bytes_q(self, array)?,
+            array_type,
+        })
+    }
+
+    /// format a value like node's util.inspect, see [inspect](../quickjs_utils/inspect/index.html)
+    pub fn inspect(
+        &self,
+        value: &QuickJsValueAdapter,
+        options: InspectOptions,
+    ) -> Result<String, JsError> {
+        inspect_q(self, value, &options)
+    }
+
     pub fn get_proxy_instance_info(
         &self,
         obj: &QuickJsValueAdapter,
@@ -980,6 +1160,15 @@ impl QuickJsRealmAdapter {
     where
         Self: Sized + 'static,
     {
+        self.to_js_value_facade_visiting(js_value, &mut vec![])
+    }
+
+    /// convert a value, visiting holds the Maps and Sets which are being converted so a cycle is detected
+    fn to_js_value_facade_visiting(
+        &self,
+        js_value: &QuickJsValueAdapter,
+        visiting: &mut Vec<QuickJsValueAdapter>,
+    ) -> Result<JsValueFacade, JsError> {
         let res: JsValueFacade = match js_value.get_js_type() {
             JsValueType::I32 => JsValueFacade::I32 {
                 val: js_value.to_i32(),
@@ -994,13 +1183,12 @@ impl QuickJsRealmAdapter {
                 val: js_value.to_bool(),
             },
             JsValueType::Object => {
-                if js_value.is_typed_array() {
-                    // todo TypedArray as JsValueType?
+                if let Some(array_type) = get_typed_array_type_q(js_value) {
                     // passing a typedarray out of the worker thread is sketchy because you either copy the buffer like we do here, or you detach the buffer effectively destroying the jsvalue
-                    // you should be better of optimizing this in native methods
+                    // use transfer_typed_array to detach instead of copying
                     JsValueFacade::TypedArray {
-                        buffer: self.copy_typed_array_buffer(js_value)?,
-                        array_type: TypedArrayType::Uint8,
+                        buffer: copy_view_bytes_q(self, js_value)?,
+                        array_type,
                     }
                 } else {
                     JsValueFacade::JsObject {
@@ -1013,16 +1201,57 @@ impl QuickJsRealmAdapter {
                     cached_object: CachedJsObjectRef::new(self, js_value.clone()),
                 },
             },
-            JsValueType::BigInt => {
-                todo!();
-            }
+            JsValueType::BigInt => JsValueFacade::BigInt {
+                val: bigints::to_string_q(self, js_value)?,
+            },
             JsValueType::Promise => JsValueFacade::JsPromise {
                 cached_promise: CachedJsPromiseRef {
                     cached_object: CachedJsObjectRef::new(self, js_value.clone()),
                 },
             },
-            JsValueType::Date => {
-                todo!();
+            JsValueType::Date => JsValueFacade::Date {
+                timestamp: dates::get_time_q(self, js_value)?,
+            },
+            JsValueType::Map | JsValueType::Set if visiting.contains(js_value) => {
+                // a Map or Set which contains itself is passed as a reference where it recurs
+                JsValueFacade::JsObject {
+                    cached_object: CachedJsObjectRef::new(self, js_value.clone()),
+                }
+            }
+            JsValueType::Map => {
+                let entries = maps::entries_q(self, js_value, |key, value| Ok((key, value)))?;
+                visiting.push(js_value.clone());
+                let res = entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        Ok((
+                            self.to_js_value_facade_visiting(&key, visiting)?,
+                            self.to_js_value_facade_visiting(&value, visiting)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, JsError>>();
+                visiting.pop();
+                JsValueFacade::Map { entries: res? }
+            }
+            JsValueType::Set => {
+                let values = sets::values_q(self, js_value, Ok)?;
+                visiting.push(js_value.clone());
+                let res = values
+                    .into_iter()
+                    .map(|value| self.to_js_value_facade_visiting(&value, visiting))
+                    .collect::<Result<Vec<_>, JsError>>();
+                visiting.pop();
+                JsValueFacade::Set { values: res? }
+            }
+            JsValueType::Symbol => {
+                let description = self.get_object_property(js_value, "description")?;
+                JsValueFacade::Symbol {
+                    description: if description.is_string() {
+                        Some(description.to_string()?)
+                    } else {
+                        None
+                    },
+                }
             }
             JsValueType::Null => JsValueFacade::Null,
             JsValueType::Undefined => JsValueFacade::Undefined,
@@ -1140,11 +1369,42 @@ impl QuickJsRealmAdapter {
                 namespace,
                 class_name,
             } => self.instantiate_proxy_with_id(namespace, class_name, instance_id),
-            JsValueFacade::TypedArray { buffer, array_type } => match array_type {
-                TypedArrayType::Uint8 => self.create_typed_array_uint8(buffer),
-            },
+            JsValueFacade::TypedArray { buffer, array_type } => {
+                self.create_typed_array(array_type, buffer)
+            }
             JsValueFacade::JsonStr { json } => self.json_parse(json.as_str()),
             JsValueFacade::SerdeValue { value } => self.serde_value_to_value_adapter(value),
+            JsValueFacade::BigInt { val } => bigints::new_bigint_str_q(self, val.as_str()),
+            JsValueFacade::Date { timestamp } => {
+                let date = dates::new_date_q(self)?;
+                dates::set_time_q(self, &date, timestamp)?;
+                Ok(date)
+            }
+            JsValueFacade::Map { entries } => {
+                let map = maps::new_map_q(self)?;
+                for (key, value) in entries {
+                    let key = self.from_js_value_facade(key)?;
+                    let value = self.from_js_value_facade(value)?;
+                    maps::set_q(self, &map, key, value)?;
+                }
+                Ok(map)
+            }
+            JsValueFacade::Set { values } => {
+                let set = sets::new_set_q(self)?;
+                for value in values {
+                    let value = self.from_js_value_facade(value)?;
+                    sets::add_q(self, &set, value)?;
+                }
+                Ok(set)
+            }
+            JsValueFacade::Symbol { description } => {
+                let args = match description {
+                    Some(description) => vec![self.create_string(description.as_str())?],
+                    None => vec![],
+                };
+                let global = get_global_q(self);
+                functions::invoke_member_function_q(self, &global, "Symbol", &args)
+            }
         }
     }
 
@@ -1157,7 +1417,8 @@ impl QuickJsRealmAdapter {
             JsValueType::F64 => Ok(Value::from(value_adapter.to_f64())),
             JsValueType::String => Ok(Value::from(value_adapter.to_string()?)),
             JsValueType::Boolean => Ok(Value::from(value_adapter.to_bool())),
-            JsValueType::Object => {
+            // Map and Set have no enumerable properties, this matches JSON.stringify
+            JsValueType::Object | JsValueType::Map | JsValueType::Set => {
                 let mut map: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
                 self.traverse_object_mut(value_adapter, |k, v| {
                     map.insert(k.to_string(), self.value_adapter_to_serde_value(v)?);
@@ -1180,11 +1441,45 @@ impl QuickJsRealmAdapter {
             JsValueType::Function => Ok(serde_json::Value::Null),
             JsValueType::BigInt => Ok(serde_json::Value::Null),
             JsValueType::Promise => Ok(serde_json::Value::Null),
-            JsValueType::Date => Ok(serde_json::Value::Null),
+            JsValueType::Date => {
+                // like JSON.stringify, an invalid date becomes null
+                if dates::get_time_q(self, value_adapter)?.is_nan() {
+                    return Ok(serde_json::Value::Null);
+                }
+                let iso =
+                    functions::invoke_member_function_q(self, value_adapter, "toISOString", &[])?;
+                Ok(Value::from(iso.to_string()?))
+            }
             JsValueType::Error => Ok(serde_json::Value::Null),
+            JsValueType::Symbol => Ok(serde_json::Value::Null),
         }
     }
 
+    /// serialize a rust value directly to a QuickJsValueAdapter without a JSON round trip
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+    /// let rt = QuickJsRuntimeBuilder::new().build();
+    /// rt.loop_realm_sync(None, |_rt, realm| {
+    ///     let arr = realm.to_value_adapter(&vec![1, 2, 3]).expect("could not serialize");
+    ///     assert!(arr.is_array());
+    /// });
+    /// ```
+    pub fn to_value_adapter<T>(&self, value: &T) -> Result<QuickJsValueAdapter, JsError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        serialization::to_value_adapter_q(self, value)
+    }
+
+    /// deserialize a QuickJsValueAdapter directly to a rust value without a JSON round trip
+    pub fn from_value_adapter<T>(&self, value: &QuickJsValueAdapter) -> Result<T, JsError>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        serialization::from_value_adapter_q(self, value)
+    }
+
     pub fn serde_value_to_value_adapter(
         &self,
         value: Value,
@@ -1267,10 +1562,12 @@ impl QuickJsRealmAdapter {
 pub mod tests {
     use crate::builder::QuickJsRuntimeBuilder;
     use crate::facades::tests::init_test_rt;
-    use crate::jsutils::Script;
+    use crate::jsutils::{JsValueType, Script};
     use crate::quickjs_utils;
     use crate::quickjs_utils::primitives::to_i32;
     use crate::quickjs_utils::{functions, get_global_q, objects};
+    use crate::values::JsValueFacade;
+    use futures::executor::block_on;
 
     #[test]
     fn test_eval() {
@@ -1386,4 +1683,175 @@ pub mod tests {
             q_js_rt.gc();
         });
     }
+
+    #[test]
+    fn test_value_facade_conversions() {
+        let rt = QuickJsRuntimeBuilder::new().build();
+
+        let value = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "vfc.js",
+                    "(new Map([['a', new Date(1000)], ['b', new Set([1, 'x'])]]));",
+                ),
+            )
+            .expect("script failed");
+        let serde_value = block_on(value.to_serde_value()).expect("to_serde_value failed");
+        assert_eq!(
+            serde_value.to_string(),
+            r#"[["a","1970-01-01T00:00:01.000Z"],["b",[1,"x"]]]"#
+        );
+        let json = block_on(value.to_json_string()).expect("to_json_string failed");
+        assert_eq!(
+            json,
+            "new Map([['a', new Date(1000)], ['b', new Set([1, 'x'])]])"
+        );
+
+        // Dates are ISO strings like in JSON.stringify and the serde Deserializer
+        for (timestamp, iso) in [
+            (-1.0, "1969-12-31T23:59:59.999Z"),
+            (951782400000.0, "2000-02-29T00:00:00.000Z"),
+            (-62198755200000.0, "-000001-01-01T00:00:00.000Z"),
+            (8.64e15, "+275760-09-13T00:00:00.000Z"),
+        ] {
+            let date = JsValueFacade::Date { timestamp };
+            let serde_value = block_on(date.to_serde_value()).expect("to_serde_value failed");
+            assert_eq!(serde_value.as_str(), Some(iso));
+            let from_js: String = rt
+                .loop_realm_sync(None, move |_rt, realm| {
+                    let date = realm
+                        .eval(Script::new(
+                            "vfc.js",
+                            format!("new Date({timestamp});").as_str(),
+                        ))
+                        .expect("script failed");
+                    realm.from_value_adapter(&date)
+                })
+                .expect("could not deserialize");
+            assert_eq!(from_js, iso);
+        }
+        let invalid = JsValueFacade::Date {
+            timestamp: f64::NAN,
+        };
+        assert!(block_on(invalid.to_serde_value())
+            .expect("to_serde_value failed")
+            .is_null());
+    }
+
+    #[test]
+    fn test_value_facade_types() {
+        let rt = QuickJsRuntimeBuilder::new().build();
+
+        let big = rt
+            .eval_sync(None, Script::new("vft.js", "(12345678901234567890123n);"))
+            .expect("script failed");
+        assert_eq!(big.get_value_type(), JsValueType::BigInt);
+        assert_eq!(big.get_bigint_str(), "12345678901234567890123");
+        assert_eq!(big.get_bigint_i128(), Some(12345678901234567890123));
+
+        let date = rt
+            .eval_sync(None, Script::new("vft.js", "(new Date(1000));"))
+            .expect("script failed");
+        assert_eq!(date.get_value_type(), JsValueType::Date);
+        assert_eq!(date.get_timestamp(), 1000.0);
+
+        let map = rt
+            .eval_sync(
+                None,
+                Script::new("vft.js", "(new Map([['a', 1], [2, 'b']]));"),
+            )
+            .expect("script failed");
+        assert_eq!(map.get_value_type(), JsValueType::Map);
+        let entries = map.get_map_entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0.get_str(), "a");
+        assert_eq!(entries[0].1.get_i32(), 1);
+        assert_eq!(entries[1].0.get_i32(), 2);
+        assert_eq!(entries[1].1.get_str(), "b");
+
+        let set = rt
+            .eval_sync(None, Script::new("vft.js", "(new Set([3, 4, 3]));"))
+            .expect("script failed");
+        assert_eq!(set.get_value_type(), JsValueType::Set);
+        assert_eq!(set.get_set_values().len(), 2);
+
+        // a Map or Set which contains itself is passed as a reference where it recurs
+        let map = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "vft.js",
+                    "(() => {let m = new Map(); m.set('self', m); return m;})();",
+                ),
+            )
+            .expect("script failed");
+        let entries = map.get_map_entries();
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(entries[0].1, JsValueFacade::JsObject { .. }));
+        let set = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "vft.js",
+                    "(() => {let s = new Set(); s.add(s); s.add(new Map([['s', s]])); return s;})();",
+                ),
+            )
+            .expect("script failed");
+        let values = set.get_set_values();
+        assert_eq!(values.len(), 2);
+        assert!(matches!(values[0], JsValueFacade::JsObject { .. }));
+        assert!(matches!(
+            values[1].get_map_entries()[0].1,
+            JsValueFacade::JsObject { .. }
+        ));
+
+        let sym = rt
+            .eval_sync(None, Script::new("vft.js", "(Symbol('foo'));"))
+            .expect("script failed");
+        assert_eq!(sym.get_value_type(), JsValueType::Symbol);
+        assert_eq!(sym.get_symbol_description(), Some("foo"));
+
+        // and back
+        let ok = rt.loop_realm_sync(None, |_rt, realm| {
+            let global = get_global_q(realm);
+            let values = vec![
+                ("vBig", JsValueFacade::new_bigint(i128::MIN)),
+                ("vDate", JsValueFacade::new_date(1000.0)),
+                (
+                    "vMap",
+                    JsValueFacade::Map {
+                        entries: vec![(JsValueFacade::new_str("a"), JsValueFacade::new_i32(1))],
+                    },
+                ),
+                (
+                    "vSet",
+                    JsValueFacade::Set {
+                        values: vec![JsValueFacade::new_i32(3), JsValueFacade::new_i32(4)],
+                    },
+                ),
+                (
+                    "vSym",
+                    JsValueFacade::Symbol {
+                        description: Some("bar".to_string()),
+                    },
+                ),
+            ];
+            for (name, value) in values {
+                let value = realm.from_js_value_facade(value).expect("conversion failed");
+                realm
+                    .set_object_property(&global, name, &value)
+                    .expect("could not set prop");
+            }
+            realm
+                .eval(Script::new(
+                    "vft_back.js",
+                    "(vBig === -(2n ** 127n) && vDate.getTime() === 1000 && vMap.get('a') === 1 \
+                    && vSet.has(4) && vSet.size === 2 && typeof vSym === 'symbol' && vSym.description === 'bar');",
+                ))
+                .expect("script failed")
+                .to_bool()
+        });
+        assert!(ok);
+    }
 }
@@ -1,25 +1,33 @@
 // store in thread_local
 
+use crate::bytecodecache::{compile_cached, BytecodeCache};
+use crate::cancellation::CancellationToken;
 use crate::facades::QuickjsRuntimeFacadeInner;
+#[cfg(feature = "console")]
+use crate::features::console::{ConsoleSink, LogConsoleSink};
+use crate::jsutils::importmap::ImportMap;
 use crate::jsutils::modules::{CompiledModuleLoader, NativeModuleLoader, ScriptModuleLoader};
 use crate::jsutils::{JsError, Script, ScriptPreProcessor};
+use crate::profiler::{capture_stack, Profile, Profiler};
 use crate::quickjs_utils::compile::from_bytecode;
 use crate::quickjs_utils::modules::{
-    add_module_export, compile_module, get_module_def, get_module_name, new_module,
-    set_module_export,
+    add_module_export, get_module_def, get_module_name, new_module, set_module_export,
 };
 use crate::quickjs_utils::runtime::new_class_id;
 use crate::quickjs_utils::{gc, interrupthandler, modules, promises};
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::realmquota::{QuotaJob, RealmQuota};
 use libquickjs_sys as q;
 use serde::Serialize;
-use std::cell::RefCell;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::fmt::{Debug, Formatter};
 use std::os::raw::c_int;
 use std::panic;
 use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
 
 /// this is the internal abstract loader which is used to actually load the modules
 pub trait ModuleLoader {
@@ -124,10 +132,9 @@ impl ModuleLoader for ScriptModuleLoaderAdapter {
         log::trace!("load_module");
         let code = self.inner.load_module(realm, absolute_path);
 
-        let mut script = Script::new(absolute_path, code.as_str());
-        script = QuickJsRuntimeAdapter::pre_process(script)?;
+        let script = Script::new(absolute_path, code.as_str());
         log::trace!("load_module / 2");
-        let compiled_module = unsafe { compile_module(realm.context, script)? };
+        let compiled_module = unsafe { compile_cached(realm.context, script, true)? };
         log::trace!("load_module / 3");
         Ok(get_module_def(&compiled_module))
     }
@@ -147,7 +154,7 @@ impl ModuleLoader for ScriptModuleLoaderAdapter {
 }
 
 pub struct NativeModuleLoaderAdapter {
-    inner: Box<dyn NativeModuleLoader>,
+    pub(crate) inner: Box<dyn NativeModuleLoader>,
 }
 
 impl NativeModuleLoaderAdapter {
@@ -258,12 +265,28 @@ pub struct QuickJsRuntimeAdapter {
     id: String,
     pub(crate) context_init_hooks: RefCell<ContextInitHooks>,
     script_module_loaders: Vec<ScriptModuleLoaderAdapter>,
-    native_module_loaders: Vec<NativeModuleLoaderAdapter>,
+    pub(crate) native_module_loaders: Vec<NativeModuleLoaderAdapter>,
     compiled_module_loaders: Vec<CompiledModuleLoaderAdapter>,
     // script preprocs just preproc the input code, typescript transpiler will be special option which is run as last preproc
     pub(crate) script_pre_processors: Vec<Box<dyn ScriptPreProcessor + Send>>,
     #[allow(clippy::type_complexity)]
     pub(crate) interrupt_handler: Option<Box<dyn Fn(&QuickJsRuntimeAdapter) -> bool>>,
+    // deadline for the job which is currently running, checked by the interrupt handler
+    interrupt_deadline: Cell<Option<Instant>>,
+    interrupt_deadline_exceeded: Cell<bool>,
+    interrupt_handler_installed: Cell<bool>,
+    // the job for a realm with a quota which is currently running, checked by the interrupt handler
+    pub(crate) quota_job: RefCell<Option<QuotaJob>>,
+    // the quotas of realms which were destroyed by QuotaPolicy::DestroyRealm, applied again when a realm with the same id is created
+    pub(crate) destroyed_realm_quotas: RefCell<HashMap<String, RealmQuota>>,
+    // token of the job which is currently running, checked by the interrupt handler
+    cancellation_token: RefCell<Option<CancellationToken>>,
+    pub(crate) bytecode_cache: Option<Box<dyn BytecodeCache + Send>>,
+    pub(crate) import_map: Option<ImportMap>,
+    #[cfg(feature = "console")]
+    pub(crate) console_sink: Box<dyn ConsoleSink + Send>,
+    profiler: RefCell<Option<Profiler>>,
+    profiler_sampling: Cell<bool>,
 }
 
 thread_local! {
@@ -547,6 +570,18 @@ impl QuickJsRuntimeAdapter {
             compiled_module_loaders: vec![],
             script_pre_processors: vec![],
             interrupt_handler: None,
+            interrupt_deadline: Cell::new(None),
+            interrupt_deadline_exceeded: Cell::new(false),
+            interrupt_handler_installed: Cell::new(false),
+            quota_job: RefCell::new(None),
+            destroyed_realm_quotas: RefCell::new(HashMap::new()),
+            cancellation_token: RefCell::new(None),
+            bytecode_cache: None,
+            import_map: None,
+            #[cfg(feature = "console")]
+            console_sink: Box::new(LogConsoleSink),
+            profiler: RefCell::new(None),
+            profiler_sampling: Cell::new(false),
         };
 
         modules::set_module_loader(&q_rt);
@@ -563,10 +598,143 @@ impl QuickJsRuntimeAdapter {
         interrupt_handler: I,
     ) -> &mut Self {
         self.interrupt_handler = Some(Box::new(interrupt_handler));
-        interrupthandler::init(self);
+        self.init_interrupt_handler();
         self
     }
 
+    pub(crate) fn init_interrupt_handler(&self) {
+        if !self.interrupt_handler_installed.get() {
+            interrupthandler::init(self);
+            self.interrupt_handler_installed.set(true);
+        }
+    }
+
+    /// run a consumer with a wall-clock deadline
+    /// when a script is still running after the deadline has passed it is interrupted and the
+    /// consumer's error is replaced by a JsError with name "TimeoutError"
+    /// the runtime remains usable after a timeout
+    /// nested deadlines are supported, the earliest deadline wins
+    pub fn with_deadline<C, R>(&self, deadline: Instant, consumer: C) -> Result<R, JsError>
+    where
+        C: FnOnce() -> Result<R, JsError>,
+    {
+        self.init_interrupt_handler();
+
+        let outer_deadline = self.interrupt_deadline.get();
+        let effective_deadline = match outer_deadline {
+            Some(outer) if outer < deadline => outer,
+            _ => deadline,
+        };
+        self.interrupt_deadline.set(Some(effective_deadline));
+
+        let res = consumer();
+
+        self.interrupt_deadline.set(outer_deadline);
+
+        match res {
+            Ok(r) => {
+                self.interrupt_deadline_exceeded.set(false);
+                Ok(r)
+            }
+            Err(err) => {
+                if self.interrupt_deadline_exceeded.replace(false) {
+                    log::debug!("job interrupted because deadline was exceeded: {}", err);
+                    Err(JsError::new(
+                        "TimeoutError".to_string(),
+                        "script execution exceeded its deadline".to_string(),
+                        err.get_stack().to_string(),
+                    ))
+                } else {
+                    Err(err)
+                }
+            }
+        }
+    }
+
+    /// check if the deadline of the current job has passed, used by the interrupt handler
+    pub(crate) fn is_deadline_exceeded(&self) -> bool {
+        match self.interrupt_deadline.get() {
+            Some(deadline) if Instant::now() >= deadline => {
+                self.interrupt_deadline_exceeded.set(true);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// run a consumer which can be cancelled with a CancellationToken
+    /// when the token is cancelled while a script is running the script is interrupted
+    pub fn with_cancellation<C, R>(&self, token: &CancellationToken, consumer: C) -> R
+    where
+        C: FnOnce() -> R,
+    {
+        self.init_interrupt_handler();
+        let outer_token = self.cancellation_token.replace(Some(token.clone()));
+        let res = consumer();
+        self.cancellation_token.replace(outer_token);
+        res
+    }
+
+    /// check if the token of the current job was cancelled, used by the interrupt handler
+    pub(crate) fn is_cancelled(&self) -> bool {
+        match &*self.cancellation_token.borrow() {
+            Some(token) => token.is_cancelled(),
+            None => false,
+        }
+    }
+
+    /// check if the job for a realm with a quota exceeded that quota, used by the interrupt handler
+    pub(crate) fn is_quota_exceeded(&self) -> bool {
+        match &*self.quota_job.borrow() {
+            Some(job) => job.is_exceeded(self),
+            None => false,
+        }
+    }
+
+    /// check if a BytecodeCache was set for this runtime
+    pub fn has_bytecode_cache(&self) -> bool {
+        self.bytecode_cache.is_some()
+    }
+
+    /// start the sampling profiler, the JS call stack is sampled at most once per interval while script is running
+    /// if the profiler was already running its samples are discarded
+    pub fn start_profiling(&self, interval: Duration) {
+        self.init_interrupt_handler();
+        *self.profiler.borrow_mut() = Some(Profiler::new(interval));
+    }
+
+    /// stop the sampling profiler and get the Profile, if the profiler was not started an empty Profile is returned
+    pub fn stop_profiling(&self) -> Profile {
+        let profiler = self.profiler.borrow_mut().take();
+        profiler
+            .unwrap_or_else(|| Profiler::new(Duration::ZERO))
+            .finish()
+    }
+
+    /// take a sample if the profiler is running, called by the interrupt handler
+    pub(crate) fn sample_profiler(&self) {
+        let due = match &*self.profiler.borrow() {
+            Some(profiler) => profiler.is_due(),
+            None => false,
+        };
+        // capturing the stack may run script (Error.prepareStackTrace in quickjs-ng), guard against sampling from within a sample
+        if !due || self.profiler_sampling.replace(true) {
+            return;
+        }
+        let stack_res = capture_stack(self.get_main_realm());
+        self.profiler_sampling.set(false);
+        match stack_res {
+            Ok(stack) => {
+                if let Some(profiler) = &mut *self.profiler.borrow_mut() {
+                    profiler.add_sample(stack);
+                }
+            }
+            Err(e) => {
+                log::error!("could not capture stack for profiler: {}", e);
+            }
+        }
+    }
+
     pub fn add_script_module_loader(&mut self, sml: ScriptModuleLoaderAdapter) {
         self.script_module_loaders.push(sml);
     }
@@ -584,6 +752,21 @@ impl QuickJsRuntimeAdapter {
         self.get_context("__main__")
     }
 
+    /// apply the import map of this runtime (if any) to a specifier, returns the specifier as is if it is not mapped
+    pub fn apply_import_map<'a>(
+        &self,
+        ref_path: &str,
+        specifier: &'a str,
+    ) -> Result<Cow<'a, str>, JsError> {
+        if let Some(import_map) = &self.import_map {
+            if let Some(mapped) = import_map.resolve(ref_path, specifier)? {
+                log::trace!("import map mapped {} to {}", specifier, mapped);
+                return Ok(Cow::Owned(mapped));
+            }
+        }
+        Ok(Cow::Borrowed(specifier))
+    }
+
     pub fn with_all_module_loaders<C, R>(&self, consumer: C) -> Option<R>
     where
         C: Fn(&dyn ModuleLoader) -> Option<R>,
@@ -729,9 +912,25 @@ impl QuickJsRuntimeAdapter {
         self.id.as_str()
     }
 
+    /// load the raw source of a module using the runtimes script_module loaders, this is used for json, text and bytes imports
+    pub(crate) fn load_module_source(
+        &self,
+        realm: &QuickJsRealmAdapter,
+        absolute_path: &str,
+    ) -> Option<Vec<u8>> {
+        for loader in &self.script_module_loaders {
+            if loader.has_module(realm, absolute_path) {
+                return Some(loader.inner.load_module_bytes(realm, absolute_path));
+            }
+        }
+        None
+    }
+
     /// this method tries to load a module script using the runtimes script_module loaders
     pub fn load_module_script_opt(&self, ref_path: &str, path: &str) -> Option<Script> {
         let realm = self.get_main_realm();
+        let path = self.apply_import_map(ref_path, path).ok()?;
+        let path = path.as_ref();
         for loader in &self.script_module_loaders {
             let i = &loader.inner;
             if let Some(normalized) = i.normalize_path(realm, ref_path, path) {
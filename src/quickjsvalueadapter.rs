@@ -2,7 +2,7 @@
 
 use crate::jsutils::{JsError, JsValueType};
 use crate::quickjs_utils::typedarrays::is_typed_array;
-use crate::quickjs_utils::{arrays, errors, functions, primitives, promises};
+use crate::quickjs_utils::{arrays, dates, errors, functions, maps, primitives, promises, sets};
 use crate::reflection::is_proxy_instance;
 use libquickjs_sys as q;
 use std::hash::{Hash, Hasher};
@@ -267,6 +267,7 @@ pub(crate) const TAG_STRING_ROPE: i64 = libquickjs_sys::JS_TAG_STRING_ROPE as i6
 
 pub(crate) const TAG_MODULE: i64 = libquickjs_sys::JS_TAG_MODULE as i64;
 pub(crate) const TAG_FUNCTION_BYTECODE: i64 = libquickjs_sys::JS_TAG_FUNCTION_BYTECODE as i64;
+pub(crate) const TAG_SYMBOL: i64 = libquickjs_sys::JS_TAG_SYMBOL as i64;
 pub(crate) const TAG_OBJECT: i64 = libquickjs_sys::JS_TAG_OBJECT as i64;
 pub(crate) const TAG_INT: i64 = libquickjs_sys::JS_TAG_INT as i64;
 pub(crate) const TAG_BOOL: i64 = libquickjs_sys::JS_TAG_BOOL as i64;
@@ -310,11 +311,18 @@ impl QuickJsValueAdapter {
                     JsValueType::Array
                 } else if unsafe { promises::is_promise(self.context, self) } {
                     JsValueType::Promise
+                } else if unsafe { dates::is_date(self.context, self) } {
+                    JsValueType::Date
+                } else if unsafe { maps::is_map(self.context, self) } {
+                    JsValueType::Map
+                } else if unsafe { sets::is_set(self) } {
+                    JsValueType::Set
                 } else {
                     JsValueType::Object
                 }
             }
             TAG_BIG_INT | TAG_SHORT_BIG_INT => JsValueType::BigInt,
+            TAG_SYMBOL => JsValueType::Symbol,
             TAG_MODULE => todo!(),
             _ => JsValueType::Undefined,
         }
@@ -332,6 +340,7 @@ impl QuickJsValueAdapter {
         match self.get_tag() {
             TAG_BIG_INT => "bigint",
             TAG_STRING => "string",
+            TAG_SYMBOL => "symbol",
             #[cfg(feature = "bellard")]
             TAG_STRING_ROPE => "string",
             TAG_MODULE => "module",
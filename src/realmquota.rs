@@ -0,0 +1,510 @@
+//! per realm resource accounting and quotas
+//!
+//! when a [RealmQuota] is set for a realm the runtime keeps track of the resources used by that realm:
+//! * the (approximate) heap bytes attributed to the realm, this is the growth of the runtime heap during jobs which target the realm
+//! * the time spent running jobs which target the realm (e.g. eval, invoke_function, loop_realm and timers)
+//! * the number of pending timers (setTimeout / setInterval) and pending promises created by rust (e.g. create_resolving_promise)
+//!
+//! because all realms share one runtime heap the heap accounting is an approximation, memory allocated by a realm but
+//! freed during a job of another realm is not credited back, promise reactions which run after a job are accounted to that job
+//!
+//! creating a timer or promise beyond the quota always fails with a QuotaExceededError (which is thrown in script), what
+//! else happens when a quota is exceeded depends on the [QuotaPolicy]
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! use quickjs_runtime::realmquota::{QuotaPolicy, RealmQuota};
+//! use std::time::Duration;
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! rt.create_realm("tenant1").expect("could not create realm");
+//! rt.set_realm_quota(
+//!     "tenant1",
+//!     Some(RealmQuota::new(QuotaPolicy::Throw).max_cpu_time(Duration::from_millis(50))),
+//! )
+//! .expect("could not set quota");
+//! let res = rt.eval_sync(Some("tenant1"), Script::new("endless.js", "while(true){}"));
+//! assert_eq!(res.expect_err("script should have been interrupted").get_name(), "QuotaExceededError");
+//! let usage = rt.get_realm_usage("tenant1").expect("no such realm");
+//! assert!(usage.cpu_time >= Duration::from_millis(50));
+//! ```
+
+use crate::jsutils::JsError;
+use crate::quickjs_utils::get_memory_usage;
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
+use hirofa_utils::eventloop::EventLoop;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+// computing the heap size walks all objects so it is only done this often while a job is running
+const HEAP_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// what to do when a realm exceeds its quota
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaPolicy {
+    /// the job which exceeds the quota runs to completion, after that all new jobs for the realm are rejected with a
+    /// QuotaExceededError (and timers of the realm no longer fire) until the usage is reset or a new quota is set
+    RejectJobs,
+    /// the job which exceeds the cpu time or heap quota is interrupted and fails with a QuotaExceededError
+    ///
+    /// usage is cumulative so later jobs fail as well while the realm is still over its quota
+    Throw,
+    /// like Throw but the realm is also destroyed after the job which exceeded the quota
+    ///
+    /// a later job for the same realm name will create a fresh realm, the quota of the destroyed realm is set for that
+    /// realm (with a fresh usage)
+    DestroyRealm,
+}
+
+/// the limits for a realm, all limits are optional, a quota without limits only enables the accounting
+#[derive(Clone, Debug)]
+pub struct RealmQuota {
+    policy: QuotaPolicy,
+    max_heap_bytes: Option<usize>,
+    max_cpu_time: Option<Duration>,
+    max_pending_timers: Option<usize>,
+    max_pending_promises: Option<usize>,
+}
+
+impl RealmQuota {
+    pub fn new(policy: QuotaPolicy) -> Self {
+        Self {
+            policy,
+            max_heap_bytes: None,
+            max_cpu_time: None,
+            max_pending_timers: None,
+            max_pending_promises: None,
+        }
+    }
+    /// limit the heap bytes attributed to the realm
+    pub fn max_heap_bytes(mut self, bytes: usize) -> Self {
+        self.max_heap_bytes = Some(bytes);
+        self
+    }
+    /// limit the total time spent in jobs for the realm
+    pub fn max_cpu_time(mut self, cpu_time: Duration) -> Self {
+        self.max_cpu_time = Some(cpu_time);
+        self
+    }
+    /// limit the number of pending timeouts and intervals
+    pub fn max_pending_timers(mut self, timers: usize) -> Self {
+        self.max_pending_timers = Some(timers);
+        self
+    }
+    /// limit the number of pending promises which are created by rust (and resolved by a producer)
+    pub fn max_pending_promises(mut self, promises: usize) -> Self {
+        self.max_pending_promises = Some(promises);
+        self
+    }
+    pub fn get_policy(&self) -> QuotaPolicy {
+        self.policy
+    }
+    fn interrupts(&self) -> bool {
+        self.policy != QuotaPolicy::RejectJobs
+            && (self.max_cpu_time.is_some() || self.max_heap_bytes.is_some())
+    }
+}
+
+/// the resources used by a realm, heap_bytes and cpu_time are only tracked while a quota is set
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RealmUsage {
+    pub heap_bytes: usize,
+    pub cpu_time: Duration,
+    pub jobs: u64,
+    pub pending_timers: usize,
+    pub pending_promises: usize,
+    /// the reason why jobs for the realm are rejected (see QuotaPolicy::RejectJobs)
+    pub exhausted: Option<String>,
+}
+
+/// the job for a realm with a quota which is currently running, checked by the interrupt handler
+pub(crate) struct QuotaJob {
+    cpu_deadline: Option<Instant>,
+    heap_limit: Option<i64>,
+    next_heap_check: Cell<Instant>,
+    exceeded: RefCell<Option<String>>,
+}
+
+impl QuotaJob {
+    pub(crate) fn get_exceeded(&self) -> Option<String> {
+        self.exceeded.borrow().clone()
+    }
+
+    pub(crate) fn is_exceeded(&self, q_js_rt: &QuickJsRuntimeAdapter) -> bool {
+        if self.exceeded.borrow().is_some() {
+            return true;
+        }
+        let now = Instant::now();
+        let reason = if matches!(self.cpu_deadline, Some(deadline) if now >= deadline) {
+            Some("cpu time quota exceeded".to_string())
+        } else {
+            match self.heap_limit {
+                Some(limit) if now >= self.next_heap_check.get() => {
+                    self.next_heap_check.set(now + HEAP_CHECK_INTERVAL);
+                    if heap_size(q_js_rt) > limit {
+                        Some("heap quota exceeded".to_string())
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            }
+        };
+        let exceeded = reason.is_some();
+        if exceeded {
+            self.exceeded.replace(reason);
+        }
+        exceeded
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct RealmAccounting {
+    quota: RefCell<Option<RealmQuota>>,
+    heap_bytes: Cell<i64>,
+    cpu_time: Cell<Duration>,
+    jobs: Cell<u64>,
+    pending_timers: RefCell<HashSet<i32>>,
+    exhausted: RefCell<Option<String>>,
+}
+
+pub(crate) fn new_quota_error(reason: &str) -> JsError {
+    JsError::new(
+        "QuotaExceededError".to_string(),
+        reason.to_string(),
+        "".to_string(),
+    )
+}
+
+fn heap_size(q_js_rt: &QuickJsRuntimeAdapter) -> i64 {
+    unsafe { get_memory_usage(q_js_rt.runtime) }.malloc_size
+}
+
+impl RealmAccounting {
+    pub(crate) fn set_quota(&self, quota: Option<RealmQuota>) {
+        self.exhausted.replace(None);
+        self.quota.replace(quota);
+    }
+
+    pub(crate) fn reset_usage(&self) {
+        self.heap_bytes.set(0);
+        self.cpu_time.set(Duration::ZERO);
+        self.jobs.set(0);
+        self.exhausted.replace(None);
+    }
+
+    pub(crate) fn get_usage(&self, realm: &QuickJsRealmAdapter) -> RealmUsage {
+        RealmUsage {
+            heap_bytes: self.heap_bytes.get().max(0) as usize,
+            cpu_time: self.cpu_time.get(),
+            jobs: self.jobs.get(),
+            pending_timers: self.pending_timers.borrow().len(),
+            pending_promises: realm.pending_promise_count(),
+            exhausted: self.exhausted.borrow().clone(),
+        }
+    }
+
+    /// fails if new jobs for the realm should be rejected
+    pub(crate) fn check_quota(&self) -> Result<(), JsError> {
+        let exhausted = self.exhausted.borrow().clone();
+        match exhausted {
+            Some(reason) => Err(new_quota_error(reason.as_str())),
+            None => Ok(()),
+        }
+    }
+
+    /// register a new timer, fails if that would exceed the quota
+    pub(crate) fn add_timer(&self, realm: &QuickJsRealmAdapter) -> Result<(), JsError> {
+        let pending = self.pending_timers.borrow().len();
+        let max = self
+            .quota
+            .borrow()
+            .as_ref()
+            .and_then(|q| q.max_pending_timers);
+        match max {
+            Some(max) if pending >= max => {
+                let reason = format!("pending timers quota of {max} exceeded");
+                self.quota_exceeded(realm, reason.as_str());
+                Err(new_quota_error(reason.as_str()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn timer_added(&self, id: i32) {
+        self.pending_timers.borrow_mut().insert(id);
+    }
+
+    /// remove a timer which fired or was cleared, returns false if the timer did not belong to this realm
+    pub(crate) fn timer_removed(&self, id: i32) -> bool {
+        self.pending_timers.borrow_mut().remove(&id)
+    }
+
+    /// check if a new pending promise may be created
+    pub(crate) fn add_promise(&self, realm: &QuickJsRealmAdapter) -> Result<(), JsError> {
+        let max = self
+            .quota
+            .borrow()
+            .as_ref()
+            .and_then(|q| q.max_pending_promises);
+        match max {
+            Some(max) if realm.pending_promise_count() >= max => {
+                let reason = format!("pending promises quota of {max} exceeded");
+                self.quota_exceeded(realm, reason.as_str());
+                Err(new_quota_error(reason.as_str()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// run a job for the realm, when a quota is set the used time and heap are accounted and the quota is enforced
+    pub(crate) fn run_job<R, J: FnOnce() -> R>(
+        &self,
+        q_js_rt: &QuickJsRuntimeAdapter,
+        realm: &QuickJsRealmAdapter,
+        job: J,
+    ) -> R {
+        // the quota is copied so the job may set a new quota for the realm
+        let quota = self.quota.borrow().clone();
+        let quota = match quota {
+            Some(quota) => quota,
+            None => return job(),
+        };
+
+        let heap_before = heap_size(q_js_rt);
+        let start = Instant::now();
+
+        let quota_job = if quota.interrupts() {
+            q_js_rt.init_interrupt_handler();
+            Some(QuotaJob {
+                cpu_deadline: quota
+                    .max_cpu_time
+                    .map(|max| start + max.saturating_sub(self.cpu_time.get())),
+                heap_limit: quota
+                    .max_heap_bytes
+                    .map(|max| heap_before + max as i64 - self.heap_bytes.get()),
+                next_heap_check: Cell::new(start),
+                exceeded: RefCell::new(None),
+            })
+        } else {
+            None
+        };
+        let outer_job = q_js_rt.quota_job.replace(quota_job);
+
+        let res = job();
+
+        let quota_job = q_js_rt.quota_job.replace(outer_job);
+
+        self.cpu_time.set(self.cpu_time.get() + start.elapsed());
+        self.heap_bytes
+            .set((self.heap_bytes.get() + heap_size(q_js_rt) - heap_before).max(0));
+        self.jobs.set(self.jobs.get() + 1);
+
+        let reason = quota_job
+            .and_then(|job| job.exceeded.take())
+            .or_else(|| self.exceeded_reason(&quota));
+        if let Some(reason) = reason {
+            self.quota_exceeded(realm, reason.as_str());
+        }
+
+        res
+    }
+
+    fn exceeded_reason(&self, quota: &RealmQuota) -> Option<String> {
+        if matches!(quota.max_cpu_time, Some(max) if self.cpu_time.get() >= max) {
+            Some("cpu time quota exceeded".to_string())
+        } else if matches!(quota.max_heap_bytes, Some(max) if self.heap_bytes.get() > max as i64) {
+            Some("heap quota exceeded".to_string())
+        } else {
+            None
+        }
+    }
+
+    fn quota_exceeded(&self, realm: &QuickJsRealmAdapter, reason: &str) {
+        let policy = self.quota.borrow().as_ref().map(|quota| quota.policy);
+        let policy = match policy {
+            Some(policy) => policy,
+            None => return,
+        };
+        log::warn!("realm {} exceeded its quota: {}", realm.id, reason);
+        match policy {
+            QuotaPolicy::RejectJobs => {
+                self.exhausted.replace(Some(reason.to_string()));
+            }
+            QuotaPolicy::Throw => {}
+            QuotaPolicy::DestroyRealm => {
+                if realm.id == "__main__" {
+                    log::error!("the main realm can not be destroyed, rejecting jobs instead");
+                    self.exhausted.replace(Some(reason.to_string()));
+                } else {
+                    // reject jobs until the realm is actually destroyed
+                    self.exhausted.replace(Some(reason.to_string()));
+                    let id = realm.id.clone();
+                    EventLoop::add_local_void(move || {
+                        let exists = QuickJsRuntimeAdapter::do_with(|q_js_rt| {
+                            match q_js_rt.opt_context(id.as_str()) {
+                                Some(realm) => {
+                                    // keep the quota for a realm which is created with the same id
+                                    let quota = realm.accounting.quota.borrow().clone();
+                                    if let Some(quota) = quota {
+                                        q_js_rt
+                                            .destroyed_realm_quotas
+                                            .borrow_mut()
+                                            .insert(id.clone(), quota);
+                                    }
+                                    true
+                                }
+                                None => false,
+                            }
+                        });
+                        if exists {
+                            if let Err(e) = QuickJsRuntimeAdapter::remove_context(id.as_str()) {
+                                log::error!("could not destroy realm {}: {}", id, e);
+                            }
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::jsutils::Script;
+    use crate::realmquota::{QuotaPolicy, RealmQuota};
+    use std::time::Duration;
+
+    #[test]
+    fn test_realm_quota() {
+        let rt = init_test_rt();
+
+        // accounting only
+        rt.create_realm("quota_acc")
+            .expect("could not create realm");
+        rt.set_realm_quota("quota_acc", Some(RealmQuota::new(QuotaPolicy::Throw)))
+            .expect("could not set quota");
+        rt.eval_sync(
+            Some("quota_acc"),
+            Script::new(
+                "quota_acc.js",
+                "globalThis.data = []; for (let x = 0; x < 10000; x++) {data.push({x});} setTimeout(() => {}, 60000);",
+            ),
+        )
+        .expect("script failed");
+        let usage = rt.get_realm_usage("quota_acc").expect("no such realm");
+        assert_eq!(usage.jobs, 1);
+        assert_eq!(usage.pending_timers, 1);
+        assert!(usage.heap_bytes > 10000);
+
+        // timers
+        rt.create_realm("quota_timers")
+            .expect("could not create realm");
+        rt.set_realm_quota(
+            "quota_timers",
+            Some(RealmQuota::new(QuotaPolicy::Throw).max_pending_timers(2)),
+        )
+        .expect("could not set quota");
+        let res = rt
+            .eval_sync(
+                Some("quota_timers"),
+                Script::new(
+                    "quota_timers.js",
+                    "let ids = [setTimeout(() => {}, 60000), setTimeout(() => {}, 60000)]; try {setTimeout(() => {}, 60000); 'no error';} catch(ex) {clearTimeout(ids[0]); ex.name;}",
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_str(), "QuotaExceededError");
+        let usage = rt.get_realm_usage("quota_timers").expect("no such realm");
+        assert_eq!(usage.pending_timers, 1);
+
+        // cpu time with RejectJobs
+        rt.create_realm("quota_reject")
+            .expect("could not create realm");
+        rt.set_realm_quota(
+            "quota_reject",
+            Some(RealmQuota::new(QuotaPolicy::RejectJobs).max_cpu_time(Duration::from_millis(20))),
+        )
+        .expect("could not set quota");
+        rt.eval_sync(
+            Some("quota_reject"),
+            Script::new(
+                "quota_reject.js",
+                "let start = Date.now(); while (Date.now() - start < 50) {} 'done';",
+            ),
+        )
+        .expect("job which exceeds the quota should run to completion");
+        let res = rt.eval_sync(Some("quota_reject"), Script::new("quota_reject2.js", "1;"));
+        assert_eq!(
+            res.expect_err("job should have been rejected").get_name(),
+            "QuotaExceededError"
+        );
+        rt.reset_realm_usage("quota_reject")
+            .expect("could not reset usage");
+        rt.eval_sync(Some("quota_reject"), Script::new("quota_reject3.js", "1;"))
+            .expect("job should run after reset");
+
+        // destroy
+        rt.create_realm("quota_destroy")
+            .expect("could not create realm");
+        rt.set_realm_quota(
+            "quota_destroy",
+            Some(
+                RealmQuota::new(QuotaPolicy::DestroyRealm).max_cpu_time(Duration::from_millis(20)),
+            ),
+        )
+        .expect("could not set quota");
+        let res = rt.eval_sync(
+            Some("quota_destroy"),
+            Script::new("quota_destroy.js", "while(true) {}"),
+        );
+        assert_eq!(
+            res.expect_err("job should have been interrupted")
+                .get_name(),
+            "QuotaExceededError"
+        );
+        assert!(!rt.has_realm("quota_destroy").expect("has_realm failed"));
+
+        // the quota is set again for a realm with the same name
+        let res = rt.eval_sync(
+            Some("quota_destroy"),
+            Script::new("quota_destroy2.js", "while(true) {}"),
+        );
+        assert_eq!(
+            res.expect_err("job in the new realm should have been interrupted")
+                .get_name(),
+            "QuotaExceededError"
+        );
+        assert!(!rt.has_realm("quota_destroy").expect("has_realm failed"));
+    }
+
+    #[test]
+    fn test_set_quota_in_job() {
+        let rt = init_test_rt();
+        rt.create_realm("quota_in_job")
+            .expect("could not create realm");
+        // the quota of a realm may be set or cleared by a job for that realm
+        rt.loop_realm_sync(Some("quota_in_job"), |_rt, realm| {
+            realm.set_quota(Some(RealmQuota::new(QuotaPolicy::Throw)));
+        });
+        rt.loop_realm_sync(Some("quota_in_job"), |_rt, realm| {
+            realm.set_quota(None);
+        });
+        rt.loop_realm_sync(Some("quota_in_job"), |_rt, realm| {
+            realm.set_quota(Some(
+                RealmQuota::new(QuotaPolicy::RejectJobs).max_cpu_time(Duration::from_secs(60)),
+            ));
+        });
+        rt.eval_sync(Some("quota_in_job"), Script::new("quota_in_job.js", "1;"))
+            .expect("script failed");
+        let usage = rt.get_realm_usage("quota_in_job").expect("no such realm");
+        // the job which cleared the quota and the script were accounted
+        assert_eq!(usage.jobs, 2);
+    }
+}
@@ -0,0 +1,408 @@
+//! realm templates, capture a fully initialised realm once and instantiate new realms from it cheaply
+//!
+//! a [RealmTemplateBuilder] lists the scripts and modules which make up a realm (e.g. a framework and its configuration),
+//! when the template is built those are compiled to bytecode once and run in a scratch realm, after that the listed
+//! globals are serialized, new realms created from the template only run the bytecode and restore the serialized globals
+//! instead of parsing the code again
+//!
+//! init scripts (see [RealmTemplateBuilder::init_script]) only run while building the template, their results are
+//! carried over to new realms via the captured globals, captured globals should contain data only (objects, arrays,
+//! primitives, typed arrays etc.), functions can not be captured and should be defined by a script or module instead
+//!
+//! context init hooks (e.g. from [set_function](../facades/struct.QuickJsRuntimeFacade.html#method.set_function) or
+//! the features) are run for every realm created from a template before the template is applied
+//!
+//! a RealmTemplate is Send and cheap to clone so it may be used in several runtimes (e.g. in a
+//! [QuickJsRuntimePool](../runtimepool/struct.QuickJsRuntimePool.html)), it is bound to the engine it was created with though
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! use quickjs_runtime::realmtemplate::RealmTemplateBuilder;
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! let template = rt
+//!     .create_realm_template(
+//!         RealmTemplateBuilder::new()
+//!             .script(Script::new("framework.js", "function greet(name) { return 'hello ' + name; }"))
+//!             .init_script(Script::new("config.js", "globalThis.config = {name: 'tenant'};"))
+//!             .capture_global("config"),
+//!     )
+//!     .expect("could not create template");
+//! rt.create_realm_from_template("tenant1", &template)
+//!     .expect("could not create realm");
+//! let res = rt
+//!     .eval_sync(Some("tenant1"), Script::new("t.js", "greet(config.name);"))
+//!     .expect("script failed");
+//! assert_eq!(res.get_str(), "hello tenant");
+//! ```
+
+use crate::jsutils::{JsError, Script};
+use crate::quickjs_utils::compile::{compile, from_bytecode, run_compiled_function, to_bytecode};
+use crate::quickjs_utils::modules::{compile_module, resolve_module};
+use crate::quickjs_utils::promises::PromiseState;
+use crate::quickjs_utils::{errors, get_global_q, objects, promises};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use libquickjs_sys as q;
+use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+static SCRATCH_REALM_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+enum TemplateEntry {
+    Script(Script),
+    Module(Script),
+    InitScript(Script),
+}
+
+/// builder for a [RealmTemplate], see the module docs for an example
+#[derive(Default)]
+pub struct RealmTemplateBuilder {
+    entries: Vec<TemplateEntry>,
+    globals: Vec<String>,
+}
+
+impl RealmTemplateBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// add a script which is compiled once and run (as bytecode) in every realm created from the template
+    pub fn script(mut self, script: Script) -> Self {
+        self.entries.push(TemplateEntry::Script(script));
+        self
+    }
+    /// add a module which is compiled once and run (as bytecode) in every realm created from the template
+    pub fn module(mut self, script: Script) -> Self {
+        self.entries.push(TemplateEntry::Module(script));
+        self
+    }
+    /// add a script which is only run when the template is built, use [capture_global](#method.capture_global) to carry
+    /// the data it produces over to new realms
+    pub fn init_script(mut self, script: Script) -> Self {
+        self.entries.push(TemplateEntry::InitScript(script));
+        self
+    }
+    /// serialize a global of the initialised realm and restore it in every realm created from the template
+    ///
+    /// all captured globals are serialized as one graph so references between them (and cycles) are preserved
+    pub fn capture_global(mut self, name: &str) -> Self {
+        self.globals.push(name.to_string());
+        self
+    }
+
+    /// build the template, this needs to run in the event loop thread
+    pub(crate) fn build_template(self) -> Result<RealmTemplate, JsError> {
+        let scratch_id = format!(
+            "__realm_template_{}__",
+            SCRATCH_REALM_COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        QuickJsRuntimeAdapter::create_context(scratch_id.as_str())?;
+        let res = QuickJsRuntimeAdapter::do_with(|q_js_rt| {
+            let realm = q_js_rt.get_context(scratch_id.as_str());
+            self.build_in_realm(realm)
+        });
+        QuickJsRuntimeAdapter::remove_context(scratch_id.as_str())
+            .map_err(|e| JsError::new_string(format!("{e}")))?;
+        res
+    }
+
+    fn build_in_realm(self, realm: &QuickJsRealmAdapter) -> Result<RealmTemplate, JsError> {
+        let mut steps = vec![];
+        for entry in self.entries {
+            match entry {
+                TemplateEntry::Script(script) => {
+                    let script = QuickJsRuntimeAdapter::pre_process(script)?;
+                    let compiled = unsafe { compile(realm.context, script)? };
+                    steps.push(TemplateStep {
+                        bytecode: unsafe { to_bytecode(realm.context, &compiled) },
+                    });
+                    run_step(realm, &compiled)?;
+                }
+                TemplateEntry::Module(script) => {
+                    let script = QuickJsRuntimeAdapter::pre_process(script)?;
+                    let compiled = unsafe { compile_module(realm.context, script)? };
+                    steps.push(TemplateStep {
+                        bytecode: unsafe { to_bytecode(realm.context, &compiled) },
+                    });
+                    run_step(realm, &compiled)?;
+                }
+                TemplateEntry::InitScript(script) => {
+                    realm.eval(script)?;
+                }
+            }
+        }
+
+        let globals = if self.globals.is_empty() {
+            None
+        } else {
+            Some(write_globals(realm, &self.globals)?)
+        };
+
+        Ok(RealmTemplate {
+            inner: Arc::new(RealmTemplateInner {
+                steps,
+                global_names: self.globals,
+                globals,
+            }),
+        })
+    }
+}
+
+struct TemplateStep {
+    /// the bytecode of a script or a module
+    bytecode: Vec<u8>,
+}
+
+struct RealmTemplateInner {
+    steps: Vec<TemplateStep>,
+    global_names: Vec<String>,
+    globals: Option<Vec<u8>>,
+}
+
+/// a captured realm, created by [create_realm_template](../facades/struct.QuickJsRuntimeFacade.html#method.create_realm_template)
+#[derive(Clone)]
+pub struct RealmTemplate {
+    inner: Arc<RealmTemplateInner>,
+}
+
+impl RealmTemplate {
+    /// run the bytecode of the template and restore the captured globals in a realm
+    pub fn apply(&self, realm: &QuickJsRealmAdapter) -> Result<(), JsError> {
+        for step in &self.inner.steps {
+            let compiled = unsafe { from_bytecode(realm.context, &step.bytecode)? };
+            run_step(realm, &compiled)?;
+        }
+        if let Some(globals) = &self.inner.globals {
+            let globals_obj = read_globals(realm, globals)?;
+            let global = get_global_q(realm);
+            for name in &self.inner.global_names {
+                let value = objects::get_property_q(realm, &globals_obj, name)?;
+                objects::set_property_q(realm, &global, name, &value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// run a compiled script or module, a module is resolved first (a module read from bytecode has not loaded its imports yet)
+/// and fails when the Promise of its evaluation is rejected
+fn run_step(realm: &QuickJsRealmAdapter, compiled: &QuickJsValueAdapter) -> Result<(), JsError> {
+    unsafe {
+        if !compiled.is_module() {
+            return run_compiled_function(realm.context, compiled).map(|_| ());
+        }
+        resolve_module(realm.context, compiled)?;
+        let res = run_compiled_function(realm.context, compiled)?;
+        if promises::is_promise(realm.context, &res) {
+            if let PromiseState::Rejected(reason) = promises::get_promise_state(realm.context, &res)
+            {
+                return Err(if reason.is_object() {
+                    errors::error_to_js_error(realm.context, &reason)
+                } else {
+                    JsError::new_string(reason.to_string()?)
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_globals(realm: &QuickJsRealmAdapter, names: &[String]) -> Result<Vec<u8>, JsError> {
+    let global = get_global_q(realm);
+    let globals_obj = objects::create_object_q(realm)?;
+    for name in names {
+        let value = objects::get_property_q(realm, &global, name)?;
+        objects::set_property_q(realm, &globals_obj, name, &value)?;
+    }
+    unsafe {
+        let mut len = 0;
+        let slice_u8 = q::JS_WriteObject(
+            realm.context,
+            &mut len,
+            *globals_obj.borrow_value(),
+            q::JS_WRITE_OBJ_REFERENCE as i32,
+        );
+        if slice_u8.is_null() {
+            let msg = match QuickJsRealmAdapter::get_exception(realm.context) {
+                Some(ex) => format!("{ex}"),
+                None => "unknown error".to_string(),
+            };
+            return Err(JsError::new_string(format!(
+                "could not capture globals {names:?} (only data can be captured): {msg}"
+            )));
+        }
+        let ret = std::slice::from_raw_parts(slice_u8, len as _).to_vec();
+        q::js_free(realm.context, slice_u8 as *mut c_void);
+        Ok(ret)
+    }
+}
+
+fn read_globals(realm: &QuickJsRealmAdapter, bytes: &[u8]) -> Result<QuickJsValueAdapter, JsError> {
+    unsafe {
+        let raw = q::JS_ReadObject(
+            realm.context,
+            bytes.as_ptr(),
+            bytes.len() as _,
+            q::JS_READ_OBJ_REFERENCE as i32,
+        );
+        let obj_ref =
+            QuickJsValueAdapter::new(realm.context, raw, false, true, "read_globals result");
+        if obj_ref.is_exception() {
+            let ex_opt = QuickJsRealmAdapter::get_exception(realm.context);
+            if let Some(ex) = ex_opt {
+                Err(ex)
+            } else {
+                Err(JsError::new_str(
+                    "read_globals failed and could not get exception",
+                ))
+            }
+        } else {
+            Ok(obj_ref)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::facades::tests::init_test_rt;
+    use crate::jsutils::Script;
+    use crate::realmtemplate::RealmTemplateBuilder;
+    use crate::values::JsValueFacade;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_realm_template() {
+        let rt = init_test_rt();
+
+        let init_count = Arc::new(AtomicUsize::new(0));
+        let init_count2 = init_count.clone();
+        rt.set_function(&[], "countInit", move |_realm, _args| {
+            init_count2.fetch_add(1, Ordering::SeqCst);
+            Ok(JsValueFacade::Null)
+        })
+        .expect("set_function failed");
+
+        let template = rt
+            .create_realm_template(
+                RealmTemplateBuilder::new()
+                    .script(Script::new(
+                        "template_framework.js",
+                        "var prefix = 'hello '; function greet(name) { return prefix + name; }",
+                    ))
+                    .module(Script::new(
+                        "template_framework.mjs",
+                        "globalThis.fromModule = 'mod';",
+                    ))
+                    .init_script(Script::new(
+                        "template_config.js",
+                        "countInit(); globalThis.config = {names: ['a', 'b']}; config.self = config;",
+                    ))
+                    .capture_global("config"),
+            )
+            .expect("could not create template");
+
+        rt.create_realm_from_template("tenant1", &template)
+            .expect("could not create tenant1");
+        rt.create_realm_from_template("tenant2", &template)
+            .expect("could not create tenant2");
+
+        let res = rt
+            .eval_sync(
+                Some("tenant2"),
+                Script::new(
+                    "test_template.js",
+                    "[greet(config.names[1]), fromModule, config.self === config].join(',');",
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_str(), "hello b,mod,true");
+        assert_eq!(init_count.load(Ordering::SeqCst), 1);
+
+        // realms do not share the captured data
+        rt.eval_sync(
+            Some("tenant1"),
+            Script::new("test_template2.js", "config.names.push('c');"),
+        )
+        .expect("script failed");
+        let res = rt
+            .eval_sync(
+                Some("tenant2"),
+                Script::new("test_template3.js", "config.names.length;"),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_i32(), 2);
+
+        // functions can not be captured
+        let res = rt.create_realm_template(
+            RealmTemplateBuilder::new()
+                .init_script(Script::new(
+                    "template_func.js",
+                    "globalThis.f = function() {};",
+                ))
+                .capture_global("f"),
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_realm_template_imports() {
+        let rt = init_test_rt();
+
+        let template = rt
+            .create_realm_template(RealmTemplateBuilder::new().module(Script::new(
+                "template_imports.mjs",
+                "import {foo, mltpl} from 'template_dep.mes';\nglobalThis.fromDep = foo + mltpl(2, 3);",
+            )))
+            .expect("could not create template");
+        rt.create_realm_from_template("tenant_imports", &template)
+            .expect("could not create realm");
+
+        let res = rt
+            .eval_sync(
+                Some("tenant_imports"),
+                Script::new("test_template_imports.js", "fromDep;"),
+            )
+            .expect("script failed");
+        assert_eq!(res.get_str(), "bar6");
+    }
+
+    #[test]
+    fn test_realm_template_module_throws() {
+        let rt = init_test_rt();
+
+        let res = rt.create_realm_template(RealmTemplateBuilder::new().module(Script::new(
+            "template_throws.mjs",
+            "throw new Error('module failed');",
+        )));
+        let err = res.err().expect("template should not build");
+        assert_eq!(err.get_message(), "module failed");
+
+        // a module which only throws when the template is applied
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs2 = runs.clone();
+        rt.set_function(&[], "templateRuns", move |_realm, _args| {
+            Ok(JsValueFacade::new_i32(
+                runs2.fetch_add(1, Ordering::SeqCst) as i32 + 1,
+            ))
+        })
+        .expect("set_function failed");
+        let template = rt
+            .create_realm_template(RealmTemplateBuilder::new().module(Script::new(
+                "template_throws_on_apply.mjs",
+                "if (templateRuns() > 1) { throw new TypeError('applied'); }",
+            )))
+            .expect("could not create template");
+        let err = rt
+            .create_realm_from_template("tenant_throws", &template)
+            .err()
+            .expect("template should not apply");
+        assert_eq!(err.get_name(), "TypeError");
+        assert_eq!(err.get_message(), "applied");
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
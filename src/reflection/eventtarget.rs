@@ -1,45 +1,417 @@
 //! EventTarget utils
 //!
+//! Proxy classes which are an EventTarget (see [Proxy::event_target](crate::reflection::Proxy::event_target)) dispatch events like the DOM does:
+//! * listeners are called in the order in which they were added, adding a listener twice (with the same capture option) has no effect
+//! * the listener options `capture`, `once`, `passive` and `signal` (an AbortSignal) are supported
+//! * an Event (or CustomEvent) honours `cancelable`, `preventDefault()`, `stopPropagation()` and `stopImmediatePropagation()`, dispatching returns false if the Event was canceled
+//! * Events which bubble are also dispatched on the bubble target of an instance (and on its bubble target), see [set_event_bubble_target]
+//! * an exception in a listener does not prevent the other listeners from being called, the first exception is rethrown when dispatching is done
+//!
+//! a plain object may also be dispatched (with an event id), it bubbles if its `bubbles` property is true and is canceled if its `cancelable` property is true and a listener returns false
+//!
+//! the Event, CustomEvent, EventTarget, AbortController and AbortSignal classes are installed as globals by the `eventtarget` feature
 
-use crate::jsutils::JsError;
+use crate::jsutils::{JsError, Script};
 use crate::quickjs_utils;
-use crate::quickjs_utils::objects::{create_object_q, set_property_q};
 use crate::quickjs_utils::primitives::from_bool;
-use crate::quickjs_utils::{functions, objects, parse_args, primitives};
+use crate::quickjs_utils::{arrays, compile, functions, objects, parse_args, primitives};
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsvalueadapter::QuickJsValueAdapter;
-use crate::reflection::{get_proxy, get_proxy_instance_info, Proxy};
+use crate::reflection::{get_proxy_instance_info, is_proxy_instance_q, Proxy};
 use libquickjs_sys as q;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+/// the listener functions of an event with the records (the listener and its options) which are passed to the dispatch function
+type Listeners = Vec<(QuickJsValueAdapter, QuickJsValueAdapter)>;
+
+/// the part of the EventTarget impl which is written in script, this is evaluated once per realm
+const EVENT_TARGET_JS: &str = r#"
+(function () {
+    'use strict';
+    const NONE = 0, CAPTURING_PHASE = 1, AT_TARGET = 2, BUBBLING_PHASE = 3;
+    const eventStates = new WeakMap();
+    const eventDetails = new WeakMap();
+    const targetListeners = new WeakMap();
+    const signalStates = new WeakMap();
+    const controllerSignals = new WeakMap();
+
+    function newState(type, init) {
+        init = init || {};
+        return {
+            type: String(type), bubbles: !!init.bubbles, cancelable: !!init.cancelable, composed: !!init.composed,
+            defaultPrevented: false, stop: false, stopImmediate: false, passive: false, dispatching: false,
+            target: null, currentTarget: null, eventPhase: NONE, path: [], timeStamp: Date.now()
+        };
+    }
+    function stateOf(event) {
+        const state = eventStates.get(event);
+        if (!state) {
+            throw new TypeError('Illegal invocation');
+        }
+        return state;
+    }
+
+    class Event {
+        constructor(type, init) {
+            if (arguments.length === 0) {
+                throw new TypeError("Failed to construct 'Event': 1 argument required");
+            }
+            eventStates.set(this, newState(type, init));
+        }
+        get type() { return stateOf(this).type; }
+        get bubbles() { return stateOf(this).bubbles; }
+        get cancelable() { return stateOf(this).cancelable; }
+        get composed() { return stateOf(this).composed; }
+        get defaultPrevented() { return stateOf(this).defaultPrevented; }
+        get target() { return stateOf(this).target; }
+        get srcElement() { return stateOf(this).target; }
+        get currentTarget() { return stateOf(this).currentTarget; }
+        get eventPhase() { return stateOf(this).eventPhase; }
+        get timeStamp() { return stateOf(this).timeStamp; }
+        get isTrusted() { return false; }
+        get cancelBubble() { return stateOf(this).stop; }
+        set cancelBubble(value) { if (value) { stateOf(this).stop = true; } }
+        get returnValue() { return !stateOf(this).defaultPrevented; }
+        set returnValue(value) { if (!value) { this.preventDefault(); } }
+        composedPath() {
+            const state = stateOf(this);
+            return state.dispatching ? state.path.slice() : [];
+        }
+        preventDefault() {
+            const state = stateOf(this);
+            if (state.cancelable && !state.passive) {
+                state.defaultPrevented = true;
+            }
+        }
+        stopPropagation() { stateOf(this).stop = true; }
+        stopImmediatePropagation() {
+            const state = stateOf(this);
+            state.stop = true;
+            state.stopImmediate = true;
+        }
+    }
+    for (const [name, value] of [['NONE', NONE], ['CAPTURING_PHASE', CAPTURING_PHASE], ['AT_TARGET', AT_TARGET], ['BUBBLING_PHASE', BUBBLING_PHASE]]) {
+        Object.defineProperty(Event, name, { value, enumerable: true });
+        Object.defineProperty(Event.prototype, name, { value, enumerable: true });
+    }
+
+    class CustomEvent extends Event {
+        constructor(type, init) {
+            super(type, init);
+            eventDetails.set(this, init && init.detail !== undefined ? init.detail : null);
+        }
+        get detail() { return eventDetails.get(this); }
+    }
+
+    // the options of addEventListener may be a boolean (the capture option), null means the listener should not be added
+    // onAbort is called when the signal is aborted after the record was marked as removed
+    function toRecord(callback, options, onAbort) {
+        if (typeof options === 'boolean') {
+            options = { capture: options };
+        } else if (options === null || typeof options !== 'object') {
+            options = {};
+        }
+        const signal = options.signal || null;
+        if (signal && signal.aborted) {
+            return null;
+        }
+        const record = { callback, capture: !!options.capture, once: !!options.once, passive: !!options.passive, signal, removed: false };
+        if (signal instanceof AbortSignal) {
+            signal.addEventListener('abort', () => {
+                record.removed = true;
+                if (typeof onAbort === 'function') {
+                    onAbort();
+                }
+            }, { once: true });
+        }
+        return record;
+    }
 
-fn with_proxy_instances_map<C, R>(
+    function recordsOf(target, type, create) {
+        let types = targetListeners.get(target);
+        if (!types) {
+            if (!create) {
+                return [];
+            }
+            types = new Map();
+            targetListeners.set(target, types);
+        }
+        let records = types.get(type);
+        if (!records) {
+            records = [];
+            if (create) {
+                types.set(type, records);
+            }
+        }
+        for (let i = records.length - 1; i >= 0; i--) {
+            if (records[i].removed) {
+                records.splice(i, 1);
+            }
+        }
+        return records;
+    }
+
+    // path is an Array of [target, records] from the target to its outermost bubble target
+    function dispatch(event, path) {
+        // a plain object gets a state for this dispatch only
+        const state = eventStates.get(event) || newState('', event);
+        if (state.dispatching) {
+            throw new Error('InvalidStateError: the event is already being dispatched');
+        }
+        state.dispatching = true;
+        state.target = path[0][0];
+        state.path = path.map((entry) => entry[0]);
+        let error = null;
+        const invoke = ([currentTarget, records], phase) => {
+            state.currentTarget = currentTarget;
+            state.eventPhase = phase;
+            for (const record of records.slice()) {
+                if (record.removed) {
+                    continue;
+                }
+                if (record.signal && record.signal.aborted) {
+                    record.removed = true;
+                    continue;
+                }
+                if ((phase === CAPTURING_PHASE && !record.capture) || (phase === BUBBLING_PHASE && record.capture)) {
+                    continue;
+                }
+                if (record.once) {
+                    record.removed = true;
+                }
+                state.passive = record.passive;
+                try {
+                    const callback = record.callback;
+                    const ret = typeof callback === 'function' ? callback.call(currentTarget, event) : callback.handleEvent(event);
+                    if (ret === false && state.cancelable && !state.passive) {
+                        state.defaultPrevented = true;
+                    }
+                } catch (e) {
+                    if (error === null) {
+                        error = { e };
+                    }
+                }
+                state.passive = false;
+                if (state.stopImmediate) {
+                    break;
+                }
+            }
+        };
+        for (let i = path.length - 1; i > 0 && !state.stop; i--) {
+            invoke(path[i], CAPTURING_PHASE);
+        }
+        if (!state.stop) {
+            invoke(path[0], AT_TARGET);
+        }
+        if (state.bubbles) {
+            for (let i = 1; i < path.length && !state.stop; i++) {
+                invoke(path[i], BUBBLING_PHASE);
+            }
+        }
+        state.dispatching = false;
+        state.eventPhase = NONE;
+        state.currentTarget = null;
+        state.stop = false;
+        state.stopImmediate = false;
+        if (error !== null) {
+            throw error.e;
+        }
+        return !state.defaultPrevented;
+    }
+
+    class EventTarget {
+        addEventListener(type, callback, options) {
+            if (callback === null || callback === undefined) {
+                return;
+            }
+            type = String(type);
+            // recordsOf drops the removed records
+            const record = toRecord(callback, options, () => recordsOf(this, type, false));
+            if (record === null) {
+                return;
+            }
+            const records = recordsOf(this, type, true);
+            if (!records.some((r) => r.callback === callback && r.capture === record.capture)) {
+                records.push(record);
+            }
+        }
+        removeEventListener(type, callback, options) {
+            const capture = typeof options === 'boolean' ? options : !!(options && options.capture);
+            const records = recordsOf(this, String(type), false);
+            const index = records.findIndex((r) => r.callback === callback && r.capture === capture);
+            if (index >= 0) {
+                records[index].removed = true;
+                records.splice(index, 1);
+            }
+        }
+        dispatchEvent(event) {
+            if (!eventStates.has(event)) {
+                throw new TypeError("Failed to execute 'dispatchEvent' on 'EventTarget': parameter 1 is not of type 'Event'");
+            }
+            return dispatch(event, [[this, recordsOf(this, event.type, false)]]);
+        }
+    }
+
+    let constructingSignal = false;
+    class AbortSignal extends EventTarget {
+        constructor() {
+            if (!constructingSignal) {
+                throw new TypeError('Illegal constructor');
+            }
+            super();
+            signalStates.set(this, { aborted: false, reason: undefined });
+        }
+        get aborted() { return signalStates.get(this).aborted; }
+        get reason() { return signalStates.get(this).reason; }
+        throwIfAborted() {
+            const state = signalStates.get(this);
+            if (state.aborted) {
+                throw state.reason;
+            }
+        }
+        static abort(reason) {
+            const signal = newSignal();
+            abortSignal(signal, reason);
+            return signal;
+        }
+    }
+    function newSignal() {
+        constructingSignal = true;
+        try {
+            return new AbortSignal();
+        } finally {
+            constructingSignal = false;
+        }
+    }
+    function abortSignal(signal, reason) {
+        const state = signalStates.get(signal);
+        if (state.aborted) {
+            return;
+        }
+        if (reason === undefined) {
+            reason = new Error('signal is aborted without reason');
+            reason.name = 'AbortError';
+        }
+        state.aborted = true;
+        state.reason = reason;
+        signal.dispatchEvent(new Event('abort'));
+    }
+
+    class AbortController {
+        constructor() { controllerSignals.set(this, newSignal()); }
+        get signal() { return controllerSignals.get(this); }
+        abort(reason) { abortSignal(controllerSignals.get(this), reason); }
+    }
+
+    return { Event, CustomEvent, EventTarget, AbortController, AbortSignal, toRecord, recordsOf, dispatch };
+})();
+"#;
+
+/// get the part of the EventTarget impl which is written in script (Event, CustomEvent, EventTarget, AbortController, AbortSignal and the dispatch function), this is created once per realm
+pub(crate) fn get_intrinsics(q_ctx: &QuickJsRealmAdapter) -> Result<QuickJsValueAdapter, JsError> {
+    if let Some(intrinsics) = &*q_ctx.event_intrinsics.borrow() {
+        return Ok(intrinsics.clone());
+    }
+    // compiled and run directly, this is internal code so it should not be pre-processed or stored in a bytecode cache
+    let intrinsics = unsafe {
+        let compiled = compile::compile(
+            q_ctx.context,
+            Script::new("quickjs_runtime_eventtarget.js", EVENT_TARGET_JS),
+        )?;
+        compile::run_compiled_function(q_ctx.context, &compiled)?
+    };
+    q_ctx.event_intrinsics.replace(Some(intrinsics.clone()));
+    Ok(intrinsics)
+}
+
+fn invoke_intrinsic(
     q_ctx: &QuickJsRealmAdapter,
-    proxy_class_name: &str,
-    consumer: C,
-) -> R
-where
-    C: FnOnce(
-        &HashMap<usize, HashMap<String, HashMap<QuickJsValueAdapter, QuickJsValueAdapter>>>,
-    ) -> R,
-{
-    let listeners = &*q_ctx.proxy_event_listeners.borrow();
-    if listeners.contains_key(proxy_class_name) {
-        let proxy_instance_map = listeners.get(proxy_class_name).unwrap();
-        consumer(proxy_instance_map)
-    } else {
-        consumer(&HashMap::new())
+    name: &str,
+    args: &[&QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let intrinsics = get_intrinsics(q_ctx)?;
+    let func = objects::get_property_q(q_ctx, &intrinsics, name)?;
+    functions::call_function_q_ref_args(q_ctx, &func, args, None)
+}
+
+fn get_flag(q_ctx: &QuickJsRealmAdapter, record: &QuickJsValueAdapter, name: &str) -> bool {
+    objects::get_property_q(q_ctx, record, name)
+        .map(|flag| flag.is_bool() && flag.to_bool())
+        .unwrap_or(false)
+}
+
+fn mark_removed(q_ctx: &QuickJsRealmAdapter, record: &QuickJsValueAdapter) {
+    // a dispatch which is in progress should skip the listener
+    if let Err(e) = objects::set_property_q(q_ctx, record, "removed", &from_bool(true)) {
+        log::error!("could not mark listener as removed: {}", e);
     }
 }
 
+/// the capture option of removeEventListener
+fn get_capture_option(q_ctx: &QuickJsRealmAdapter, options: Option<&QuickJsValueAdapter>) -> bool {
+    match options {
+        Some(options) if options.is_bool() => options.to_bool(),
+        Some(options) if options.is_object() => get_flag(q_ctx, options, "capture"),
+        _ => false,
+    }
+}
+
+/// the returned listeners (which were removed) should be dropped after the listener map is no longer borrowed, dropping them may finalize Proxy instances
+fn add_listener(
+    q_ctx: &QuickJsRealmAdapter,
+    listeners: &mut Listeners,
+    listener_func: QuickJsValueAdapter,
+    record: QuickJsValueAdapter,
+) -> Listeners {
+    let removed = prune_listeners(q_ctx, listeners);
+    let capture = get_flag(q_ctx, &record, "capture");
+    if !listeners
+        .iter()
+        .any(|(func, r)| func == &listener_func && get_flag(q_ctx, r, "capture") == capture)
+    {
+        listeners.push((listener_func, record));
+    }
+    removed
+}
+
+/// remove a listener, if capture is None the listener is removed regardless of its capture option
+fn remove_listener(
+    q_ctx: &QuickJsRealmAdapter,
+    listeners: &mut Listeners,
+    listener_func: &QuickJsValueAdapter,
+    capture: Option<bool>,
+) -> Listeners {
+    let (removed, kept) = std::mem::take(listeners)
+        .into_iter()
+        .partition(|(func, record)| {
+            func == listener_func
+                && capture
+                    .map(|c| get_flag(q_ctx, record, "capture") == c)
+                    .unwrap_or(true)
+        });
+    *listeners = kept;
+    for (_func, record) in &removed {
+        mark_removed(q_ctx, record);
+    }
+    removed
+}
+
+/// remove the listeners which were marked as removed (once listeners which were dispatched and listeners with an aborted signal)
+fn prune_listeners(q_ctx: &QuickJsRealmAdapter, listeners: &mut Listeners) -> Listeners {
+    let (removed, kept) = std::mem::take(listeners)
+        .into_iter()
+        .partition(|(_func, record)| get_flag(q_ctx, record, "removed"));
+    *listeners = kept;
+    removed
+}
+
 fn with_proxy_instances_map_mut<C, R>(
     q_ctx: &QuickJsRealmAdapter,
     proxy_class_name: &str,
     consumer: C,
 ) -> R
 where
-    C: FnOnce(
-        &mut HashMap<usize, HashMap<String, HashMap<QuickJsValueAdapter, QuickJsValueAdapter>>>,
-    ) -> R,
+    C: FnOnce(&mut HashMap<usize, HashMap<String, Listeners>>) -> R,
 {
     let listeners = &mut *q_ctx.proxy_event_listeners.borrow_mut();
     if !listeners.contains_key(proxy_class_name) {
@@ -58,13 +430,13 @@ fn with_listener_map_mut<C, R>(
     consumer: C,
 ) -> R
 where
-    C: FnOnce(&mut HashMap<QuickJsValueAdapter, QuickJsValueAdapter>) -> R,
+    C: FnOnce(&mut Listeners) -> R,
 {
     with_proxy_instances_map_mut(q_ctx, proxy_class_name, |proxy_instance_map| {
         let event_id_map = proxy_instance_map.entry(instance_id).or_default();
 
         if !event_id_map.contains_key(event_id) {
-            event_id_map.insert(event_id.to_string(), HashMap::new());
+            event_id_map.insert(event_id.to_string(), vec![]);
         }
 
         let listener_map = event_id_map.get_mut(event_id).unwrap();
@@ -73,29 +445,6 @@ where
     })
 }
 
-fn with_listener_map<C, R>(
-    q_ctx: &QuickJsRealmAdapter,
-    proxy_class_name: &str,
-    instance_id: usize,
-    event_id: &str,
-    consumer: C,
-) -> R
-where
-    C: FnOnce(&HashMap<QuickJsValueAdapter, QuickJsValueAdapter>) -> R,
-{
-    with_proxy_instances_map(q_ctx, proxy_class_name, |proxy_instance_map| {
-        if let Some(event_id_map) = proxy_instance_map.get(&instance_id) {
-            if let Some(listener_map) = event_id_map.get(event_id) {
-                consumer(listener_map)
-            } else {
-                consumer(&HashMap::new())
-            }
-        } else {
-            consumer(&HashMap::new())
-        }
-    })
-}
-
 fn with_static_listener_map<C, R>(
     q_ctx: &QuickJsRealmAdapter,
     proxy_class_name: &str,
@@ -103,7 +452,7 @@ fn with_static_listener_map<C, R>(
     consumer: C,
 ) -> R
 where
-    C: FnOnce(&mut HashMap<QuickJsValueAdapter, QuickJsValueAdapter>) -> R,
+    C: FnOnce(&mut Listeners) -> R,
 {
     let static_listeners = &mut *q_ctx.proxy_static_event_listeners.borrow_mut();
     if !static_listeners.contains_key(proxy_class_name) {
@@ -111,12 +460,59 @@ where
     }
     let proxy_static_map = static_listeners.get_mut(proxy_class_name).unwrap();
     if !proxy_static_map.contains_key(event_id) {
-        proxy_static_map.insert(event_id.to_string(), HashMap::new());
+        proxy_static_map.insert(event_id.to_string(), vec![]);
     }
     let event_map = proxy_static_map.get_mut(event_id).unwrap();
     consumer(event_map)
 }
 
+/// the function which is called when the signal of a listener is aborted, it drops the listener right away instead of at the next dispatch
+///
+/// instance_id is None for a static listener, undefined is returned if the options have no signal
+fn new_abort_callback(
+    q_ctx: &QuickJsRealmAdapter,
+    proxy_class_name: &str,
+    instance_id: Option<usize>,
+    event_id: &str,
+    options_obj: &QuickJsValueAdapter,
+) -> Result<QuickJsValueAdapter, JsError> {
+    if !options_obj.is_object()
+        || !objects::get_property_q(q_ctx, options_obj, "signal")?.is_object()
+    {
+        return Ok(quickjs_utils::new_undefined_ref());
+    }
+    let proxy_class_name = proxy_class_name.to_string();
+    let event_id = event_id.to_string();
+    functions::new_function_q(
+        q_ctx,
+        "onAbort",
+        move |q_ctx, _this, _args| {
+            let _removed = match instance_id {
+                Some(instance_id) => {
+                    with_proxy_instances_map_mut(q_ctx, proxy_class_name.as_str(), |map| {
+                        // the instance may have been finalized already
+                        map.get_mut(&instance_id)
+                            .and_then(|event_id_map| event_id_map.get_mut(event_id.as_str()))
+                            .map(|listeners| prune_listeners(q_ctx, listeners))
+                            .unwrap_or_default()
+                    })
+                }
+                None => with_static_listener_map(
+                    q_ctx,
+                    proxy_class_name.as_str(),
+                    event_id.as_str(),
+                    |listeners| prune_listeners(q_ctx, listeners),
+                ),
+            };
+            Ok(quickjs_utils::new_undefined_ref())
+        },
+        0,
+    )
+}
+
+/// add a listener to an instance of a Proxy class, options_obj may be an object with the options (capture, once, passive and signal), a boolean (capture) or undefined
+///
+/// an error while reading the options is logged, see [try_add_event_listener] to handle it
 pub fn add_event_listener(
     q_ctx: &QuickJsRealmAdapter,
     proxy_class_name: &str,
@@ -125,17 +521,56 @@ pub fn add_event_listener(
     listener_func: QuickJsValueAdapter,
     options_obj: QuickJsValueAdapter,
 ) {
+    if let Err(e) = try_add_event_listener(
+        q_ctx,
+        proxy_class_name,
+        event_id,
+        instance_id,
+        listener_func,
+        options_obj,
+    ) {
+        log::error!("could not add event listener: {}", e);
+    }
+}
+
+/// add a listener to an instance of a Proxy class, see [add_event_listener]
+pub fn try_add_event_listener(
+    q_ctx: &QuickJsRealmAdapter,
+    proxy_class_name: &str,
+    event_id: &str,
+    instance_id: usize,
+    listener_func: QuickJsValueAdapter,
+    options_obj: QuickJsValueAdapter,
+) -> Result<(), JsError> {
     log::trace!(
         "eventtarget::add_listener_to_map p:{} e:{} i:{}",
         proxy_class_name,
         event_id,
         instance_id
     );
-    with_listener_map_mut(q_ctx, proxy_class_name, instance_id, event_id, |map| {
-        let _ = map.insert(listener_func, options_obj);
-    })
+    let on_abort = new_abort_callback(
+        q_ctx,
+        proxy_class_name,
+        Some(instance_id),
+        event_id,
+        &options_obj,
+    )?;
+    let record = invoke_intrinsic(
+        q_ctx,
+        "toRecord",
+        &[&listener_func, &options_obj, &on_abort],
+    )?;
+    if record.is_null() {
+        // the signal was already aborted
+        return Ok(());
+    }
+    let _removed = with_listener_map_mut(q_ctx, proxy_class_name, instance_id, event_id, |map| {
+        add_listener(q_ctx, map, listener_func, record)
+    });
+    Ok(())
 }
 
+/// add a listener to a Proxy class, see [add_event_listener]
 pub fn add_static_event_listener(
     q_ctx: &QuickJsRealmAdapter,
     proxy_class_name: &str,
@@ -143,16 +578,46 @@ pub fn add_static_event_listener(
     listener_func: QuickJsValueAdapter,
     options_obj: QuickJsValueAdapter,
 ) {
+    if let Err(e) = try_add_static_event_listener(
+        q_ctx,
+        proxy_class_name,
+        event_id,
+        listener_func,
+        options_obj,
+    ) {
+        log::error!("could not add static event listener: {}", e);
+    }
+}
+
+/// add a listener to a Proxy class, see [try_add_event_listener]
+pub fn try_add_static_event_listener(
+    q_ctx: &QuickJsRealmAdapter,
+    proxy_class_name: &str,
+    event_id: &str,
+    listener_func: QuickJsValueAdapter,
+    options_obj: QuickJsValueAdapter,
+) -> Result<(), JsError> {
     log::trace!(
         "eventtarget::add_static_listener_to_map p:{} e:{}",
         proxy_class_name,
         event_id
     );
-    with_static_listener_map(q_ctx, proxy_class_name, event_id, |map| {
-        let _ = map.insert(listener_func, options_obj);
-    })
+    let on_abort = new_abort_callback(q_ctx, proxy_class_name, None, event_id, &options_obj)?;
+    let record = invoke_intrinsic(
+        q_ctx,
+        "toRecord",
+        &[&listener_func, &options_obj, &on_abort],
+    )?;
+    if record.is_null() {
+        return Ok(());
+    }
+    let _removed = with_static_listener_map(q_ctx, proxy_class_name, event_id, |map| {
+        add_listener(q_ctx, map, listener_func, record)
+    });
+    Ok(())
 }
 
+/// remove a listener from an instance of a Proxy class (regardless of its capture option)
 pub fn remove_event_listener(
     q_ctx: &QuickJsRealmAdapter,
     proxy_class_name: &str,
@@ -166,11 +631,12 @@ pub fn remove_event_listener(
         event_id,
         instance_id
     );
-    with_listener_map_mut(q_ctx, proxy_class_name, instance_id, event_id, |map| {
-        let _ = map.remove(listener_func);
-    })
+    let _removed = with_listener_map_mut(q_ctx, proxy_class_name, instance_id, event_id, |map| {
+        remove_listener(q_ctx, map, listener_func, None)
+    });
 }
 
+/// remove a listener from a Proxy class (regardless of its capture option)
 pub fn remove_static_event_listener(
     q_ctx: &QuickJsRealmAdapter,
     proxy_class_name: &str,
@@ -182,9 +648,9 @@ pub fn remove_static_event_listener(
         proxy_class_name,
         event_id
     );
-    with_static_listener_map(q_ctx, proxy_class_name, event_id, |map| {
-        let _ = map.remove(listener_func);
-    })
+    let _removed = with_static_listener_map(q_ctx, proxy_class_name, event_id, |map| {
+        remove_listener(q_ctx, map, listener_func, None)
+    });
 }
 
 fn remove_map(q_ctx: &QuickJsRealmAdapter, proxy_class_name: &str, instance_id: usize) {
@@ -194,13 +660,132 @@ fn remove_map(q_ctx: &QuickJsRealmAdapter, proxy_class_name: &str, instance_id:
         instance_id
     );
 
-    with_proxy_instances_map_mut(q_ctx, proxy_class_name, |map| {
-        let _ = map.remove(&instance_id);
-    });
+    let _removed =
+        with_proxy_instances_map_mut(q_ctx, proxy_class_name, |map| map.remove(&instance_id));
+    let _bubble_target = q_ctx
+        .proxy_event_bubble_targets
+        .borrow_mut()
+        .remove(&(proxy_class_name.to_string(), instance_id));
+}
+
+/// set the bubble target (parent) of an instance of a Proxy class, Events which bubble are dispatched on the bubble target after the instance
+///
+/// the bubble target may be an instance of a Proxy class which is an EventTarget (which may have a bubble target itself) or an EventTarget object, None removes the bubble target
+pub fn set_event_bubble_target(
+    q_ctx: &QuickJsRealmAdapter,
+    proxy_class_name: &str,
+    instance_id: usize,
+    bubble_target: Option<QuickJsValueAdapter>,
+) {
+    let key = (proxy_class_name.to_string(), instance_id);
+    // the old target is dropped after the map is no longer borrowed
+    let _old = {
+        let targets = &mut *q_ctx.proxy_event_bubble_targets.borrow_mut();
+        match bubble_target {
+            Some(bubble_target) => targets.insert(key, bubble_target),
+            None => targets.remove(&key),
+        }
+    };
+}
+
+fn get_records(
+    q_ctx: &QuickJsRealmAdapter,
+    listeners: &mut Listeners,
+) -> (Vec<QuickJsValueAdapter>, Listeners) {
+    let removed = prune_listeners(q_ctx, listeners);
+    let records = listeners
+        .iter()
+        .map(|(_func, record)| record.clone())
+        .collect();
+    (records, removed)
+}
+
+fn new_path_entry(
+    q_ctx: &QuickJsRealmAdapter,
+    target: &QuickJsValueAdapter,
+    records: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let records_ref = arrays::create_array_q(q_ctx)?;
+    for (index, record) in records.iter().enumerate() {
+        arrays::set_element_q(q_ctx, &records_ref, index as u32, record)?;
+    }
+    let entry = arrays::create_array_q(q_ctx)?;
+    arrays::set_element_q(q_ctx, &entry, 0, target)?;
+    arrays::set_element_q(q_ctx, &entry, 1, &records_ref)?;
+    Ok(entry)
+}
+
+fn dispatch_instance_event(
+    q_ctx: &QuickJsRealmAdapter,
+    proxy_class_name: &str,
+    instance_id: usize,
+    target: Option<QuickJsValueAdapter>,
+    event_id: &str,
+    event: QuickJsValueAdapter,
+) -> Result<bool, JsError> {
+    let path = arrays::create_array_q(q_ctx)?;
+
+    let (records, _removed) = with_listener_map_mut(
+        q_ctx,
+        proxy_class_name,
+        instance_id,
+        event_id,
+        |listeners| get_records(q_ctx, listeners),
+    );
+    let target = target.unwrap_or_else(quickjs_utils::new_null_ref);
+    arrays::set_element_q(q_ctx, &path, 0, &new_path_entry(q_ctx, &target, &records)?)?;
+
+    // add the bubble targets to the path
+    let mut current = (proxy_class_name.to_string(), instance_id);
+    let mut visited = HashSet::new();
+    visited.insert(current.clone());
+    let mut index = 1;
+    loop {
+        let bubble_target = q_ctx
+            .proxy_event_bubble_targets
+            .borrow()
+            .get(&current)
+            .cloned();
+        let Some(bubble_target) = bubble_target else {
+            break;
+        };
+        if is_proxy_instance_q(q_ctx, &bubble_target) {
+            let info = get_proxy_instance_info(bubble_target.borrow_value());
+            let next = (info.class_name.clone(), info.id);
+            if !visited.insert(next.clone()) {
+                return Err(JsError::new_str(
+                    "the bubble targets of an EventTarget form a cycle",
+                ));
+            }
+            let (records, _removed) =
+                with_listener_map_mut(q_ctx, next.0.as_str(), next.1, event_id, |listeners| {
+                    get_records(q_ctx, listeners)
+                });
+            let entry = new_path_entry(q_ctx, &bubble_target, &records)?;
+            arrays::set_element_q(q_ctx, &path, index, &entry)?;
+            current = next;
+            index += 1;
+        } else {
+            // an EventTarget object, it has no bubble target
+            let event_id_ref = primitives::from_string_q(q_ctx, event_id)?;
+            let records_ref =
+                invoke_intrinsic(q_ctx, "recordsOf", &[&bubble_target, &event_id_ref])?;
+            let entry = arrays::create_array_q(q_ctx)?;
+            arrays::set_element_q(q_ctx, &entry, 0, &bubble_target)?;
+            arrays::set_element_q(q_ctx, &entry, 1, &records_ref)?;
+            arrays::set_element_q(q_ctx, &path, index, &entry)?;
+            break;
+        }
+    }
+
+    let res = invoke_intrinsic(q_ctx, "dispatch", &[&event, &path])?;
+    Ok(res.is_bool() && res.to_bool())
 }
 
 /// dispatch an Event on an instance of a Proxy class
 /// the return value is false if event is cancelable and at least one of the event listeners which received event called Event.preventDefault. Otherwise it returns true
+///
+/// as there is no reference to the instance the target of the Event is null (the listeners of bubble targets do get a currentTarget)
 pub fn dispatch_event(
     q_ctx: &QuickJsRealmAdapter,
     proxy: &Proxy,
@@ -209,26 +794,14 @@ pub fn dispatch_event(
     event: QuickJsValueAdapter,
 ) -> Result<bool, JsError> {
     let proxy_class_name = proxy.get_class_name();
-
-    with_listener_map(
+    dispatch_instance_event(
         q_ctx,
         proxy_class_name.as_str(),
         instance_id,
+        None,
         event_id,
-        |listeners| -> Result<(), JsError> {
-            let func_args = [event];
-            for entry in listeners {
-                let listener = entry.0;
-                let _res = functions::call_function_q(q_ctx, listener, &func_args, None)?;
-
-                // todo chekc if _res is bool, for cancel and such
-                // and if event is cancelabble and preventDefault was called and such
-            }
-            Ok(())
-        },
-    )?;
-
-    Ok(true)
+        event,
+    )
 }
 
 /// dispatch an Event on a Proxy class
@@ -239,28 +812,21 @@ pub fn dispatch_static_event(
     event_id: &str,
     event: QuickJsValueAdapter,
 ) -> Result<bool, JsError> {
-    with_static_listener_map(
-        q_ctx,
-        proxy_class_name,
-        event_id,
-        |listeners| -> Result<(), JsError> {
-            let func_args = [event];
-            for entry in listeners {
-                let listener = entry.0;
-                let _res = functions::call_function_q(q_ctx, listener, &func_args, None)?;
-
-                // todo chekc if _res is bool, for cancel and such
-                // and if event is cancelabble and preventDefault was called and such
-            }
-            Ok(())
-        },
-    )?;
-
-    Ok(true)
-}
-
-pub fn _set_event_bubble_target() {
-    unimplemented!()
+    let (records, _removed) =
+        with_static_listener_map(q_ctx, proxy_class_name, event_id, |listeners| {
+            get_records(q_ctx, listeners)
+        });
+    let target = q_ctx
+        .proxy_constructor_refs
+        .borrow()
+        .get(proxy_class_name)
+        .cloned()
+        .unwrap_or_else(quickjs_utils::new_null_ref);
+    let path = arrays::create_array_q(q_ctx)?;
+    arrays::set_element_q(q_ctx, &path, 0, &new_path_entry(q_ctx, &target, &records)?)?;
+
+    let res = invoke_intrinsic(q_ctx, "dispatch", &[&event, &path])?;
+    Ok(res.is_bool() && res.to_bool())
 }
 
 fn events_instance_finalizer(q_ctx: &QuickJsRealmAdapter, proxy_class_name: &str, id: usize) {
@@ -270,7 +836,6 @@ fn events_instance_finalizer(q_ctx: &QuickJsRealmAdapter, proxy_class_name: &str
 
 pub(crate) fn impl_event_target(proxy: Proxy) -> Proxy {
     // add (static)     addEventListener(), dispatchEvent(), removeEventListener()
-    // a fn getEventsObj will be used to conditionally create and return an Object with Sets per eventId to store the listeners
 
     let proxy_class_name = proxy.get_class_name();
 
@@ -299,6 +864,30 @@ pub(crate) fn impl_event_target(proxy: Proxy) -> Proxy {
     proxy
 }
 
+/// check the args of addEventListener and removeEventListener (type, listener and options), the listener may be a function or an object with a handleEvent method
+fn is_valid_listener_args(q_ctx: &QuickJsRealmAdapter, args: &[QuickJsValueAdapter]) -> bool {
+    args.len() >= 2
+        && args[0].is_string()
+        && (functions::is_function_q(q_ctx, &args[1]) || args[1].is_object())
+}
+
+/// get the event id and event object from the args of dispatchEvent, these are either an Event or an event id and an object
+fn get_dispatch_args(
+    q_ctx: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<(String, QuickJsValueAdapter), JsError> {
+    if args.len() == 1 && args[0].is_object() {
+        let type_ref = objects::get_property_q(q_ctx, &args[0], "type")?;
+        Ok((primitives::to_string_q(q_ctx, &type_ref)?, args[0].clone()))
+    } else if args.len() == 2 && args[0].is_string() {
+        Ok((primitives::to_string_q(q_ctx, &args[0])?, args[1].clone()))
+    } else {
+        Err(JsError::new_str(
+            "dispatchEvent requires an Event or 2 arguments (eventId: String and eventObj: Object)",
+        ))
+    }
+}
+
 unsafe extern "C" fn ext_add_event_listener(
     ctx: *mut q::JSContext,
     this_val: q::JSValue,
@@ -308,9 +897,6 @@ unsafe extern "C" fn ext_add_event_listener(
     // require 2 or 3 args, string, function, object
     // if third is boolean it is option {capture: true}
 
-    // events_obj will be structured like this
-    // ___eventListeners___: {eventId<String>: Map<Function, Object>} // the key of the map is the function, the value are the options
-
     let res = QuickJsRealmAdapter::with_context(ctx, |q_ctx| {
         let args = parse_args(ctx, argc, argv);
 
@@ -319,33 +905,24 @@ unsafe extern "C" fn ext_add_event_listener(
 
         let proxy_info = get_proxy_instance_info(this_ref.borrow_value());
 
-        if args.len() < 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
+        if !is_valid_listener_args(q_ctx, &args) {
             Err(JsError::new_str("addEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
         } else {
             let event_id = primitives::to_string_q(q_ctx, &args[0])?;
             let listener_func = args[1].clone();
+            let options = args
+                .get(2)
+                .cloned()
+                .unwrap_or_else(quickjs_utils::new_undefined_ref);
 
-            // use the passed options arg or create a new obj
-            let options_obj = if args.len() == 3 && args[2].is_object() {
-                args[2].clone()
-            } else {
-                create_object_q(q_ctx)?
-            };
-            // if the third args was a boolean then set that bool as the capture option
-            if args.len() == 3 && args[2].is_bool() {
-                set_property_q(q_ctx, &options_obj, "capture", &args[2])?;
-            }
-
-            add_event_listener(
+            try_add_event_listener(
                 q_ctx,
                 proxy_info.class_name.as_str(),
                 event_id.as_str(),
                 proxy_info.id,
                 listener_func,
-                options_obj,
-            );
-
-            Ok(())
+                options,
+            )
         }
     });
     match res {
@@ -368,18 +945,19 @@ unsafe extern "C" fn ext_remove_event_listener(
 
         let proxy_info = get_proxy_instance_info(this_ref.borrow_value());
 
-        if args.len() != 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
+        if !is_valid_listener_args(q_ctx, &args) {
             Err(JsError::new_str("removeEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
         } else {
             let event_id = primitives::to_string_q(q_ctx, &args[0])?;
-            let listener_func = args[1].clone();
+            let listener_func = &args[1];
+            let capture = get_capture_option(q_ctx, args.get(2));
 
-            remove_event_listener(
+            let _removed = with_listener_map_mut(
                 q_ctx,
                 proxy_info.class_name.as_str(),
-                event_id.as_str(),
                 proxy_info.id,
-                &listener_func,
+                event_id.as_str(),
+                |map| remove_listener(q_ctx, map, listener_func, Some(capture)),
             );
 
             Ok(())
@@ -400,25 +978,20 @@ unsafe extern "C" fn ext_dispatch_event(
     let res = QuickJsRealmAdapter::with_context(ctx, |q_ctx| {
         let args = parse_args(ctx, argc, argv);
 
-        let this_ref =
-            QuickJsValueAdapter::new(ctx, this_val, true, true, "remove_event_listener_this");
+        let this_ref = QuickJsValueAdapter::new(ctx, this_val, true, true, "dispatch_event_this");
 
         let proxy_info = get_proxy_instance_info(this_ref.borrow_value());
 
-        if args.len() != 2 || !args[0].is_string() {
-            Err(JsError::new_str(
-                "dispatchEvent requires at least 2 arguments (eventId: String and eventObj: Object)",
-            ))
-        } else {
-            let event_id = primitives::to_string_q(q_ctx, &args[0])?;
-            let evt_obj = args[1].clone();
-
-            let proxy = get_proxy(q_ctx, proxy_info.class_name.as_str()).unwrap();
+        let (event_id, evt_obj) = get_dispatch_args(q_ctx, &args)?;
 
-            let res = dispatch_event(q_ctx, &proxy, proxy_info.id, event_id.as_str(), evt_obj)?;
-
-            Ok(res)
-        }
+        dispatch_instance_event(
+            q_ctx,
+            proxy_info.class_name.as_str(),
+            proxy_info.id,
+            Some(this_ref.clone()),
+            event_id.as_str(),
+            evt_obj,
+        )
     });
     match res {
         Ok(res) => {
@@ -455,32 +1028,23 @@ unsafe extern "C" fn ext_add_static_event_listener(
 
         let proxy_name = get_static_proxy_class_name(q_ctx, &this_ref);
 
-        if args.len() < 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
+        if !is_valid_listener_args(q_ctx, &args) {
             Err(JsError::new_str("addEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
         } else {
             let event_id = primitives::to_string_q(q_ctx, &args[0])?;
             let listener_func = args[1].clone();
+            let options = args
+                .get(2)
+                .cloned()
+                .unwrap_or_else(quickjs_utils::new_undefined_ref);
 
-            // use the passed options arg or create a new obj
-            let options_obj = if args.len() == 3 && args[2].is_object() {
-                args[2].clone()
-            } else {
-                create_object_q(q_ctx)?
-            };
-            // if the third args was a boolean then set that bool as the capture option
-            if args.len() == 3 && args[2].is_bool() {
-                set_property_q(q_ctx, &options_obj, "capture", &args[2])?;
-            }
-
-            add_static_event_listener(
+            try_add_static_event_listener(
                 q_ctx,
                 proxy_name.as_str(),
                 event_id.as_str(),
                 listener_func,
-                options_obj,
-            );
-
-            Ok(())
+                options,
+            )
         }
     });
     match res {
@@ -503,18 +1067,17 @@ unsafe extern "C" fn ext_remove_static_event_listener(
 
         let proxy_name = get_static_proxy_class_name(q_ctx, &this_ref);
 
-        if args.len() != 2 || !args[0].is_string() || !functions::is_function_q(q_ctx, &args[1]) {
+        if !is_valid_listener_args(q_ctx, &args) {
             Err(JsError::new_str("removeEventListener requires at least 2 arguments (eventId: String and Listener: Function"))
         } else {
             let event_id = primitives::to_string_q(q_ctx, &args[0])?;
-            let listener_func = args[1].clone();
+            let listener_func = &args[1];
+            let capture = get_capture_option(q_ctx, args.get(2));
 
-            remove_static_event_listener(
-                q_ctx,
-                proxy_name.as_str(),
-                event_id.as_str(),
-                &listener_func,
-            );
+            let _removed =
+                with_static_listener_map(q_ctx, proxy_name.as_str(), event_id.as_str(), |map| {
+                    remove_listener(q_ctx, map, listener_func, Some(capture))
+                });
 
             Ok(())
         }
@@ -534,24 +1097,13 @@ unsafe extern "C" fn ext_dispatch_static_event(
     let res = QuickJsRealmAdapter::with_context(ctx, |q_ctx| {
         let args = parse_args(ctx, argc, argv);
 
-        let this_ref =
-            QuickJsValueAdapter::new(ctx, this_val, true, true, "remove_event_listener_this");
+        let this_ref = QuickJsValueAdapter::new(ctx, this_val, true, true, "dispatch_event_this");
 
         let proxy_name = get_static_proxy_class_name(q_ctx, &this_ref);
 
-        if args.len() != 2 || !args[0].is_string() {
-            Err(JsError::new_str(
-                "dispatchEvent requires at least 2 arguments (eventId: String and eventObj: Object)",
-            ))
-        } else {
-            let event_id = primitives::to_string_q(q_ctx, &args[0])?;
-            let evt_obj = args[1].clone();
+        let (event_id, evt_obj) = get_dispatch_args(q_ctx, &args)?;
 
-            let res =
-                dispatch_static_event(q_ctx, proxy_name.as_str(), event_id.as_str(), evt_obj)?;
-
-            Ok(res)
-        }
+        dispatch_static_event(q_ctx, proxy_name.as_str(), event_id.as_str(), evt_obj)
     });
     match res {
         Ok(res) => {
@@ -564,12 +1116,14 @@ unsafe extern "C" fn ext_dispatch_static_event(
 
 #[cfg(test)]
 pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
     use crate::facades::tests::init_test_rt;
-    use crate::jsutils::Script;
+    use crate::jsutils::{JsError, Script, ScriptPreProcessor};
     use crate::quickjs_utils::get_global_q;
     use crate::quickjs_utils::objects::{create_object_q, get_property_q};
     use crate::quickjs_utils::primitives::to_i32;
-    use crate::reflection::eventtarget::dispatch_event;
+    use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+    use crate::reflection::eventtarget::{dispatch_event, set_event_bubble_target};
     use crate::reflection::{get_proxy, Proxy};
     use std::sync::{Arc, Mutex};
 
@@ -672,4 +1226,167 @@ pub mod tests {
             assert_eq!(_target_ref.get_ref_count(), 2); // one for me one for global
         });
     }
+
+    #[cfg(feature = "eventtarget")]
+    #[test]
+    fn test_proxy_event_semantics() {
+        let rt = init_test_rt();
+        rt.loop_realm_sync(None, |_rt, realm| {
+            Proxy::new()
+                .name("TreeNode")
+                .constructor(|_rt, _realm, _id, _args| Ok(()))
+                .method("setParent", |_rt, realm, id, args| {
+                    set_event_bubble_target(realm, "TreeNode", *id, args.first().cloned());
+                    realm.create_undefined()
+                })
+                .event_target()
+                .install(realm, true)
+                .expect("could not install TreeNode");
+        });
+
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_proxy_event_semantics.js",
+                    r#"
+                    (() => {
+                        const parent = new TreeNode();
+                        const child = new TreeNode();
+                        child.setParent(parent);
+                        const log = [];
+                        parent.addEventListener('change', (e) => log.push('parent-capture:' + e.eventPhase), {capture: true});
+                        parent.addEventListener('change', (e) => log.push('parent:' + e.eventPhase + ':' + (e.target === child) + ':' + (e.currentTarget === parent)));
+                        child.addEventListener('change', (e) => { log.push('child:' + e.detail); e.preventDefault(); });
+                        child.addEventListener('change', () => log.push('once'), {once: true});
+                        const stopper = (e) => { log.push('stopper'); e.stopImmediatePropagation(); };
+                        child.addEventListener('change', stopper);
+                        child.addEventListener('change', stopper);
+                        const controller = new AbortController();
+                        child.addEventListener('change', () => log.push('aborted'), {signal: controller.signal});
+                        controller.abort();
+                        const r1 = child.dispatchEvent(new CustomEvent('change', {detail: 'a', bubbles: true, cancelable: true}));
+                        child.removeEventListener('change', stopper);
+                        const r2 = child.dispatchEvent(new CustomEvent('change', {detail: 'b', bubbles: true}));
+                        const r3 = child.dispatchEvent('legacy', {cancelable: true});
+                        child.addEventListener('legacy', () => false);
+                        const r4 = child.dispatchEvent('legacy', {cancelable: true});
+                        return [log.join(','), r1, r2, r3, r4].join('|');
+                    })();
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "parent-capture:1,child:a,once,stopper,parent-capture:1,child:b,parent:3:true:true|false|true|true|false"
+        );
+    }
+
+    #[cfg(feature = "eventtarget")]
+    #[test]
+    fn test_proxy_event_abort() {
+        let rt = init_test_rt();
+        let counts = rt.loop_realm_sync(None, |_rt, realm| {
+            Proxy::new()
+                .name("AbortableThing")
+                .constructor(|_rt, _realm, _id, _args| Ok(()))
+                .event_target()
+                .install(realm, true)
+                .expect("could not install AbortableThing");
+
+            let count_listeners = |realm: &QuickJsRealmAdapter| -> usize {
+                let instance_listeners: usize = realm
+                    .proxy_event_listeners
+                    .borrow()
+                    .get("AbortableThing")
+                    .map(|instances| {
+                        instances
+                            .values()
+                            .flat_map(|event_id_map| event_id_map.values())
+                            .map(|listeners| listeners.len())
+                            .sum()
+                    })
+                    .unwrap_or(0);
+                let static_listeners: usize = realm
+                    .proxy_static_event_listeners
+                    .borrow()
+                    .get("AbortableThing")
+                    .map(|event_id_map| {
+                        event_id_map
+                            .values()
+                            .map(|listeners| listeners.len())
+                            .sum()
+                    })
+                    .unwrap_or(0);
+                instance_listeners + static_listeners
+            };
+
+            realm
+                .eval(Script::new(
+                    "test_proxy_event_abort.js",
+                    r#"
+                    globalThis.abortableThing = new AbortableThing();
+                    globalThis.abortController = new AbortController();
+                    abortableThing.addEventListener('change', () => {}, {signal: abortController.signal});
+                    abortableThing.addEventListener('change', () => {});
+                    AbortableThing.addEventListener('change', () => {}, {signal: abortController.signal});
+                    "#,
+                ))
+                .expect("script failed");
+            let before = count_listeners(realm);
+            realm
+                .eval(Script::new(
+                    "test_proxy_event_abort2.js",
+                    "abortController.abort();",
+                ))
+                .expect("abort failed");
+            (before, count_listeners(realm))
+        });
+        // the listeners are removed when the signal is aborted, not at the next dispatch
+        assert_eq!(counts, (3, 1));
+    }
+
+    struct PathRecorder {
+        paths: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ScriptPreProcessor for PathRecorder {
+        fn process(&self, script: &mut Script) -> Result<(), JsError> {
+            self.paths
+                .lock()
+                .unwrap()
+                .push(script.get_path().to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_intrinsics_not_pre_processed() {
+        let paths = Arc::new(Mutex::new(vec![]));
+        let rt = QuickJsRuntimeBuilder::new()
+            .script_pre_processor(PathRecorder {
+                paths: paths.clone(),
+            })
+            .build();
+        let res = rt.loop_realm_sync(None, |_rt, realm| {
+            Proxy::new()
+                .name("PlainThing")
+                .constructor(|_rt, _realm, _id, _args| Ok(()))
+                .event_target()
+                .install(realm, true)
+                .expect("could not install PlainThing");
+            realm
+                .eval(Script::new(
+                    "test_intrinsics.js",
+                    "let plainThing = new PlainThing(); let called = 0; plainThing.addEventListener('e', () => called++); plainThing.dispatchEvent('e', {}); called;",
+                ))
+                .expect("script failed")
+                .to_i32()
+        });
+        assert_eq!(res, 1);
+        let paths = &*paths.lock().unwrap();
+        assert!(paths.iter().any(|p| p == "test_intrinsics.js"));
+        assert!(!paths.iter().any(|p| p == "quickjs_runtime_eventtarget.js"));
+    }
 }
@@ -0,0 +1,155 @@
+//! iterator utils for Proxy classes, see [Proxy::iterator](crate::reflection::Proxy::iterator) and [Proxy::async_iterator](crate::reflection::Proxy::async_iterator)
+//!
+
+use crate::jsutils::JsError;
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::reflection::{define_symbol_property, ProxyIterator, ProxyStream};
+use crate::values::JsValueFacade;
+use futures::lock::Mutex;
+use futures::StreamExt;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// create an iterator result object ({value, done})
+fn new_iterator_result(
+    realm: &QuickJsRealmAdapter,
+    value: &QuickJsValueAdapter,
+    done: bool,
+) -> Result<QuickJsValueAdapter, JsError> {
+    let res = realm.create_object()?;
+    realm.set_object_property(&res, "value", value)?;
+    realm.set_object_property(&res, "done", &realm.create_boolean(done)?)?;
+    Ok(res)
+}
+
+/// create a JS iterator object which takes its items from a rust Iterator
+/// the Iterator is dropped when it is done, when it produced an Err or when the loop is exited early (iterator.return())
+pub(crate) fn new_iterator(
+    realm: &QuickJsRealmAdapter,
+    iterator: Box<ProxyIterator>,
+) -> Result<QuickJsValueAdapter, JsError> {
+    let state = Rc::new(RefCell::new(Some(iterator)));
+    let next_state = state.clone();
+
+    let next_func = realm.create_function(
+        "next",
+        move |realm, _this, _args| {
+            let item = match &mut *next_state.borrow_mut() {
+                Some(iterator) => iterator.next(),
+                None => None,
+            };
+            match item {
+                Some(Ok(value)) => {
+                    let value = realm.from_js_value_facade(value)?;
+                    new_iterator_result(realm, &value, false)
+                }
+                Some(Err(e)) => {
+                    let _ = next_state.borrow_mut().take();
+                    Err(e)
+                }
+                None => {
+                    let _ = next_state.borrow_mut().take();
+                    new_iterator_result(realm, &realm.create_undefined()?, true)
+                }
+            }
+        },
+        0,
+    )?;
+    let return_func = realm.create_function(
+        "return",
+        move |realm, _this, args| {
+            let _ = state.borrow_mut().take();
+            let value = match args.first() {
+                Some(value) => value.clone(),
+                None => realm.create_undefined()?,
+            };
+            new_iterator_result(realm, &value, true)
+        },
+        1,
+    )?;
+
+    let iterator_obj = realm.create_object()?;
+    realm.set_object_property(&iterator_obj, "next", &next_func)?;
+    realm.set_object_property(&iterator_obj, "return", &return_func)?;
+    // iterators are iterable themselves
+    let self_func = realm.create_function(
+        "[Symbol.iterator]",
+        |_realm, this, _args| Ok(this.clone()),
+        0,
+    )?;
+    define_symbol_property(realm, &iterator_obj, "iterator", &self_func)?;
+    Ok(iterator_obj)
+}
+
+/// create a JS async iterator object which takes its items from a rust Stream
+/// the Stream is only polled when a script calls next() so a slow consumer is never flooded (back-pressure),
+/// calls to next() are serialized and the Stream is dropped when it is done, when it produced an Err or when the loop is exited early (iterator.return())
+pub(crate) fn new_async_iterator(
+    realm: &QuickJsRealmAdapter,
+    stream: ProxyStream,
+) -> Result<QuickJsValueAdapter, JsError> {
+    let state = Arc::new(Mutex::new(Some(stream)));
+    let next_state = state.clone();
+
+    let next_func = realm.create_function(
+        "next",
+        move |realm, _this, _args| {
+            let state = next_state.clone();
+            realm.create_resolving_promise_async(
+                async move {
+                    let mut lock = state.lock().await;
+                    let item = match lock.as_mut() {
+                        Some(stream) => stream.next().await,
+                        None => None,
+                    };
+                    match item {
+                        Some(Ok(value)) => Ok(Some(value)),
+                        Some(Err(e)) => {
+                            let _ = lock.take();
+                            Err(e)
+                        }
+                        None => {
+                            let _ = lock.take();
+                            Ok(None)
+                        }
+                    }
+                },
+                |realm, item: Option<JsValueFacade>| match item {
+                    Some(value) => {
+                        let value = realm.from_js_value_facade(value)?;
+                        new_iterator_result(realm, &value, false)
+                    }
+                    None => new_iterator_result(realm, &realm.create_undefined()?, true),
+                },
+            )
+        },
+        0,
+    )?;
+    let return_func = realm.create_function(
+        "return",
+        move |realm, _this, _args| {
+            let state = state.clone();
+            realm.create_resolving_promise_async(
+                async move {
+                    let _ = state.lock().await.take();
+                    Ok(())
+                },
+                |realm, _| new_iterator_result(realm, &realm.create_undefined()?, true),
+            )
+        },
+        1,
+    )?;
+
+    let iterator_obj = realm.create_object()?;
+    realm.set_object_property(&iterator_obj, "next", &next_func)?;
+    realm.set_object_property(&iterator_obj, "return", &return_func)?;
+    let self_func = realm.create_function(
+        "[Symbol.asyncIterator]",
+        |_realm, this, _args| Ok(this.clone()),
+        0,
+    )?;
+    define_symbol_property(realm, &iterator_obj, "asyncIterator", &self_func)?;
+    Ok(iterator_obj)
+}
@@ -0,0 +1,185 @@
+//! runtime support for Proxy classes generated by the `#[js_class]` macro (see the `macros` feature)
+//!
+//! the generated code stores instances in a [JsClassInstances] and converts arguments and return values with serde
+//! (see [serialization](crate::quickjs_utils::serialization)), these utils may also be used when writing a Proxy by hand
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! use quickjs_runtime::reflection::jsclass::{arg, ret, JsClassInstances};
+//! use quickjs_runtime::reflection::Proxy;
+//!
+//! struct Counter {
+//!     count: i32,
+//! }
+//!
+//! thread_local! {
+//!     static COUNTERS: JsClassInstances<Counter> = JsClassInstances::new();
+//! }
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! rt.loop_realm_sync(None, |_rt, realm| {
+//!     Proxy::new()
+//!         .name("Counter")
+//!         .constructor(|_rt, realm, id, args| {
+//!             let count: i32 = arg(realm, args, 0)?;
+//!             COUNTERS.with(|c| c.insert(realm, id, Counter { count }));
+//!             Ok(())
+//!         })
+//!         .method("increment", |_rt, realm, id, _args| {
+//!             let res = COUNTERS.with(|c| c.with_instance_mut(realm, *id, |counter| {
+//!                 counter.count += 1;
+//!                 counter.count
+//!             }))?;
+//!             ret(realm, &res)
+//!         })
+//!         .finalizer(|_rt, realm, id| {
+//!             COUNTERS.with(|c| c.remove(realm, id));
+//!         })
+//!         .install(realm, true)
+//!         .expect("could not install Counter");
+//! });
+//! let res = rt.eval_sync(None, Script::new("counter.js", "new Counter(41).increment();")).expect("script failed");
+//! assert_eq!(res.get_i32(), 42);
+//! ```
+
+use crate::jsutils::JsError;
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::reflection::JsProxyInstanceId;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::rc::Rc;
+
+/// storage for the rust instances of a Proxy class, instances are keyed by realm and instance id
+/// this is meant to be used in a thread_local
+pub struct JsClassInstances<T> {
+    instances: RefCell<HashMap<(String, JsProxyInstanceId), Rc<RefCell<T>>>>,
+}
+
+impl<T> Default for JsClassInstances<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> JsClassInstances<T> {
+    pub fn new() -> Self {
+        Self {
+            instances: RefCell::new(HashMap::new()),
+        }
+    }
+    /// store an instance (usually from the constructor of a Proxy)
+    pub fn insert(&self, realm: &QuickJsRealmAdapter, id: JsProxyInstanceId, instance: T) {
+        self.instances
+            .borrow_mut()
+            .insert((realm.id.clone(), id), Rc::new(RefCell::new(instance)));
+    }
+    /// remove an instance (usually from the finalizer of a Proxy)
+    pub fn remove(&self, realm: &QuickJsRealmAdapter, id: JsProxyInstanceId) -> Option<T> {
+        self.instances
+            .borrow_mut()
+            .remove(&(realm.id.clone(), id))
+            .and_then(|instance| Rc::try_unwrap(instance).ok())
+            .map(|instance| instance.into_inner())
+    }
+    /// the number of stored instances
+    pub fn len(&self) -> usize {
+        self.instances.borrow().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.instances.borrow().is_empty()
+    }
+    /// run a closure with a reference to an instance
+    pub fn with_instance<R, C>(
+        &self,
+        realm: &QuickJsRealmAdapter,
+        id: JsProxyInstanceId,
+        consumer: C,
+    ) -> Result<R, JsError>
+    where
+        C: FnOnce(&T) -> R,
+    {
+        // the map is only borrowed to look up the instance so a method may create or finalize other instances
+        let instance = self.get_instance(realm, id)?;
+        let borrowed = instance.try_borrow().map_err(|_| {
+            JsError::new_str(
+                "instance is already borrowed mutably (recursive call to a &mut self method?)",
+            )
+        })?;
+        Ok(consumer(&borrowed))
+    }
+    /// run a closure with a mutable reference to an instance
+    pub fn with_instance_mut<R, C>(
+        &self,
+        realm: &QuickJsRealmAdapter,
+        id: JsProxyInstanceId,
+        consumer: C,
+    ) -> Result<R, JsError>
+    where
+        C: FnOnce(&mut T) -> R,
+    {
+        let instance = self.get_instance(realm, id)?;
+        let mut borrowed = instance
+            .try_borrow_mut()
+            .map_err(|_| JsError::new_str("instance is already borrowed (recursive call?)"))?;
+        Ok(consumer(&mut borrowed))
+    }
+    fn get_instance(
+        &self,
+        realm: &QuickJsRealmAdapter,
+        id: JsProxyInstanceId,
+    ) -> Result<Rc<RefCell<T>>, JsError> {
+        let instances = &*self.instances.borrow();
+        instances
+            .get(&(realm.id.clone(), id))
+            .cloned()
+            .ok_or_else(|| JsError::new_string(format!("no instance found with id {id}")))
+    }
+}
+
+/// convert an argument to a rust value, a missing argument is converted as undefined (so it may be an Option)
+pub fn arg<T>(
+    realm: &QuickJsRealmAdapter,
+    args: &[QuickJsValueAdapter],
+    index: usize,
+) -> Result<T, JsError>
+where
+    T: DeserializeOwned,
+{
+    let res = match args.get(index) {
+        Some(value) => realm.from_value_adapter(value),
+        None => realm.from_value_adapter(&realm.create_undefined()?),
+    };
+    res.map_err(|e| {
+        JsError::new(
+            "TypeError".to_string(),
+            format!("invalid argument {index}: {}", e.get_message()),
+            "".to_string(),
+        )
+    })
+}
+
+/// convert a rust value to a return value
+pub fn ret<T>(realm: &QuickJsRealmAdapter, value: &T) -> Result<QuickJsValueAdapter, JsError>
+where
+    T: Serialize + ?Sized,
+{
+    realm.to_value_adapter(value)
+}
+
+/// create a Promise which resolves with the (converted) result of a future, the future is run in the runtime's thread pool
+pub fn ret_promise<F, T>(
+    realm: &QuickJsRealmAdapter,
+    future: F,
+) -> Result<QuickJsValueAdapter, JsError>
+where
+    F: Future<Output = Result<T, JsError>> + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    realm.create_resolving_promise_async(future, |realm, value| realm.to_value_adapter(&value))
+}
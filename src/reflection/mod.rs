@@ -9,6 +9,8 @@ use crate::quickjs_utils::{atoms, errors, functions, objects, parse_args, primit
 use crate::quickjsrealmadapter::QuickJsRealmAdapter;
 use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
 use crate::quickjsvalueadapter::QuickJsValueAdapter;
+use crate::values::JsValueFacade;
+use futures::stream::BoxStream;
 use libquickjs_sys as q;
 use log::trace;
 use rand::{thread_rng, Rng};
@@ -20,6 +22,9 @@ use std::rc::Rc;
 pub type JsProxyInstanceId = usize;
 
 pub mod eventtarget;
+pub mod iterators;
+pub mod jsclass;
+pub mod typings;
 
 pub type ProxyConstructor = dyn Fn(
         &QuickJsRuntimeAdapter,
@@ -82,6 +87,11 @@ pub type ProxyCatchAllSetter = dyn Fn(
     ) -> Result<(), JsError>
     + 'static;
 
+/// the items of a Proxy iterator, see [Proxy::iterator]
+pub type ProxyIterator = dyn Iterator<Item = Result<JsValueFacade, JsError>>;
+/// the items of a Proxy async iterator, see [Proxy::async_iterator]
+pub type ProxyStream = BoxStream<'static, Result<JsValueFacade, JsError>>;
+
 static CNAME: &str = "ProxyInstanceClass\0";
 static SCNAME: &str = "ProxyStaticClass\0";
 
@@ -213,7 +223,7 @@ pub(crate) fn init_statics() {
     });
 }
 
-fn next_id(proxy: &Proxy) -> usize {
+pub(crate) fn next_id(proxy: &Proxy) -> usize {
     let mappings = &*proxy.proxy_instance_id_mappings.borrow();
     if mappings.len() == MAX_INSTANCE_NUM {
         panic!("too many instances"); // todo report ex
@@ -226,6 +236,240 @@ fn next_id(proxy: &Proxy) -> usize {
     r
 }
 
+/// name of the property of the static class object which holds the class_name of the Proxy
+const CLASS_NAME_PROP: &str = "__proxy_class__";
+
+/// get a Proxy and its parent classes (see [Proxy::extends]), the Proxy itself comes first
+fn get_proxy_chain<'a>(
+    registry: &'a HashMap<String, Rc<Proxy>>,
+    class_name: &'a str,
+) -> Vec<&'a Proxy> {
+    let mut chain: Vec<&Proxy> = vec![];
+    let mut next = Some(class_name);
+    while let Some(cn) = next {
+        match registry.get(cn) {
+            Some(proxy) if !chain.iter().any(|p| std::ptr::eq(*p, proxy.as_ref())) => {
+                chain.push(proxy);
+                next = proxy.extends.as_deref();
+            }
+            _ => break,
+        }
+    }
+    chain
+}
+
+/// get the class_name of a Proxy constructor, this also works for its static class object and (JS) subclasses of the constructor
+/// # Safety
+/// please make sure context is still valid
+unsafe fn get_static_class_name(
+    context: *mut q::JSContext,
+    obj: &QuickJsValueAdapter,
+) -> Option<String> {
+    if !obj.is_object() {
+        return None;
+    }
+    let class_name_ref = get_property(context, obj, CLASS_NAME_PROP).ok()?;
+    if class_name_ref.is_string() {
+        primitives::to_string(context, &class_name_ref).ok()
+    } else {
+        None
+    }
+}
+
+/// define a property keyed by a well-known Symbol (e.g. "iterator" for Symbol.iterator), the property is not enumerable, writable or configurable
+pub(crate) fn define_symbol_property(
+    q_ctx: &QuickJsRealmAdapter,
+    obj: &QuickJsValueAdapter,
+    symbol_name: &str,
+    value: &QuickJsValueAdapter,
+) -> Result<(), JsError> {
+    let symbol_ref = objects::get_property_q(q_ctx, &quickjs_utils::get_global_q(q_ctx), "Symbol")?;
+    let well_known_symbol_ref = objects::get_property_q(q_ctx, &symbol_ref, symbol_name)?;
+    unsafe {
+        let atom = q::JS_ValueToAtom(q_ctx.context, *well_known_symbol_ref.borrow_value());
+        let res = q::JS_DefinePropertyValue(
+            q_ctx.context,
+            *obj.borrow_value(),
+            atom,
+            value.clone_value_incr_rc(),
+            0,
+        );
+        q::JS_FreeAtom(q_ctx.context, atom);
+        if res < 0 {
+            return Err(JsError::new_string(format!(
+                "could not define Symbol.{symbol_name}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn set_prototype(
+    q_ctx: &QuickJsRealmAdapter,
+    obj: &QuickJsValueAdapter,
+    prototype: &QuickJsValueAdapter,
+) -> Result<(), JsError> {
+    let res = unsafe {
+        q::JS_SetPrototype(
+            q_ctx.context,
+            *obj.borrow_value(),
+            *prototype.borrow_value(),
+        )
+    };
+    if res < 0 {
+        if let Some(err) = q_ctx.get_exception_ctx() {
+            Err(err)
+        } else {
+            Err(JsError::new_str("could not set prototype"))
+        }
+    } else {
+        Ok(())
+    }
+}
+
+/// call the constructors of a Proxy and its parent classes (parents first), when a constructor fails the parents which were already constructed are finalized
+pub(crate) fn call_constructors(
+    q_js_rt: &QuickJsRuntimeAdapter,
+    q_ctx: &QuickJsRealmAdapter,
+    registry: &HashMap<String, Rc<Proxy>>,
+    class_name: &str,
+    instance_id: usize,
+    args: &[QuickJsValueAdapter],
+) -> Result<(), JsError> {
+    let chain = get_proxy_chain(registry, class_name);
+    for (x, proxy) in chain.iter().enumerate().rev() {
+        if let Some(constructor) = &proxy.constructor {
+            if let Err(err) = constructor(q_js_rt, q_ctx, instance_id, args) {
+                for constructed in chain.iter().skip(x + 1) {
+                    for finalizer in &constructed.finalizers {
+                        finalizer(q_js_rt, q_ctx, instance_id);
+                    }
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Symbol.hasInstance of Proxy classes, instances of a Proxy class (or one of its subclasses) are checked by class_name,
+/// for JS subclasses of a Proxy class the prototype chain is checked (like instanceof normally does)
+fn proxy_has_instance(
+    realm: &QuickJsRealmAdapter,
+    this: &QuickJsValueAdapter,
+    args: &[QuickJsValueAdapter],
+) -> Result<QuickJsValueAdapter, JsError> {
+    let instance = match args.first() {
+        Some(instance) if instance.is_object() && this.is_object() => instance,
+        _ => return realm.create_boolean(false),
+    };
+
+    if let Some(class_name) = unsafe { get_static_class_name(realm.context, this) } {
+        let is_proxy_constructor = realm
+            .proxy_constructor_refs
+            .borrow()
+            .get(&class_name)
+            .map(|constructor_ref| constructor_ref == this)
+            .unwrap_or(false);
+        if is_proxy_constructor && instance.is_proxy_instance() {
+            let (instance_class_name, _id) = realm.get_proxy_instance_info(instance)?;
+            let registry = &*realm.proxy_registry.borrow();
+            let res = get_proxy_chain(registry, instance_class_name.as_str())
+                .iter()
+                .any(|proxy| proxy.get_class_name() == class_name);
+            return realm.create_boolean(res);
+        }
+    }
+
+    let prototype_ref = objects::get_property_q(realm, this, "prototype")?;
+    let mut current = objects::get_prototype_of_q(realm, instance)?;
+    while current.is_object() {
+        if current == prototype_ref {
+            return realm.create_boolean(true);
+        }
+        current = objects::get_prototype_of_q(realm, &current)?;
+    }
+    realm.create_boolean(false)
+}
+
+/// get or set a property on the prototype of an instance with Reflect.get / Reflect.set (with the instance as receiver),
+/// this is how properties which are not implemented by the Proxy (like the methods of a JS subclass) are found
+/// # Safety
+/// please make sure context is still valid
+unsafe fn reflect_prototype_prop(
+    context: *mut q::JSContext,
+    obj: &QuickJsValueAdapter,
+    atom: q::JSAtom,
+    receiver: &QuickJsValueAdapter,
+    value: Option<&QuickJsValueAdapter>,
+) -> Result<Option<QuickJsValueAdapter>, JsError> {
+    let prototype_ref = objects::get_prototype_of(context, obj)?;
+    if !prototype_ref.is_object() {
+        return Ok(None);
+    }
+    let key_ref = QuickJsValueAdapter::new(
+        context,
+        q::JS_AtomToValue(context, atom),
+        false,
+        true,
+        "reflection::reflect_prototype_prop key",
+    );
+    let reflect_ref = get_property(context, &quickjs_utils::get_global(context), "Reflect")?;
+    let res = if let Some(value) = value {
+        let set_ref = get_property(context, &reflect_ref, "set")?;
+        functions::call_function(
+            context,
+            &set_ref,
+            &[prototype_ref, key_ref, value.clone(), receiver.clone()],
+            None,
+        )?
+    } else {
+        let get_ref = get_property(context, &reflect_ref, "get")?;
+        functions::call_function(
+            context,
+            &get_ref,
+            &[prototype_ref, key_ref, receiver.clone()],
+            None,
+        )?
+    };
+    Ok(Some(res))
+}
+
+/// get a property which is defined by a JS subclass, those are found in the prototype chain before the prototype of the Proxy class
+/// # Safety
+/// please make sure context is still valid
+unsafe fn get_subclass_prop(
+    q_ctx: &QuickJsRealmAdapter,
+    obj: &QuickJsValueAdapter,
+    atom: q::JSAtom,
+    receiver: &QuickJsValueAdapter,
+    class_name: &str,
+) -> Result<Option<QuickJsValueAdapter>, JsError> {
+    let proxy_constructor_ref = q_ctx
+        .proxy_constructor_refs
+        .borrow()
+        .get(class_name)
+        .cloned();
+    let proxy_prototype_ref = match proxy_constructor_ref {
+        Some(constructor_ref) => objects::get_property_q(q_ctx, &constructor_ref, "prototype")?,
+        None => return Ok(None),
+    };
+    let mut current = objects::get_prototype_of(q_ctx.context, obj)?;
+    while current.is_object() && current != proxy_prototype_ref {
+        if q::JS_GetOwnProperty(
+            q_ctx.context,
+            std::ptr::null_mut(),
+            *current.borrow_value(),
+            atom,
+        ) > 0
+        {
+            return reflect_prototype_prop(q_ctx.context, obj, atom, receiver, None);
+        }
+        current = objects::get_prototype_of(q_ctx.context, &current)?;
+    }
+    Ok(None)
+}
+
 /// The Proxy struct can be used to create a class in JavaScript who's methods can be implemented in rust
 /// # Example
 /// ```rust
@@ -329,6 +573,8 @@ pub struct Proxy {
     )>,
     is_event_target: bool,
     is_static_event_target: bool,
+    extends: Option<String>,
+    typings: typings::ProxyTypings,
     pub(crate) proxy_instance_id_mappings: RefCell<HashMap<usize, Box<ProxyInstanceInfo>>>,
 }
 
@@ -362,6 +608,8 @@ impl Proxy {
             static_catch_all: None,
             is_event_target: false,
             is_static_event_target: false,
+            extends: None,
+            typings: Default::default(),
             proxy_instance_id_mappings: RefCell::new(Default::default()),
         }
     }
@@ -409,6 +657,42 @@ impl Proxy {
             cn.to_string()
         }
     }
+    /// let this Proxy class extend another Proxy class (by class_name, e.g. "com.hirofa.Animal"), the parent class should be installed first
+    ///
+    /// instances share a single instance id with their parent classes, the constructors of the parent classes are called first (with the same arguments),
+    /// methods, getters and setters (also static) are looked up in the class and then its parent classes and all finalizers are called
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::jsutils::Script;
+    ///
+    /// let rt = QuickJsRuntimeBuilder::new().build();
+    /// rt.loop_realm_sync(None, |_rt, realm| {
+    ///     Proxy::new()
+    ///         .name("Animal")
+    ///         .constructor(|_rt, _realm, _id, _args| Ok(()))
+    ///         .method("speak", |_rt, realm, _id, _args| realm.create_string("..."))
+    ///         .install(realm, true)
+    ///         .expect("could not install Animal");
+    ///     Proxy::new()
+    ///         .name("Dog")
+    ///         .extends("Animal")
+    ///         .method("fetch", |_rt, realm, _id, _args| realm.create_string("stick"))
+    ///         .install(realm, true)
+    ///         .expect("could not install Dog");
+    /// });
+    /// let res = rt.eval_sync(None, Script::new("extends.js", "let d = new Dog(); [d instanceof Animal, d.speak(), d.fetch()].join(',');")).expect("script failed");
+    /// assert_eq!(res.get_str(), "true,...,stick");
+    /// ```
+    pub fn extends(mut self, parent_class_name: &str) -> Self {
+        self.extends = Some(parent_class_name.to_string());
+        self
+    }
+    /// get the class_name of the parent class (see [Proxy::extends])
+    pub fn get_parent_class_name(&self) -> Option<&str> {
+        self.extends.as_deref()
+    }
     /// add a constructor for the Proxy class
     /// this will enable a script to create a new instance of a Proxy class
     /// if omitted the Proxy class will not be constructable from script
@@ -528,6 +812,7 @@ impl Proxy {
     {
         self.getters_setters
             .insert(name.to_string(), (Box::new(getter), Box::new(setter)));
+        self.typings.readonly.remove(name);
         self
     }
     /// add a getter and setter to the Proxy class, these will be available as a member of an instance of this Proxy class
@@ -540,7 +825,9 @@ impl Proxy {
             ) -> Result<QuickJsValueAdapter, JsError>
             + 'static,
     {
-        self.getter_setter(name, getter, |_rt, _realm, _id, _val| Ok(()))
+        let mut ret = self.getter_setter(name, getter, |_rt, _realm, _id, _val| Ok(()));
+        ret.typings.readonly.insert(name.to_string());
+        ret
     }
     /// add a catchall getter and setter to the Proxy class, these will be used for properties which are not specifically defined as getter, setter or method in this Proxy
     pub fn catch_all_getter_setter<G, S>(mut self, getter: G, setter: S) -> Self
@@ -565,6 +852,106 @@ impl Proxy {
 
         self
     }
+    /// make instances of the Proxy class iterable (`for (const item of instance)`), the closure is called for every iteration and returns a rust Iterator which produces the items
+    /// # Example
+    /// ```rust
+    /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::jsutils::Script;
+    /// use quickjs_runtime::values::JsValueFacade;
+    ///
+    /// let rt = QuickJsRuntimeBuilder::new().build();
+    /// rt.loop_realm_sync(None, |_rt, realm| {
+    ///     Proxy::new()
+    ///         .name("Range")
+    ///         .constructor(|_rt, _realm, _id, _args| Ok(()))
+    ///         .iterator(|_rt, _realm, _id| Ok(Box::new((1..=3).map(|i| Ok(JsValueFacade::new_i32(i))))))
+    ///         .install(realm, true)
+    ///         .expect("could not install Range");
+    /// });
+    /// let res = rt.eval_sync(None, Script::new("iter.js", "[...new Range()].join(',');")).expect("script failed");
+    /// assert_eq!(res.get_str(), "1,2,3");
+    /// ```
+    pub fn iterator<I>(self, iterator: I) -> Self
+    where
+        I: Fn(
+                &QuickJsRuntimeAdapter,
+                &QuickJsRealmAdapter,
+                &usize,
+            ) -> Result<Box<ProxyIterator>, JsError>
+            + 'static,
+    {
+        self.method("Symbol.iterator", move |rt, realm, id, _args| {
+            iterators::new_iterator(realm, iterator(rt, realm, id)?)
+        })
+    }
+    /// make instances of the Proxy class async iterable (`for await (const item of instance)`), the closure is called for every iteration and returns a Stream which produces the items
+    ///
+    /// the Stream is polled (in the runtime's thread pool) for a single item each time next() is called so a script which consumes slowly applies back-pressure,
+    /// the Stream is dropped when the loop is done or exited early
+    /// # Example
+    /// ```rust
+    /// use futures::StreamExt;
+    /// use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+    /// use quickjs_runtime::reflection::Proxy;
+    /// use quickjs_runtime::jsutils::Script;
+    /// use quickjs_runtime::values::JsValueFacade;
+    ///
+    /// let rt = QuickJsRuntimeBuilder::new().build();
+    /// rt.loop_realm_sync(None, |_rt, realm| {
+    ///     Proxy::new()
+    ///         .name("Cursor")
+    ///         .constructor(|_rt, _realm, _id, _args| Ok(()))
+    ///         .async_iterator(|_rt, _realm, _id| {
+    ///             Ok(futures::stream::iter(vec!["a", "b"]).map(|row| Ok(JsValueFacade::new_str(row))).boxed())
+    ///         })
+    ///         .install(realm, true)
+    ///         .expect("could not install Cursor");
+    /// });
+    /// let res = rt.eval_sync(None, Script::new("iter.js", "(async () => {let rows = []; for await (const row of new Cursor()) {rows.push(row);} return rows.join(',');})()")).expect("script failed");
+    /// let JsValueFacade::JsPromise { cached_promise } = res else { panic!("not a promise") };
+    /// let rows = cached_promise.get_promise_result_sync().expect("timed out").expect("rejected");
+    /// assert_eq!(rows.get_str(), "a,b");
+    /// ```
+    pub fn async_iterator<I>(self, async_iterator: I) -> Self
+    where
+        I: Fn(&QuickJsRuntimeAdapter, &QuickJsRealmAdapter, &usize) -> Result<ProxyStream, JsError>
+            + 'static,
+    {
+        self.method("Symbol.asyncIterator", move |rt, realm, id, _args| {
+            iterators::new_async_iterator(realm, async_iterator(rt, realm, id)?)
+        })
+    }
+    /// annotate a member (method, getter/setter or "constructor") with a TypeScript typing, this is used by [typings::generate_typings]
+    ///
+    /// for methods and the constructor the typing is the signature without the name (e.g. "(a: number, b: number): number"), for getters/setters it is the type (e.g. "string")
+    pub fn typing(mut self, name: &str, typing: &str) -> Self {
+        self.typings
+            .members
+            .insert(name.to_string(), typing.to_string());
+        self
+    }
+    /// annotate a static member (method or getter/setter) with a TypeScript typing, see [Proxy::typing]
+    pub fn static_typing(mut self, name: &str, typing: &str) -> Self {
+        self.typings
+            .static_members
+            .insert(name.to_string(), typing.to_string());
+        self
+    }
+    /// declare the type of the Event object of an event which is dispatched on instances (e.g. ("change", "{ value: number }")), this is used by [typings::generate_typings]
+    pub fn event_typing(mut self, event_id: &str, event_type: &str) -> Self {
+        self.typings
+            .events
+            .insert(event_id.to_string(), event_type.to_string());
+        self
+    }
+    /// declare the type of the Event object of an event which is dispatched on the Proxy class, see [Proxy::event_typing]
+    pub fn static_event_typing(mut self, event_id: &str, event_type: &str) -> Self {
+        self.typings
+            .static_events
+            .insert(event_id.to_string(), event_type.to_string());
+        self
+    }
     /// indicate the Proxy class should implement the EventTarget interface, this will result in the addEventListener, removeEventListener and dispatchEvent methods to be available on instances of the Proxy class
     pub fn event_target(mut self) -> Self {
         self.is_event_target = true;
@@ -586,7 +973,20 @@ impl Proxy {
         }
 
         let prim_cn = self.get_class_name();
-        let prim_cn2 = prim_cn.clone();
+
+        if let Some(parent_class_name) = &self.extends {
+            match get_proxy(q_ctx, parent_class_name) {
+                Some(parent) => {
+                    // listeners are stored per class so subclasses of an EventTarget get their own methods and finalizer
+                    self.is_event_target = self.is_event_target || parent.is_event_target;
+                }
+                None => {
+                    return Err(JsError::new_string(format!(
+                        "parent class {parent_class_name} of {prim_cn} is not installed"
+                    )));
+                }
+            }
+        }
 
         // todo turn these into native methods
         self = self.method("Symbol.toPrimitive", move |_rt, q_ctx, id, _args| {
@@ -597,18 +997,6 @@ impl Proxy {
             Ok(prim)
         });
         let prim_cn = self.get_class_name();
-        self = self.static_method("Symbol.hasInstance", move |_rt, realm, args| {
-            if args.len() == 1 {
-                let instance = &args[0];
-                if instance.is_proxy_instance() {
-                    let info = realm.get_proxy_instance_info(instance)?;
-                    if info.0.eq(prim_cn2.as_str()) {
-                        return realm.create_boolean(true);
-                    }
-                }
-            }
-            realm.create_boolean(false)
-        });
         self = self.static_method("Symbol.toPrimitive", move |_rt, q_ctx, _args| {
             let prim = primitives::from_string_q(q_ctx, format!("Proxy::{prim_cn}").as_str())?;
             Ok(prim)
@@ -707,6 +1095,56 @@ impl Proxy {
             0,
         )?;
 
+        // the static class object identifies the Proxy for its constructor and (JS) subclasses of that constructor
+        objects::set_property2_q(
+            q_ctx,
+            &class_val_ref,
+            CLASS_NAME_PROP,
+            &primitives::from_string_q(q_ctx, &self.get_class_name())?,
+            0,
+        )?;
+
+        // a prototype object is needed for `class X extends ProxyClass {}` and is the prototype of instances
+        let prototype_ref = objects::create_object_q(q_ctx)?;
+        if let Some(parent_class_name) = &self.extends {
+            let parent_constructor_ref = q_ctx
+                .proxy_constructor_refs
+                .borrow()
+                .get(parent_class_name)
+                .cloned()
+                .ok_or_else(|| {
+                    JsError::new_string(format!("parent class {parent_class_name} not found"))
+                })?;
+            let parent_prototype_ref =
+                objects::get_property_q(q_ctx, &parent_constructor_ref, "prototype")?;
+            set_prototype(q_ctx, &prototype_ref, &parent_prototype_ref)?;
+            set_prototype(q_ctx, &class_val_ref, &parent_constructor_ref)?;
+        }
+        objects::set_property2_q(q_ctx, &prototype_ref, "constructor", &constructor_ref, 0)?;
+        // methods are also added to the prototype so JS subclasses can call them with super.method()
+        for method_name in self.methods.keys().filter(|n| !n.starts_with("Symbol.")) {
+            let function_data_ref = primitives::from_string_q(q_ctx, method_name)?;
+            let func_ref = functions::new_native_function_data_q(
+                q_ctx,
+                Some(proxy_instance_method),
+                method_name,
+                1,
+                function_data_ref,
+            )?;
+            objects::set_property2_q(
+                q_ctx,
+                &prototype_ref,
+                method_name,
+                &func_ref,
+                (q::JS_PROP_CONFIGURABLE | q::JS_PROP_WRITABLE) as i32,
+            )?;
+        }
+        objects::set_property2_q(q_ctx, &constructor_ref, "prototype", &prototype_ref, 0)?;
+
+        let has_instance_ref =
+            functions::new_function_q(q_ctx, "[Symbol.hasInstance]", proxy_has_instance, 1)?;
+        define_symbol_property(q_ctx, &constructor_ref, "hasInstance", &has_instance_ref)?;
+
         // todo impl namespace here
         if add_variable_to_global {
             log::trace!("reflection::Proxy::install_class_prop / 8");
@@ -823,13 +1261,15 @@ pub fn new_instance2(
     q_ctx: &QuickJsRealmAdapter,
 ) -> Result<(usize, QuickJsValueAdapter), JsError> {
     let instance_id = next_id(proxy);
-    Ok((instance_id, new_instance3(proxy, instance_id, q_ctx)?))
+    Ok((instance_id, new_instance3(proxy, instance_id, q_ctx, None)?))
 }
 
+/// create an instance of a Proxy class, new_target is the constructor (or a JS subclass of the constructor) which is used for the prototype of the instance
 pub(crate) fn new_instance3(
     proxy: &Proxy,
     instance_id: usize,
     q_ctx: &QuickJsRealmAdapter,
+    new_target: Option<&QuickJsValueAdapter>,
 ) -> Result<QuickJsValueAdapter, JsError> {
     let ctx = q_ctx.context;
     let class_id = PROXY_INSTANCE_CLASS_ID.with(|rc| *rc.borrow());
@@ -860,6 +1300,11 @@ pub(crate) fn new_instance3(
         };
     }
 
+    let proxy_constructor_refs = &*q_ctx.proxy_constructor_refs.borrow();
+    let proxy_constructor = proxy_constructor_refs
+        .get(&class_name)
+        .expect("proxy was not installed properly");
+
     let mappings = &mut *proxy.proxy_instance_id_mappings.borrow_mut();
     assert!(!mappings.contains_key(&instance_id));
 
@@ -867,6 +1312,7 @@ pub(crate) fn new_instance3(
         id: instance_id,
         class_name: proxy.get_class_name(),
         context_id: q_ctx.id.clone(),
+        is_subclass_instance: new_target.is_some_and(|nt| nt != proxy_constructor),
     });
 
     let ibp: &mut ProxyInstanceInfo = &mut bx;
@@ -884,14 +1330,16 @@ pub(crate) fn new_instance3(
         0,
     )?;
 
-    let proxy_constructor_refs = &*q_ctx.proxy_constructor_refs.borrow();
-
-    let constructor = proxy_constructor_refs
-        .get(&class_name)
-        .expect("proxy was not installed properly");
+    let constructor = new_target.unwrap_or(proxy_constructor);
 
     set_property2_q(q_ctx, &class_val_ref, "constructor", constructor, 0)?;
 
+    let mut prototype_ref = objects::get_property_q(q_ctx, constructor, "prototype")?;
+    if !prototype_ref.is_object() {
+        prototype_ref = objects::get_property_q(q_ctx, proxy_constructor, "prototype")?;
+    }
+    set_prototype(q_ctx, &class_val_ref, &prototype_ref)?;
+
     Ok(class_val_ref)
 }
 
@@ -921,9 +1369,8 @@ unsafe extern "C" fn constructor(
 ) -> q::JSValue {
     log::trace!("constructor called, this_tag={}", this_val.tag);
 
-    // this is the function we created earlier (the constructor)
-    // so classname = this.name;
-    let this_ref = QuickJsValueAdapter::new(
+    // this is new.target, the function we created earlier (the constructor) or a JS subclass of it
+    let new_target_ref = QuickJsValueAdapter::new(
         context,
         this_val,
         false,
@@ -931,24 +1378,36 @@ unsafe extern "C" fn constructor(
         "reflection::constructor this_val",
     );
     QuickJsRuntimeAdapter::do_with(|q_js_rt| {
-        let name_ref = objects::get_property(context, &this_ref, "name").expect("name get failed");
-        let class_name =
-            functions::call_to_string(context, &name_ref).expect("name.toString failed");
-
         let q_ctx = q_js_rt.get_quickjs_context(context);
 
+        let class_name = match get_static_class_name(context, &new_target_ref) {
+            Some(class_name) => class_name,
+            None => return q_ctx.report_ex("no such proxy"),
+        };
+
         let registry = &*q_ctx.proxy_registry.borrow();
         if let Some(proxy) = registry.get(&class_name) {
-            if let Some(constructor) = &proxy.constructor {
+            if get_proxy_chain(registry, class_name.as_str())
+                .iter()
+                .any(|p| p.constructor.is_some())
+            {
                 // construct
 
                 let args_vec = parse_args(context, argc, argv);
                 let instance_id = next_id(proxy);
-                let constructor_res = constructor(q_js_rt, q_ctx, instance_id, &args_vec);
+                let constructor_res = call_constructors(
+                    q_js_rt,
+                    q_ctx,
+                    registry,
+                    class_name.as_str(),
+                    instance_id,
+                    &args_vec,
+                );
 
                 match constructor_res {
                     Ok(()) => {
-                        let instance_ref_res = new_instance3(proxy, instance_id, q_ctx);
+                        let instance_ref_res =
+                            new_instance3(proxy, instance_id, q_ctx, Some(&new_target_ref));
 
                         match instance_ref_res {
                             Ok(instance_ref) => instance_ref.clone_value_incr_rc(),
@@ -978,6 +1437,7 @@ pub(crate) struct ProxyInstanceInfo {
     id: usize,
     class_name: String, // todo, store all proxies in an autoidmap with a usize as key and store proxy_class_id here instead of string
     context_id: String, // todo store all context ids in an autoidmap with a usize as key and store context_id here instead of string
+    is_subclass_instance: bool, // constructed as a JS subclass (new.target was not the Proxy constructor)
 }
 
 fn get_proxy_instance_info(val: &q::JSValue) -> &ProxyInstanceInfo {
@@ -1005,10 +1465,13 @@ unsafe extern "C" fn finalizer(_rt: *mut q::JSRuntime, val: q::JSValue) {
         let registry = &*q_ctx.proxy_registry.borrow();
         let proxy = registry.get(&info.class_name).unwrap();
 
-        for finalizer in &proxy.finalizers {
-            log::trace!("calling Proxy's finalizer");
-            finalizer(q_js_rt, q_ctx, info.id);
-            log::trace!("after calling Proxy's finalizer");
+        // finalizers of parent classes are called after the finalizers of the class itself
+        for chain_proxy in get_proxy_chain(registry, info.class_name.as_str()) {
+            for finalizer in &chain_proxy.finalizers {
+                log::trace!("calling Proxy's finalizer");
+                finalizer(q_js_rt, q_ctx, info.id);
+                log::trace!("after calling Proxy's finalizer");
+            }
         }
 
         {
@@ -1033,7 +1496,7 @@ unsafe extern "C" fn proxy_static_get_prop(
     // static proxy class, not an instance
     trace!("proxy_static_get_prop");
 
-    let _obj_ref = QuickJsValueAdapter::new(
+    let obj_ref = QuickJsValueAdapter::new(
         context,
         obj,
         false,
@@ -1051,19 +1514,26 @@ unsafe extern "C" fn proxy_static_get_prop(
     QuickJsRuntimeAdapter::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
 
-        let proxy_name_ref = objects::get_property(context, &receiver_ref, "name")
-            .ok()
-            .unwrap();
-        let proxy_name = primitives::to_string(context, &proxy_name_ref)
-            .ok()
-            .unwrap();
+        // obj is the static class object, the receiver may also be a subclass
+        let proxy_name = get_static_class_name(context, &obj_ref).unwrap_or_default();
         trace!("proxy_static_get_prop: {}", proxy_name);
 
         let prop_name = atoms::to_string2(context, &atom).expect("could not get name");
         trace!("proxy_static_get_prop: prop: {}", prop_name);
 
         let registry = &*q_ctx.proxy_registry.borrow();
-        if let Some(proxy) = registry.get(proxy_name.as_str()) {
+        let chain = get_proxy_chain(registry, proxy_name.as_str());
+        if !chain.is_empty() {
+            let n = prop_name.as_str();
+            // the first class in the chain which defines the property implements it
+            let proxy = chain
+                .iter()
+                .find(|p| {
+                    p.static_methods.contains_key(n)
+                        || p.static_native_methods.contains_key(n)
+                        || p.static_getters_setters.contains_key(n)
+                })
+                .unwrap_or(&chain[0]);
             if proxy.static_methods.contains_key(prop_name.as_str()) {
                 trace!("found method for {}", prop_name);
 
@@ -1113,7 +1583,9 @@ unsafe extern "C" fn proxy_static_get_prop(
                         q_ctx.report_ex(es.as_str())
                     }
                 }
-            } else if let Some(catch_all_getter_setter) = &proxy.static_catch_all {
+            } else if let Some(catch_all_getter_setter) =
+                chain.iter().find_map(|p| p.static_catch_all.as_ref())
+            {
                 // call the getter
                 let getter = &catch_all_getter_setter.0;
                 let res: Result<QuickJsValueAdapter, JsError> =
@@ -1143,7 +1615,7 @@ unsafe extern "C" fn proxy_instance_get_prop(
 ) -> q::JSValue {
     trace!("proxy_instance_get_prop");
 
-    let _obj_ref = QuickJsValueAdapter::new(
+    let obj_ref = QuickJsValueAdapter::new(
         context,
         obj,
         false,
@@ -1168,10 +1640,31 @@ unsafe extern "C" fn proxy_instance_get_prop(
 
         trace!("obj_ref.classname = {}", info.class_name);
 
+        // properties of a JS subclass override those of the Proxy
+        if info.is_subclass_instance {
+            match get_subclass_prop(q_ctx, &obj_ref, atom, &receiver_ref, &info.class_name) {
+                Ok(Some(res)) => return res.clone_value_incr_rc(),
+                Ok(None) => {}
+                Err(e) => {
+                    return q_ctx.report_ex(format!("proxy_instance_get failed: {e}").as_str())
+                }
+            }
+        }
+
         // see if we have a matching method
 
         let registry = &*q_ctx.proxy_registry.borrow();
-        let proxy = registry.get(&info.class_name).unwrap();
+        let chain = get_proxy_chain(registry, info.class_name.as_str());
+        let n = prop_name.as_str();
+        // the first class in the chain which defines the property implements it
+        let proxy = chain
+            .iter()
+            .find(|p| {
+                p.methods.contains_key(n)
+                    || p.native_methods.contains_key(n)
+                    || p.getters_setters.contains_key(n)
+            })
+            .unwrap_or(&chain[0]);
         if proxy.methods.contains_key(prop_name.as_str()) {
             trace!("found method for {}", prop_name);
 
@@ -1226,7 +1719,9 @@ unsafe extern "C" fn proxy_instance_get_prop(
                     errors::throw(context, err)
                 }
             }
-        } else if let Some(catch_all_getter_setter) = &proxy.catch_all {
+        } else if let Some(catch_all_getter_setter) =
+            chain.iter().find_map(|p| p.catch_all.as_ref())
+        {
             // call the getter
             let getter = &catch_all_getter_setter.0;
             let res: Result<QuickJsValueAdapter, JsError> =
@@ -1247,8 +1742,12 @@ unsafe extern "C" fn proxy_instance_get_prop(
                 }
             }
         } else {
-            // return null if nothing was returned
-            quickjs_utils::new_undefined()
+            // look in the prototype chain (e.g. methods of a JS subclass or Object.prototype)
+            match reflect_prototype_prop(context, &obj_ref, atom, &receiver_ref, None) {
+                Ok(Some(res)) => res.clone_value_incr_rc(),
+                Ok(None) => quickjs_utils::new_undefined(),
+                Err(e) => q_ctx.report_ex(format!("proxy_instance_get failed: {e}").as_str()),
+            }
         }
     })
 
@@ -1283,6 +1782,14 @@ unsafe extern "C" fn proxy_instance_method(
     func_data: *mut q::JSValue,
 ) -> q::JSValue {
     trace!("proxy_instance_method");
+    // methods are also added to the prototype so this may be any object
+    let class_id = PROXY_INSTANCE_CLASS_ID.with(|rc| *rc.borrow());
+    if q::JS_GetOpaque(this_val, class_id).is_null() {
+        return QuickJsRealmAdapter::report_ex_ctx(
+            context,
+            "proxy_instance_method called on an object which is not a Proxy instance",
+        );
+    }
     QuickJsRuntimeAdapter::do_with(|q_js_rt| {
         let q_ctx = q_js_rt.get_quickjs_context(context);
 
@@ -1303,10 +1810,10 @@ unsafe extern "C" fn proxy_instance_method(
         trace!("proxy_instance_method: {}", func_name);
 
         let registry = &*q_ctx.proxy_registry.borrow();
-        let proxy = registry
-            .get(proxy_instance_info.class_name.as_str())
-            .unwrap();
-        if let Some(method) = proxy.methods.get(func_name.as_str()) {
+        let method_opt = get_proxy_chain(registry, proxy_instance_info.class_name.as_str())
+            .into_iter()
+            .find_map(|p| p.methods.get(func_name.as_str()));
+        if let Some(method) = method_opt {
             // todo report ex
             let m_res: Result<QuickJsValueAdapter, JsError> =
                 method(q_js_rt, q_ctx, &proxy_instance_info.id, &args_vec);
@@ -1353,11 +1860,11 @@ unsafe extern "C" fn proxy_static_method(
             "reflection::proxy_static_method this_val",
         );
 
-        let proxy_name_ref = objects::get_property(context, &this_ref, "name")
-            .ok()
-            .unwrap();
-        let proxy_name =
-            primitives::to_string(context, &proxy_name_ref).expect("could not to_string classname");
+        // this is the constructor or a subclass of the constructor
+        let proxy_name = match get_static_class_name(context, &this_ref) {
+            Some(proxy_name) => proxy_name,
+            None => return q_ctx.report_ex("proxy_static_method called on a non proxy class"),
+        };
 
         let args_vec = parse_args(context, argc, argv);
 
@@ -1374,8 +1881,10 @@ unsafe extern "C" fn proxy_static_method(
         trace!("proxy_static_method: {}", func_name);
 
         let registry = &*q_ctx.proxy_registry.borrow();
-        let proxy = registry.get(proxy_name.as_str()).unwrap();
-        if let Some(method) = proxy.static_methods.get(func_name.as_str()) {
+        let method_opt = get_proxy_chain(registry, proxy_name.as_str())
+            .into_iter()
+            .find_map(|p| p.static_methods.get(func_name.as_str()));
+        if let Some(method) = method_opt {
             let m_res: Result<QuickJsValueAdapter, JsError> = method(q_js_rt, q_ctx, &args_vec);
             match m_res {
                 Ok(m_res_ref) => m_res_ref.clone_value_incr_rc(),
@@ -1401,7 +1910,7 @@ unsafe extern "C" fn proxy_static_method(
 
 unsafe extern "C" fn proxy_static_set_prop(
     context: *mut q::JSContext,
-    _obj: q::JSValue,
+    obj: q::JSValue,
     atom: q::JSAtom,
     value: q::JSValue,
     receiver: q::JSValue,
@@ -1416,12 +1925,12 @@ unsafe extern "C" fn proxy_static_set_prop(
         false,
         "reflection::proxy_static_set_prop value",
     );
-    let receiver_ref = QuickJsValueAdapter::new(
+    let obj_ref = QuickJsValueAdapter::new(
         context,
-        receiver,
+        obj,
         false,
         false,
-        "reflection::proxy_static_set_prop value",
+        "reflection::proxy_static_set_prop obj",
     );
 
     QuickJsRuntimeAdapter::do_with(|rt| {
@@ -1432,17 +1941,17 @@ unsafe extern "C" fn proxy_static_set_prop(
 
         // see if we have a matching gettersetter
 
-        let proxy_name_ref = objects::get_property(context, &receiver_ref, "name")
-            .ok()
-            .unwrap();
-        let proxy_name = primitives::to_string(context, &proxy_name_ref)
-            .ok()
-            .unwrap();
+        // obj is the static class object, the receiver may also be a subclass
+        let proxy_name = get_static_class_name(context, &obj_ref).unwrap_or_default();
         trace!("proxy_static_set_prop: {}", proxy_name);
 
         let registry = &*realm.proxy_registry.borrow();
-        if let Some(proxy) = registry.get(proxy_name.as_str()) {
-            if let Some(getter_setter) = proxy.static_getters_setters.get(prop_name.as_str()) {
+        let chain = get_proxy_chain(registry, proxy_name.as_str());
+        if !chain.is_empty() {
+            if let Some(getter_setter) = chain
+                .iter()
+                .find_map(|p| p.static_getters_setters.get(prop_name.as_str()))
+            {
                 // call the setter
                 let setter = &getter_setter.1;
                 let res: Result<(), JsError> = setter(rt, realm, value_ref);
@@ -1456,7 +1965,9 @@ unsafe extern "C" fn proxy_static_set_prop(
                         -1
                     }
                 }
-            } else if let Some(catch_all_getter_setter) = &proxy.static_catch_all {
+            } else if let Some(catch_all_getter_setter) =
+                chain.iter().find_map(|p| p.static_catch_all.as_ref())
+            {
                 // call the setter
                 let setter = &catch_all_getter_setter.1;
                 let res: Result<(), JsError> = setter(rt, realm, prop_name.as_str(), value_ref);
@@ -1505,6 +2016,19 @@ unsafe extern "C" fn proxy_static_set_prop(
     })
 }
 
+/// throw the error of a setter as an error with the name of the JsError (e.g. a TypeError for an invalid value)
+unsafe fn throw_setter_error(context: *mut q::JSContext, prop_name: &str, e: &JsError) {
+    let msg = format!("proxy_instance_set_prop failed: {}", e.get_message());
+    let nat_stack = format!(
+        "    at Proxy instance setter [{}]\n{}",
+        prop_name,
+        e.get_stack()
+    );
+    let err = errors::new_error(context, e.get_name(), msg.as_str(), nat_stack.as_str())
+        .expect("create error failed");
+    errors::throw(context, err);
+}
+
 unsafe extern "C" fn proxy_instance_set_prop(
     context: *mut q::JSContext,
     obj: q::JSValue,
@@ -1536,23 +2060,25 @@ unsafe extern "C" fn proxy_instance_set_prop(
         // see if we have a matching gettersetter
 
         let registry = &*realm.proxy_registry.borrow();
-        let proxy = registry.get(&info.class_name).unwrap();
+        let chain = get_proxy_chain(registry, info.class_name.as_str());
 
-        if let Some(getter_setter) = proxy.getters_setters.get(prop_name.as_str()) {
+        if let Some(getter_setter) = chain
+            .iter()
+            .find_map(|p| p.getters_setters.get(prop_name.as_str()))
+        {
             // call the setter
             let setter = &getter_setter.1;
             let res: Result<(), JsError> = setter(rt, realm, &info.id, value_ref);
             match res {
                 Ok(_) => 0,
                 Err(e) => {
-                    // fail, todo do i need ex?
-                    let err = format!("proxy_instance_set_prop failed: {e}");
-                    log::error!("{}", err);
-                    let _ = realm.report_ex(err.as_str());
+                    throw_setter_error(context, prop_name.as_str(), &e);
                     -1
                 }
             }
-        } else if let Some(catch_all_getter_setter) = &proxy.catch_all {
+        } else if let Some(catch_all_getter_setter) =
+            chain.iter().find_map(|p| p.catch_all.as_ref())
+        {
             // call the setter
             let setter = &catch_all_getter_setter.1;
             let res: Result<(), JsError> =
@@ -1560,15 +2086,12 @@ unsafe extern "C" fn proxy_instance_set_prop(
             match res {
                 Ok(_) => 0,
                 Err(e) => {
-                    // fail, todo do i need ex?
-                    let err = format!("proxy_instance_set_prop failed: {e}");
-                    log::error!("{}", err);
-                    let _ = realm.report_ex(err.as_str());
+                    throw_setter_error(context, prop_name.as_str(), &e);
                     -1
                 }
             }
         } else {
-            // if not handler just add to receiver
+            // if not handler set it through the prototype chain (setters of a JS subclass) which adds it to the receiver
 
             let receiver_ref = QuickJsValueAdapter::new(
                 context,
@@ -1577,8 +2100,28 @@ unsafe extern "C" fn proxy_instance_set_prop(
                 false,
                 "reflection::proxy_instance_set_prop receiver",
             );
+            let obj_ref = QuickJsValueAdapter::new(
+                context,
+                obj,
+                false,
+                false,
+                "reflection::proxy_instance_set_prop obj",
+            );
 
-            match realm.set_object_property(&receiver_ref, prop_name.as_str(), &value_ref) {
+            let res = match reflect_prototype_prop(
+                context,
+                &obj_ref,
+                atom,
+                &receiver_ref,
+                Some(&value_ref),
+            ) {
+                Ok(Some(_)) => Ok(()),
+                Ok(None) => {
+                    realm.set_object_property(&receiver_ref, prop_name.as_str(), &value_ref)
+                }
+                Err(e) => Err(e),
+            };
+            match res {
                 Ok(()) => 0,
                 Err(e) => {
                     let err = format!("proxy_instance_set_prop failed, {}", e);
@@ -1668,6 +2211,52 @@ pub mod tests {
         assert!(err.contains("cant run"));
     }
 
+    #[test]
+    pub fn test_proxy_setter_ex() {
+        log::info!("> test_proxy_setter_ex");
+
+        let rt = init_test_rt();
+        let res = rt.exe_rt_task_in_event_loop(|q_js_rt| {
+            let q_ctx = q_js_rt.get_main_realm();
+            let _ = Proxy::new()
+                .constructor(|_q_js_rt, _q_ctx, _id, _args| Ok(()))
+                .getter_setter(
+                    "size",
+                    |_rt, realm, _id| realm.create_i32(1),
+                    |_rt, _realm, _id, _val| {
+                        Err(JsError::new(
+                            "TypeError".to_string(),
+                            "size is read only".to_string(),
+                            "".to_string(),
+                        ))
+                    },
+                )
+                .catch_all_getter_setter(
+                    |_rt, realm, _id, _name| realm.create_undefined(),
+                    |_rt, _realm, _id, name, _val| {
+                        Err(JsError::new_string(format!("can not set {name}")))
+                    },
+                )
+                .name("SetterTest")
+                .install(q_ctx, true);
+            let res = q_ctx
+                .eval(Script::new(
+                    "test_setter_ex.es",
+                    "let t = new SetterTest(); let errors = [];\n\
+                     try { t.size = 2; } catch (e) { errors.push(e.name, e.message); }\n\
+                     try { t.other = 2; } catch (e) { errors.push(e.name, e.message); }\n\
+                     errors.join('|');",
+                ))
+                .expect("script failed");
+            res.to_string().expect("not a string")
+        });
+
+        assert_eq!(
+            res,
+            "TypeError|proxy_instance_set_prop failed: size is read only|Error|proxy_instance_set_prop failed: can not set other"
+        );
+    }
+
     #[test]
     pub fn test_proxy_instanceof() {
         log::info!("> test_proxy_instanceof");
@@ -1978,4 +2567,229 @@ pub mod tests {
             );
         });
     }
+
+    #[test]
+    pub fn test_proxy_extends() {
+        thread_local! {
+            static LOG: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+        }
+        fn log(line: String) {
+            LOG.with(|rc| rc.borrow_mut().push(line));
+        }
+
+        let rt = init_test_rt();
+        rt.loop_realm_sync(None, |_rt, realm| {
+            Proxy::new()
+                .name("Animal")
+                .namespace(&["com", "company"])
+                .constructor(|_rt, _realm, _id, _args| {
+                    log("construct Animal".to_string());
+                    Ok(())
+                })
+                .method("speak", |_rt, realm, _id, _args| realm.create_string("..."))
+                .method("describe", |_rt, realm, _id, _args| {
+                    realm.create_string("animal")
+                })
+                .getter("legs", |_rt, realm, _id| realm.create_i32(4))
+                .static_method("kingdom", |_rt, realm, _args| {
+                    realm.create_string("animalia")
+                })
+                .finalizer(|_rt, _realm, _id| log("finalize Animal".to_string()))
+                .install(realm, true)
+                .expect("could not install Animal");
+            Proxy::new()
+                .name("Dog")
+                .namespace(&["com", "company"])
+                .extends("com.company.Animal")
+                .constructor(|_rt, _realm, _id, _args| {
+                    log("construct Dog".to_string());
+                    Ok(())
+                })
+                .method("speak", |_rt, realm, _id, _args| {
+                    realm.create_string("woof")
+                })
+                .finalizer(|_rt, _realm, _id| log("finalize Dog".to_string()))
+                .install(realm, true)
+                .expect("could not install Dog");
+
+            let err = Proxy::new()
+                .name("Orphan")
+                .extends("com.company.Missing")
+                .install(realm, true)
+                .expect_err("parent should not be found");
+            assert!(err.get_message().contains("com.company.Missing"));
+        });
+
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_proxy_extends.js",
+                    r#"
+                    (() => {
+                        const { Animal, Dog } = com.company;
+                        let d = new Dog();
+                        let a = new Animal();
+                        return [
+                            d.speak(), d.describe(), d.legs, Dog.kingdom(),
+                            d instanceof Dog, d instanceof Animal, a instanceof Dog, a instanceof Animal,
+                            Object.getPrototypeOf(Dog.prototype) === Animal.prototype, d.constructor === Dog,
+                            typeof d.toString
+                        ].join(",");
+                    })();
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "woof,animal,4,animalia,true,true,false,true,true,true,function"
+        );
+        rt.gc_sync();
+
+        let log = rt.exe_rt_task_in_event_loop(|_rt| LOG.with(|rc| rc.borrow().clone()));
+        assert_eq!(log[0], "construct Animal");
+        assert_eq!(log[1], "construct Dog");
+        assert_eq!(log.iter().filter(|l| *l == "finalize Animal").count(), 2);
+        assert_eq!(log.iter().filter(|l| *l == "finalize Dog").count(), 1);
+        let dog_finalized = log.iter().position(|l| l == "finalize Dog").unwrap();
+        assert!(log[dog_finalized + 1..].contains(&"finalize Animal".to_string()));
+    }
+
+    #[test]
+    pub fn test_proxy_js_subclass() {
+        let rt = init_test_rt();
+        rt.loop_realm_sync(None, |_rt, realm| {
+            Proxy::new()
+                .name("RustThing")
+                .constructor(|_rt, _realm, id, args| {
+                    let name = args
+                        .first()
+                        .map(|a| a.to_string())
+                        .transpose()?
+                        .unwrap_or_default();
+                    TEST_INSTANCES.with(|rc| rc.borrow_mut().insert(id, name));
+                    Ok(())
+                })
+                .method("getName", |_rt, realm, id, _args| {
+                    let name = TEST_INSTANCES.with(|rc| rc.borrow().get(id).cloned());
+                    realm.create_string(name.unwrap_or_default().as_str())
+                })
+                .static_method("create", |_rt, realm, _args| realm.create_string("static"))
+                .finalizer(|_rt, _realm, id| {
+                    TEST_INSTANCES.with(|rc| rc.borrow_mut().remove(&id));
+                })
+                .install(realm, true)
+                .expect("could not install RustThing");
+        });
+
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_proxy_js_subclass.js",
+                    r#"
+                    (() => {
+                        class MyThing extends RustThing {
+                            constructor(name) {
+                                super(name.toUpperCase());
+                                this.extra = 'extra';
+                                this.target = new.target.name;
+                            }
+                            getName() {
+                                return 'my ' + super.getName();
+                            }
+                            get shout() {
+                                return this.getName() + '!';
+                            }
+                        }
+                        class MySubThing extends MyThing {}
+                        let t = new MyThing('abc');
+                        let s = new MySubThing('def');
+                        let r = new RustThing('plain');
+                        return [
+                            t.getName(), t.shout, t.extra, t.target, s.target, s.getName(), MySubThing.create(),
+                            t instanceof MyThing, t instanceof RustThing, s instanceof MyThing, r instanceof MyThing,
+                            r instanceof RustThing, r.getName()
+                        ].join(",");
+                    })();
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        assert_eq!(
+            res.get_str(),
+            "my ABC,my ABC!,extra,MyThing,MySubThing,my DEF,static,true,true,true,false,true,plain"
+        );
+    }
+
+    #[test]
+    pub fn test_proxy_iterators() {
+        use crate::values::JsValueFacade;
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let produced = Arc::new(AtomicUsize::new(0));
+        let produced_stream = produced.clone();
+
+        let rt = init_test_rt();
+        rt.loop_realm_sync(None, move |_rt, realm| {
+            Proxy::new()
+                .name("Cursor")
+                .constructor(|_rt, _realm, _id, _args| Ok(()))
+                .iterator(|_rt, _realm, _id| {
+                    Ok(Box::new((1..=3).map(|i| Ok(JsValueFacade::new_i32(i)))))
+                })
+                .async_iterator(move |_rt, _realm, _id| {
+                    let produced = produced_stream.clone();
+                    Ok(futures::stream::unfold(0, move |i| {
+                        let produced = produced.clone();
+                        async move {
+                            produced.fetch_add(1, Ordering::SeqCst);
+                            Some((Ok(JsValueFacade::new_i32(i)), i + 1))
+                        }
+                    })
+                    .boxed())
+                })
+                .install(realm, true)
+                .expect("could not install Cursor");
+        });
+
+        let res = rt
+            .eval_sync(
+                None,
+                Script::new(
+                    "test_proxy_iterators.js",
+                    r#"
+                    (async () => {
+                        let sum = 0;
+                        for (const x of new Cursor()) {
+                            sum += x;
+                        }
+                        let spread = [...new Cursor()].join("-");
+                        let rows = [];
+                        for await (const x of new Cursor()) {
+                            rows.push(x);
+                            if (x === 1) {
+                                break;
+                            }
+                        }
+                        return [sum, spread, rows.join("-")].join(",");
+                    })();
+                    "#,
+                ),
+            )
+            .expect("script failed");
+        let JsValueFacade::JsPromise { cached_promise } = res else {
+            panic!("expected a promise");
+        };
+        let res = cached_promise
+            .get_promise_result_sync()
+            .expect("promise timed out")
+            .expect("promise rejected");
+        assert_eq!(res.get_str(), "6,1-2-3,0-1");
+        // the stream is only polled when the script asks for the next item
+        assert_eq!(produced.load(Ordering::SeqCst), 2);
+    }
 }
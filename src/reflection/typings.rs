@@ -0,0 +1,361 @@
+//! generate TypeScript declarations (.d.ts) for the Proxy classes and native modules of a realm
+//!
+//! members are declared with `any` types unless they are annotated on the Proxy builder (see [Proxy::typing](crate::reflection::Proxy::typing))
+//! or typed by the [NativeModuleLoader](crate::jsutils::modules::NativeModuleLoader)
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::reflection::Proxy;
+//!
+//! let rt = QuickJsRuntimeBuilder::new().build();
+//! let typings = rt.loop_realm_sync(None, |_rt, realm| {
+//!     Proxy::new()
+//!         .namespace(&["com", "hirofa"])
+//!         .name("Calculator")
+//!         .constructor(|_rt, _realm, _id, _args| Ok(()))
+//!         .method("add", |_rt, realm, _id, args| realm.create_i32(args[0].to_i32() + args[1].to_i32()))
+//!         .typing("add", "(a: number, b: number): number")
+//!         .install(realm, true)
+//!         .expect("could not install Calculator");
+//!     realm.generate_typings().expect("could not generate typings")
+//! });
+//! assert!(typings.contains("add(a: number, b: number): number;"));
+//! std::fs::write(std::env::temp_dir().join("quickjs_runtime.d.ts"), typings).expect("could not write typings");
+//! ```
+
+use crate::jsutils::JsError;
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
+use crate::reflection::Proxy;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+
+const EVENT_TARGET_METHODS: [&str; 3] =
+    ["addEventListener", "removeEventListener", "dispatchEvent"];
+
+/// the TypeScript annotations of a Proxy class, these are added via the Proxy builder
+#[derive(Default)]
+pub(crate) struct ProxyTypings {
+    pub(crate) members: HashMap<String, String>,
+    pub(crate) static_members: HashMap<String, String>,
+    pub(crate) events: BTreeMap<String, String>,
+    pub(crate) static_events: BTreeMap<String, String>,
+    pub(crate) readonly: HashSet<String>,
+}
+
+/// generate the declarations for all Proxy classes installed in a realm and for all modules of the native module loaders of the runtime
+pub fn generate_typings(realm: &QuickJsRealmAdapter) -> Result<String, JsError> {
+    let mut out = String::new();
+    writeln!(out, "// generated by quickjs_runtime, do not edit").map_err(fmt_err)?;
+
+    // group classes by namespace, sorted so the output is stable
+    let mut namespaces: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    {
+        let registry = &*realm.proxy_registry.borrow();
+        for proxy in registry.values() {
+            let declaration = declare_class(proxy)?;
+            let namespace = proxy.namespace.as_ref().map(|ns| ns.join("."));
+            namespaces
+                .entry(namespace.unwrap_or_default())
+                .or_default()
+                .push(declaration);
+        }
+    }
+    for (namespace, mut classes) in namespaces {
+        classes.sort();
+        writeln!(out).map_err(fmt_err)?;
+        if namespace.is_empty() {
+            for class in classes {
+                write!(out, "declare {}", class.replace("\n    ", "\n")).map_err(fmt_err)?;
+            }
+        } else {
+            writeln!(out, "declare namespace {namespace} {{").map_err(fmt_err)?;
+            for class in classes {
+                write!(out, "    export {class}").map_err(fmt_err)?;
+            }
+            writeln!(out, "}}").map_err(fmt_err)?;
+        }
+    }
+
+    QuickJsRuntimeAdapter::do_with(|q_js_rt| -> Result<(), JsError> {
+        for loader in &q_js_rt.native_module_loaders {
+            let loader = &*loader.inner;
+            let mut module_names = loader.get_module_names(realm);
+            module_names.sort();
+            for module_name in module_names {
+                writeln!(out).map_err(fmt_err)?;
+                writeln!(out, "declare module \"{module_name}\" {{").map_err(fmt_err)?;
+                for export_name in loader.get_module_export_names(realm, module_name) {
+                    let typing = loader
+                        .get_module_export_typing(realm, module_name, export_name)
+                        .unwrap_or_else(|| "any".to_string());
+                    if export_name == "default" {
+                        writeln!(out, "    const _default: {typing};").map_err(fmt_err)?;
+                        writeln!(out, "    export default _default;").map_err(fmt_err)?;
+                    } else {
+                        writeln!(out, "    export const {export_name}: {typing};")
+                            .map_err(fmt_err)?;
+                    }
+                }
+                writeln!(out, "}}").map_err(fmt_err)?;
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(out)
+}
+
+fn fmt_err(e: std::fmt::Error) -> JsError {
+    JsError::new_string(format!("could not write typings: {e}"))
+}
+
+/// declare a single class (indented for use in a namespace)
+fn declare_class(proxy: &Proxy) -> Result<String, JsError> {
+    let typings = &proxy.typings;
+    let mut out = String::new();
+
+    let name = proxy.name.as_deref().unwrap_or("__nameless_class__");
+    match &proxy.extends {
+        Some(parent) => writeln!(out, "class {name} extends {parent} {{"),
+        None => writeln!(out, "class {name} {{"),
+    }
+    .map_err(fmt_err)?;
+
+    if let Some(signature) = typings.members.get("constructor") {
+        writeln!(out, "        constructor{signature};").map_err(fmt_err)?;
+    } else if proxy.constructor.is_some() {
+        writeln!(out, "        constructor(...args: any[]);").map_err(fmt_err)?;
+    } else if proxy.extends.is_none() {
+        // not constructable from script, subclasses inherit the constructor (if any) of their parent
+        writeln!(out, "        private constructor();").map_err(fmt_err)?;
+    }
+
+    // statics
+    let mut static_props: Vec<&String> = proxy.static_getters_setters.keys().collect();
+    static_props.sort();
+    for prop in static_props {
+        let typing = type_of(&typings.static_members, prop);
+        writeln!(out, "        static {}: {typing};", member_name(prop)).map_err(fmt_err)?;
+    }
+    let mut static_methods: Vec<&String> = proxy
+        .static_methods
+        .keys()
+        .chain(proxy.static_native_methods.keys())
+        .filter(|m| !(proxy.is_static_event_target && EVENT_TARGET_METHODS.contains(&m.as_str())))
+        .collect();
+    static_methods.sort();
+    for method in static_methods {
+        if let Some(declaration) = declare_method(&typings.static_members, method) {
+            writeln!(out, "        static {declaration};").map_err(fmt_err)?;
+        }
+    }
+    if proxy.is_static_event_target {
+        declare_event_target(&mut out, "static ", &typings.static_events)?;
+    }
+    if proxy.static_catch_all.is_some() {
+        writeln!(out, "        static [key: string]: any;").map_err(fmt_err)?;
+    }
+
+    // instance members
+    let mut props: Vec<&String> = proxy.getters_setters.keys().collect();
+    props.sort();
+    for prop in props {
+        let typing = type_of(&typings.members, prop);
+        let readonly = if typings.readonly.contains(prop) {
+            "readonly "
+        } else {
+            ""
+        };
+        writeln!(out, "        {readonly}{}: {typing};", member_name(prop)).map_err(fmt_err)?;
+    }
+    let mut methods: Vec<&String> = proxy
+        .methods
+        .keys()
+        .chain(proxy.native_methods.keys())
+        .filter(|m| !(proxy.is_event_target && EVENT_TARGET_METHODS.contains(&m.as_str())))
+        .collect();
+    methods.sort();
+    for method in methods {
+        if let Some(declaration) = declare_method(&typings.members, method) {
+            writeln!(out, "        {declaration};").map_err(fmt_err)?;
+        }
+    }
+    if proxy.is_event_target {
+        declare_event_target(&mut out, "", &typings.events)?;
+    }
+    if proxy.catch_all.is_some() {
+        writeln!(out, "        [key: string]: any;").map_err(fmt_err)?;
+    }
+
+    writeln!(out, "    }}").map_err(fmt_err)?;
+    Ok(out)
+}
+
+fn type_of<'a>(typings: &'a HashMap<String, String>, name: &str) -> &'a str {
+    typings.get(name).map(|s| s.as_str()).unwrap_or("any")
+}
+
+fn declare_method(typings: &HashMap<String, String>, name: &str) -> Option<String> {
+    let default_signature = match name {
+        // added to every Proxy class by install
+        "Symbol.toPrimitive" => return None,
+        "Symbol.iterator" => "(): Iterator<any>",
+        "Symbol.asyncIterator" => "(): AsyncIterator<any>",
+        _ => "(...args: any[]): any",
+    };
+    let signature = typings
+        .get(name)
+        .map(|s| s.as_str())
+        .unwrap_or(default_signature);
+    Some(format!("{}{signature}", member_name(name)))
+}
+
+/// quote names which are not valid identifiers, well-known symbols are declared as computed names
+fn member_name(name: &str) -> String {
+    if name.starts_with("Symbol.") {
+        return format!("[{name}]");
+    }
+    let mut chars = name.chars();
+    let valid = match chars.next() {
+        Some(c) => {
+            (c.is_alphabetic() || c == '_' || c == '$')
+                && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        }
+        None => false,
+    };
+    if valid {
+        name.to_string()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+fn declare_event_target(
+    out: &mut String,
+    prefix: &str,
+    events: &BTreeMap<String, String>,
+) -> Result<(), JsError> {
+    // an overload per known event and a generic fallback
+    for (event_id, event_type) in events {
+        writeln!(
+            out,
+            "        {prefix}addEventListener(type: {event_id:?}, listener: (evt: {event_type}) => void, options?: any): void;"
+        )
+        .map_err(fmt_err)?;
+    }
+    writeln!(
+        out,
+        "        {prefix}addEventListener(type: string, listener: (evt: any) => void, options?: any): void;"
+    )
+    .map_err(fmt_err)?;
+    writeln!(
+        out,
+        "        {prefix}removeEventListener(type: string, listener: (evt: any) => void, options?: any): void;"
+    )
+    .map_err(fmt_err)?;
+    writeln!(out, "        {prefix}dispatchEvent(evt: Event): boolean;").map_err(fmt_err)?;
+    writeln!(
+        out,
+        "        {prefix}dispatchEvent(type: string, evt: any): boolean;"
+    )
+    .map_err(fmt_err)?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
+    use crate::jsutils::modules::NativeModuleLoader;
+    use crate::quickjs_utils::primitives;
+    use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+    use crate::quickjsvalueadapter::QuickJsValueAdapter;
+    use crate::reflection::Proxy;
+
+    struct TypedModuleLoader {}
+
+    impl NativeModuleLoader for TypedModuleLoader {
+        fn has_module(&self, _realm: &QuickJsRealmAdapter, module_name: &str) -> bool {
+            module_name == "greetings"
+        }
+
+        fn get_module_export_names(
+            &self,
+            _realm: &QuickJsRealmAdapter,
+            _module_name: &str,
+        ) -> Vec<&str> {
+            vec!["hello", "count"]
+        }
+
+        fn get_module_exports(
+            &self,
+            _realm: &QuickJsRealmAdapter,
+            _module_name: &str,
+        ) -> Vec<(&str, QuickJsValueAdapter)> {
+            vec![
+                ("hello", primitives::from_bool(true)),
+                ("count", primitives::from_i32(1)),
+            ]
+        }
+
+        fn get_module_names(&self, _realm: &QuickJsRealmAdapter) -> Vec<&str> {
+            vec!["greetings"]
+        }
+
+        fn get_module_export_typing(
+            &self,
+            _realm: &QuickJsRealmAdapter,
+            _module_name: &str,
+            export_name: &str,
+        ) -> Option<String> {
+            (export_name == "count").then(|| "number".to_string())
+        }
+    }
+
+    #[test]
+    fn test_typings() {
+        let rt = QuickJsRuntimeBuilder::new()
+            .native_module_loader(TypedModuleLoader {})
+            .build();
+        let typings = rt.loop_realm_sync(None, |_rt, realm| {
+            Proxy::new()
+                .namespace(&["com", "hirofa"])
+                .name("Animal")
+                .constructor(|_rt, _realm, _id, _args| Ok(()))
+                .typing("constructor", "(name: string)")
+                .getter("name", |_rt, realm, _id| realm.create_string("a"))
+                .typing("name", "string")
+                .method("speak", |_rt, realm, _id, _args| realm.create_undefined())
+                .static_method("create", |_rt, realm, _args| realm.create_undefined())
+                .static_typing("create", "(name: string): Animal")
+                .event_target()
+                .event_typing("move", "{ x: number }")
+                .install(realm, true)
+                .expect("could not install Animal");
+            Proxy::new()
+                .namespace(&["com", "hirofa"])
+                .name("Dog")
+                .extends("com.hirofa.Animal")
+                .install(realm, true)
+                .expect("could not install Dog");
+            Proxy::new()
+                .name("Util")
+                .static_method("now", |_rt, realm, _args| realm.create_i32(1))
+                .install(realm, true)
+                .expect("could not install Util");
+            realm
+                .generate_typings()
+                .expect("could not generate typings")
+        });
+
+        assert!(typings.contains("declare namespace com.hirofa {\n    export class Animal {\n        constructor(name: string);\n        static create(name: string): Animal;\n        readonly name: string;\n        speak(...args: any[]): any;\n"));
+        assert!(typings.contains(
+            "        addEventListener(type: \"move\", listener: (evt: { x: number }) => void, options?: any): void;\n"
+        ));
+        assert!(typings.contains("    export class Dog extends com.hirofa.Animal {\n"));
+        assert!(typings.contains("declare class Util {\n    private constructor();\n    static now(...args: any[]): any;\n}\n"));
+        assert!(typings.contains("declare module \"greetings\" {\n    export const hello: any;\n    export const count: number;\n}\n"));
+        assert!(!typings.contains("toPrimitive"));
+    }
+}
@@ -0,0 +1,380 @@
+//! a pool of runtimes which can be used to spread script work over several EventLoop threads
+//!
+//! every [QuickJsRuntimeFacade](../facades/struct.QuickJsRuntimeFacade.html) has exactly one worker thread, the
+//! QuickJsRuntimePool owns several facades which are all built with the same factory and thus
+//! have the same init hooks, module loaders and settings
+//!
+//! please note that state is not shared between the members of a pool, a value set in globalThis by a
+//! job which ran in one member is not visible to a job which runs in another member
+//!
+//! # Example
+//! ```rust
+//! use quickjs_runtime::builder::QuickJsRuntimeBuilder;
+//! use quickjs_runtime::jsutils::Script;
+//! use quickjs_runtime::runtimepool::{PoolDispatchStrategy, QuickJsRuntimePool};
+//! let pool = QuickJsRuntimePool::new(2, PoolDispatchStrategy::RoundRobin, QuickJsRuntimeBuilder::new);
+//! let res = pool.eval_sync(None, Script::new("pool.js", "(6 * 7);")).expect("script failed");
+//! assert_eq!(res.get_i32(), 42);
+//! ```
+
+use crate::builder::QuickJsRuntimeBuilder;
+use crate::facades::QuickJsRuntimeFacade;
+use crate::jsutils::{JsError, Script};
+use crate::quickjsrealmadapter::QuickJsRealmAdapter;
+use crate::quickjsruntimeadapter::{MemoryUsage, QuickJsRuntimeAdapter};
+use crate::values::JsValueFacade;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+pub type RuntimeBuilderFactory = dyn Fn() -> QuickJsRuntimeBuilder + Send + Sync + 'static;
+
+/// determines which member of a pool is used to run a job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolDispatchStrategy {
+    /// use all members in turn
+    RoundRobin,
+    /// use the member with the least amount of jobs in flight
+    LeastLoaded,
+}
+
+struct PoolMember {
+    facade: QuickJsRuntimeFacade,
+    in_flight: AtomicUsize,
+}
+
+/// decrements the in flight counter of a member when dropped (also when a future is dropped before completing)
+struct LoadGuard {
+    member: Arc<PoolMember>,
+}
+
+impl LoadGuard {
+    fn new(member: Arc<PoolMember>) -> Self {
+        member.in_flight.fetch_add(1, Ordering::SeqCst);
+        Self { member }
+    }
+}
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        self.member.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub struct QuickJsRuntimePool {
+    members: Vec<Mutex<Arc<PoolMember>>>,
+    factory: Box<RuntimeBuilderFactory>,
+    strategy: PoolDispatchStrategy,
+    next: AtomicUsize,
+}
+
+impl QuickJsRuntimePool {
+    /// create a new pool with size members, every member is built with a builder produced by the factory
+    pub fn new<F>(size: usize, strategy: PoolDispatchStrategy, factory: F) -> Self
+    where
+        F: Fn() -> QuickJsRuntimeBuilder + Send + Sync + 'static,
+    {
+        assert!(size > 0, "a pool needs at least one member");
+
+        let members = (0..size)
+            .map(|_| Mutex::new(Self::new_member(&factory)))
+            .collect();
+
+        Self {
+            members,
+            factory: Box::new(factory),
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn new_member(factory: &dyn Fn() -> QuickJsRuntimeBuilder) -> Arc<PoolMember> {
+        Arc::new(PoolMember {
+            facade: factory().build(),
+            in_flight: AtomicUsize::new(0),
+        })
+    }
+
+    /// the number of members in this pool
+    pub fn size(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn get_strategy(&self) -> PoolDispatchStrategy {
+        self.strategy
+    }
+
+    /// get the number of jobs which are currently in flight per member
+    pub fn in_flight(&self) -> Vec<usize> {
+        self.members
+            .iter()
+            .map(|m| {
+                m.lock()
+                    .expect("pool member lock poisoned")
+                    .in_flight
+                    .load(Ordering::SeqCst)
+            })
+            .collect()
+    }
+
+    fn member(&self, index: usize) -> Arc<PoolMember> {
+        self.members
+            .get(index)
+            .expect("no such pool member")
+            .lock()
+            .expect("pool member lock poisoned")
+            .clone()
+    }
+
+    fn select_index(&self) -> usize {
+        match self.strategy {
+            PoolDispatchStrategy::RoundRobin => {
+                self.next.fetch_add(1, Ordering::SeqCst) % self.members.len()
+            }
+            PoolDispatchStrategy::LeastLoaded => {
+                let loads = self.in_flight();
+                // start at a rotating offset so members with an equal load are used in turn
+                let offset = self.next.fetch_add(1, Ordering::SeqCst);
+                let mut best = offset % loads.len();
+                for x in 0..loads.len() {
+                    let idx = (offset + x) % loads.len();
+                    if loads[idx] < loads[best] {
+                        best = idx;
+                    }
+                }
+                best
+            }
+        }
+    }
+
+    fn select(&self) -> LoadGuard {
+        LoadGuard::new(self.member(self.select_index()))
+    }
+
+    /// run a closure with the facade of a specific member
+    /// this may be used to run init code in every member, e.g. `for x in 0..pool.size() {pool.with_member(x, |rt| {...})}`
+    pub fn with_member<C, R>(&self, index: usize, consumer: C) -> R
+    where
+        C: FnOnce(&QuickJsRuntimeFacade) -> R,
+    {
+        let guard = LoadGuard::new(self.member(index));
+        consumer(&guard.member.facade)
+    }
+
+    /// add a job to a realm of one of the members, see [QuickJsRuntimeFacade::loop_realm]
+    pub fn loop_realm<
+        R: Send + 'static,
+        C: FnOnce(&QuickJsRuntimeAdapter, &QuickJsRealmAdapter) -> R + Send + 'static,
+    >(
+        &self,
+        realm_name: Option<&str>,
+        consumer: C,
+    ) -> Pin<Box<dyn Future<Output = R> + Send>> {
+        let guard = self.select();
+        let fut = guard.member.facade.loop_realm(realm_name, consumer);
+        Box::pin(async move {
+            let res = fut.await;
+            drop(guard);
+            res
+        })
+    }
+
+    /// add a job to a realm of one of the members and wait for the result, see [QuickJsRuntimeFacade::loop_realm_sync]
+    pub fn loop_realm_sync<
+        R: Send + 'static,
+        C: FnOnce(&QuickJsRuntimeAdapter, &QuickJsRealmAdapter) -> R + Send + 'static,
+    >(
+        &self,
+        realm_name: Option<&str>,
+        consumer: C,
+    ) -> R {
+        let guard = self.select();
+        guard.member.facade.loop_realm_sync(realm_name, consumer)
+    }
+
+    /// evaluate a script in one of the members, see [QuickJsRuntimeFacade::eval]
+    #[allow(clippy::type_complexity)]
+    pub fn eval(
+        &self,
+        realm_name: Option<&str>,
+        script: Script,
+    ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
+        let guard = self.select();
+        let fut = guard.member.facade.eval(realm_name, script);
+        Box::pin(async move {
+            let res = fut.await;
+            drop(guard);
+            res
+        })
+    }
+
+    /// evaluate a script in one of the members and wait for the result, see [QuickJsRuntimeFacade::eval_sync]
+    pub fn eval_sync(
+        &self,
+        realm_name: Option<&str>,
+        script: Script,
+    ) -> Result<JsValueFacade, JsError> {
+        let guard = self.select();
+        guard.member.facade.eval_sync(realm_name, script)
+    }
+
+    /// invoke a function in one of the members, see [QuickJsRuntimeFacade::invoke_function]
+    #[allow(clippy::type_complexity)]
+    pub fn invoke_function(
+        &self,
+        realm_name: Option<&str>,
+        namespace: &[&str],
+        method_name: &str,
+        args: Vec<JsValueFacade>,
+    ) -> Pin<Box<dyn Future<Output = Result<JsValueFacade, JsError>> + Send>> {
+        let guard = self.select();
+        let fut = guard
+            .member
+            .facade
+            .invoke_function(realm_name, namespace, method_name, args);
+        Box::pin(async move {
+            let res = fut.await;
+            drop(guard);
+            res
+        })
+    }
+
+    /// invoke a function in one of the members and wait for the result, see [QuickJsRuntimeFacade::invoke_function_sync]
+    pub fn invoke_function_sync(
+        &self,
+        realm_name: Option<&str>,
+        namespace: &[&str],
+        method_name: &str,
+        args: Vec<JsValueFacade>,
+    ) -> Result<JsValueFacade, JsError> {
+        let guard = self.select();
+        guard
+            .member
+            .facade
+            .invoke_function_sync(realm_name, namespace, method_name, args)
+    }
+
+    /// get the memory usage of all members, the index in the Vec is the index of the member
+    pub async fn memory_usage(&self) -> Vec<MemoryUsage> {
+        let members: Vec<Arc<PoolMember>> = (0..self.size()).map(|x| self.member(x)).collect();
+        let mut ret = Vec::with_capacity(members.len());
+        for member in members {
+            ret.push(member.facade.memory_usage().await);
+        }
+        ret
+    }
+
+    /// drop a member and replace it with a freshly built runtime
+    /// jobs which are still in flight for the old member will complete before it is actually dropped
+    pub fn recycle(&self, index: usize) {
+        log::debug!("QuickJsRuntimePool::recycle {}", index);
+        let new_member = Self::new_member(&*self.factory);
+        let old_member = {
+            let lock = &mut *self
+                .members
+                .get(index)
+                .expect("no such pool member")
+                .lock()
+                .expect("pool member lock poisoned");
+            std::mem::replace(lock, new_member)
+        };
+        // drop outside of the lock
+        drop(old_member);
+    }
+
+    /// recycle all members which use more than max_memory_used_size bytes
+    /// returns the indexes of the members which were recycled
+    pub async fn recycle_if_memory_exceeds(&self, max_memory_used_size: i64) -> Vec<usize> {
+        let usages = self.memory_usage().await;
+        let mut recycled = vec![];
+        for (index, usage) in usages.iter().enumerate() {
+            if usage.memory_used_size > max_memory_used_size {
+                self.recycle(index);
+                recycled.push(index);
+            }
+        }
+        recycled
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::builder::QuickJsRuntimeBuilder;
+    use crate::jsutils::Script;
+    use crate::runtimepool::{PoolDispatchStrategy, QuickJsRuntimePool};
+    use crate::values::JsValueConvertable;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_pool_round_robin() {
+        let pool = QuickJsRuntimePool::new(3, PoolDispatchStrategy::RoundRobin, || {
+            QuickJsRuntimeBuilder::new()
+        });
+        assert_eq!(pool.size(), 3);
+
+        // every member gets its own counter
+        for _ in 0..6 {
+            pool.eval_sync(
+                None,
+                Script::new("pool_rr.js", "globalThis.ct = (globalThis.ct || 0) + 1;"),
+            )
+            .expect("script failed");
+        }
+        for x in 0..pool.size() {
+            let ct = pool.with_member(x, |rt| {
+                rt.eval_sync(None, Script::new("pool_rr2.js", "(globalThis.ct);"))
+                    .expect("script failed")
+                    .get_i32()
+            });
+            assert_eq!(ct, 2);
+        }
+        assert_eq!(pool.in_flight(), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn test_pool_invoke_and_recycle() {
+        let pool = QuickJsRuntimePool::new(2, PoolDispatchStrategy::LeastLoaded, || {
+            QuickJsRuntimeBuilder::new().realm_adapter_init_hook(|_rt, realm| {
+                realm.install_function(
+                    &["com", "pool"],
+                    "mul",
+                    |_rt, realm, _this, args| {
+                        let a = args[0].to_i32();
+                        let b = args[1].to_i32();
+                        realm.create_i32(a * b)
+                    },
+                    2,
+                )
+            })
+        });
+
+        let res = block_on(pool.invoke_function(
+            None,
+            &["com", "pool"],
+            "mul",
+            vec![6.to_js_value_facade(), 7.to_js_value_facade()],
+        ))
+        .expect("invoke failed");
+        assert_eq!(res.get_i32(), 42);
+
+        let usages = block_on(pool.memory_usage());
+        assert_eq!(usages.len(), 2);
+
+        pool.recycle(1);
+        let res = pool
+            .with_member(1, |rt| {
+                rt.invoke_function_sync(
+                    None,
+                    &["com", "pool"],
+                    "mul",
+                    vec![3.to_js_value_facade(), 3.to_js_value_facade()],
+                )
+            })
+            .expect("invoke failed");
+        assert_eq!(res.get_i32(), 9);
+
+        let recycled = block_on(pool.recycle_if_memory_exceeds(0));
+        assert_eq!(recycled, vec![0, 1]);
+    }
+}
@@ -1,5 +1,6 @@
 // public transpile function which can also be used by gcs to transpile clientside ts
 
+use crate::jsutils::stacktrace::{parse_stack_trace, serialize_stack};
 use crate::jsutils::JsError;
 use crate::jsutils::Script;
 use crate::quickjs_utils::modules::detect_module;
@@ -7,7 +8,6 @@ use crate::quickjsruntimeadapter::QuickJsRuntimeAdapter;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::io;
-use std::str::FromStr;
 use std::sync::Arc;
 use swc::Compiler;
 use swc_common::errors::{ColorConfig, Handler};
@@ -240,88 +240,17 @@ pub(crate) fn transpile_serverside(
 
     // register in source_maps so fix_stack can use it later
     if let Some(map_str) = script.get_map() {
-        SOURCE_MAPS.with(|rc| {
-            let maps = &mut *rc.borrow_mut();
-            maps.insert(script.get_path().to_string(), map_str.to_string());
-        })
+        register_source_map(script.get_path(), map_str);
     }
     Ok(())
 }
 
-#[derive(Debug)]
-struct StackEntry {
-    function_name: String,
-    file_name: String,
-    line_number: Option<u32>,
-    column_number: Option<u32>,
-}
-
-impl FromStr for StackEntry {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // remove 'at '
-        let s = &s[3..];
-
-        let mut parts = s.splitn(2, ' ');
-        let function_name = parts.next().unwrap_or("unnamed").to_string();
-        let mut file_name = parts.next().unwrap_or("(unknown)").to_string();
-        if file_name.starts_with('(') {
-            file_name = file_name.as_str()[1..].to_string();
-        }
-        if file_name.ends_with(')') {
-            file_name = file_name.as_str()[..file_name.len() - 1].to_string();
-        }
-        file_name = file_name.replace("://", "_double_point_placeholder_//");
-
-        let parts: Vec<&str> = file_name.split(':').collect();
-
-        let file_name = parts[0]
-            .to_string()
-            .replace("_double_point_placeholder_//", "://");
-        let line_number = parts.get(1).and_then(|s| s.parse::<u32>().ok());
-        let column_number = parts.get(2).and_then(|s| s.parse::<u32>().ok());
-
-        Ok(StackEntry {
-            function_name,
-            file_name,
-            column_number,
-            line_number,
-        })
-    }
-}
-
-fn parse_stack_trace(stack_trace: &str) -> Result<Vec<StackEntry>, String> {
-    let entries: Vec<StackEntry> = stack_trace
-        .lines()
-        .map(|line| line.trim())
-        .filter(|line| !line.is_empty())
-        .map(|line| line.parse::<StackEntry>())
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(entries)
-}
-
-fn serialize_stack(entries: &[StackEntry]) -> String {
-    let mut result = String::new();
-
-    for entry in entries {
-        let fname_lnum = if let Some(line_number) = entry.line_number {
-            if let Some(column_number) = entry.column_number {
-                format!("{}:{line_number}:{column_number}", entry.file_name)
-            } else {
-                format!("{}:{line_number}", entry.file_name)
-            }
-        } else {
-            entry.file_name.clone()
-        };
-
-        result.push_str(&format!("    at {} ({fname_lnum})", entry.function_name));
-
-        result.push('\n');
-    }
-
-    result
+/// register the source map of a transpiled script, used when the transpiled script was read from a bytecode cache
+pub(crate) fn register_source_map(path: &str, map_str: &str) {
+    SOURCE_MAPS.with(|rc| {
+        let maps = &mut *rc.borrow_mut();
+        maps.insert(path.to_string(), map_str.to_string());
+    })
 }
 
 pub(crate) fn unmap_stack_trace(stack_trace: &str) -> String {
@@ -385,8 +314,8 @@ pub fn fix_stack_trace(stack_trace: &str, maps: &HashMap<String, String>) -> Str
 #[cfg(test)]
 pub mod tests {
     use crate::facades::tests::init_test_rt;
+    use crate::jsutils::stacktrace::{parse_stack_trace, serialize_stack};
     use crate::jsutils::{JsValueType, Script};
-    use crate::typescript::{parse_stack_trace, serialize_stack};
 
     #[test]
     fn test_ts() {
@@ -388,8 +388,58 @@ impl CachedJsFunctionRef {
     }
 }
 
+/// the kind of a TypedArray, DataView or ArrayBuffer in a JsValueFacade::TypedArray
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
 pub enum TypedArrayType {
     Uint8,
+    Uint8Clamped,
+    Int8,
+    Uint16,
+    Int16,
+    Uint32,
+    Int32,
+    Float16,
+    Float32,
+    Float64,
+    BigInt64,
+    BigUint64,
+    DataView,
+    ArrayBuffer,
+}
+
+impl TypedArrayType {
+    /// the name of the JS constructor for this type
+    pub fn get_constructor_name(&self) -> &'static str {
+        match self {
+            TypedArrayType::Uint8 => "Uint8Array",
+            TypedArrayType::Uint8Clamped => "Uint8ClampedArray",
+            TypedArrayType::Int8 => "Int8Array",
+            TypedArrayType::Uint16 => "Uint16Array",
+            TypedArrayType::Int16 => "Int16Array",
+            TypedArrayType::Uint32 => "Uint32Array",
+            TypedArrayType::Int32 => "Int32Array",
+            TypedArrayType::Float16 => "Float16Array",
+            TypedArrayType::Float32 => "Float32Array",
+            TypedArrayType::Float64 => "Float64Array",
+            TypedArrayType::BigInt64 => "BigInt64Array",
+            TypedArrayType::BigUint64 => "BigUint64Array",
+            TypedArrayType::DataView => "DataView",
+            TypedArrayType::ArrayBuffer => "ArrayBuffer",
+        }
+    }
+    /// the size of a single element in bytes, 1 for DataView and ArrayBuffer
+    pub fn get_bytes_per_element(&self) -> usize {
+        match self {
+            TypedArrayType::Uint8
+            | TypedArrayType::Uint8Clamped
+            | TypedArrayType::Int8
+            | TypedArrayType::DataView
+            | TypedArrayType::ArrayBuffer => 1,
+            TypedArrayType::Uint16 | TypedArrayType::Int16 | TypedArrayType::Float16 => 2,
+            TypedArrayType::Uint32 | TypedArrayType::Int32 | TypedArrayType::Float32 => 4,
+            TypedArrayType::Float64 | TypedArrayType::BigInt64 | TypedArrayType::BigUint64 => 8,
+        }
+    }
 }
 
 /// The JsValueFacade is a Send-able representation of a value in the Script engine
@@ -448,6 +498,7 @@ pub enum JsValueFacade {
         class_name: &'static str,
         instance_id: JsProxyInstanceId,
     },
+    // the bytes of a TypedArray, DataView or ArrayBuffer, for a view only the viewed part of its buffer
     TypedArray {
         buffer: Vec<u8>,
         array_type: TypedArrayType,
@@ -458,6 +509,26 @@ pub enum JsValueFacade {
     SerdeValue {
         value: serde_json::Value,
     },
+    // BigInt as a decimal string, see get_bigint_i128 for an i128 representation
+    BigInt {
+        val: String,
+    },
+    // Date as a timestamp (milliseconds since the epoch)
+    Date {
+        timestamp: f64,
+    },
+    // Map as entries in insertion order
+    Map {
+        entries: Vec<(JsValueFacade, JsValueFacade)>,
+    },
+    // Set as values in insertion order
+    Set {
+        values: Vec<JsValueFacade>,
+    },
+    // Symbol by its description, converting back to a JSValue creates a new (unique) Symbol
+    Symbol {
+        description: Option<String>,
+    },
     Null,
     Undefined,
 }
@@ -490,6 +561,14 @@ impl JsValueFacade {
             val: DefaultAtom::from(val),
         }
     }
+    pub fn new_bigint(val: i128) -> Self {
+        Self::BigInt {
+            val: val.to_string(),
+        }
+    }
+    pub fn new_date(timestamp: f64) -> Self {
+        Self::Date { timestamp }
+    }
     pub fn new_callback<
         F: Fn(&[JsValueFacade]) -> Result<JsValueFacade, JsError> + Send + Sync + 'static,
     >(
@@ -586,6 +665,57 @@ impl JsValueFacade {
             }
         }
     }
+    /// get the value of a BigInt as an i128, returns None if the value does not fit in an i128
+    pub fn get_bigint_i128(&self) -> Option<i128> {
+        match self {
+            JsValueFacade::BigInt { val } => val.parse::<i128>().ok(),
+            _ => {
+                panic!("Not a BigInt");
+            }
+        }
+    }
+    /// get the value of a BigInt as a decimal string
+    pub fn get_bigint_str(&self) -> &str {
+        match self {
+            JsValueFacade::BigInt { val } => val.as_str(),
+            _ => {
+                panic!("Not a BigInt");
+            }
+        }
+    }
+    /// get the timestamp of a Date in milliseconds since the epoch
+    pub fn get_timestamp(&self) -> f64 {
+        match self {
+            JsValueFacade::Date { timestamp } => *timestamp,
+            _ => {
+                panic!("Not a Date");
+            }
+        }
+    }
+    pub fn get_map_entries(&self) -> &[(JsValueFacade, JsValueFacade)] {
+        match self {
+            JsValueFacade::Map { entries } => entries.as_slice(),
+            _ => {
+                panic!("Not a Map");
+            }
+        }
+    }
+    pub fn get_set_values(&self) -> &[JsValueFacade] {
+        match self {
+            JsValueFacade::Set { values } => values.as_slice(),
+            _ => {
+                panic!("Not a Set");
+            }
+        }
+    }
+    pub fn get_symbol_description(&self) -> Option<&str> {
+        match self {
+            JsValueFacade::Symbol { description } => description.as_deref(),
+            _ => {
+                panic!("Not a Symbol");
+            }
+        }
+    }
     pub fn is_null_or_undefined(&self) -> bool {
         matches!(self, JsValueFacade::Null | JsValueFacade::Undefined)
     }
@@ -609,6 +739,11 @@ impl JsValueFacade {
             JsValueFacade::ProxyInstance { .. } => JsValueType::Object,
             JsValueFacade::TypedArray { .. } => JsValueType::Object,
             JsValueFacade::JsonStr { .. } => JsValueType::Object,
+            JsValueFacade::BigInt { .. } => JsValueType::BigInt,
+            JsValueFacade::Date { .. } => JsValueType::Date,
+            JsValueFacade::Map { .. } => JsValueType::Map,
+            JsValueFacade::Set { .. } => JsValueType::Set,
+            JsValueFacade::Symbol { .. } => JsValueType::Symbol,
             JsValueFacade::SerdeValue { value } => match value {
                 serde_json::Value::Null => JsValueType::Null,
                 serde_json::Value::Bool(_) => JsValueType::Boolean,
@@ -691,6 +826,13 @@ impl JsValueFacade {
             JsValueFacade::TypedArray { .. } => "TypedArray".to_string(),
             JsValueFacade::JsonStr { json } => format!("JsonStr: '{json}'"),
             JsValueFacade::SerdeValue { value } => format!("Serde value: {value}"),
+            JsValueFacade::BigInt { val } => format!("BigInt: {val}"),
+            JsValueFacade::Date { timestamp } => format!("Date: {timestamp}"),
+            JsValueFacade::Map { entries } => format!("Map: [size={}]", entries.len()),
+            JsValueFacade::Set { values } => format!("Set: [size={}]", values.len()),
+            JsValueFacade::Symbol { description } => {
+                format!("Symbol: {}", description.as_deref().unwrap_or(""))
+            }
         }
     }
     pub async fn to_serde_value(&self) -> Result<serde_json::Value, JsError> {
@@ -714,6 +856,30 @@ impl JsValueFacade {
             JsValueFacade::TypedArray { .. } => Ok(Value::Null),
             JsValueFacade::JsonStr { json } => Ok(serde_json::from_str(json).unwrap()),
             JsValueFacade::SerdeValue { value } => Ok(value.clone()),
+            JsValueFacade::BigInt { val } => Ok(Value::from(val.as_str())),
+            // an ISO string like JSON.stringify (and the serde Deserializer of QuickJsValueAdapter)
+            JsValueFacade::Date { timestamp } => {
+                Ok(to_iso_string(*timestamp).map_or(Value::Null, Value::from))
+            }
+            // the entries as an array of [key, value] pairs
+            JsValueFacade::Map { entries } => {
+                let mut pairs = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    pairs.push(Value::Array(vec![
+                        Box::pin(key.to_serde_value()).await?,
+                        Box::pin(value.to_serde_value()).await?,
+                    ]));
+                }
+                Ok(Value::Array(pairs))
+            }
+            JsValueFacade::Set { values } => {
+                let mut items = Vec::with_capacity(values.len());
+                for value in values {
+                    items.push(Box::pin(value.to_serde_value()).await?);
+                }
+                Ok(Value::Array(items))
+            }
+            JsValueFacade::Symbol { .. } => Ok(Value::Null),
         }
     }
     pub async fn to_json_string(&self) -> Result<String, JsError> {
@@ -737,10 +903,65 @@ impl JsValueFacade {
             JsValueFacade::TypedArray { .. } => Ok("[]".to_string()),
             JsValueFacade::JsonStr { json } => Ok(json.clone()),
             JsValueFacade::SerdeValue { value } => Ok(serde_json::to_string(value).unwrap()),
+            JsValueFacade::BigInt { val } => Ok(format!("{val}n")),
+            JsValueFacade::Date { timestamp } => Ok(format!("new Date({timestamp})")),
+            JsValueFacade::Map { entries } => {
+                let mut pairs = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    pairs.push(format!(
+                        "[{}, {}]",
+                        Box::pin(key.to_json_string()).await?,
+                        Box::pin(value.to_json_string()).await?
+                    ));
+                }
+                Ok(format!("new Map([{}])", pairs.join(", ")))
+            }
+            JsValueFacade::Set { values } => {
+                let mut items = Vec::with_capacity(values.len());
+                for value in values {
+                    items.push(Box::pin(value.to_json_string()).await?);
+                }
+                Ok(format!("new Set([{}])", items.join(", ")))
+            }
+            JsValueFacade::Symbol { .. } => Ok("Symbol()".to_string()),
         }
     }
 }
 
+/// format a timestamp like Date.prototype.toISOString, None for an invalid Date (which JSON.stringify turns into null)
+fn to_iso_string(timestamp: f64) -> Option<String> {
+    // a valid Date is at most 8.64e15 milliseconds from the epoch
+    if !timestamp.is_finite() || timestamp.abs() > 8.64e15 {
+        return None;
+    }
+    let ms = timestamp.trunc() as i64;
+    let ms_of_day = ms.rem_euclid(86_400_000);
+    // civil_from_days from http://howardhinnant.github.io/date_algorithms.html
+    let z = ms.div_euclid(86_400_000) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    let year = if (0..=9999).contains(&year) {
+        format!("{year:04}")
+    } else if year < 0 {
+        format!("-{:06}", -year)
+    } else {
+        format!("+{year:06}")
+    };
+    Some(format!(
+        "{year}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        ms_of_day / 3_600_000,
+        ms_of_day / 60_000 % 60,
+        ms_of_day / 1000 % 60,
+        ms_of_day % 1000
+    ))
+}
+
 impl Debug for JsValueFacade {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.stringify().as_str())